@@ -43,6 +43,7 @@ use routing::{
 use std::{
     collections::HashSet,
     convert::TryInto,
+    env,
     net::{IpAddr, Ipv4Addr, SocketAddr},
     thread::{self, JoinHandle},
 };
@@ -213,6 +214,9 @@ fn start_node(
     let (node, event_rx, _client_event_rx) = Node::new(NodeConfig {
         first,
         transport_config,
+        bootstrap_cache_file: Some(
+            env::temp_dir().join(format!("routing-minimal-{}.cache", index)),
+        ),
         ..Default::default()
     });
 
@@ -330,18 +334,62 @@ fn handle_event(
                 elders
             );
         }
-        Event::MessageReceived { content, src, dst } => log::info!(
-            "Node #{} received message - src: {:?}, dst: {:?}, content: {}",
+        Event::MessageReceived {
+            content,
+            src,
+            dst,
+            trace,
+        } => log::info!(
+            "Node #{} received message - src: {:?}, dst: {:?}, content: {}, trace: {:?}",
             index,
             src,
             dst,
+            HexFmt(content),
+            trace
+        ),
+        Event::MessageUndelivered { content, dst } => log::warn!(
+            "Node #{} failed to deliver message - dst: {:?}, content: {}",
+            index,
+            dst,
             HexFmt(content)
         ),
+        Event::Delivered(hash) => log::info!("Node #{} delivered message {:?}", index, hash),
+        Event::DeliveryFailed(hash) => {
+            log::warn!("Node #{} failed to deliver message {:?}", index, hash)
+        }
         Event::Consensus(payload) => log::info!(
             "Node #{} reached consensus - payload: {}",
             index,
             HexFmt(payload)
         ),
+        Event::DkgTimedOut {
+            participants,
+            attempt,
+        } => log::info!(
+            "Node #{} DKG timed out - participants: {:?}, attempt: {}",
+            index,
+            participants,
+            attempt
+        ),
+        Event::DkgRestarted {
+            participants,
+            excluded,
+            attempt,
+        } => log::info!(
+            "Node #{} DKG restarted - participants: {:?}, excluded: {:?}, attempt: {}",
+            index,
+            participants,
+            excluded,
+            attempt
+        ),
+        Event::ConsensusStalled { unresponsive } => log::warn!(
+            "Node #{} consensus stalled - unresponsive: {:?}",
+            index,
+            unresponsive
+        ),
+        Event::Bootstrapping(progress) => {
+            log::info!("Node #{} bootstrapping - {:?}", index, progress)
+        }
         Event::Terminated => {
             log::info!("Node #{} terminated", index);
             return false;
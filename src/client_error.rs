@@ -47,7 +47,9 @@ pub enum ClientError {
     /// Insufficient balance for performing a given mutating operation
     LowBalance,
     /// The loss of sacrificial copies indicates the network as a whole is no longer having
-    /// enough space to accept further put request so have to wait for more nodes to join
+    /// enough space to accept further put request so have to wait for more nodes to join.
+    /// Routing itself never produces this error, the vaults do. A vault seeing it should make
+    /// sure its section accepts new members (see `Node::set_accepting_members`).
     NetworkFull,
     /// Network error occurring at Vault level which has no bearing on clients, e.g. serialisation
     /// failure or database failure
@@ -0,0 +1,184 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+#[cfg(any(test, feature = "trusted-leader-consensus"))]
+use super::leader;
+#[cfg(all(test, feature = "mock"))]
+use super::parsec::ParsecMap;
+use super::{
+    parsec::{self, DkgResultWrapper},
+    NetworkEvent, Proof,
+};
+use crate::{
+    id::{FullId, PublicId},
+    rng::MainRng,
+    section::EldersInfo,
+};
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Debug, Formatter},
+};
+
+/// Algorithm used by the section elders to reach consensus.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConsensusAlgorithm {
+    /// Leaderless, asynchronous BFT consensus using PARSEC.
+    Parsec,
+    /// Simple leader based consensus where the elder with the lowest name orders all the votes.
+    ///
+    /// **NOT Byzantine fault tolerant - only for tests and fully trusted deployments.** The leader
+    /// is trusted with everything: it deals all the section key shares (and sends them in
+    /// plaintext), its commits and DKG results are applied without checking any signatures or
+    /// quorum, and there is no leader rotation, so the section makes no progress while it is down.
+    /// A single malicious or compromised leader can take over the section.
+    ///
+    /// Requires the `trusted-leader-consensus` feature. All nodes in the network must use the
+    /// same algorithm.
+    #[cfg(any(test, feature = "trusted-leader-consensus"))]
+    Leader,
+}
+
+impl Default for ConsensusAlgorithm {
+    fn default() -> Self {
+        Self::Parsec
+    }
+}
+
+/// Output of a consensus algorithm.
+pub enum Decision {
+    /// The instance has been started with the given group and related info.
+    Genesis {
+        group: BTreeSet<PublicId>,
+        related_info: Vec<u8>,
+    },
+    /// A single vote reached consensus. The votes still need to accumulate to form an event.
+    Vote { event: NetworkEvent, proof: Proof },
+    /// The distributed key generation started by a `StartDkg` vote completed.
+    DkgResult {
+        participants: BTreeSet<PublicId>,
+        dkg_result: DkgResultWrapper,
+    },
+    /// The given peer was proven malicious, for example for voting for conflicting events.
+    Accusation { offender: PublicId },
+}
+
+/// Request sent by the consensus algorithm to other nodes.
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ConsensusRequest {
+    /// Request of `ConsensusAlgorithm::Parsec`.
+    Parsec(parsec::Request),
+    /// Request of `ConsensusAlgorithm::Leader`.
+    #[cfg(any(test, feature = "trusted-leader-consensus"))]
+    Leader(leader::Message),
+}
+
+impl Debug for ConsensusRequest {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Parsec(_) => write!(f, "Parsec(..)"),
+            #[cfg(any(test, feature = "trusted-leader-consensus"))]
+            Self::Leader(msg) => write!(f, "Leader({:?})", msg),
+        }
+    }
+}
+
+/// Response to a `ConsensusRequest`.
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ConsensusResponse {
+    /// Response of `ConsensusAlgorithm::Parsec`.
+    Parsec(parsec::Response),
+    /// Response of `ConsensusAlgorithm::Leader`.
+    #[cfg(any(test, feature = "trusted-leader-consensus"))]
+    Leader(leader::Message),
+}
+
+impl Debug for ConsensusResponse {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Parsec(_) => write!(f, "Parsec(..)"),
+            #[cfg(any(test, feature = "trusted-leader-consensus"))]
+            Self::Leader(msg) => write!(f, "Leader({:?})", msg),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CreateGossipError {
+    MissingVersion,
+    UnexpectedTarget,
+    Other(parsec::Error),
+}
+
+impl From<parsec::Error> for CreateGossipError {
+    fn from(src: parsec::Error) -> Self {
+        Self::Other(src)
+    }
+}
+
+/// Consensus algorithm the `ConsensusEngine` is built on.
+///
+/// The algorithm is run in versioned instances. A new instance is started on every elders change
+/// (or pruning) and messages for older instances are ignored. Distributed key generation is
+/// started by voting for `AccumulatingEvent::StartDkg` and completes with `Decision::DkgResult`.
+pub trait Consensus {
+    /// Starts a new instance of the given version for the given elders.
+    fn init(
+        &mut self,
+        rng: &mut MainRng,
+        full_id: FullId,
+        elders_info: &EldersInfo,
+        serialised_state: Vec<u8>,
+        version: u64,
+    );
+
+    /// Version of the latest instance.
+    fn last_version(&self) -> u64;
+
+    fn vote_for(&mut self, event: NetworkEvent);
+
+    /// Returns the next decision of the latest instance, if any.
+    fn poll(&mut self) -> Option<Decision>;
+
+    /// Returns our votes in the latest instance that have not been polled yet.
+    fn our_unpolled_votes(&self) -> Vec<NetworkEvent>;
+
+    fn has_unpolled_observations(&self) -> bool;
+
+    fn create_gossip(
+        &mut self,
+        version: u64,
+        target: &PublicId,
+    ) -> Result<ConsensusRequest, CreateGossipError>;
+
+    fn handle_request(
+        &mut self,
+        version: u64,
+        request: ConsensusRequest,
+        src: PublicId,
+    ) -> Option<ConsensusResponse>;
+
+    fn handle_response(&mut self, version: u64, response: ConsensusResponse, src: PublicId);
+
+    /// Peers we can send gossip requests to on our own initiative.
+    fn gossip_recipients(&self) -> Vec<&PublicId>;
+
+    /// Returns whether we should send gossip now.
+    fn should_send_gossip(&mut self) -> bool;
+
+    /// Called at the start of every gossip period.
+    fn reset_gossip_period(&mut self);
+
+    /// Votes for `ParsecPrune` if the state of the latest instance grew too large.
+    fn prune_if_needed(&mut self);
+
+    // Enable tests to simulate other members.
+    #[cfg(all(test, feature = "mock"))]
+    fn as_parsec_map_mut(&mut self) -> Option<&mut ParsecMap> {
+        None
+    }
+}
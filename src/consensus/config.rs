@@ -0,0 +1,117 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{algorithm::ConsensusAlgorithm, parsec::PARSEC_SIZE_LIMIT};
+use crate::time::Duration;
+use std::path::PathBuf;
+
+/// Period within which the number of sent gossip messages is limited. When the period ends, the
+/// limit resets at a new period starts.
+pub const GOSSIP_PERIOD: Duration = Duration::from_secs(1);
+
+// Longest gossip period of an idle section.
+const GOSSIP_IDLE_PERIOD: Duration = Duration::from_secs(8);
+
+// Maximum number of gossip messages a node can send within one gossip period.
+const GOSSIP_LIMIT: usize = 5;
+
+/// Longest time the consensus messages are held back for the journal to be synced to disk, when
+/// the consensus state is persisted.
+pub const JOURNAL_SYNC_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Default time without any consensus progress, while there are observations waiting for it,
+/// after which consensus is considered stalled.
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Consensus configuration. Must be compatible across all nodes in the network.
+#[derive(Clone, Debug)]
+pub struct ConsensusConfig {
+    /// Algorithm the elders use to reach consensus. Must be identical for all nodes.
+    pub algorithm: ConsensusAlgorithm,
+    /// Gossip configuration.
+    pub gossip: GossipConfig,
+    /// Directory to persist the consensus state in, or `None` to keep it in memory only. When set,
+    /// a restarted elder that rejoins the same consensus instance resumes from the persisted state,
+    /// and one that rejoins after the instance changed votes again for the events it voted for
+    /// that didn't accumulate, instead of losing its in-flight votes.
+    pub store_dir: Option<PathBuf>,
+    /// Time without any consensus progress, while there are observations waiting for it, after
+    /// which consensus is considered stalled and `Event::ConsensusStalled` is raised. If it is
+    /// still stalled after another timeout, the unresponsive elders are voted offline.
+    pub stall_timeout: Duration,
+}
+
+impl Default for ConsensusConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: ConsensusAlgorithm::default(),
+            gossip: GossipConfig::default(),
+            store_dir: None,
+            stall_timeout: STALL_TIMEOUT,
+        }
+    }
+}
+
+/// Configuration of the consensus gossip.
+///
+/// The gossip period adapts to the load: while there are observations waiting for consensus, it
+/// is `busy_period`. Every period without any, it doubles, up to `idle_period`.
+#[derive(Clone, Copy, Debug)]
+pub struct GossipConfig {
+    /// Gossip period while there are observations waiting for consensus.
+    pub busy_period: Duration,
+    /// Longest gossip period while idle.
+    pub idle_period: Duration,
+    /// Maximum number of gossip messages a node can send within one gossip period.
+    pub limit: usize,
+    /// Estimated size (in bytes) of the parsec gossip graph above which we vote for pruning it.
+    pub parsec_size_limit: u64,
+}
+
+impl GossipConfig {
+    /// Returns the period to use after `current` given whether we are busy.
+    pub(crate) fn next_period(&self, current: Duration, busy: bool) -> Duration {
+        if busy {
+            self.busy_period
+        } else {
+            (current * 2).max(self.busy_period).min(self.idle_period)
+        }
+    }
+}
+
+impl Default for GossipConfig {
+    fn default() -> Self {
+        Self {
+            busy_period: GOSSIP_PERIOD,
+            idle_period: GOSSIP_IDLE_PERIOD,
+            limit: GOSSIP_LIMIT,
+            parsec_size_limit: PARSEC_SIZE_LIMIT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn period_backs_off_when_idle() {
+        let config = GossipConfig::default();
+        let mut period = config.busy_period;
+
+        period = config.next_period(period, false);
+        assert_eq!(period, config.busy_period * 2);
+
+        for _ in 0..10 {
+            period = config.next_period(period, false);
+        }
+        assert_eq!(period, config.idle_period);
+
+        assert_eq!(config.next_period(period, true), config.busy_period);
+    }
+}
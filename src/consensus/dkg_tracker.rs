@@ -0,0 +1,292 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{id::PublicId, section::EldersInfo, time::Duration};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Time to wait for a DKG round to produce a result before considering it stalled.
+pub const DKG_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum number of times a stalled DKG round is restarted with the responsive subset of its
+/// participants before we give up and just keep waiting for the last one, without timing it out
+/// again.
+pub const DKG_MAX_ATTEMPTS: u8 = 3;
+
+/// A single in-progress DKG round.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DkgRound {
+    /// The participants of this round.
+    pub participants: BTreeSet<PublicId>,
+    /// The elders info the key is being generated for.
+    pub elders_info: EldersInfo,
+    /// How many times this round has been (re)started. The first round is attempt `1`.
+    pub attempt: u8,
+    /// Whether this round timed out already and is kept waiting for.
+    pub timed_out: bool,
+    // The timeout of this round, or `None` if it's no longer timed out.
+    timer_token: Option<u64>,
+}
+
+/// Keeps track of the DKG rounds we started and detects when they stall.
+#[derive(Default)]
+pub struct DkgTracker {
+    // Rounds waiting for their `DkgResult`, keyed by participants.
+    pending: BTreeMap<BTreeSet<PublicId>, DkgRound>,
+    // Rounds that timed out or were superseded, with the section versions they were for. Their
+    // results, if they ever arrive, are ignored.
+    abandoned: BTreeMap<BTreeSet<PublicId>, u64>,
+    // Participants of pending rounds that we lost connection to.
+    lost: BTreeSet<PublicId>,
+}
+
+impl DkgTracker {
+    /// Starts tracking a new DKG round. `timer_token` identifies the timeout of this round.
+    pub fn start(&mut self, elders_info: EldersInfo, attempt: u8, timer_token: u64) {
+        let participants: BTreeSet<_> = elders_info.elder_ids().copied().collect();
+        let _ = self.abandoned.remove(&participants);
+        let _ = self.pending.insert(
+            participants.clone(),
+            DkgRound {
+                participants,
+                elders_info,
+                attempt,
+                timed_out: false,
+                timer_token: Some(timer_token),
+            },
+        );
+    }
+
+    /// Re-arms the timeout of a round that stalled but could not be restarted.
+    pub fn resume(&mut self, round: DkgRound, timer_token: u64) {
+        self.keep_pending(round, Some(timer_token))
+    }
+
+    /// Keeps waiting for the result of a round that stalled and won't be restarted anymore, without
+    /// timing it out again.
+    pub fn wait(&mut self, round: DkgRound) {
+        self.keep_pending(round, None)
+    }
+
+    fn keep_pending(&mut self, round: DkgRound, timer_token: Option<u64>) {
+        let _ = self.abandoned.remove(&round.participants);
+        let _ = self.pending.insert(
+            round.participants.clone(),
+            DkgRound {
+                timed_out: true,
+                timer_token,
+                ..round
+            },
+        );
+    }
+
+    /// Marks the round with the given participants as completed and returns its elders info, if
+    /// the round is pending.
+    pub fn complete(&mut self, participants: &BTreeSet<PublicId>) -> Option<EldersInfo> {
+        let round = self.pending.remove(participants)?;
+        if self.pending.is_empty() {
+            self.lost.clear();
+        }
+
+        Some(round.elders_info)
+    }
+
+    /// Forgets the abandoned round with the given participants. Returns whether there was one.
+    pub fn forget_abandoned(&mut self, participants: &BTreeSet<PublicId>) -> bool {
+        self.abandoned.remove(participants).is_some()
+    }
+
+    /// Abandons all the rounds that are superseded by our section reaching the given version.
+    pub fn prune(&mut self, version: u64) {
+        let superseded: Vec<_> = self
+            .pending
+            .values()
+            .filter(|round| round.elders_info.version <= version)
+            .map(|round| round.participants.clone())
+            .collect();
+
+        self.abandoned
+            .retain(|_, abandoned_version| *abandoned_version >= version);
+
+        for participants in superseded {
+            if let Some(round) = self.pending.remove(&participants) {
+                let _ = self
+                    .abandoned
+                    .insert(participants, round.elders_info.version);
+            }
+        }
+
+        if self.pending.is_empty() {
+            self.lost.clear();
+        }
+    }
+
+    /// If `token` is the timeout of a pending round, abandons that round and returns it.
+    pub fn handle_timeout(&mut self, token: u64) -> Option<DkgRound> {
+        let participants = self
+            .pending
+            .values()
+            .find(|round| round.timer_token == Some(token))
+            .map(|round| round.participants.clone())?;
+        let round = self.pending.remove(&participants)?;
+        let _ = self
+            .abandoned
+            .insert(participants, round.elders_info.version);
+
+        Some(round)
+    }
+
+    /// Records that we lost connection to the given peer, if it takes part in any pending round.
+    pub fn handle_peer_lost(&mut self, pub_id: &PublicId) {
+        if self
+            .pending
+            .keys()
+            .any(|participants| participants.contains(pub_id))
+        {
+            let _ = self.lost.insert(*pub_id);
+        }
+    }
+
+    /// Returns whether we lost connection to the given peer during the pending rounds.
+    pub fn is_lost(&self, pub_id: &PublicId) -> bool {
+        self.lost.contains(pub_id)
+    }
+
+    /// Returns the participants of all the pending rounds.
+    pub fn pending_participants(&self) -> impl Iterator<Item = &BTreeSet<PublicId>> {
+        self.pending.keys()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        id::{FullId, P2pNode},
+        rng, unwrap,
+        xor_space::Prefix,
+    };
+
+    fn gen_elders_info(count: usize) -> EldersInfo {
+        let mut rng = rng::new();
+        let elders = (0..count)
+            .map(|index| {
+                let id = FullId::gen(&mut rng);
+                let addr = ([127, 0, 0, 1], 9000 + index as u16).into();
+                (*id.public_id().name(), P2pNode::new(*id.public_id(), addr))
+            })
+            .collect();
+
+        EldersInfo::new(elders, Prefix::default(), 1)
+    }
+
+    #[test]
+    fn complete_pending_round() {
+        let info = gen_elders_info(4);
+        let participants: BTreeSet<_> = info.elder_ids().copied().collect();
+
+        let mut tracker = DkgTracker::default();
+        tracker.start(info.clone(), 1, 0);
+
+        assert_eq!(tracker.complete(&participants), Some(info));
+        assert_eq!(tracker.complete(&participants), None);
+        assert!(!tracker.forget_abandoned(&participants));
+    }
+
+    #[test]
+    fn timed_out_round_is_abandoned() {
+        let info = gen_elders_info(4);
+        let participants: BTreeSet<_> = info.elder_ids().copied().collect();
+
+        let mut tracker = DkgTracker::default();
+        tracker.start(info, 1, 7);
+
+        assert!(tracker.handle_timeout(6).is_none());
+
+        let round = tracker.handle_timeout(7).expect("round not timed out");
+        assert_eq!(round.participants, participants);
+        assert_eq!(round.attempt, 1);
+        assert!(tracker.handle_timeout(7).is_none());
+
+        // A late result of the abandoned round is recognised as such.
+        assert_eq!(tracker.complete(&participants), None);
+        assert!(tracker.forget_abandoned(&participants));
+    }
+
+    #[test]
+    fn resumed_round_can_complete() {
+        let info = gen_elders_info(4);
+        let participants: BTreeSet<_> = info.elder_ids().copied().collect();
+
+        let mut tracker = DkgTracker::default();
+        tracker.start(info.clone(), 1, 0);
+
+        let round = tracker.handle_timeout(0).expect("round not timed out");
+        assert!(!round.timed_out);
+        tracker.resume(round, 1);
+
+        assert!(tracker.handle_timeout(0).is_none());
+        assert_eq!(tracker.complete(&participants), Some(info));
+    }
+
+    #[test]
+    fn waited_for_round_no_longer_times_out() {
+        let info = gen_elders_info(4);
+        let participants: BTreeSet<_> = info.elder_ids().copied().collect();
+
+        let mut tracker = DkgTracker::default();
+        tracker.start(info.clone(), DKG_MAX_ATTEMPTS, 0);
+
+        let round = tracker.handle_timeout(0).expect("round not timed out");
+        tracker.wait(round);
+
+        assert!(tracker.handle_timeout(0).is_none());
+        assert!(tracker
+            .pending_participants()
+            .any(|pending| *pending == participants));
+        assert_eq!(tracker.complete(&participants), Some(info));
+    }
+
+    #[test]
+    fn prune_abandons_superseded_rounds() {
+        let old_info = gen_elders_info(4);
+        let old_participants: BTreeSet<_> = old_info.elder_ids().copied().collect();
+        let new_info = EldersInfo::new(old_info.elders.clone(), old_info.prefix, 2);
+
+        let mut tracker = DkgTracker::default();
+        tracker.start(old_info, 1, 0);
+        tracker.prune(1);
+
+        assert!(tracker.handle_timeout(0).is_none());
+        assert_eq!(tracker.complete(&old_participants), None);
+
+        // Newer rounds are kept.
+        tracker.start(new_info.clone(), 1, 1);
+        tracker.prune(1);
+        assert_eq!(tracker.complete(&old_participants), Some(new_info));
+    }
+
+    #[test]
+    fn lost_peers_are_tracked_only_for_pending_rounds() {
+        let info = gen_elders_info(4);
+        let participant = *unwrap!(info.elder_ids().next());
+        let stranger = *unwrap!(gen_elders_info(1).elder_ids().next());
+        let participants: BTreeSet<_> = info.elder_ids().copied().collect();
+
+        let mut tracker = DkgTracker::default();
+        tracker.start(info, 1, 0);
+        tracker.handle_peer_lost(&participant);
+        tracker.handle_peer_lost(&stranger);
+
+        assert!(tracker.is_lost(&participant));
+        assert!(!tracker.is_lost(&stranger));
+
+        // Completing the last pending round forgets the lost peers.
+        assert!(tracker.complete(&participants).is_some());
+        assert!(!tracker.is_lost(&participant));
+    }
+}
@@ -26,7 +26,7 @@ pub const UNRESPONSIVE_THRESHOLD: usize = 48;
 /// The period (X consensued observations) during which node be considered as unresponsive.
 pub const UNRESPONSIVE_WINDOW: usize = 64;
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 struct VoteStatuses {
     tracked_events: VecDeque<Rc<AccumulatingEvent>>,
     unvoted: BTreeMap<PublicId, BTreeSet<Rc<AccumulatingEvent>>>,
@@ -79,7 +79,7 @@ impl VoteStatuses {
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct EventAccumulator {
     // A map containing network events that have not been accumulated yet, together with their
     // proofs that have been collected so far. We are still waiting for more proofs, or to reach a
@@ -91,6 +91,8 @@ pub struct EventAccumulator {
     accumulated_events: BTreeSet<AccumulatingEvent>,
     // A struct retains the order of insertion, and keeps tracking of which node has not involved.
     // Entry will be created when an event reached consensus.
+    // Not persisted: it only feeds the detection of unresponsive nodes which restarts from scratch.
+    #[serde(skip)]
     vote_statuses: VoteStatuses,
 }
 
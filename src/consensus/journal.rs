@@ -0,0 +1,318 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! On-disk journal of the inputs to the latest consensus instance.
+//!
+//! Every vote, every gossip message handled and every gossip request created by the instance is
+//! appended to the journal before it is applied, and the journal is truncated whenever a new
+//! instance is started. Replaying the journal into a fresh instance rebuilds the gossip graph and
+//! our in-flight votes. The state derived from the polled decisions - the event accumulator and our
+//! section state, including the churn event backlog - is checkpointed into the journal too, so the
+//! decisions handled before the restart aren't handled again.
+//!
+//! The records are buffered and synced to disk in batches. The consensus messages derived from
+//! them are held back until the next sync, so a restart never forgets state the other elders
+//! already saw.
+
+use super::{
+    algorithm::{ConsensusRequest, ConsensusResponse},
+    event_accumulator::EventAccumulator,
+    NetworkEvent,
+};
+use crate::{error::Result, id::PublicId, section::EldersInfo};
+use std::{
+    convert::TryInto,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+const FILE_NAME: &str = "consensus.journal";
+
+// Maximum size of a single record. The length prefix of a record read back is not trusted beyond
+// this.
+const MAX_RECORD_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+pub enum Record {
+    /// Start of a new instance. Always the first record of the journal.
+    Init {
+        version: u64,
+        elders_info: EldersInfo,
+        serialised_state: Vec<u8>,
+    },
+    Vote(NetworkEvent),
+    Request {
+        version: u64,
+        src: PublicId,
+        request: ConsensusRequest,
+    },
+    Response {
+        version: u64,
+        src: PublicId,
+        response: ConsensusResponse,
+    },
+    Gossip {
+        version: u64,
+        target: PublicId,
+    },
+    /// State derived from the first `polled` decisions of the instance: the event accumulator and
+    /// our serialised section state (including the churn event backlog).
+    Checkpoint {
+        polled: u64,
+        accumulator: EventAccumulator,
+        serialised_state: Vec<u8>,
+    },
+}
+
+/// Contents of a journal read back from disk.
+pub struct JournalContents {
+    pub version: u64,
+    pub elders_info: EldersInfo,
+    pub serialised_state: Vec<u8>,
+    // All the records following the `Init` one.
+    pub records: Vec<Record>,
+}
+
+pub struct ConsensusJournal {
+    path: PathBuf,
+    file: Option<BufWriter<File>>,
+    // Whether records were appended since the last sync.
+    dirty: bool,
+}
+
+impl ConsensusJournal {
+    /// Opens the journal stored in the given directory. Nothing is written until `start`.
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            path: dir.join(FILE_NAME),
+            file: None,
+            dirty: false,
+        }
+    }
+
+    /// Reads the journal back. Returns `None` if there is no journal or it doesn't start with an
+    /// `Init` record. A partially written record at the end (e.g. due to a crash) is ignored.
+    pub fn read(&self) -> Result<Option<JournalContents>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        let mut reader = BufReader::new(file);
+
+        let (version, elders_info, serialised_state) = match read_record(&mut reader)? {
+            Some(Record::Init {
+                version,
+                elders_info,
+                serialised_state,
+            }) => (version, elders_info, serialised_state),
+            _ => return Ok(None),
+        };
+
+        let mut records = Vec::new();
+        while let Some(record) = read_record(&mut reader)? {
+            records.push(record);
+        }
+
+        Ok(Some(JournalContents {
+            version,
+            elders_info,
+            serialised_state,
+            records,
+        }))
+    }
+
+    /// Discards the current contents and starts journalling a new instance.
+    pub fn start(
+        &mut self,
+        version: u64,
+        elders_info: &EldersInfo,
+        serialised_state: &[u8],
+    ) -> Result<()> {
+        self.file = None;
+        self.dirty = false;
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.file = Some(BufWriter::new(file));
+
+        self.append(&Record::Init {
+            version,
+            elders_info: elders_info.clone(),
+            serialised_state: serialised_state.to_vec(),
+        })?;
+        self.sync()
+    }
+
+    /// Appends the record. It's only guaranteed to be on disk after the next `sync`.
+    pub fn append(&mut self, record: &Record) -> Result<()> {
+        let file = if let Some(file) = &mut self.file {
+            file
+        } else {
+            return Ok(());
+        };
+
+        let bytes = bincode::serialize(record)?;
+        if bytes.len() > MAX_RECORD_SIZE {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "journal record too big").into());
+        }
+
+        let len = bytes.len() as u32;
+
+        let mut buffer = Vec::with_capacity(4 + bytes.len());
+        buffer.extend_from_slice(&len.to_le_bytes());
+        buffer.extend_from_slice(&bytes);
+
+        file.write_all(&buffer)?;
+        self.dirty = true;
+
+        Ok(())
+    }
+
+    /// Returns whether there are records appended since the last sync.
+    pub fn is_dirty(&self) -> bool {
+        self.file.is_some() && self.dirty
+    }
+
+    /// Writes the records appended since the last sync to disk.
+    pub fn sync(&mut self) -> Result<()> {
+        let file = match &mut self.file {
+            Some(file) if self.dirty => file,
+            _ => return Ok(()),
+        };
+
+        file.flush()?;
+        file.get_ref().sync_data()?;
+        self.dirty = false;
+
+        Ok(())
+    }
+}
+
+fn read_record<R: Read>(reader: &mut R) -> Result<Option<Record>> {
+    let mut len = [0; 4];
+    if !read_exact_or_eof(reader, &mut len)? {
+        return Ok(None);
+    }
+
+    let len = u32::from_le_bytes(len).try_into().unwrap_or(usize::MAX);
+    if len > MAX_RECORD_SIZE {
+        warn!("Ignoring consensus journal record of invalid size {}", len);
+        return Ok(None);
+    }
+
+    let mut bytes = vec![0; len];
+    if !read_exact_or_eof(reader, &mut bytes)? {
+        return Ok(None);
+    }
+
+    match bincode::deserialize(&bytes) {
+        Ok(record) => Ok(Some(record)),
+        Err(error) => {
+            warn!("Ignoring corrupt consensus journal record: {}", error);
+            Ok(None)
+        }
+    }
+}
+
+// Returns `false` if the reader ended before `buffer` was filled.
+fn read_exact_or_eof<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<bool> {
+    match reader.read_exact(buffer) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consensus::AccumulatingEvent, rng, unwrap};
+    use rand::Rng;
+    use std::env;
+
+    fn temp_dir() -> PathBuf {
+        let mut rng = rng::new();
+        env::temp_dir().join(format!("routing-journal-{:016x}", rng.gen::<u64>()))
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let dir = temp_dir();
+        let mut journal = ConsensusJournal::new(&dir);
+        assert!(unwrap!(journal.read()).is_none());
+
+        let elders_info = EldersInfo::default();
+        unwrap!(journal.start(3, &elders_info, &[1, 2, 3]));
+
+        let event = AccumulatingEvent::User(vec![4, 5]).into_network_event();
+        unwrap!(journal.append(&Record::Vote(event.clone())));
+        unwrap!(journal.sync());
+
+        let contents = unwrap!(unwrap!(journal.read()));
+        assert_eq!(contents.version, 3);
+        assert_eq!(contents.serialised_state, vec![1, 2, 3]);
+        match &contents.records[..] {
+            [Record::Vote(read_event)] => assert_eq!(*read_event, event),
+            _ => panic!("unexpected records"),
+        }
+
+        // Starting a new instance discards the previous records.
+        unwrap!(journal.start(4, &elders_info, &[]));
+        let contents = unwrap!(unwrap!(journal.read()));
+        assert_eq!(contents.version, 4);
+        assert!(contents.records.is_empty());
+
+        unwrap!(fs::remove_dir_all(&dir));
+    }
+
+    #[test]
+    fn ignore_partially_written_record() {
+        let dir = temp_dir();
+        let mut journal = ConsensusJournal::new(&dir);
+        unwrap!(journal.start(0, &EldersInfo::default(), &[]));
+
+        let event = AccumulatingEvent::User(vec![6]).into_network_event();
+        unwrap!(journal.append(&Record::Vote(event)));
+        unwrap!(journal.sync());
+
+        // Simulate a crash in the middle of writing a record.
+        let mut file = unwrap!(OpenOptions::new().append(true).open(&journal.path));
+        unwrap!(file.write_all(&[100, 0, 0, 0, 1, 2]));
+
+        let contents = unwrap!(unwrap!(journal.read()));
+        assert_eq!(contents.records.len(), 1);
+
+        unwrap!(fs::remove_dir_all(&dir));
+    }
+
+    #[test]
+    fn ignore_record_of_invalid_size() {
+        let dir = temp_dir();
+        let mut journal = ConsensusJournal::new(&dir);
+        unwrap!(journal.start(0, &EldersInfo::default(), &[]));
+
+        // A corrupt length prefix mustn't make us allocate a huge buffer.
+        let mut file = unwrap!(OpenOptions::new().append(true).open(&journal.path));
+        unwrap!(file.write_all(&u32::MAX.to_le_bytes()));
+        unwrap!(file.write_all(&[0; 16]));
+
+        let contents = unwrap!(unwrap!(journal.read()));
+        assert!(contents.records.is_empty());
+
+        unwrap!(fs::remove_dir_all(&dir));
+    }
+}
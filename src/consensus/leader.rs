@@ -0,0 +1,950 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Simple leader based consensus.
+//!
+//! The elder with the lowest name is the leader. The other elders periodically send their signed
+//! votes to the leader, which appends them to an ordered log and replies with the log entries the
+//! sender doesn't have yet. An entry is committed once a quorum of elders confirmed having it, and
+//! committed entries are output in log order. DKG uses the leader as a trusted dealer.
+//!
+//! **This is NOT Byzantine fault tolerant** and is only compiled with the
+//! `trusted-leader-consensus` feature (or in tests). The leader is trusted completely:
+//!
+//! - it deals every DKG secret key share and sends them in plaintext, so it (and anyone who can
+//!   read the traffic) knows the section secret key,
+//! - the other elders apply its commits and DKG results without checking any signatures or quorum,
+//!   so it can commit votes nobody made quorum on,
+//! - it can censor votes (but not forge them, as every vote is signed by its voter),
+//! - there is no leader rotation, so if it stops responding the section makes no progress until
+//!   the next consensus reset.
+//!
+//! Use PARSEC for any deployment where not all the nodes are trusted.
+
+use super::{
+    algorithm::{Consensus, ConsensusRequest, ConsensusResponse, CreateGossipError, Decision},
+    config::GossipConfig,
+    parsec::{generate_bls_threshold_secret_key, DkgResult, DkgResultWrapper},
+    AccumulatingEvent, NetworkEvent, Proof,
+};
+use crate::{
+    id::{FullId, PublicId},
+    rng::{self, MainRng},
+    section::{quorum_count, EldersInfo},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Debug, Formatter},
+    mem,
+};
+
+// Number of log entries after which we vote for pruning.
+#[cfg(not(feature = "mock_base"))]
+const LOG_SIZE_LIMIT: usize = 100_000;
+#[cfg(feature = "mock_base")]
+const LOG_SIZE_LIMIT: usize = 500;
+
+/// Message exchanged between the leader and the other nodes.
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Message {
+    // Votes the sender wants the leader to order.
+    votes: Vec<Vote>,
+    // Number of log entries the sender has.
+    known: u64,
+    // Log index of the first entry in `entries`.
+    first: u64,
+    // Log entries the recipient is missing.
+    entries: Vec<Entry>,
+    // Number of committed log entries.
+    committed: u64,
+    // Serialised DKG secret key shares of the recipient, by log index of their `DkgResult` entry.
+    key_shares: Vec<(u64, Vec<u8>)>,
+}
+
+impl Debug for Message {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Message {{ votes: {}, known: {}, entries: {}..{}, committed: {} }}",
+            self.votes.len(),
+            self.known,
+            self.first,
+            self.first + self.entries.len() as u64,
+            self.committed,
+        )
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+struct Vote {
+    event: NetworkEvent,
+    proof: Proof,
+}
+
+impl Vote {
+    fn new(full_id: &FullId, event: NetworkEvent) -> Option<Self> {
+        let bytes = bincode::serialize(&event).ok()?;
+        let proof = Proof {
+            pub_id: *full_id.public_id(),
+            sig: full_id.sign(&bytes),
+        };
+
+        Some(Self { event, proof })
+    }
+
+    fn verify(&self, elders: &BTreeSet<PublicId>) -> bool {
+        if !elders.contains(&self.proof.pub_id) {
+            return false;
+        }
+
+        match bincode::serialize(&self.event) {
+            Ok(bytes) => self.proof.pub_id.verify(&bytes, &self.proof.sig),
+            Err(_) => false,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+enum Entry {
+    Vote(Vote),
+    DkgResult {
+        participants: BTreeSet<PublicId>,
+        public_key_set: bls::PublicKeySet,
+    },
+}
+
+/// Leader based implementation of `Consensus`.
+pub struct LeaderConsensus {
+    instance: Option<Instance>,
+    send_gossip: bool,
+    // Number of requests we sent within this gossip period.
+    gossip_count: usize,
+    pruning_voted_for: bool,
+    config: GossipConfig,
+}
+
+impl LeaderConsensus {
+    pub fn new(config: GossipConfig) -> Self {
+        Self {
+            instance: None,
+            send_gossip: false,
+            gossip_count: 0,
+            pruning_voted_for: false,
+            config,
+        }
+    }
+}
+
+impl Consensus for LeaderConsensus {
+    fn init(
+        &mut self,
+        rng: &mut MainRng,
+        full_id: FullId,
+        elders_info: &EldersInfo,
+        serialised_state: Vec<u8>,
+        version: u64,
+    ) {
+        if let Some(instance) = &self.instance {
+            if instance.version >= version {
+                return;
+            }
+        }
+
+        let instance = if let Some(instance) =
+            Instance::new(rng, full_id, elders_info, serialised_state, version)
+        {
+            instance
+        } else {
+            error!(
+                "Not initialising leader consensus v{} - {:?} has no elders.",
+                version, elders_info
+            );
+            return;
+        };
+
+        self.instance = Some(instance);
+        self.pruning_voted_for = false;
+        info!("Init new leader consensus v{}", version);
+    }
+
+    fn last_version(&self) -> u64 {
+        if let Some(instance) = &self.instance {
+            instance.version
+        } else {
+            log_or_panic!(log::Level::Error, "LeaderConsensus is not initialised.");
+            0
+        }
+    }
+
+    fn vote_for(&mut self, event: NetworkEvent) {
+        trace!("Vote for Event {:?}", event);
+
+        if let Some(instance) = &mut self.instance {
+            if instance.vote_for(event) {
+                self.send_gossip = true;
+            }
+        }
+    }
+
+    fn poll(&mut self) -> Option<Decision> {
+        self.instance.as_mut()?.poll()
+    }
+
+    fn our_unpolled_votes(&self) -> Vec<NetworkEvent> {
+        self.instance
+            .as_ref()
+            .map(Instance::our_unpolled_votes)
+            .unwrap_or_else(Vec::new)
+    }
+
+    fn has_unpolled_observations(&self) -> bool {
+        self.instance
+            .as_ref()
+            .map(Instance::has_unpolled_observations)
+            .unwrap_or(false)
+    }
+
+    fn create_gossip(
+        &mut self,
+        version: u64,
+        target: &PublicId,
+    ) -> Result<ConsensusRequest, CreateGossipError> {
+        let instance = self
+            .instance
+            .as_mut()
+            .filter(|instance| instance.version == version)
+            .ok_or(CreateGossipError::MissingVersion)?;
+
+        let msg = if instance.is_leader() {
+            instance.create_update(target)
+        } else if *target == instance.leader {
+            instance.create_pull()
+        } else {
+            return Err(CreateGossipError::UnexpectedTarget);
+        };
+
+        self.gossip_count += 1;
+
+        Ok(ConsensusRequest::Leader(msg))
+    }
+
+    fn handle_request(
+        &mut self,
+        version: u64,
+        request: ConsensusRequest,
+        src: PublicId,
+    ) -> Option<ConsensusResponse> {
+        let msg = match request {
+            ConsensusRequest::Leader(msg) => msg,
+            ConsensusRequest::Parsec(_) => {
+                debug!("Unexpected parsec request from {}", src);
+                return None;
+            }
+        };
+
+        let instance = self
+            .instance
+            .as_mut()
+            .filter(|instance| instance.version == version)?;
+
+        let response = if instance.is_leader() {
+            instance.handle_pull(msg, &src)
+        } else if src == instance.leader {
+            instance.handle_update(msg);
+            instance.create_pull()
+        } else {
+            debug!("Unexpected leader consensus request from {}", src);
+            return None;
+        };
+
+        Some(ConsensusResponse::Leader(response))
+    }
+
+    fn handle_response(&mut self, version: u64, response: ConsensusResponse, src: PublicId) {
+        let msg = match response {
+            ConsensusResponse::Leader(msg) => msg,
+            ConsensusResponse::Parsec(_) => {
+                debug!("Unexpected parsec response from {}", src);
+                return;
+            }
+        };
+
+        let instance = if let Some(instance) = self
+            .instance
+            .as_mut()
+            .filter(|instance| instance.version == version)
+        {
+            instance
+        } else {
+            return;
+        };
+
+        if instance.is_leader() {
+            instance.handle_ack(&msg, &src);
+        } else if src == instance.leader {
+            instance.handle_update(msg);
+
+            // Keep pulling until everything we have is committed.
+            if instance.is_elder() && instance.committed < instance.log.len() {
+                self.send_gossip = true;
+            }
+        } else {
+            debug!("Unexpected leader consensus response from {}", src);
+        }
+    }
+
+    fn gossip_recipients(&self) -> Vec<&PublicId> {
+        match &self.instance {
+            Some(instance) if instance.is_elder() && !instance.is_leader() => {
+                vec![&instance.leader]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn should_send_gossip(&mut self) -> bool {
+        let send_gossip = mem::replace(&mut self.send_gossip, false);
+
+        if !send_gossip {
+            return false;
+        }
+
+        if self.gossip_count >= self.config.limit {
+            trace!("not sending leader consensus request: limit reached");
+            return false;
+        }
+
+        true
+    }
+
+    fn reset_gossip_period(&mut self) {
+        self.gossip_count = 0;
+
+        // Members have to keep pulling from the leader to learn about new entries and commits.
+        if let Some(instance) = &self.instance {
+            if instance.is_elder() && !instance.is_leader() {
+                self.send_gossip = true;
+            }
+        }
+    }
+
+    fn prune_if_needed(&mut self) {
+        let log_size = self
+            .instance
+            .as_ref()
+            .map(|instance| instance.log.len())
+            .unwrap_or(0);
+
+        if log_size > LOG_SIZE_LIMIT && !self.pruning_voted_for {
+            self.vote_for(AccumulatingEvent::ParsecPrune.into_network_event());
+            self.pruning_voted_for = true;
+        }
+    }
+}
+
+// Single versioned instance of the leader consensus.
+struct Instance {
+    version: u64,
+    full_id: FullId,
+    elders: BTreeSet<PublicId>,
+    leader: PublicId,
+    rng: MainRng,
+    genesis: Option<Decision>,
+    log: Vec<Entry>,
+    // Number of entries known to be committed.
+    committed: usize,
+    // Number of entries already output by `poll`.
+    polled: usize,
+    // Our votes the leader hasn't ordered yet.
+    our_pending: Vec<Vote>,
+    // Our DKG secret key shares, by log index of their `DkgResult` entry.
+    our_key_shares: BTreeMap<usize, bls::SecretKeyShare>,
+    // State only the leader maintains.
+    leader_state: Option<LeaderState>,
+}
+
+#[derive(Default)]
+struct LeaderState {
+    // Number of log entries each node confirmed having.
+    acks: BTreeMap<PublicId, usize>,
+    // Votes already in the log, to reject duplicates.
+    ordered: BTreeSet<(PublicId, NetworkEvent)>,
+    // Voters of `StartDkg` by its participants.
+    dkg_votes: BTreeMap<BTreeSet<PublicId>, BTreeSet<PublicId>>,
+    // DKG secret key shares of all the participants, by log index of their `DkgResult` entry.
+    key_shares: BTreeMap<usize, BTreeMap<PublicId, bls::SecretKeyShare>>,
+}
+
+impl Instance {
+    fn new(
+        rng: &mut MainRng,
+        full_id: FullId,
+        elders_info: &EldersInfo,
+        serialised_state: Vec<u8>,
+        version: u64,
+    ) -> Option<Self> {
+        let elders: BTreeSet<_> = elders_info.elder_ids().copied().collect();
+        // `EldersInfo` is keyed by name, so the first elder is the one with the lowest name.
+        let leader = *elders_info.elder_ids().next()?;
+        let leader_state = if leader == *full_id.public_id() {
+            Some(LeaderState::default())
+        } else {
+            None
+        };
+
+        Some(Self {
+            version,
+            full_id,
+            genesis: Some(Decision::Genesis {
+                group: elders.clone(),
+                related_info: serialised_state,
+            }),
+            elders,
+            leader,
+            rng: rng::new_from(rng),
+            log: Vec::new(),
+            committed: 0,
+            polled: 0,
+            our_pending: Vec::new(),
+            our_key_shares: BTreeMap::new(),
+            leader_state,
+        })
+    }
+
+    fn our_id(&self) -> &PublicId {
+        self.full_id.public_id()
+    }
+
+    fn is_elder(&self) -> bool {
+        self.elders.contains(self.our_id())
+    }
+
+    fn is_leader(&self) -> bool {
+        self.leader_state.is_some()
+    }
+
+    // Returns whether the vote needs to be sent to the leader.
+    fn vote_for(&mut self, event: NetworkEvent) -> bool {
+        if !self.is_elder() {
+            trace!("Not voting for {:?}: not an elder", event);
+            return false;
+        }
+
+        let vote = if let Some(vote) = Vote::new(&self.full_id, event) {
+            vote
+        } else {
+            return false;
+        };
+
+        if self.is_leader() {
+            self.order(vote);
+            self.update_committed();
+            false
+        } else {
+            self.our_pending.push(vote);
+            true
+        }
+    }
+
+    fn poll(&mut self) -> Option<Decision> {
+        if let Some(genesis) = self.genesis.take() {
+            return Some(genesis);
+        }
+
+        while self.polled < self.committed {
+            let index = self.polled;
+            self.polled += 1;
+
+            match self.log[index].clone() {
+                Entry::Vote(Vote { event, .. })
+                    if matches!(event.payload, AccumulatingEvent::StartDkg(_)) =>
+                {
+                    // `StartDkg` votes are ordered only to let the voters know the leader got
+                    // them. The outcome is the `DkgResult` entry.
+                    continue;
+                }
+                Entry::Vote(Vote { event, proof }) => return Some(Decision::Vote { event, proof }),
+                Entry::DkgResult {
+                    participants,
+                    public_key_set,
+                } => {
+                    let secret_key_share = self.our_key_shares.remove(&index);
+                    if secret_key_share.is_none() && participants.contains(self.our_id()) {
+                        log_or_panic!(
+                            log::Level::Error,
+                            "Missing our DKG secret key share for {:?}",
+                            participants
+                        );
+                    }
+
+                    return Some(Decision::DkgResult {
+                        participants,
+                        dkg_result: DkgResultWrapper(DkgResult::new(
+                            public_key_set,
+                            secret_key_share,
+                        )),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    fn our_unpolled_votes(&self) -> Vec<NetworkEvent> {
+        let our_id = self.our_id();
+        let ordered = self.log[self.polled..]
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Vote(vote) if vote.proof.pub_id == *our_id => Some(&vote.event),
+                Entry::Vote(_) | Entry::DkgResult { .. } => None,
+            });
+
+        self.our_pending
+            .iter()
+            .map(|vote| &vote.event)
+            .chain(ordered)
+            .filter(|event| !matches!(event.payload, AccumulatingEvent::StartDkg(_)))
+            .cloned()
+            .collect()
+    }
+
+    fn has_unpolled_observations(&self) -> bool {
+        self.genesis.is_some() || self.polled < self.log.len() || !self.our_pending.is_empty()
+    }
+
+    // Request of a member to the leader, carrying our pending votes.
+    fn create_pull(&self) -> Message {
+        Message {
+            votes: self.our_pending.clone(),
+            known: self.log.len() as u64,
+            first: self.log.len() as u64,
+            entries: vec![],
+            committed: self.committed as u64,
+            key_shares: vec![],
+        }
+    }
+
+    // Message of the leader with the log entries `target` is missing.
+    fn create_update(&self, target: &PublicId) -> Message {
+        let first = self
+            .leader_state
+            .as_ref()
+            .and_then(|state| state.acks.get(target))
+            .copied()
+            .unwrap_or(0);
+        self.create_update_from(target, first)
+    }
+
+    fn create_update_from(&self, target: &PublicId, first: usize) -> Message {
+        let first = first.min(self.log.len());
+        let key_shares = self
+            .leader_state
+            .iter()
+            .flat_map(|state| state.key_shares.range(first..))
+            .filter_map(|(index, shares)| {
+                let share = shares.get(target)?;
+                let bytes = bincode::serialize(&bls::serde_impl::SerdeSecret(share)).ok()?;
+                Some((*index as u64, bytes))
+            })
+            .collect();
+
+        Message {
+            votes: vec![],
+            known: self.log.len() as u64,
+            first: first as u64,
+            entries: self.log[first..].to_vec(),
+            committed: self.committed as u64,
+            key_shares,
+        }
+    }
+
+    // Leader: handle a pull request from a member.
+    fn handle_pull(&mut self, msg: Message, src: &PublicId) -> Message {
+        for vote in msg.votes {
+            if vote.proof.pub_id == *src {
+                self.order(vote);
+            } else {
+                debug!("Vote from {} relayed by {}", vote.proof.pub_id, src);
+            }
+        }
+
+        self.handle_ack(&msg, src);
+        self.create_update_from(src, msg.known as usize)
+    }
+
+    // Leader: record the number of log entries `src` has.
+    fn handle_ack(&mut self, msg: &Message, src: &PublicId) {
+        let known = (msg.known as usize).min(self.log.len());
+        if let Some(state) = &mut self.leader_state {
+            let _ = state.acks.insert(*src, known);
+        }
+
+        self.update_committed();
+    }
+
+    // Member: apply the log entries received from the leader.
+    fn handle_update(&mut self, msg: Message) {
+        let first = msg.first as usize;
+        if first > self.log.len() {
+            trace!(
+                "Ignoring leader consensus update: missing entries {}..{}",
+                self.log.len(),
+                first
+            );
+            return;
+        }
+
+        for entry in msg.entries.into_iter().skip(self.log.len() - first) {
+            if let Entry::Vote(vote) = &entry {
+                if !vote.verify(&self.elders) {
+                    log_or_panic!(
+                        log::Level::Error,
+                        "Invalid vote in leader consensus log: {:?}",
+                        vote
+                    );
+                    break;
+                }
+
+                self.our_pending.retain(|pending| pending != vote);
+            }
+
+            self.log.push(entry);
+        }
+
+        for (index, bytes) in msg.key_shares {
+            match bincode::deserialize::<bls::serde_impl::SerdeSecret<bls::SecretKeyShare>>(&bytes)
+            {
+                Ok(share) => {
+                    let _ = self.our_key_shares.insert(index as usize, share.0);
+                }
+                Err(error) => debug!("Failed to deserialise DKG key share: {:?}", error),
+            }
+        }
+
+        self.committed = self
+            .committed
+            .max((msg.committed as usize).min(self.log.len()));
+    }
+
+    // Leader: append the vote to the log, unless invalid or already there.
+    fn order(&mut self, vote: Vote) {
+        if !vote.verify(&self.elders) {
+            debug!("Invalid vote from {}: {:?}", vote.proof.pub_id, vote.event);
+            return;
+        }
+
+        let state = if let Some(state) = &mut self.leader_state {
+            state
+        } else {
+            return;
+        };
+
+        if !state
+            .ordered
+            .insert((vote.proof.pub_id, vote.event.clone()))
+        {
+            return;
+        }
+
+        let start_dkg = if let AccumulatingEvent::StartDkg(participants) = &vote.event.payload {
+            let voters = state.dkg_votes.entry(participants.clone()).or_default();
+            let _ = voters.insert(vote.proof.pub_id);
+
+            if voters.len() == quorum_count(self.elders.len()) {
+                Some(participants.clone())
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        self.log.push(Entry::Vote(vote));
+
+        if let Some(participants) = start_dkg {
+            self.deal_keys(participants);
+        }
+    }
+
+    // Leader: act as trusted dealer of the keys for the given participants.
+    fn deal_keys(&mut self, participants: BTreeSet<PublicId>) {
+        let secret_key_set = generate_bls_threshold_secret_key(&mut self.rng, participants.len());
+        let shares: BTreeMap<_, _> = participants
+            .iter()
+            .enumerate()
+            .map(|(index, pub_id)| (*pub_id, secret_key_set.secret_key_share(index)))
+            .collect();
+
+        let index = self.log.len();
+        if let Some(share) = shares.get(self.full_id.public_id()) {
+            let _ = self.our_key_shares.insert(index, share.clone());
+        }
+        if let Some(state) = &mut self.leader_state {
+            let _ = state.key_shares.insert(index, shares);
+        }
+
+        self.log.push(Entry::DkgResult {
+            participants,
+            public_key_set: secret_key_set.public_keys(),
+        });
+    }
+
+    // Leader: commit all the entries a quorum of elders has.
+    fn update_committed(&mut self) {
+        let state = if let Some(state) = &self.leader_state {
+            state
+        } else {
+            return;
+        };
+
+        let mut known: Vec<_> = self
+            .elders
+            .iter()
+            .map(|pub_id| {
+                if pub_id == self.full_id.public_id() {
+                    self.log.len()
+                } else {
+                    state.acks.get(pub_id).copied().unwrap_or(0)
+                }
+            })
+            .collect();
+        known.sort_unstable_by(|lhs, rhs| rhs.cmp(lhs));
+
+        let quorum = quorum_count(self.elders.len());
+        if let Some(committed) = known.get(quorum - 1) {
+            self.committed = self.committed.max(*committed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{id::P2pNode, rng, unwrap, xor_space::Prefix};
+
+    fn create_elders(rng: &mut MainRng, count: usize) -> (Vec<FullId>, EldersInfo) {
+        let mut full_ids: Vec<_> = (0..count).map(|_| FullId::gen(rng)).collect();
+        full_ids.sort_by(|lhs, rhs| lhs.public_id().name().cmp(rhs.public_id().name()));
+
+        let elders = full_ids
+            .iter()
+            .enumerate()
+            .map(|(index, id)| {
+                let addr = ([127, 0, 0, 1], 9000 + index as u16).into();
+                (*id.public_id().name(), P2pNode::new(*id.public_id(), addr))
+            })
+            .collect();
+
+        (full_ids, EldersInfo::new(elders, Prefix::default(), 0))
+    }
+
+    fn create_nodes(rng: &mut MainRng, count: usize) -> Vec<LeaderConsensus> {
+        let (full_ids, elders_info) = create_elders(rng, count);
+        full_ids
+            .into_iter()
+            .map(|full_id| {
+                let mut consensus = LeaderConsensus::new(GossipConfig::default());
+                consensus.init(rng, full_id, &elders_info, vec![], 0);
+                consensus
+            })
+            .collect()
+    }
+
+    fn id(node: &LeaderConsensus) -> PublicId {
+        *unwrap!(node.instance.as_ref()).our_id()
+    }
+
+    // Every member pulls from the leader (node 0) once.
+    fn exchange(nodes: &mut [LeaderConsensus]) {
+        let leader_id = id(&nodes[0]);
+
+        for index in 1..nodes.len() {
+            let member_id = id(&nodes[index]);
+            let request = unwrap!(nodes[index].create_gossip(0, &leader_id));
+            let response = unwrap!(nodes[0].handle_request(0, request, member_id));
+            nodes[index].handle_response(0, response, leader_id);
+        }
+    }
+
+    // Enough exchanges for the leader to order the votes, for the members to acknowledge them and
+    // to learn they got committed.
+    fn exchange_until_committed(nodes: &mut [LeaderConsensus]) {
+        for _ in 0..3 {
+            exchange(nodes);
+        }
+    }
+
+    fn poll_all(node: &mut LeaderConsensus) -> Vec<Decision> {
+        std::iter::from_fn(|| node.poll()).collect()
+    }
+
+    fn user_event(payload: u8) -> NetworkEvent {
+        AccumulatingEvent::User(vec![payload]).into_network_event()
+    }
+
+    fn polled_votes(decisions: Vec<Decision>) -> Vec<(NetworkEvent, PublicId)> {
+        decisions
+            .into_iter()
+            .filter_map(|decision| match decision {
+                Decision::Vote { event, proof } => Some((event, proof.pub_id)),
+                Decision::Genesis { .. }
+                | Decision::DkgResult { .. }
+                | Decision::Accusation { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_node_commits_immediately() {
+        let mut rng = rng::new();
+        let mut nodes = create_nodes(&mut rng, 1);
+
+        nodes[0].vote_for(user_event(0));
+
+        let decisions = poll_all(&mut nodes[0]);
+        assert!(matches!(decisions[0], Decision::Genesis { .. }));
+        assert_eq!(
+            polled_votes(decisions),
+            vec![(user_event(0), id(&nodes[0]))]
+        );
+        assert!(!nodes[0].has_unpolled_observations());
+    }
+
+    #[test]
+    fn votes_are_output_in_the_same_order_by_all_nodes() {
+        let mut rng = rng::new();
+        let mut nodes = create_nodes(&mut rng, 4);
+
+        for (index, node) in nodes.iter_mut().enumerate().rev() {
+            node.vote_for(user_event(index as u8));
+        }
+
+        // Not committed before a quorum has the entries.
+        assert_eq!(polled_votes(poll_all(&mut nodes[0])), vec![]);
+
+        exchange_until_committed(&mut nodes);
+
+        let expected = polled_votes(poll_all(&mut nodes[0]));
+        assert_eq!(expected.len(), 4);
+
+        for node in &mut nodes[1..] {
+            assert_eq!(polled_votes(poll_all(node)), expected);
+            assert!(!node.has_unpolled_observations());
+        }
+    }
+
+    #[test]
+    fn duplicate_and_forged_votes_are_not_ordered() {
+        let mut rng = rng::new();
+        let mut nodes = create_nodes(&mut rng, 4);
+        let leader_id = id(&nodes[0]);
+        let member_id = id(&nodes[1]);
+
+        nodes[1].vote_for(user_event(0));
+        let request = unwrap!(nodes[1].create_gossip(0, &leader_id));
+
+        let _ = nodes[0].handle_request(0, request.clone(), member_id);
+        let _ = nodes[0].handle_request(0, request.clone(), member_id);
+        // Relayed by someone other than the voter.
+        let _ = nodes[0].handle_request(0, request, id(&nodes[2]));
+
+        assert_eq!(unwrap!(nodes[0].instance.as_ref()).log.len(), 1);
+    }
+
+    #[test]
+    fn dkg_result_is_dealt_to_participants() {
+        let mut rng = rng::new();
+        let mut nodes = create_nodes(&mut rng, 4);
+        let participants: BTreeSet<_> = nodes.iter().map(id).collect();
+
+        for node in &mut nodes {
+            node.vote_for(AccumulatingEvent::StartDkg(participants.clone()).into_network_event());
+        }
+
+        exchange_until_committed(&mut nodes);
+
+        let message = b"hello";
+        let mut public_key_set = None;
+        let mut signature_shares = BTreeMap::new();
+
+        for (index, node) in nodes.iter_mut().enumerate() {
+            let dkg_result = poll_all(node)
+                .into_iter()
+                .find_map(|decision| match decision {
+                    Decision::DkgResult {
+                        participants: actual,
+                        dkg_result,
+                    } => {
+                        assert_eq!(actual, participants);
+                        Some(dkg_result.0)
+                    }
+                    Decision::Genesis { .. }
+                    | Decision::Vote { .. }
+                    | Decision::Accusation { .. } => None,
+                })
+                .expect("no DKG result");
+
+            if let Some(pk_set) = &public_key_set {
+                assert_eq!(*pk_set, dkg_result.public_key_set);
+            } else {
+                public_key_set = Some(dkg_result.public_key_set.clone());
+            }
+
+            let secret_key_share = dkg_result.secret_key_share.expect("no secret key share");
+            let _ = signature_shares.insert(index, secret_key_share.sign(message));
+        }
+
+        let public_key_set = unwrap!(public_key_set);
+        let signature = public_key_set
+            .combine_signatures(
+                signature_shares
+                    .iter()
+                    .map(|(index, share)| (*index, share)),
+            )
+            .unwrap();
+        assert!(public_key_set.public_key().verify(&signature, message));
+    }
+
+    #[test]
+    fn empty_elders_info_is_rejected() {
+        let mut rng = rng::new();
+        let (mut full_ids, elders_info) = create_elders(&mut rng, 1);
+        let full_id = full_ids.remove(0);
+
+        let mut node = LeaderConsensus::new(GossipConfig::default());
+        node.init(&mut rng, full_id.clone(), &elders_info, vec![], 0);
+
+        // A newer instance with no elders is ignored and the current one kept.
+        let empty_info = EldersInfo::new(Default::default(), Prefix::default(), 1);
+        node.init(&mut rng, full_id, &empty_info, vec![], 1);
+        assert_eq!(node.last_version(), 0);
+
+        node.vote_for(user_event(0));
+        assert_eq!(
+            polled_votes(poll_all(&mut node)),
+            vec![(user_event(0), id(&node))]
+        );
+    }
+
+    #[test]
+    fn unpolled_votes_are_returned_for_revote() {
+        let mut rng = rng::new();
+        let mut nodes = create_nodes(&mut rng, 4);
+
+        nodes[1].vote_for(user_event(1));
+        assert_eq!(nodes[1].our_unpolled_votes(), vec![user_event(1)]);
+
+        exchange(&mut nodes);
+        assert_eq!(nodes[1].our_unpolled_votes(), vec![user_event(1)]);
+
+        exchange_until_committed(&mut nodes);
+        let _ = poll_all(&mut nodes[1]);
+        assert!(nodes[1].our_unpolled_votes().is_empty());
+    }
+}
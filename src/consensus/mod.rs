@@ -6,20 +6,30 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod algorithm;
+mod config;
+mod dkg_tracker;
 mod event_accumulator;
 mod genesis_prefix_info;
+mod journal;
+#[cfg(any(test, feature = "trusted-leader-consensus"))]
+mod leader;
 mod network_event;
 mod parsec;
 mod proof;
 
 pub use self::{
+    algorithm::{
+        ConsensusAlgorithm, ConsensusRequest, ConsensusResponse, CreateGossipError, Decision,
+    },
+    config::{ConsensusConfig, GossipConfig, GOSSIP_PERIOD, JOURNAL_SYNC_INTERVAL, STALL_TIMEOUT},
+    dkg_tracker::{DkgRound, DkgTracker, DKG_MAX_ATTEMPTS, DKG_TIMEOUT},
     event_accumulator::{AccumulatingProof, InsertError},
     genesis_prefix_info::GenesisPrefixInfo,
     network_event::{AccumulatingEvent, NetworkEvent, OnlinePayload},
     parsec::{
-        generate_bls_threshold_secret_key, generate_first_dkg_result, Block, CreateGossipError,
-        DkgResult, DkgResultWrapper, Observation, ParsecNetworkEvent, Request as ParsecRequest,
-        Response as ParsecResponse, GOSSIP_PERIOD,
+        generate_bls_threshold_secret_key, generate_first_dkg_result, DkgResult, DkgResultWrapper,
+        Observation, ParsecNetworkEvent, Request as ParsecRequest,
     },
     proof::{Proof, ProofSet},
 };
@@ -27,8 +37,12 @@ pub use self::{
 #[cfg(feature = "mock_base")]
 pub use self::event_accumulator::{UNRESPONSIVE_THRESHOLD, UNRESPONSIVE_WINDOW};
 
+#[cfg(any(test, feature = "trusted-leader-consensus"))]
+use self::leader::LeaderConsensus;
 use self::{
+    algorithm::Consensus,
     event_accumulator::{EventAccumulator, RemainingEvents},
+    journal::{ConsensusJournal, JournalContents, Record},
     parsec::ParsecMap,
 };
 use crate::{
@@ -36,30 +50,241 @@ use crate::{
     messages::Variant,
     rng::MainRng,
     section::EldersInfo,
-    time::Duration,
+    time::{Duration, Instant},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    mem,
 };
-use std::collections::BTreeSet;
 
-// Distributed consensus mechanism backed by the Parsec algorithm.
+/// State restored from the consensus journal when the engine was created.
+pub enum Restored {
+    /// The journalled instance was resumed. Contains our serialised section state as of the last
+    /// checkpoint. The decisions polled before it won't be polled again.
+    SharedState(Vec<u8>),
+    /// The journal was of an earlier instance. Contains the events we voted for in it that didn't
+    /// accumulate, to vote for again.
+    Votes(Vec<NetworkEvent>),
+}
+
+// Distributed consensus mechanism backed by the selected consensus algorithm.
 pub struct ConsensusEngine {
-    parsec_map: ParsecMap,
+    config: ConsensusConfig,
+    consensus: Box<dyn Consensus>,
     accumulator: EventAccumulator,
+    // Current gossip period. Adapts to the load between the busy and idle periods of the config.
+    gossip_period: Duration,
+    journal: Option<ConsensusJournal>,
+    // Number of decisions polled from the current instance, and how many of them the last
+    // checkpoint in the journal covers.
+    polled: u64,
+    checkpoint_polled: u64,
+    restored: Option<Restored>,
+    // Liveness monitor: when we last made progress and when we last heard from each peer.
+    last_progress: Instant,
+    last_heard: BTreeMap<PublicId, Instant>,
+    // Number of consecutive stall timeouts elapsed without progress.
+    stall_checks: u32,
+    // Peers proven malicious by the consensus algorithm that weren't reported yet.
+    accused: BTreeSet<PublicId>,
 }
 
 impl ConsensusEngine {
     pub fn new(
+        config: ConsensusConfig,
         rng: &mut MainRng,
         full_id: FullId,
         elders_info: &EldersInfo,
         serialised_state: Vec<u8>,
         parsec_version: u64,
     ) -> Self {
-        let mut parsec_map = ParsecMap::default();
-        parsec_map.init(rng, full_id, elders_info, serialised_state, parsec_version);
-
-        Self {
-            parsec_map,
+        let consensus: Box<dyn Consensus> = match config.algorithm {
+            ConsensusAlgorithm::Parsec => Box::new(ParsecMap::new(config.gossip)),
+            #[cfg(any(test, feature = "trusted-leader-consensus"))]
+            ConsensusAlgorithm::Leader => Box::new(LeaderConsensus::new(config.gossip)),
+        };
+        let gossip_period = config.gossip.busy_period;
+        let journal = config.store_dir.as_deref().map(ConsensusJournal::new);
+
+        let mut engine = Self {
+            config,
+            consensus,
             accumulator: EventAccumulator::default(),
+            gossip_period,
+            journal,
+            polled: 0,
+            checkpoint_polled: 0,
+            restored: None,
+            last_progress: Instant::now(),
+            last_heard: BTreeMap::new(),
+            stall_checks: 0,
+            accused: BTreeSet::new(),
+        };
+
+        if !engine.restore(rng, full_id.clone(), elders_info, parsec_version) {
+            engine.finalise_reset(rng, full_id, elders_info, serialised_state, parsec_version);
+        }
+
+        engine
+    }
+
+    pub fn config(&self) -> &ConsensusConfig {
+        &self.config
+    }
+
+    // Restores the instance of the given version by replaying the journal, if the journal is of
+    // that instance. Returns whether it did. The connection info of the elders might have changed
+    // since the journal was written (e.g. our own after the restart), so the instance is identified
+    // by its version and prefix only. If the journal is of an earlier instance instead, only our
+    // votes that didn't accumulate in it are restored, to be voted for again by the caller.
+    fn restore(
+        &mut self,
+        rng: &mut MainRng,
+        full_id: FullId,
+        elders_info: &EldersInfo,
+        parsec_version: u64,
+    ) -> bool {
+        let contents = match self.journal.as_ref().map(ConsensusJournal::read) {
+            Some(Ok(Some(contents))) => contents,
+            Some(Err(error)) => {
+                warn!("Failed to read the consensus journal: {}", error);
+                return false;
+            }
+            _ => return false,
+        };
+
+        if contents.version == parsec_version && contents.elders_info.prefix == elders_info.prefix {
+            self.resume(rng, full_id, contents);
+            true
+        } else if contents.version < parsec_version
+            && contents
+                .elders_info
+                .prefix
+                .is_compatible(&elders_info.prefix)
+        {
+            let votes = unaccumulated_votes(full_id.public_id(), contents.records);
+            info!(
+                "Restoring {} votes from the journal of consensus v{}",
+                votes.len(),
+                contents.version
+            );
+            self.restored = Some(Restored::Votes(votes));
+            false
+        } else {
+            false
+        }
+    }
+
+    fn resume(&mut self, rng: &mut MainRng, full_id: FullId, contents: JournalContents) {
+        info!(
+            "Restoring consensus v{} from {} journalled records",
+            contents.version,
+            contents.records.len()
+        );
+
+        // Starting the instance rewrites the journal, dropping any partially written record.
+        self.finalise_reset(
+            rng,
+            full_id,
+            &contents.elders_info,
+            contents.serialised_state,
+            contents.version,
+        );
+
+        let mut checkpoint = None;
+
+        for record in contents.records {
+            match record {
+                Record::Init { .. } => (),
+                Record::Vote(event) => self.vote_for(event),
+                Record::Request {
+                    version,
+                    src,
+                    request,
+                } => {
+                    let _ = self.handle_request(version, request, src);
+                }
+                Record::Response {
+                    version,
+                    src,
+                    response,
+                } => self.handle_consensus_response(version, response, src),
+                Record::Gossip { version, target } => {
+                    let _ = self.gossip(version, &target);
+                }
+                Record::Checkpoint {
+                    polled,
+                    accumulator,
+                    serialised_state,
+                } => checkpoint = Some((polled, accumulator, serialised_state)),
+            }
+        }
+
+        // The decisions covered by the checkpoint were already handled before the restart, so
+        // skip them and take their outcome from the checkpoint instead.
+        if let Some((polled, accumulator, serialised_state)) = checkpoint {
+            while self.polled < polled && self.consensus.poll().is_some() {
+                self.polled += 1;
+            }
+
+            self.accumulator = accumulator;
+            self.checkpoint(serialised_state.clone());
+            self.restored = Some(Restored::SharedState(serialised_state));
+        }
+
+        self.sync_journal();
+    }
+
+    /// Takes the state restored from the journal when the engine was created, if any.
+    pub fn take_restored(&mut self) -> Option<Restored> {
+        self.restored.take()
+    }
+
+    /// Returns whether decisions were polled since the last checkpoint and so a new one should be
+    /// written.
+    pub fn needs_checkpoint(&self) -> bool {
+        self.journal.is_some() && self.polled != self.checkpoint_polled
+    }
+
+    /// Journals the state derived from the decisions polled so far, given our serialised section
+    /// state, so that a restarted instance doesn't handle them again.
+    pub fn checkpoint(&mut self, serialised_state: Vec<u8>) {
+        if self.journal.is_none() {
+            return;
+        }
+
+        let record = Record::Checkpoint {
+            polled: self.polled,
+            accumulator: self.accumulator.clone(),
+            serialised_state,
+        };
+        self.journal(|| record);
+        self.checkpoint_polled = self.polled;
+    }
+
+    fn journal(&mut self, record: impl FnOnce() -> Record) {
+        if let Some(journal) = &mut self.journal {
+            if let Err(error) = journal.append(&record()) {
+                warn!("Failed to write to the consensus journal: {}", error);
+            }
+        }
+    }
+
+    /// Returns whether there are journalled records not synced to disk yet. The consensus messages
+    /// created since the last sync must not be sent before `sync_journal`.
+    pub fn needs_journal_sync(&self) -> bool {
+        self.journal
+            .as_ref()
+            .map(ConsensusJournal::is_dirty)
+            .unwrap_or(false)
+    }
+
+    /// Makes sure the journal is on disk, so we can send what we derived from it.
+    pub fn sync_journal(&mut self) {
+        if let Some(journal) = &mut self.journal {
+            if let Err(error) = journal.sync() {
+                warn!("Failed to sync the consensus journal: {}", error);
+            }
         }
     }
 
@@ -68,8 +293,12 @@ impl ConsensusEngine {
         &mut self,
         our_elders: &EldersInfo,
     ) -> Option<(AccumulatingEvent, AccumulatingProof)> {
-        while let Some(block) = self.parsec_map.poll() {
-            if let Some(output) = self.handle_parsec_block(block, our_elders) {
+        while let Some(decision) = self.consensus.poll() {
+            self.polled += 1;
+            self.last_progress = Instant::now();
+            self.stall_checks = 0;
+
+            if let Some(output) = self.handle_decision(decision, our_elders) {
                 return Some(output);
             }
         }
@@ -77,49 +306,42 @@ impl ConsensusEngine {
         None
     }
 
-    fn handle_parsec_block(
+    fn handle_decision(
         &mut self,
-        block: Block,
+        decision: Decision,
         our_elders: &EldersInfo,
     ) -> Option<(AccumulatingEvent, AccumulatingProof)> {
-        // TODO: implement Block::into_payload in parsec to avoid cloning.
-        match block.payload() {
-            Observation::Genesis {
+        match decision {
+            Decision::Genesis {
                 group,
                 related_info,
             } => {
                 // FIXME: Validate with Chain info.
 
                 trace!(
-                    "Parsec Genesis v{}: group: {:?}, related_info: {}",
-                    self.parsec_map.last_version(),
+                    "Consensus Genesis v{}: group: {:?}, related_info: {}",
+                    self.consensus.last_version(),
                     group,
                     related_info.len()
                 );
 
                 Some((
                     AccumulatingEvent::Genesis {
-                        group: group.clone(),
-                        related_info: related_info.clone(),
+                        group,
+                        related_info,
                     },
                     AccumulatingProof::default(),
                 ))
             }
-            Observation::OpaquePayload(event) => {
-                let proof = block.proofs().iter().next()?;
-                let proof = Proof {
-                    pub_id: *proof.public_id(),
-                    sig: *proof.signature(),
-                };
-
+            Decision::Vote { event, proof } => {
                 trace!(
-                    "Parsec OpaquePayload v{}: {} - {:?}",
-                    self.parsec_map.last_version(),
+                    "Consensus Vote v{}: {} - {:?}",
+                    self.consensus.last_version(),
                     proof.pub_id(),
                     event
                 );
 
-                let (event, signature) = AccumulatingEvent::from_network_event(event.clone());
+                let (event, signature) = AccumulatingEvent::from_network_event(event);
 
                 // TODO: merge these three steps (add_proof, incomplete_events, poll_event) into a
                 // single one, to make the process less fragile.
@@ -144,34 +366,30 @@ impl ConsensusEngine {
                 self.accumulator
                     .poll_event(event, our_elders.elder_ids().cloned().collect())
             }
-            Observation::DkgResult {
+            Decision::DkgResult {
                 participants,
                 dkg_result,
             } => {
                 trace!(
-                    "Parsec DkgResult v{}: {:?}",
-                    self.parsec_map.last_version(),
+                    "Consensus DkgResult v{}: {:?}",
+                    self.consensus.last_version(),
                     participants
                 );
                 Some((
                     AccumulatingEvent::DkgResult {
-                        participants: participants.clone(),
-                        dkg_result: dkg_result.clone(),
+                        participants,
+                        dkg_result,
                     },
                     AccumulatingProof::default(),
                 ))
             }
-            Observation::Add { .. }
-            | Observation::Remove { .. }
-            | Observation::Accusation { .. }
-            | Observation::StartDkg(_)
-            | Observation::DkgMessage(_) => {
-                log_or_panic!(
-                    log::Level::Error,
-                    "unexpected Parsec observation v{}: {:?}",
-                    self.parsec_map.last_version(),
-                    block.payload()
+            Decision::Accusation { offender } => {
+                trace!(
+                    "Consensus Accusation v{}: {}",
+                    self.consensus.last_version(),
+                    offender
                 );
+                let _ = self.accused.insert(offender);
                 None
             }
         }
@@ -194,6 +412,11 @@ impl ConsensusEngine {
             | AccumulatingEvent::ParsecPrune
             | AccumulatingEvent::Relocate(_)
             | AccumulatingEvent::RelocatePrepare(_, _)
+            | AccumulatingEvent::RelocateRequest { .. }
+            | AccumulatingEvent::Admission { .. }
+            | AccumulatingEvent::Ban(_)
+            | AccumulatingEvent::RequestMerge(_)
+            | AccumulatingEvent::Merge(_)
             | AccumulatingEvent::User(_) => our_elders.is_quorum(proofs),
 
             AccumulatingEvent::Genesis { .. }
@@ -215,22 +438,7 @@ impl ConsensusEngine {
 
         unaccumulated_events
             .into_iter()
-            .chain(
-                self.parsec_map
-                    .our_unpolled_observations()
-                    .filter_map(|obs| match obs {
-                        parsec::Observation::OpaquePayload(event) => Some(event),
-
-                        parsec::Observation::Genesis { .. }
-                        | parsec::Observation::Add { .. }
-                        | parsec::Observation::Remove { .. }
-                        | parsec::Observation::Accusation { .. }
-                        | parsec::Observation::StartDkg(_)
-                        | parsec::Observation::DkgResult { .. }
-                        | parsec::Observation::DkgMessage(_) => None,
-                    })
-                    .cloned(),
-            )
+            .chain(self.consensus.our_unpolled_votes())
             .filter(|event| !accumulated_events.contains(&event.payload))
             .collect()
     }
@@ -244,7 +452,19 @@ impl ConsensusEngine {
         serialised_state: Vec<u8>,
         parsec_version: u64,
     ) {
-        self.parsec_map
+        self.last_progress = Instant::now();
+        self.last_heard.clear();
+        self.stall_checks = 0;
+        self.polled = 0;
+        self.checkpoint_polled = 0;
+
+        if let Some(journal) = &mut self.journal {
+            if let Err(error) = journal.start(parsec_version, elders_info, &serialised_state) {
+                warn!("Failed to start the consensus journal: {}", error);
+            }
+        }
+
+        self.consensus
             .init(rng, full_id, elders_info, serialised_state, parsec_version)
     }
 
@@ -255,8 +475,14 @@ impl ConsensusEngine {
         self.accumulator.detect_unresponsive(members)
     }
 
+    /// Returns the peers proven malicious since the last call.
+    pub fn take_accused(&mut self) -> BTreeSet<PublicId> {
+        mem::take(&mut self.accused)
+    }
+
     pub fn vote_for(&mut self, event: NetworkEvent) {
-        self.parsec_map.vote_for(event)
+        self.journal(|| Record::Vote(event.clone()));
+        self.consensus.vote_for(event)
     }
 
     pub fn create_gossip(
@@ -264,59 +490,459 @@ impl ConsensusEngine {
         version: u64,
         target: &PublicId,
     ) -> Result<Variant, CreateGossipError> {
-        self.parsec_map.create_gossip(version, target)
+        let request = self.gossip(version, target)?;
+        Ok(Variant::ConsensusRequest(version, request))
     }
 
-    pub fn handle_parsec_request(
+    // Creating gossip changes the state of the instance too (e.g. adds a sync event to the parsec
+    // gossip graph), so it's journalled like the handling of the gossip.
+    fn gossip(
+        &mut self,
+        version: u64,
+        target: &PublicId,
+    ) -> Result<ConsensusRequest, CreateGossipError> {
+        let request = self.consensus.create_gossip(version, target)?;
+        self.journal(|| Record::Gossip {
+            version,
+            target: *target,
+        });
+        Ok(request)
+    }
+
+    pub fn handle_consensus_request(
         &mut self,
         msg_version: u64,
-        request: ParsecRequest,
+        request: ConsensusRequest,
         pub_id: PublicId,
     ) -> Option<Variant> {
-        self.parsec_map.handle_request(msg_version, request, pub_id)
+        let response = self.handle_request(msg_version, request, pub_id)?;
+        Some(Variant::ConsensusResponse(msg_version, response))
     }
 
-    pub fn handle_parsec_response(
+    fn handle_request(
         &mut self,
         msg_version: u64,
-        response: ParsecResponse,
+        request: ConsensusRequest,
+        pub_id: PublicId,
+    ) -> Option<ConsensusResponse> {
+        if msg_version == self.consensus.last_version() {
+            let _ = self.last_heard.insert(pub_id, Instant::now());
+            self.journal(|| Record::Request {
+                version: msg_version,
+                src: pub_id,
+                request: request.clone(),
+            });
+        }
+
+        self.consensus.handle_request(msg_version, request, pub_id)
+    }
+
+    pub fn handle_consensus_response(
+        &mut self,
+        msg_version: u64,
+        response: ConsensusResponse,
         pub_id: PublicId,
     ) {
-        self.parsec_map
+        if msg_version == self.consensus.last_version() {
+            let _ = self.last_heard.insert(pub_id, Instant::now());
+            self.journal(|| Record::Response {
+                version: msg_version,
+                src: pub_id,
+                response: response.clone(),
+            });
+        }
+
+        self.consensus
             .handle_response(msg_version, response, pub_id)
     }
 
     pub fn prune_if_needed(&mut self) {
-        self.parsec_map.prune_if_needed()
+        self.consensus.prune_if_needed()
     }
 
     pub fn parsec_version(&self) -> u64 {
-        self.parsec_map.last_version()
+        self.consensus.last_version()
     }
 
     pub fn gossip_period(&self) -> Duration {
-        self.parsec_map.gossip_period()
+        self.gossip_period
     }
 
+    /// Starts a new gossip period. Its length is the busy period if there are unpolled
+    /// observations, otherwise the previous period doubled, up to the idle period.
     pub fn reset_gossip_period(&mut self) {
-        self.parsec_map.reset_gossip_period()
+        self.consensus.reset_gossip_period();
+        self.gossip_period = self
+            .config
+            .gossip
+            .next_period(self.gossip_period, self.has_unpolled_observations());
+    }
+
+    /// Switches back to the busy gossip period if we were idle but now have unpolled
+    /// observations. Returns whether the period changed, in which case the gossip timer should be
+    /// rescheduled.
+    pub fn speed_up_gossip(&mut self) -> bool {
+        if self.gossip_period > self.config.gossip.busy_period && self.has_unpolled_observations() {
+            self.gossip_period = self.config.gossip.busy_period;
+            true
+        } else {
+            false
+        }
     }
 
     pub fn should_send_gossip(&mut self) -> bool {
-        self.parsec_map.should_send_gossip()
+        self.consensus.should_send_gossip()
     }
 
     pub fn gossip_recipients(&self) -> Vec<&PublicId> {
-        self.parsec_map.gossip_recipients()
+        self.consensus.gossip_recipients()
+    }
+
+    /// Checks whether consensus stalled: there are observations waiting for consensus, but
+    /// nothing has been polled for the configured stall timeout. Returns the elders we haven't
+    /// heard from within that time once per stall timeout elapsed without progress, `None`
+    /// otherwise. See `stall_checks` for how many times in a row it did.
+    pub fn check_stalled(
+        &mut self,
+        our_elders: &EldersInfo,
+        our_id: &PublicId,
+    ) -> Option<BTreeSet<PublicId>> {
+        let now = Instant::now();
+
+        if !self.consensus.has_unpolled_observations() {
+            self.last_progress = now;
+            self.stall_checks = 0;
+            return None;
+        }
+
+        if now - self.last_progress < self.config.stall_timeout {
+            return None;
+        }
+
+        self.last_progress = now;
+        self.stall_checks += 1;
+
+        let stall_timeout = self.config.stall_timeout;
+        let unresponsive = our_elders
+            .elder_ids()
+            .filter(|id| *id != our_id)
+            .filter(|id| {
+                self.last_heard
+                    .get(id)
+                    .map(|time| now - *time >= stall_timeout)
+                    .unwrap_or(true)
+            })
+            .copied()
+            .collect();
+
+        Some(unresponsive)
+    }
+
+    /// Number of consecutive times `check_stalled` reported a stall.
+    pub fn stall_checks(&self) -> u32 {
+        self.stall_checks
     }
 
-    #[cfg(feature = "mock_base")]
-    pub fn parsec_map(&self) -> &ParsecMap {
-        &self.parsec_map
+    pub fn has_unpolled_observations(&self) -> bool {
+        self.consensus.has_unpolled_observations()
     }
 
     #[cfg(all(test, feature = "mock"))]
     pub fn parsec_map_mut(&mut self) -> &mut ParsecMap {
-        &mut self.parsec_map
+        self.consensus
+            .as_parsec_map_mut()
+            .expect("consensus engine not backed by parsec")
+    }
+}
+
+// Returns the events we voted for that didn't accumulate according to the given journal records.
+fn unaccumulated_votes(our_id: &PublicId, records: Vec<Record>) -> Vec<NetworkEvent> {
+    let mut accumulator = EventAccumulator::default();
+    let mut votes = Vec::new();
+
+    for record in records {
+        match record {
+            Record::Vote(event) => votes.push(event),
+            Record::Checkpoint {
+                accumulator: checkpoint_accumulator,
+                ..
+            } => accumulator = checkpoint_accumulator,
+            Record::Init { .. }
+            | Record::Request { .. }
+            | Record::Response { .. }
+            | Record::Gossip { .. } => (),
+        }
+    }
+
+    let RemainingEvents {
+        unaccumulated_events,
+        accumulated_events,
+    } = accumulator.reset_accumulator(our_id);
+
+    unaccumulated_events
+        .into_iter()
+        .chain(votes)
+        .filter(|event| !accumulated_events.contains(&event.payload))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{id::P2pNode, rng, unwrap, xor_space::Prefix};
+    use rand::Rng;
+    use std::{env, fs};
+
+    fn create_elders(rng: &mut MainRng, count: usize) -> (Vec<FullId>, EldersInfo) {
+        let mut full_ids: Vec<_> = (0..count).map(|_| FullId::gen(rng)).collect();
+        full_ids.sort_by(|lhs, rhs| lhs.public_id().name().cmp(rhs.public_id().name()));
+
+        let elders = full_ids
+            .iter()
+            .enumerate()
+            .map(|(index, id)| {
+                let addr = ([127, 0, 0, 1], 9000 + index as u16).into();
+                (*id.public_id().name(), P2pNode::new(*id.public_id(), addr))
+            })
+            .collect();
+
+        (full_ids, EldersInfo::new(elders, Prefix::default(), 0))
+    }
+
+    fn temp_dir(rng: &mut MainRng) -> std::path::PathBuf {
+        env::temp_dir().join(format!("routing-consensus-{:016x}", rng.gen::<u64>()))
+    }
+
+    fn create_engine(
+        rng: &mut MainRng,
+        algorithm: ConsensusAlgorithm,
+        store_dir: Option<&std::path::Path>,
+        full_id: &FullId,
+        elders_info: &EldersInfo,
+        parsec_version: u64,
+    ) -> ConsensusEngine {
+        let config = ConsensusConfig {
+            algorithm,
+            store_dir: store_dir.map(|dir| dir.to_path_buf()),
+            ..ConsensusConfig::default()
+        };
+
+        ConsensusEngine::new(
+            config,
+            rng,
+            full_id.clone(),
+            elders_info,
+            vec![],
+            parsec_version,
+        )
+    }
+
+    // The `src` engine pulls from the `dst` one.
+    fn exchange(
+        src: &mut ConsensusEngine,
+        src_id: &FullId,
+        dst: &mut ConsensusEngine,
+        dst_id: &FullId,
+    ) {
+        let request = match unwrap!(src.create_gossip(0, dst_id.public_id())) {
+            Variant::ConsensusRequest(_, request) => request,
+            variant => panic!("unexpected variant {:?}", variant),
+        };
+        let response = match dst.handle_consensus_request(0, request, *src_id.public_id()) {
+            Some(Variant::ConsensusResponse(_, response)) => response,
+            variant => panic!("unexpected variant {:?}", variant),
+        };
+        src.handle_consensus_response(0, response, *dst_id.public_id());
+    }
+
+    fn polled_events(
+        engine: &mut ConsensusEngine,
+        elders_info: &EldersInfo,
+    ) -> Vec<AccumulatingEvent> {
+        std::iter::from_fn(|| engine.poll(elders_info))
+            .map(|(event, _)| event)
+            .collect()
+    }
+
+    // Gossips both ways until both engines polled the event. Returns the events polled by each.
+    fn gossip_until_polled(
+        lhs: &mut ConsensusEngine,
+        lhs_id: &FullId,
+        rhs: &mut ConsensusEngine,
+        rhs_id: &FullId,
+        elders_info: &EldersInfo,
+        event: &AccumulatingEvent,
+    ) -> (Vec<AccumulatingEvent>, Vec<AccumulatingEvent>) {
+        let mut lhs_events = Vec::new();
+        let mut rhs_events = Vec::new();
+
+        for _ in 0..100 {
+            exchange(lhs, lhs_id, rhs, rhs_id);
+            exchange(rhs, rhs_id, lhs, lhs_id);
+            lhs_events.extend(polled_events(lhs, elders_info));
+            rhs_events.extend(polled_events(rhs, elders_info));
+
+            if lhs_events.contains(event) && rhs_events.contains(event) {
+                break;
+            }
+        }
+
+        (lhs_events, rhs_events)
+    }
+
+    #[test]
+    fn resume_from_journal_after_restart() {
+        let mut rng = rng::new();
+        let dir = temp_dir(&mut rng);
+        let (full_ids, elders_info) = create_elders(&mut rng, 2);
+        let event = AccumulatingEvent::User(vec![0]);
+        let algorithm = ConsensusAlgorithm::Leader;
+
+        let mut leader = create_engine(&mut rng, algorithm, None, &full_ids[0], &elders_info, 0);
+        let mut member = create_engine(
+            &mut rng,
+            algorithm,
+            Some(&dir),
+            &full_ids[1],
+            &elders_info,
+            0,
+        );
+
+        leader.vote_for(event.clone().into_network_event());
+        member.vote_for(event.clone().into_network_event());
+        exchange(&mut member, &full_ids[1], &mut leader, &full_ids[0]);
+
+        // Restart the member. Without the journal its vote would be lost and the event would never
+        // reach the quorum.
+        drop(member);
+        let mut member = create_engine(
+            &mut rng,
+            algorithm,
+            Some(&dir),
+            &full_ids[1],
+            &elders_info,
+            0,
+        );
+
+        for _ in 0..3 {
+            exchange(&mut member, &full_ids[1], &mut leader, &full_ids[0]);
+        }
+
+        assert!(polled_events(&mut leader, &elders_info).contains(&event));
+        assert!(polled_events(&mut member, &elders_info).contains(&event));
+
+        unwrap!(fs::remove_dir_all(&dir));
+    }
+
+    #[test]
+    fn resume_parsec_from_journal_after_restart() {
+        #[cfg(feature = "mock")]
+        parsec::init_mock();
+
+        let mut rng = rng::new();
+        let dir = temp_dir(&mut rng);
+        let (full_ids, elders_info) = create_elders(&mut rng, 2);
+        let first = AccumulatingEvent::User(vec![0]);
+        let second = AccumulatingEvent::User(vec![1]);
+        let algorithm = ConsensusAlgorithm::Parsec;
+
+        let mut other = create_engine(&mut rng, algorithm, None, &full_ids[0], &elders_info, 0);
+        let mut member = create_engine(
+            &mut rng,
+            algorithm,
+            Some(&dir),
+            &full_ids[1],
+            &elders_info,
+            0,
+        );
+
+        other.vote_for(first.clone().into_network_event());
+        member.vote_for(first.clone().into_network_event());
+        let (_, polled) = gossip_until_polled(
+            &mut other,
+            &full_ids[0],
+            &mut member,
+            &full_ids[1],
+            &elders_info,
+            &first,
+        );
+        assert!(polled.contains(&first));
+        assert!(member.needs_checkpoint());
+        member.checkpoint(vec![7]);
+
+        // Vote for another event, but restart the member before it accumulates.
+        other.vote_for(second.clone().into_network_event());
+        member.vote_for(second.clone().into_network_event());
+        exchange(&mut member, &full_ids[1], &mut other, &full_ids[0]);
+        member.sync_journal();
+
+        drop(member);
+        let mut member = create_engine(
+            &mut rng,
+            algorithm,
+            Some(&dir),
+            &full_ids[1],
+            &elders_info,
+            0,
+        );
+
+        match member.take_restored() {
+            Some(Restored::SharedState(serialised_state)) => assert_eq!(serialised_state, vec![7]),
+            _ => panic!("shared state not restored"),
+        }
+
+        let (_, polled) = gossip_until_polled(
+            &mut other,
+            &full_ids[0],
+            &mut member,
+            &full_ids[1],
+            &elders_info,
+            &second,
+        );
+        assert!(polled.contains(&second));
+        // The decisions handled before the checkpoint aren't handled again.
+        assert!(!polled.contains(&first));
+
+        unwrap!(fs::remove_dir_all(&dir));
+    }
+
+    #[test]
+    fn restore_unaccumulated_votes_into_new_instance() {
+        let mut rng = rng::new();
+        let dir = temp_dir(&mut rng);
+        let (full_ids, elders_info) = create_elders(&mut rng, 2);
+        let event = AccumulatingEvent::User(vec![0]);
+        let algorithm = ConsensusAlgorithm::Leader;
+
+        let mut engine = create_engine(
+            &mut rng,
+            algorithm,
+            Some(&dir),
+            &full_ids[1],
+            &elders_info,
+            0,
+        );
+        engine.vote_for(event.clone().into_network_event());
+        engine.sync_journal();
+
+        // Restart after our section moved on to the next instance.
+        drop(engine);
+        let mut engine = create_engine(
+            &mut rng,
+            algorithm,
+            Some(&dir),
+            &full_ids[1],
+            &elders_info,
+            1,
+        );
+
+        match engine.take_restored() {
+            Some(Restored::Votes(votes)) => {
+                assert!(votes.iter().any(|vote| vote.payload == event))
+            }
+            _ => panic!("votes not restored"),
+        }
+
+        unwrap!(fs::remove_dir_all(&dir));
     }
 }
@@ -9,9 +9,9 @@
 use crate::{
     consensus::{DkgResultWrapper, Observation, ParsecNetworkEvent},
     id::{P2pNode, PublicId},
-    messages::MessageHash,
+    messages::{MergeRequest, MessageHash},
     relocation::RelocateDetails,
-    section::EldersInfo,
+    section::{BanDetails, EldersInfo},
     Prefix, XorName,
 };
 use hex_fmt::HexFmt;
@@ -29,6 +29,8 @@ pub struct OnlinePayload {
     pub age: u8,
     // The key of the destination section that the joining node knows, if any.
     pub their_knowledge: Option<bls::PublicKey>,
+    // The name the joining node had before it left our section, if it is rejoining.
+    pub previous_name: Option<XorName>,
 }
 
 /// Routing Network events
@@ -90,6 +92,30 @@ pub enum AccumulatingEvent {
     // Voted to initiate the relocation if value <= 0, otherwise re-vote with value - 1.
     RelocatePrepare(RelocateDetails, i32),
 
+    // Voted by the node operators for relocating the member with the given name out of our
+    // section to the given destination.
+    RelocateRequest {
+        name: XorName,
+        destination: XorName,
+    },
+
+    // Voted to change whether our section accepts new members. The version must immediately
+    // follow the current admission version of our section, otherwise the event is ignored.
+    Admission {
+        accepting: bool,
+        version: u64,
+    },
+
+    // Voted to ban a peer proven malicious, either by us or by a neighbour section.
+    Ban(BanDetails),
+
+    // Voted to ask our sibling section to absorb us because we became too small. The value is our
+    // elders version, to make requests made at different times unique.
+    RequestMerge(u64),
+
+    // Voted for received request of our sibling section to be merged into our section.
+    Merge(Box<MergeRequest>),
+
     // Opaque user-defined event.
     User(Vec<u8>),
 }
@@ -158,6 +184,19 @@ impl Debug for AccumulatingEvent {
             Self::RelocatePrepare(payload, count_down) => {
                 write!(formatter, "RelocatePrepare({:?}, {})", payload, count_down)
             }
+            Self::RelocateRequest { name, destination } => write!(
+                formatter,
+                "RelocateRequest {{ name: {}, destination: {} }}",
+                name, destination
+            ),
+            Self::Admission { accepting, version } => write!(
+                formatter,
+                "Admission {{ accepting: {}, version: {} }}",
+                accepting, version
+            ),
+            Self::Ban(details) => write!(formatter, "Ban({:?})", details),
+            Self::RequestMerge(version) => write!(formatter, "RequestMerge({})", version),
+            Self::Merge(payload) => write!(formatter, "Merge({:?})", payload),
             Self::User(payload) => write!(formatter, "User({:<8})", HexFmt(payload)),
         }
     }
@@ -7,12 +7,14 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    consensus::{AccumulatingEvent, NetworkEvent},
+    consensus::{
+        algorithm::{Consensus, ConsensusRequest, ConsensusResponse, CreateGossipError, Decision},
+        config::GossipConfig,
+        AccumulatingEvent, NetworkEvent,
+    },
     id::{FullId, PublicId},
-    messages::Variant,
     rng::{self, MainRng, RngCompat},
     section::EldersInfo,
-    time::Duration,
 };
 #[cfg(feature = "mock")]
 use crate::{crypto, mock::parsec as inner};
@@ -22,9 +24,7 @@ use parsec as inner;
 use std::collections::BTreeSet;
 use std::{
     collections::{btree_map::Entry, BTreeMap},
-    env,
     fmt::{self, Display},
-    str::FromStr,
 };
 
 #[cfg(feature = "mock")]
@@ -46,22 +46,15 @@ pub use inner::{DkgResult, DkgResultWrapper};
 // The maximum number of parsec instances to store.
 const MAX_PARSECS: usize = 10;
 
-// Limit in production
+// Default limit in production
 #[cfg(not(feature = "mock_base"))]
-const PARSEC_SIZE_LIMIT: u64 = 1_000_000_000;
+pub(super) const PARSEC_SIZE_LIMIT: u64 = 1_000_000_000;
 // Limit in integration tests
 #[cfg(all(feature = "mock_base", not(feature = "mock")))]
-const PARSEC_SIZE_LIMIT: u64 = 20_000_000;
+pub(super) const PARSEC_SIZE_LIMIT: u64 = 20_000_000;
 // Limit for integration tests with mock-parsec
 #[cfg(feature = "mock")]
-const PARSEC_SIZE_LIMIT: u64 = 500;
-
-/// Period within which the number of sent gossip messages is limited. When the period ends, the
-/// limit resets at a new period starts.
-pub const GOSSIP_PERIOD: Duration = Duration::from_secs(1);
-
-// Maximum number of gossip messages a node can send within one gossip period.
-const GOSSIP_LIMIT: usize = 5;
+pub(super) const PARSEC_SIZE_LIMIT: u64 = 500;
 
 // Keep track of size in case we need to prune.
 #[derive(Default, Debug, PartialEq, Eq)]
@@ -75,8 +68,8 @@ impl ParsecSizeCounter {
         self.size_counter += size;
     }
 
-    fn needs_pruning(&self) -> bool {
-        self.size_counter > PARSEC_SIZE_LIMIT && !self.pruning_voted_for
+    fn needs_pruning(&self, limit: u64) -> bool {
+        self.size_counter > limit && !self.pruning_voted_for
     }
 
     fn set_pruning_voted_for(&mut self) {
@@ -96,31 +89,98 @@ pub struct ParsecMap {
     send_gossip: bool,
     // Number of gossip messages we sent within this gossip period.
     gossip_count: usize,
-
-    gossip_limit: usize,
-    gossip_period: Duration,
+    config: GossipConfig,
 }
 
-impl Default for ParsecMap {
-    fn default() -> Self {
-        let gossip_limit = parse_env_var("ROUTING_GOSSIP_LIMIT").unwrap_or(GOSSIP_LIMIT);
-        let gossip_period = parse_env_var("ROUTING_GOSSIP_PERIOD")
-            .map(Duration::from_millis)
-            .unwrap_or(GOSSIP_PERIOD);
-
+impl ParsecMap {
+    pub fn new(config: GossipConfig) -> Self {
         Self {
             map: Default::default(),
             size_counter: Default::default(),
             send_gossip: false,
             gossip_count: 0,
-            gossip_limit,
-            gossip_period,
+            config,
+        }
+    }
+
+    // Enable test to simulate other members voting
+    #[cfg(all(test, feature = "mock"))]
+    pub fn vote_for_as(&mut self, obs: Observation<NetworkEvent, PublicId>, vote_id: &FullId) {
+        if let Some(ref mut parsec) = self.map.values_mut().last() {
+            parsec.vote_for_as(obs, vote_id)
+        }
+    }
+
+    // Enable test to simulate other members signing and getting the right pk_set
+    #[cfg(all(test, feature = "mock"))]
+    pub fn get_dkg_result_as(
+        &mut self,
+        participants: BTreeSet<PublicId>,
+        vote_id: &FullId,
+    ) -> Option<DkgResult> {
+        if let Some(ref mut parsec) = self.map.values_mut().last() {
+            return Some(parsec.get_dkg_result_as(participants, vote_id));
+        }
+        None
+    }
+
+    // Enable test to simulate a member that doesn't respond during DKG.
+    #[cfg(all(test, feature = "mock"))]
+    pub fn set_dkg_unresponsive(&mut self, participant: PublicId) {
+        if let Some(ref mut parsec) = self.map.values_mut().last() {
+            parsec.set_dkg_unresponsive(participant)
+        }
+    }
+
+    fn poll_block(&mut self) -> Option<Block> {
+        self.map.values_mut().last().and_then(Parsec::poll)
+    }
+
+    fn count_size(&mut self, size: u64, msg_version: u64) {
+        if self.last_version() == msg_version && self.map.contains_key(&msg_version) {
+            self.size_counter.increase_size(size);
+            trace!(
+                "Parsec size is now estimated to: {} / {}.",
+                self.size_counter,
+                self.config.parsec_size_limit,
+            );
         }
     }
+
+    fn add_new(
+        &mut self,
+        rng: &mut MainRng,
+        full_id: FullId,
+        elders_info: &EldersInfo,
+        serialised_state: Vec<u8>,
+        parsec_version: u64,
+    ) {
+        if let Entry::Vacant(entry) = self.map.entry(parsec_version) {
+            let _ = entry.insert(create(
+                rng,
+                full_id,
+                elders_info,
+                serialised_state,
+                parsec_version,
+            ));
+            self.size_counter = ParsecSizeCounter::default();
+            info!("Init new Parsec v{}", parsec_version);
+        }
+    }
+
+    fn remove_old(&mut self) {
+        let parsec_map = std::mem::take(&mut self.map);
+        self.map = parsec_map
+            .into_iter()
+            .rev()
+            .take(MAX_PARSECS)
+            .rev()
+            .collect();
+    }
 }
 
-impl ParsecMap {
-    pub fn init(
+impl Consensus for ParsecMap {
+    fn init(
         &mut self,
         rng: &mut MainRng,
         full_id: FullId,
@@ -132,12 +192,155 @@ impl ParsecMap {
         self.remove_old();
     }
 
-    pub fn handle_request(
+    fn last_version(&self) -> u64 {
+        if let Some(version) = self.map.keys().last() {
+            *version
+        } else {
+            log_or_panic!(log::Level::Error, "ParsecMap is empty.");
+            0
+        }
+    }
+
+    fn vote_for(&mut self, event: NetworkEvent) {
+        trace!("Vote for Event {:?}", event);
+
+        if let Some(parsec) = self.map.values_mut().last() {
+            let obs = event.into_obs();
+
+            match parsec.vote_for(obs) {
+                Ok(()) => {
+                    self.send_gossip = true;
+                }
+                Err(err) => trace!("Parsec vote error: {:?}", err),
+            }
+        }
+    }
+
+    fn poll(&mut self) -> Option<Decision> {
+        while let Some(block) = self.poll_block() {
+            // TODO: implement Block::into_payload in parsec to avoid cloning.
+            match block.payload() {
+                Observation::Genesis {
+                    group,
+                    related_info,
+                } => {
+                    return Some(Decision::Genesis {
+                        group: group.clone(),
+                        related_info: related_info.clone(),
+                    })
+                }
+                Observation::OpaquePayload(event) => {
+                    let proof = if let Some(proof) = block.proofs().iter().next() {
+                        proof
+                    } else {
+                        continue;
+                    };
+
+                    return Some(Decision::Vote {
+                        event: event.clone(),
+                        proof: crate::consensus::Proof {
+                            pub_id: *proof.public_id(),
+                            sig: *proof.signature(),
+                        },
+                    });
+                }
+                Observation::DkgResult {
+                    participants,
+                    dkg_result,
+                } => {
+                    return Some(Decision::DkgResult {
+                        participants: participants.clone(),
+                        dkg_result: dkg_result.clone(),
+                    })
+                }
+                Observation::Accusation { offender, .. } => {
+                    return Some(Decision::Accusation {
+                        offender: *offender,
+                    })
+                }
+                Observation::Add { .. }
+                | Observation::Remove { .. }
+                | Observation::StartDkg(_)
+                | Observation::DkgMessage(_) => {
+                    log_or_panic!(
+                        log::Level::Error,
+                        "unexpected Parsec observation v{}: {:?}",
+                        self.last_version(),
+                        block.payload()
+                    );
+                }
+            }
+        }
+
+        None
+    }
+
+    fn our_unpolled_votes(&self) -> Vec<NetworkEvent> {
+        self.map
+            .values()
+            .last()
+            .map(Parsec::our_unpolled_observations)
+            .into_iter()
+            .flatten()
+            .filter_map(|obs| match obs {
+                Observation::OpaquePayload(event) => Some(event.clone()),
+
+                Observation::Genesis { .. }
+                | Observation::Add { .. }
+                | Observation::Remove { .. }
+                | Observation::Accusation { .. }
+                | Observation::StartDkg(_)
+                | Observation::DkgResult { .. }
+                | Observation::DkgMessage(_) => None,
+            })
+            .collect()
+    }
+
+    fn has_unpolled_observations(&self) -> bool {
+        let parsec = if let Some(parsec) = self.map.values().last() {
+            parsec
+        } else {
+            return false;
+        };
+
+        parsec.has_unpolled_observations()
+    }
+
+    fn create_gossip(
+        &mut self,
+        version: u64,
+        target: &PublicId,
+    ) -> Result<ConsensusRequest, CreateGossipError> {
+        let request = self
+            .map
+            .get_mut(&version)
+            .ok_or(CreateGossipError::MissingVersion)?
+            .create_gossip(target)?;
+
+        if version == self.last_version() {
+            self.gossip_count += 1;
+        }
+
+        Ok(ConsensusRequest::Parsec(request))
+    }
+
+    fn handle_request(
         &mut self,
         msg_version: u64,
-        request: Request,
+        request: ConsensusRequest,
         pub_id: PublicId,
-    ) -> Option<Variant> {
+    ) -> Option<ConsensusResponse> {
+        // Infallible without the `trusted-leader-consensus` feature.
+        #[allow(clippy::infallible_destructuring_match)]
+        let request = match request {
+            ConsensusRequest::Parsec(request) => request,
+            #[cfg(any(test, feature = "trusted-leader-consensus"))]
+            ConsensusRequest::Leader(_) => {
+                debug!("Unexpected leader consensus request from {}", pub_id);
+                return None;
+            }
+        };
+
         // Increase the size before fetching the parsec to satisfy the borrow checker
         let ser_size = if let Ok(size) = bincode::serialized_size(&request) {
             size
@@ -156,7 +359,7 @@ impl ParsecMap {
                     self.send_gossip = true;
                 }
 
-                Some(Variant::ParsecResponse(msg_version, response))
+                Some(ConsensusResponse::Parsec(response))
             }
             Err(err) => {
                 debug!("Error handling parsec request: {:?}", err);
@@ -165,7 +368,18 @@ impl ParsecMap {
         }
     }
 
-    pub fn handle_response(&mut self, msg_version: u64, response: Response, pub_id: PublicId) {
+    fn handle_response(&mut self, msg_version: u64, response: ConsensusResponse, pub_id: PublicId) {
+        // Infallible without the `trusted-leader-consensus` feature.
+        #[allow(clippy::infallible_destructuring_match)]
+        let response = match response {
+            ConsensusResponse::Parsec(response) => response,
+            #[cfg(any(test, feature = "trusted-leader-consensus"))]
+            ConsensusResponse::Leader(_) => {
+                debug!("Unexpected leader consensus response from {}", pub_id);
+                return;
+            }
+        };
+
         // Increase the size before fetching the parsec to satisfy the borrow checker
         let ser_size = if let Ok(size) = bincode::serialized_size(&response) {
             size
@@ -185,70 +399,7 @@ impl ParsecMap {
         }
     }
 
-    pub fn create_gossip(
-        &mut self,
-        version: u64,
-        target: &PublicId,
-    ) -> Result<Variant, CreateGossipError> {
-        let request = self
-            .map
-            .get_mut(&version)
-            .ok_or(CreateGossipError::MissingVersion)?
-            .create_gossip(target)?;
-
-        if version == self.last_version() {
-            self.gossip_count += 1;
-        }
-
-        Ok(Variant::ParsecRequest(version, request))
-    }
-
-    pub fn vote_for(&mut self, event: NetworkEvent) {
-        trace!("Vote for Event {:?}", event);
-
-        if let Some(parsec) = self.map.values_mut().last() {
-            let obs = event.into_obs();
-
-            match parsec.vote_for(obs) {
-                Ok(()) => {
-                    self.send_gossip = true;
-                }
-                Err(err) => trace!("Parsec vote error: {:?}", err),
-            }
-        }
-    }
-
-    // Enable test to simulate other members voting
-    #[cfg(all(test, feature = "mock"))]
-    pub fn vote_for_as(&mut self, obs: Observation<NetworkEvent, PublicId>, vote_id: &FullId) {
-        if let Some(ref mut parsec) = self.map.values_mut().last() {
-            parsec.vote_for_as(obs, vote_id)
-        }
-    }
-
-    // Enable test to simulate other members signing and getting the right pk_set
-    #[cfg(all(test, feature = "mock"))]
-    pub fn get_dkg_result_as(
-        &mut self,
-        participants: BTreeSet<PublicId>,
-        vote_id: &FullId,
-    ) -> Option<DkgResult> {
-        if let Some(ref mut parsec) = self.map.values_mut().last() {
-            return Some(parsec.get_dkg_result_as(participants, vote_id));
-        }
-        None
-    }
-
-    pub fn last_version(&self) -> u64 {
-        if let Some(version) = self.map.keys().last() {
-            *version
-        } else {
-            log_or_panic!(log::Level::Error, "ParsecMap is empty.");
-            0
-        }
-    }
-
-    pub fn gossip_recipients(&self) -> Vec<&PublicId> {
+    fn gossip_recipients(&self) -> Vec<&PublicId> {
         self.map
             .values()
             .last()
@@ -256,40 +407,8 @@ impl ParsecMap {
             .unwrap_or_else(Vec::new)
     }
 
-    pub fn poll(&mut self) -> Option<Block> {
-        self.map.values_mut().last().and_then(Parsec::poll)
-    }
-
-    pub fn our_unpolled_observations(
-        &self,
-    ) -> impl Iterator<Item = &Observation<NetworkEvent, PublicId>> {
-        self.map
-            .values()
-            .last()
-            .map(Parsec::our_unpolled_observations)
-            .into_iter()
-            .flatten()
-    }
-
-    pub fn has_unpolled_observations(&self) -> bool {
-        let parsec = if let Some(parsec) = self.map.values().last() {
-            parsec
-        } else {
-            return false;
-        };
-
-        parsec.has_unpolled_observations()
-    }
-
-    pub fn prune_if_needed(&mut self) {
-        if self.size_counter.needs_pruning() {
-            self.vote_for(AccumulatingEvent::ParsecPrune.into_network_event());
-            self.size_counter.set_pruning_voted_for();
-        }
-    }
-
     // Returns whether we should send parsec gossip now.
-    pub fn should_send_gossip(&mut self) -> bool {
+    fn should_send_gossip(&mut self) -> bool {
         let send_gossip = self.send_gossip;
         self.send_gossip = false;
 
@@ -297,7 +416,7 @@ impl ParsecMap {
             return false;
         }
 
-        if self.gossip_count >= self.gossip_limit {
+        if self.gossip_count >= self.config.limit {
             trace!("not sending parsec request: limit reached");
             return false;
         }
@@ -305,11 +424,7 @@ impl ParsecMap {
         true
     }
 
-    pub fn gossip_period(&self) -> Duration {
-        self.gossip_period
-    }
-
-    pub fn reset_gossip_period(&mut self) {
+    fn reset_gossip_period(&mut self) {
         self.gossip_count = 0;
 
         if self.has_unpolled_observations() {
@@ -317,46 +432,19 @@ impl ParsecMap {
         }
     }
 
-    fn count_size(&mut self, size: u64, msg_version: u64) {
-        if self.last_version() == msg_version && self.map.contains_key(&msg_version) {
-            self.size_counter.increase_size(size);
-            trace!(
-                "Parsec size is now estimated to: {} / {}.",
-                self.size_counter,
-                PARSEC_SIZE_LIMIT,
-            );
-        }
-    }
-
-    fn add_new(
-        &mut self,
-        rng: &mut MainRng,
-        full_id: FullId,
-        elders_info: &EldersInfo,
-        serialised_state: Vec<u8>,
-        parsec_version: u64,
-    ) {
-        if let Entry::Vacant(entry) = self.map.entry(parsec_version) {
-            let _ = entry.insert(create(
-                rng,
-                full_id,
-                elders_info,
-                serialised_state,
-                parsec_version,
-            ));
-            self.size_counter = ParsecSizeCounter::default();
-            info!("Init new Parsec v{}", parsec_version);
+    fn prune_if_needed(&mut self) {
+        if self
+            .size_counter
+            .needs_pruning(self.config.parsec_size_limit)
+        {
+            self.vote_for(AccumulatingEvent::ParsecPrune.into_network_event());
+            self.size_counter.set_pruning_voted_for();
         }
     }
 
-    fn remove_old(&mut self) {
-        let parsec_map = std::mem::take(&mut self.map);
-        self.map = parsec_map
-            .into_iter()
-            .rev()
-            .take(MAX_PARSECS)
-            .rev()
-            .collect();
+    #[cfg(all(test, feature = "mock"))]
+    fn as_parsec_map_mut(&mut self) -> Option<&mut ParsecMap> {
+        Some(self)
     }
 }
 
@@ -422,29 +510,6 @@ fn create(
     }
 }
 
-#[derive(Debug)]
-pub enum CreateGossipError {
-    MissingVersion,
-    Other(Error),
-}
-
-impl From<Error> for CreateGossipError {
-    fn from(src: Error) -> Self {
-        Self::Other(src)
-    }
-}
-
-fn parse_env_var<T>(name: &str) -> Option<T>
-where
-    T: FromStr,
-    T::Err: Display,
-{
-    env::var(name).ok().map(|value| match value.parse() {
-        Ok(value) => value,
-        Err(error) => panic!("Failed to parse '{}': {}", name, error),
-    })
-}
-
 #[cfg(all(test, feature = "mock"))]
 mod tests {
     use super::*;
@@ -463,11 +528,11 @@ mod tests {
     #[test]
     fn parsec_size_counter() {
         let mut counter = ParsecSizeCounter::default();
-        assert!(!counter.needs_pruning());
+        assert!(!counter.needs_pruning(PARSEC_SIZE_LIMIT));
         counter.increase_size(PARSEC_SIZE_LIMIT);
-        assert!(!counter.needs_pruning());
+        assert!(!counter.needs_pruning(PARSEC_SIZE_LIMIT));
         counter.increase_size(1);
-        assert!(counter.needs_pruning());
+        assert!(counter.needs_pruning(PARSEC_SIZE_LIMIT));
     }
 
     fn create_full_ids(rng: &mut MainRng) -> Vec<FullId> {
@@ -499,7 +564,7 @@ mod tests {
     fn create_parsec_map(rng: &mut MainRng, size: u64) -> ParsecMap {
         let full_ids = create_full_ids(rng);
 
-        let mut parsec_map = ParsecMap::default();
+        let mut parsec_map = ParsecMap::new(GossipConfig::default());
         for parsec_no in 0..=size {
             init_parsec_map(&mut parsec_map, rng, full_ids.clone(), parsec_no);
         }
@@ -518,13 +583,15 @@ mod tests {
 
     impl HandleRequestResponse for Request {
         fn handle(&self, parsec_map: &mut ParsecMap, msg_version: u64, pub_id: &PublicId) {
-            let _ = parsec_map.handle_request(msg_version, self.clone(), *pub_id);
+            let request = ConsensusRequest::Parsec(self.clone());
+            let _ = parsec_map.handle_request(msg_version, request, *pub_id);
         }
     }
 
     impl HandleRequestResponse for Response {
         fn handle(&self, parsec_map: &mut ParsecMap, msg_version: u64, pub_id: &PublicId) {
-            parsec_map.handle_response(msg_version, self.clone(), *pub_id);
+            let response = ConsensusResponse::Parsec(self.clone());
+            parsec_map.handle_response(msg_version, response, *pub_id);
         }
     }
 
@@ -544,7 +611,10 @@ mod tests {
         }
 
         // Make sure we don't cross the prune limit
-        assert_eq!(parsec_map.size_counter.needs_pruning(), false);
+        assert_eq!(
+            parsec_map.size_counter.needs_pruning(PARSEC_SIZE_LIMIT),
+            false
+        );
     }
 
     fn check_prune_needed_after_msg<T: HandleRequestResponse + Serialize>(
@@ -565,7 +635,10 @@ mod tests {
         handle_msgs_just_below_prune_limit(&mut parsec_map, msg_version, &msg, pub_id);
 
         msg.handle(&mut parsec_map, msg_version, pub_id);
-        assert_eq!(parsec_map.size_counter.needs_pruning(), prune_needed);
+        assert_eq!(
+            parsec_map.size_counter.needs_pruning(PARSEC_SIZE_LIMIT),
+            prune_needed
+        );
 
         parsec_map
     }
@@ -601,10 +674,16 @@ mod tests {
         let mut parsec_map =
             check_prune_needed_after_msg(&mut rng, Response::new(), parsec_age, true);
 
-        assert_eq!(parsec_map.size_counter.needs_pruning(), true);
+        assert_eq!(
+            parsec_map.size_counter.needs_pruning(PARSEC_SIZE_LIMIT),
+            true
+        );
         let number_of_parsecs = 2;
         add_to_parsec_map(&mut rng, &mut parsec_map, number_of_parsecs + 1);
-        assert_eq!(parsec_map.size_counter.needs_pruning(), false);
+        assert_eq!(
+            parsec_map.size_counter.needs_pruning(PARSEC_SIZE_LIMIT),
+            false
+        );
     }
 
     #[test]
@@ -613,8 +692,14 @@ mod tests {
         let mut parsec_map =
             check_prune_needed_after_msg(&mut rng::new(), Response::new(), parsec_age, true);
 
-        assert_eq!(parsec_map.size_counter.needs_pruning(), true);
+        assert_eq!(
+            parsec_map.size_counter.needs_pruning(PARSEC_SIZE_LIMIT),
+            true
+        );
         parsec_map.size_counter.set_pruning_voted_for();
-        assert_eq!(parsec_map.size_counter.needs_pruning(), false);
+        assert_eq!(
+            parsec_map.size_counter.needs_pruning(PARSEC_SIZE_LIMIT),
+            false
+        );
     }
 }
@@ -7,28 +7,43 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
+    consensus::ConsensusConfig,
     error::Result,
     event::Event,
     id::{FullId, PublicId},
     location::DstLocation,
     message_filter::MessageFilter,
-    messages::{Message, QueuedMessage, Variant},
+    messages::{Message, Priority, QueuedMessage, RouteConfig, Variant},
     network_params::NetworkParams,
-    node::NodeConfig,
+    node::{BootstrapConfig, NodeConfig},
     quic_p2p::{EventSenders, OurType, Token},
+    resource_proof::ResourceProofParams,
     rng::{self, MainRng},
+    section::{AdmissionPolicy, DiversityRules, ElderSelection, RelocationPolicy, SplitPolicy},
     time::Duration,
     timer::Timer,
-    transport::{PeerStatus, Transport},
+    transport::{SendFailure, Transport},
     xor_space::XorName,
 };
 use bytes::Bytes;
 use crossbeam_channel::Sender;
-use std::{collections::VecDeque, net::SocketAddr, slice};
+use rand::Rng;
+use std::{collections::VecDeque, net::SocketAddr, slice, sync::Arc};
 
 // Core components of the node.
 pub struct Core {
     pub network_params: NetworkParams,
+    pub consensus_config: ConsensusConfig,
+    pub split_policy: Arc<dyn SplitPolicy>,
+    pub elder_selection: Arc<dyn ElderSelection>,
+    pub relocation_policy: Arc<dyn RelocationPolicy>,
+    pub admission_policy: Arc<dyn AdmissionPolicy>,
+    pub resource_proof: ResourceProofParams,
+    pub diversity: DiversityRules,
+    pub ban_duration: u64,
+    pub ban_by_ip: bool,
+    pub bootstrap_config: BootstrapConfig,
+    pub route_config: RouteConfig,
     pub full_id: FullId,
     pub transport: Transport,
     pub msg_filter: MessageFilter,
@@ -38,6 +53,26 @@ pub struct Core {
     user_event_tx: Sender<Event>,
 }
 
+/// The state of `Core` carried over from a paused node to resume it.
+pub struct ResumeParams {
+    pub network_params: NetworkParams,
+    pub consensus_config: ConsensusConfig,
+    pub split_policy: Arc<dyn SplitPolicy>,
+    pub elder_selection: Arc<dyn ElderSelection>,
+    pub relocation_policy: Arc<dyn RelocationPolicy>,
+    pub admission_policy: Arc<dyn AdmissionPolicy>,
+    pub resource_proof: ResourceProofParams,
+    pub diversity: DiversityRules,
+    pub ban_duration: u64,
+    pub ban_by_ip: bool,
+    pub bootstrap_config: BootstrapConfig,
+    pub route_config: RouteConfig,
+    pub full_id: FullId,
+    pub transport: Transport,
+    pub msg_filter: MessageFilter,
+    pub msg_queue: VecDeque<QueuedMessage>,
+}
+
 impl Core {
     pub fn new(
         mut config: NodeConfig,
@@ -49,13 +84,30 @@ impl Core {
         let full_id = config.full_id.unwrap_or_else(|| FullId::gen(&mut rng));
 
         config.transport_config.our_type = OurType::Node;
-        let transport = match Transport::new(transport_event_tx, config.transport_config) {
+        let transport = match Transport::new(
+            transport_event_tx,
+            config.transport_config,
+            config.transport_kind,
+            config.bootstrap_cache_file,
+            config.bandwidth,
+        ) {
             Ok(transport) => transport,
             Err(err) => panic!("Unable to start network transport: {:?}", err),
         };
 
         Self {
             network_params: config.network_params,
+            consensus_config: config.consensus,
+            split_policy: config.split_policy,
+            elder_selection: config.elder_selection,
+            relocation_policy: config.relocation_policy,
+            admission_policy: config.admission_policy,
+            resource_proof: config.resource_proof,
+            diversity: config.diversity,
+            ban_duration: config.ban_duration,
+            ban_by_ip: config.ban_by_ip,
+            bootstrap_config: config.bootstrap,
+            route_config: config.route,
             full_id,
             transport,
             msg_filter: Default::default(),
@@ -67,20 +119,27 @@ impl Core {
     }
 
     pub fn resume(
-        network_params: NetworkParams,
-        full_id: FullId,
-        transport: Transport,
-        msg_filter: MessageFilter,
-        msg_queue: VecDeque<QueuedMessage>,
+        params: ResumeParams,
         timer_tx: Sender<u64>,
         user_event_tx: Sender<Event>,
     ) -> Self {
         Self {
-            network_params,
-            full_id,
-            transport,
-            msg_filter,
-            msg_queue,
+            network_params: params.network_params,
+            consensus_config: params.consensus_config,
+            split_policy: params.split_policy,
+            elder_selection: params.elder_selection,
+            relocation_policy: params.relocation_policy,
+            admission_policy: params.admission_policy,
+            resource_proof: params.resource_proof,
+            diversity: params.diversity,
+            ban_duration: params.ban_duration,
+            ban_by_ip: params.ban_by_ip,
+            bootstrap_config: params.bootstrap_config,
+            route_config: params.route_config,
+            full_id: params.full_id,
+            transport: params.transport,
+            msg_filter: params.msg_filter,
+            msg_queue: params.msg_queue,
             timer: Timer::new(timer_tx),
             rng: rng::new(),
             user_event_tx,
@@ -98,7 +157,7 @@ impl Core {
     pub fn our_connection_info(&mut self) -> Result<SocketAddr> {
         self.transport.our_connection_info().map_err(|err| {
             debug!("Failed to retrieve our connection info: {:?}", err);
-            err.into()
+            err
         })
     }
 
@@ -107,22 +166,30 @@ impl Core {
         conn_infos: &[SocketAddr],
         delivery_group_size: usize,
         msg: Bytes,
+        priority: Priority,
     ) {
-        self.transport
-            .send_message_to_targets(conn_infos, delivery_group_size, msg)
+        self.transport.send_message_to_targets(
+            conn_infos,
+            delivery_group_size,
+            msg,
+            priority,
+            &self.timer,
+        )
     }
 
     pub fn send_message_to_target_later(
         &mut self,
         dst: &SocketAddr,
         message: Bytes,
+        priority: Priority,
         delay: Duration,
     ) {
         self.transport
-            .send_message_to_target_later(dst, message, &self.timer, delay)
+            .send_message_to_target_later(dst, message, priority, &self.timer, delay)
     }
 
     pub fn send_direct_message(&mut self, recipient: &SocketAddr, variant: Variant) {
+        let priority = variant.priority();
         let message = match Message::single_src(&self.full_id, DstLocation::Direct, variant) {
             Ok(message) => message,
             Err(error) => {
@@ -139,17 +206,29 @@ impl Core {
             }
         };
 
-        self.send_message_to_targets(slice::from_ref(recipient), 1, bytes)
+        self.send_message_to_targets(slice::from_ref(recipient), 1, bytes, priority)
     }
 
-    pub fn handle_unsent_message(
-        &mut self,
-        addr: SocketAddr,
-        msg: Bytes,
-        msg_token: Token,
-    ) -> PeerStatus {
+    /// Pings the node, to measure the round-trip time to it or to trigger lost peer detection.
+    pub fn send_ping(&mut self, recipient: &SocketAddr) {
+        let nonce = self.rng.gen();
+        self.transport.ping_sent(*recipient, nonce);
+        self.send_direct_message(recipient, Variant::Ping(nonce));
+    }
+
+    pub fn handle_sent_message(&mut self, addr: SocketAddr, msg_token: Token) {
+        self.transport
+            .target_succeeded(msg_token, addr, &self.timer, &mut self.rng)
+    }
+
+    pub fn handle_unsent_message(&mut self, addr: SocketAddr, msg_token: Token) -> SendFailure {
+        self.transport
+            .target_failed(msg_token, addr, &self.timer, &mut self.rng)
+    }
+
+    pub fn handle_refused_sends(&mut self) -> Vec<Bytes> {
         self.transport
-            .target_failed(msg, msg_token, addr, &self.timer)
+            .handle_refused_sends(&self.timer, &mut self.rng)
     }
 
     pub fn send_event(&self, event: Event) {
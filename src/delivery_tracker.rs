@@ -0,0 +1,209 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! End-to-end acknowledgements of user messages.
+//!
+//! The destination of an `AckedUserMessage` replies with an `Ack` signed by the same authority the
+//! message was addressed to - the node or the section. The source resends the message when the ack
+//! doesn't arrive in time and reports the outcome once it does, or once all attempts time out.
+
+use crate::{
+    error::Result,
+    location::{DstLocation, SrcLocation},
+    messages::MessageHash,
+    time::Duration,
+};
+use bincode::serialize;
+use lru_time_cache::LruCache;
+use std::collections::HashMap;
+
+/// Time to wait for the acknowledgement of a message before resending it.
+pub const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+/// Maximal number of attempts to send a message before reporting it as failed.
+pub const ACK_MAX_ATTEMPTS: u8 = 3;
+// How long we remember the messages we received, to not report their resends again.
+const RECEIVED_EXPIRY_DURATION: Duration = Duration::from_secs(20 * 60);
+// How long we remember the sequence numbers of the messages we sent. Longer than the receivers
+// remember the messages plus the time we keep resending them, so a reused sequence number never
+// makes a new message look like a resend of an old one.
+const SEQUENCE_EXPIRY_DURATION: Duration = Duration::from_secs(25 * 60);
+
+/// Returns the id of the acknowledged message. `sequence` counts the previous sends of the same
+/// content from the same source to the same destination, so identical messages sent one after the
+/// other get different ids. The id doesn't depend on who sends the message, so all the elders
+/// sending it on behalf of their section agree on it as long as they send the same messages.
+pub fn message_id(
+    src: &SrcLocation,
+    dst: &DstLocation,
+    content: &[u8],
+    sequence: u64,
+) -> Result<MessageHash> {
+    Ok(MessageHash::from_bytes(&serialize(&(
+        src, dst, content, sequence,
+    ))?))
+}
+
+/// Message sent by us that wasn't acknowledged yet.
+pub struct PendingDelivery {
+    pub id: MessageHash,
+    pub src: SrcLocation,
+    pub dst: DstLocation,
+    pub content: Vec<u8>,
+    /// The last send attempt, starting from 0.
+    pub attempt: u8,
+}
+
+pub struct DeliveryTracker {
+    // Messages we sent that weren't acknowledged yet, with the tokens of their timeouts.
+    pending: HashMap<MessageHash, (PendingDelivery, u64)>,
+    // Messages we received and acknowledged.
+    received: LruCache<MessageHash, ()>,
+    // Sequence numbers of the last messages we sent, by the ids of their first sends.
+    sequences: LruCache<MessageHash, u64>,
+}
+
+impl DeliveryTracker {
+    pub fn new() -> Self {
+        Self {
+            pending: HashMap::new(),
+            received: LruCache::with_expiry_duration(RECEIVED_EXPIRY_DURATION),
+            sequences: LruCache::with_expiry_duration(SEQUENCE_EXPIRY_DURATION),
+        }
+    }
+
+    /// Returns the id for a new send of the given message.
+    pub fn next_id(
+        &mut self,
+        src: &SrcLocation,
+        dst: &DstLocation,
+        content: &[u8],
+    ) -> Result<MessageHash> {
+        let first_id = message_id(src, dst, content, 0)?;
+        let sequence = self
+            .sequences
+            .get(&first_id)
+            .map(|sequence| sequence + 1)
+            .unwrap_or(0);
+        let _ = self.sequences.insert(first_id, sequence);
+
+        message_id(src, dst, content, sequence)
+    }
+
+    /// Starts waiting for the acknowledgement of the message, until the timer with the given token
+    /// fires.
+    pub fn insert(&mut self, delivery: PendingDelivery, timer_token: u64) {
+        let _ = self.pending.insert(delivery.id, (delivery, timer_token));
+    }
+
+    /// Handles the acknowledgement of the message with the given id. Returns whether it
+    /// acknowledged a pending message, which happens only if it comes from the destination of the
+    /// message.
+    pub fn handle_ack(&mut self, src: &SrcLocation, id: &MessageHash) -> bool {
+        match self.pending.get(id) {
+            Some((delivery, _)) if is_acked_by(&delivery.dst, src) => {
+                let _ = self.pending.remove(id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Handles the timeout with the given token. Returns the message whose acknowledgement timed
+    /// out, if any.
+    pub fn handle_timeout(&mut self, timer_token: u64) -> Option<PendingDelivery> {
+        let id = self
+            .pending
+            .iter()
+            .find(|(_, (_, token))| *token == timer_token)
+            .map(|(id, _)| *id)?;
+        self.pending.remove(&id).map(|(delivery, _)| delivery)
+    }
+
+    /// Records that we received the message with the given id. Returns whether we received it for
+    /// the first time.
+    pub fn insert_received(&mut self, id: MessageHash) -> bool {
+        self.received.insert(id, ()).is_none()
+    }
+}
+
+// Returns whether `src` is the authority that acknowledges messages sent to `dst`.
+fn is_acked_by(dst: &DstLocation, src: &SrcLocation) -> bool {
+    match (dst, src) {
+        (DstLocation::Node(dst_name), SrcLocation::Node(src_name)) => dst_name == src_name,
+        (DstLocation::Section(dst_name), SrcLocation::Section(src_prefix)) => {
+            src_prefix.matches(dst_name)
+        }
+        (DstLocation::Node(_), SrcLocation::Section(_))
+        | (DstLocation::Section(_), SrcLocation::Node(_))
+        | (DstLocation::Direct, _) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rng, unwrap,
+        xor_space::{Prefix, XorName, Xorable},
+    };
+    use rand::Rng;
+
+    #[test]
+    fn ack_from_destination_only() {
+        let mut rng = rng::new();
+        let src = SrcLocation::Node(rng.gen());
+        let dst_name: XorName = rng.gen();
+        let dst = DstLocation::Section(dst_name);
+        let id = unwrap!(message_id(&src, &dst, b"hello", 0));
+
+        let mut tracker = DeliveryTracker::new();
+        tracker.insert(
+            PendingDelivery {
+                id,
+                src,
+                dst,
+                content: b"hello".to_vec(),
+                attempt: 0,
+            },
+            1,
+        );
+
+        // The node with the destination name isn't the section.
+        assert!(!tracker.handle_ack(&SrcLocation::Node(dst_name), &id));
+        // A section not containing the destination name.
+        let other_prefix = Prefix::default().pushed(!dst_name.bit(0));
+        assert!(!tracker.handle_ack(&SrcLocation::Section(other_prefix), &id));
+
+        let prefix = Prefix::default().pushed(dst_name.bit(0));
+        assert!(tracker.handle_ack(&SrcLocation::Section(prefix), &id));
+        assert!(!tracker.handle_ack(&SrcLocation::Section(prefix), &id));
+        assert!(tracker.handle_timeout(1).is_none());
+    }
+
+    #[test]
+    fn identical_messages_get_different_ids() {
+        let mut rng = rng::new();
+        let src = SrcLocation::Node(rng.gen());
+        let dst = DstLocation::Node(rng.gen());
+
+        let mut sender = DeliveryTracker::new();
+        let id0 = unwrap!(sender.next_id(&src, &dst, b"hello"));
+        let id1 = unwrap!(sender.next_id(&src, &dst, b"hello"));
+        assert_ne!(id0, id1);
+
+        // Another elder sending the same messages on behalf of the section agrees on their ids.
+        let mut other_sender = DeliveryTracker::new();
+        assert_eq!(unwrap!(other_sender.next_id(&src, &dst, b"hello")), id0);
+        assert_eq!(unwrap!(other_sender.next_id(&src, &dst, b"hello")), id1);
+
+        let mut receiver = DeliveryTracker::new();
+        assert!(receiver.insert_received(id0));
+        assert!(receiver.insert_received(id1));
+        assert!(!receiver.insert_received(id1));
+    }
+}
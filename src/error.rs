@@ -47,4 +47,6 @@ pub enum RoutingError {
     InvalidElderDkgResult,
     #[error(display = "Error while trying to receive a message from a channel.")]
     ChannelRecvError(crossbeam_channel::RecvError),
+    #[error(display = "I/O error.")]
+    Io(std::io::Error),
 }
@@ -8,12 +8,15 @@
 
 use crate::{
     location::{DstLocation, SrcLocation},
+    messages::{MessageHash, RouteHop},
+    time::Duration,
     xor_space::{Prefix, XorName},
 };
 use hex_fmt::HexFmt;
 use std::{
     collections::BTreeSet,
     fmt::{self, Debug, Formatter},
+    net::SocketAddr,
 };
 
 /// An Event raised as node complete joining
@@ -25,6 +28,23 @@ pub enum Connected {
     Relocate,
 }
 
+/// Progress of a node bootstrapping off the network.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum BootstrapProgress {
+    /// Sent bootstrap requests to the given known peers.
+    Contacting(Vec<SocketAddr>),
+    /// Redirected to the given peers of a section closer to the one we are joining, and sent
+    /// bootstrap requests to them.
+    Redirected(Vec<SocketAddr>),
+    /// None of the contacted peers responded. Retrying after the given delay.
+    Retrying {
+        /// The retry number, starting from 1.
+        attempt: u32,
+        /// Delay before the retry.
+        delay: Duration,
+    },
+}
+
 /// An Event raised by a `Node` or `Client` via its event sender.
 ///
 /// These are sent by routing to the library's user. It allows the user to handle requests and
@@ -46,7 +66,23 @@ pub enum Event {
         src: SrcLocation,
         /// The destination location that receives the message.
         dst: DstLocation,
+        /// The nodes that sent the message on, starting with its source, if the source traced its
+        /// route (see `RouteConfig::trace`).
+        trace: Option<Vec<RouteHop>>,
+    },
+    /// A message we sent failed to reach any of the nodes we sent it to, even after resending.
+    MessageUndelivered {
+        /// The content of the message.
+        content: Vec<u8>,
+        /// The destination location of the message.
+        dst: DstLocation,
     },
+    /// The destination acknowledged receiving the message with the given hash, sent with
+    /// `Node::send_message_with_ack`.
+    Delivered(MessageHash),
+    /// The message with the given hash, sent with `Node::send_message_with_ack`, wasn't
+    /// acknowledged by its destination even after resending it.
+    DeliveryFailed(MessageHash),
     /// Consensus on a custom event.
     Consensus(Vec<u8>),
     /// The node has been promoted to elder
@@ -76,7 +112,39 @@ pub enum Event {
         /// The set of elders of our section.
         elders: BTreeSet<XorName>,
     },
-    /// Disconnected or failed to connect - restart required.
+    /// A DKG round generating the key for a new set of our elders did not complete in time.
+    DkgTimedOut {
+        /// The participants of the stalled round.
+        participants: BTreeSet<XorName>,
+        /// The attempt number of the stalled round, starting from 1.
+        attempt: u8,
+    },
+    /// A stalled DKG round has been restarted without its unresponsive participants.
+    DkgRestarted {
+        /// The participants of the new round.
+        participants: BTreeSet<XorName>,
+        /// The participants of the stalled round that were excluded from the new one.
+        excluded: BTreeSet<XorName>,
+        /// The attempt number of the new round.
+        attempt: u8,
+    },
+    /// Consensus in our section made no progress for the configured stall timeout although there
+    /// are observations waiting for it. Usually means too many elders became unresponsive at once.
+    ///
+    /// Raised once per stall. The node pings the unresponsive elders and gossips to the others,
+    /// which recovers from temporary unreachability. If the stall persists and at least two thirds
+    /// of the elders are still responsive, they restart the consensus among themselves and vote
+    /// the unresponsive elders offline, which replaces them through a new DKG and elders info
+    /// round. With fewer responsive elders the section can't recover on its own and has to be
+    /// restarted.
+    ConsensusStalled {
+        /// The elders we haven't heard from within the stall timeout.
+        unresponsive: BTreeSet<XorName>,
+    },
+    /// Progress of bootstrapping off the network.
+    Bootstrapping(BootstrapProgress),
+    /// Disconnected or failed to connect, including giving up bootstrapping after
+    /// `BootstrapConfig::max_retries` - restart required.
     RestartRequired,
     /// Startup failed - terminate.
     Terminated,
@@ -86,13 +154,27 @@ impl Debug for Event {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         match self {
             Self::Connected(connect_type) => write!(formatter, "Connected({:?})", connect_type),
-            Self::MessageReceived { content, src, dst } => write!(
+            Self::MessageReceived {
+                content,
+                src,
+                dst,
+                trace,
+            } => write!(
                 formatter,
-                "MessageReceived {{ content: \"{:<8}\", src: {:?}, dst: {:?} }}",
+                "MessageReceived {{ content: \"{:<8}\", src: {:?}, dst: {:?}, trace: {:?} }}",
                 HexFmt(content),
                 src,
+                dst,
+                trace
+            ),
+            Self::MessageUndelivered { content, dst } => write!(
+                formatter,
+                "MessageUndelivered {{ content: \"{:<8}\", dst: {:?} }}",
+                HexFmt(content),
                 dst
             ),
+            Self::Delivered(hash) => write!(formatter, "Delivered({:?})", hash),
+            Self::DeliveryFailed(hash) => write!(formatter, "DeliveryFailed({:?})", hash),
             Self::Consensus(payload) => write!(formatter, "Consensus({:<8})", HexFmt(payload)),
             Self::Promoted => write!(formatter, "Promoted"),
             Self::Demoted => write!(formatter, "Demoted"),
@@ -116,6 +198,29 @@ impl Debug for Event {
                 .field("key", key)
                 .field("elders", elders)
                 .finish(),
+            Self::DkgTimedOut {
+                participants,
+                attempt,
+            } => formatter
+                .debug_struct("DkgTimedOut")
+                .field("participants", participants)
+                .field("attempt", attempt)
+                .finish(),
+            Self::DkgRestarted {
+                participants,
+                excluded,
+                attempt,
+            } => formatter
+                .debug_struct("DkgRestarted")
+                .field("participants", participants)
+                .field("excluded", excluded)
+                .field("attempt", attempt)
+                .finish(),
+            Self::ConsensusStalled { unresponsive } => formatter
+                .debug_struct("ConsensusStalled")
+                .field("unresponsive", unresponsive)
+                .finish(),
+            Self::Bootstrapping(progress) => write!(formatter, "Bootstrapping({:?})", progress),
             Self::RestartRequired => write!(formatter, "RestartRequired"),
             Self::Terminated => write!(formatter, "Terminated"),
         }
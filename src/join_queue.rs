@@ -0,0 +1,175 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Queue of the new nodes waiting to join our section.
+//!
+//! Every elder keeps its own queue of the joiners it told to wait while the section didn't accept
+//! new members, in the order they first asked. The joiners are told to retry after a delay growing
+//! with their position, and once the section accepts new members again, only the first
+//! `JOIN_BATCH_SIZE` of them are admitted at a time. Newcomers queue behind the waiting joiners
+//! instead of overtaking them. The queue holds at most `JOIN_QUEUE_CAPACITY` joiners, the ones
+//! beyond it aren't answered, so their join times out and they bootstrap again later. Joiners that
+//! stop retrying are dropped from the queue.
+
+use crate::{
+    id::PublicId,
+    time::{Duration, Instant},
+};
+use std::collections::VecDeque;
+
+/// Maximal number of joiners waiting in the queue.
+pub const JOIN_QUEUE_CAPACITY: usize = 100;
+/// Number of joiners at the head of the queue admitted at once.
+pub const JOIN_BATCH_SIZE: usize = 10;
+/// Delay after which the joiners in the first batch of the queue are asked to retry. Each further
+/// batch retries this much later than the previous one.
+pub const JOIN_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// What to do with a join request of a new node.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JoinAdmission {
+    /// Handle the request.
+    Admit,
+    /// Tell the joiner to retry after the given delay.
+    Wait(Duration),
+    /// The queue is full. Ignore the request.
+    Full,
+}
+
+struct Joiner {
+    pub_id: PublicId,
+    // When the joiner is dropped from the queue unless it retries.
+    expires_at: Instant,
+}
+
+/// Queue of the new nodes waiting to join our section.
+#[derive(Default)]
+pub struct JoinQueue {
+    joiners: VecDeque<Joiner>,
+}
+
+impl JoinQueue {
+    /// Decides on the join request of a new node, queueing it if it has to wait.
+    pub fn handle_request(&mut self, pub_id: &PublicId, accepting: bool) -> JoinAdmission {
+        let now = Instant::now();
+        self.joiners.retain(|joiner| joiner.expires_at > now);
+
+        let position = self
+            .joiners
+            .iter()
+            .position(|joiner| joiner.pub_id == *pub_id);
+
+        if accepting && self.joiners.is_empty() {
+            return JoinAdmission::Admit;
+        }
+
+        let position = match position {
+            Some(position) => position,
+            None if self.joiners.len() >= JOIN_QUEUE_CAPACITY => return JoinAdmission::Full,
+            None => {
+                self.joiners.push_back(Joiner {
+                    pub_id: *pub_id,
+                    expires_at: now,
+                });
+                self.joiners.len() - 1
+            }
+        };
+
+        // Admitted joiners stay queued until they become members, as joining takes them more than
+        // one request.
+        let admission = if accepting && position < JOIN_BATCH_SIZE {
+            JoinAdmission::Admit
+        } else {
+            JoinAdmission::Wait(retry_delay(position))
+        };
+
+        self.joiners[position].expires_at = now + retry_delay(position) + JOIN_RETRY_DELAY;
+
+        admission
+    }
+
+    /// Removes the joiner from the queue once it became our member.
+    pub fn remove(&mut self, pub_id: &PublicId) {
+        self.joiners.retain(|joiner| joiner.pub_id != *pub_id)
+    }
+}
+
+fn retry_delay(position: usize) -> Duration {
+    JOIN_RETRY_DELAY * (1 + position / JOIN_BATCH_SIZE) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{id::FullId, rng, unwrap};
+
+    fn gen_ids(count: usize) -> Vec<PublicId> {
+        let mut rng = rng::new();
+        (0..count)
+            .map(|_| *FullId::gen(&mut rng).public_id())
+            .collect()
+    }
+
+    #[test]
+    fn admit_in_order() {
+        let mut ids = gen_ids(JOIN_BATCH_SIZE + 3);
+        let newcomer = unwrap!(ids.pop());
+        let mut queue = JoinQueue::default();
+
+        for (index, id) in ids.iter().enumerate() {
+            let expected_delay = retry_delay(index);
+            assert_eq!(
+                queue.handle_request(id, false),
+                JoinAdmission::Wait(expected_delay)
+            );
+        }
+
+        // The first batch is admitted, the rest keep waiting, and so do newcomers.
+        assert_eq!(queue.handle_request(&ids[0], true), JoinAdmission::Admit);
+        assert_eq!(
+            queue.handle_request(&ids[JOIN_BATCH_SIZE], true),
+            JoinAdmission::Wait(JOIN_RETRY_DELAY * 2)
+        );
+        assert_ne!(queue.handle_request(&newcomer, true), JoinAdmission::Admit);
+
+        // As the admitted joiners become members, the next ones move up.
+        for id in &ids[..JOIN_BATCH_SIZE] {
+            queue.remove(id);
+        }
+        assert_eq!(
+            queue.handle_request(&ids[JOIN_BATCH_SIZE], true),
+            JoinAdmission::Admit
+        );
+
+        // With nobody waiting, joiners are admitted right away.
+        for id in ids.iter().skip(JOIN_BATCH_SIZE).chain(Some(&newcomer)) {
+            queue.remove(id);
+        }
+        assert_eq!(queue.handle_request(&newcomer, true), JoinAdmission::Admit);
+    }
+
+    #[test]
+    fn bounded() {
+        let ids = gen_ids(JOIN_QUEUE_CAPACITY + 1);
+        let mut queue = JoinQueue::default();
+
+        for id in &ids[..JOIN_QUEUE_CAPACITY] {
+            assert_ne!(queue.handle_request(id, false), JoinAdmission::Full);
+        }
+        assert_eq!(
+            queue.handle_request(&ids[JOIN_QUEUE_CAPACITY], false),
+            JoinAdmission::Full
+        );
+
+        // The queued joiners keep their places.
+        assert_eq!(
+            queue.handle_request(&ids[0], false),
+            JoinAdmission::Wait(JOIN_RETRY_DELAY)
+        );
+    }
+}
@@ -80,13 +80,28 @@ mod macros;
 // Public API
 // ############################################################################
 pub use self::{
+    consensus::{ConsensusAlgorithm, ConsensusConfig, GossipConfig},
     error::RoutingError,
     id::{FullId, P2pNode, PublicId},
     location::{DstLocation, SrcLocation},
-    node::{Node, NodeConfig},
+    messages::{MessageHash, RouteConfig, RouteHop, UserPriority, DEFAULT_MAX_HOPS},
+    node::{BootstrapConfig, Node, NodeConfig},
     pause::PausedState,
     quic_p2p::Config as TransportConfig,
     quic_p2p::Event as TransportEvent,
+    resource_proof::ResourceProofParams,
+    section::{
+        AddressGroup, AdmissionPolicy, Ageing, BanDetails, BanReason, Churn,
+        DefaultAdmissionPolicy, DefaultElderSelection, DefaultRelocationPolicy, DefaultSplitPolicy,
+        DiversityRules, DiversityScope, DiversityViolation, ElderCandidate, ElderSelection,
+        KnownSection, LimitedAdmissionPolicy, MaxSizeSplitPolicy, RebalancingRelocationPolicy,
+        RejoinRules, RelocationCandidate, RelocationPolicy, SectionOccupancy, SplitCandidate,
+        SplitPolicy, DEFAULT_BAN_DURATION,
+    },
+    transport::{
+        BandwidthConfig, MemoryConfig, MemoryNetwork, PeerStats, RateLimit, TcpConfig,
+        TransportKind, MAX_OUTBOUND_DELAY,
+    },
     xor_space::{Prefix, XorName, XOR_NAME_LEN},
 };
 /// Routing events.
@@ -107,9 +122,13 @@ pub mod rng;
 #[cfg(feature = "mock_base")]
 pub use self::{
     consensus::generate_bls_threshold_secret_key,
-    messages::{AccumulatingMessage, Message, MessageHash, PlainMessage, Variant},
+    messages::{
+        AccumulatingMessage, JoinRequest, Message, PlainMessage, Priority, SrcAuthority, Variant,
+    },
     network_params::NetworkParams,
-    relocation::Overrides as RelocationOverrides,
+    relocation::{
+        Overrides as RelocationOverrides, RelocateDetails, RelocatePayload, SignedRelocateDetails,
+    },
     routing_table::delivery_group_size,
     section::{quorum_count, EldersInfo, IndexedSecretKeyShare, SectionProofChain, MIN_AGE},
     xor_space::Xorable,
@@ -119,7 +138,11 @@ pub use self::{
 #[doc(hidden)]
 pub mod test_consts {
     pub use crate::{
-        consensus::{GOSSIP_PERIOD, UNRESPONSIVE_THRESHOLD, UNRESPONSIVE_WINDOW},
+        consensus::{
+            DKG_MAX_ATTEMPTS, DKG_TIMEOUT, GOSSIP_PERIOD, STALL_TIMEOUT, UNRESPONSIVE_THRESHOLD,
+            UNRESPONSIVE_WINDOW,
+        },
+        delivery_tracker::{ACK_MAX_ATTEMPTS, ACK_TIMEOUT},
         node::{BOOTSTRAP_TIMEOUT, JOIN_TIMEOUT},
         transport::{RESEND_DELAY, RESEND_MAX_ATTEMPTS},
     };
@@ -134,15 +157,19 @@ pub use self::mock::parsec::init_mock;
 
 mod consensus;
 mod core;
+mod delivery_tracker;
 mod error;
 mod id;
+mod join_queue;
 mod location;
 mod message_filter;
 mod messages;
 mod network_params;
 mod node;
 mod pause;
+mod reachability;
 mod relocation;
+mod resource_proof;
 #[cfg(not(feature = "mock_base"))]
 mod rng;
 mod routing_table;
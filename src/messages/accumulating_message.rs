@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{DstLocation, Message, MessageHash, SrcAuthority, Variant};
+use super::{DstLocation, Message, MessageHash, Priority, Route, SrcAuthority, Variant};
 use crate::{
     error::Result,
     section::{IndexedSecretKeyShare, SectionProofChain},
@@ -104,6 +104,7 @@ impl AccumulatingMessage {
         };
 
         Some(Message {
+            route: Route::new(self.content.priority),
             src: SrcAuthority::Section {
                 prefix: self.content.src,
                 signature,
@@ -156,6 +157,8 @@ pub struct PlainMessage {
     pub dst_key: bls::PublicKey,
     /// Message body.
     pub variant: Variant,
+    /// Priority class of the message, not covered by the signature.
+    pub priority: Priority,
 }
 
 impl PlainMessage {
@@ -270,6 +273,7 @@ mod tests {
             dst: DstLocation::Section(rng.gen()),
             dst_key: gen_secret_key(rng).public_key(),
             variant: Variant::UserMessage(rng.sample_iter(Standard).take(6).collect()),
+            priority: Priority::UserNormal,
         }
     }
 
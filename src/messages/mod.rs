@@ -8,6 +8,8 @@
 
 mod accumulating_message;
 mod hash;
+mod priority;
+mod route;
 mod src_authority;
 mod variant;
 mod with_bytes;
@@ -15,8 +17,13 @@ mod with_bytes;
 pub use self::{
     accumulating_message::{AccumulatingMessage, PlainMessage},
     hash::MessageHash,
+    priority::{Priority, UserPriority},
+    route::{Route, RouteConfig, RouteHop, DEFAULT_MAX_HOPS},
     src_authority::SrcAuthority,
-    variant::{BootstrapResponse, JoinRequest, MemberKnowledge, Variant},
+    variant::{
+        BootstrapResponse, JoinRejection, JoinRequest, MemberKnowledge, MergeRequest,
+        RejoinPayload, Variant,
+    },
     with_bytes::MessageWithBytes,
 };
 use crate::{
@@ -37,6 +44,9 @@ use std::{
 pub struct Message {
     /// Destination location.
     pub dst: DstLocation,
+    /// Routing information updated by the relaying nodes. Must directly follow `dst` so the two
+    /// form the `PartialMessage` the relays can update without touching the rest of the message.
+    pub route: Route,
     /// Source authority.
     pub src: SrcAuthority,
     /// The body of the message.
@@ -52,6 +62,8 @@ pub struct Message {
 pub struct PartialMessage {
     /// Destination location.
     pub dst: DstLocation,
+    /// Routing information.
+    pub route: Route,
 }
 
 impl PartialMessage {
@@ -79,6 +91,7 @@ impl Message {
 
         Ok(Self {
             dst,
+            route: Route::new(variant.priority()),
             src: SrcAuthority::Node {
                 public_id: *src.public_id(),
                 signature,
@@ -105,7 +118,10 @@ impl Message {
     }
 
     pub(crate) fn to_partial(&self) -> PartialMessage {
-        PartialMessage { dst: self.dst }
+        PartialMessage {
+            dst: self.dst,
+            route: self.route.clone(),
+        }
     }
 }
 
@@ -115,6 +131,7 @@ impl Debug for Message {
             .debug_struct("Message")
             .field("src", &self.src.location())
             .field("dst", &self.dst)
+            .field("route", &self.route)
             .field("variant", &self.variant)
             .finish()
     }
@@ -0,0 +1,60 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+/// Priority class of a message. The transport sends the queued messages of a higher class before
+/// any of a lower class. All the routing-internal classes rank above the user ones.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum Priority {
+    /// User message sent with `UserPriority::Low`.
+    UserLow,
+    /// User message sent with `UserPriority::Normal`.
+    UserNormal,
+    /// User message sent with `UserPriority::High`.
+    UserHigh,
+    /// Routing-internal message not covered by the classes below.
+    Routing,
+    /// Consensus gossip.
+    Consensus,
+    /// Message needed to process churn: approvals, relocations and genesis updates.
+    Churn,
+}
+
+impl Priority {
+    /// Whether this is one of the classes of user messages.
+    pub fn is_user(self) -> bool {
+        self <= Self::UserHigh
+    }
+}
+
+/// Priority of a user message, relative to the other user messages. User messages are always sent
+/// after the routing-internal ones.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum UserPriority {
+    /// Bulk traffic, sent only when nothing else is queued.
+    Low,
+    /// The priority of messages sent with `Node::send_message`.
+    Normal,
+    /// Urgent traffic, sent before the other user messages.
+    High,
+}
+
+impl Default for UserPriority {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+impl From<UserPriority> for Priority {
+    fn from(priority: UserPriority) -> Self {
+        match priority {
+            UserPriority::Low => Self::UserLow,
+            UserPriority::Normal => Self::UserNormal,
+            UserPriority::High => Self::UserHigh,
+        }
+    }
+}
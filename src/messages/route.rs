@@ -0,0 +1,113 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Priority;
+use crate::xor_space::{Prefix, XorName};
+use std::cmp;
+
+/// Default maximal number of hops a message can take.
+pub const DEFAULT_MAX_HOPS: u8 = 32;
+
+/// Configuration of how messages are routed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RouteConfig {
+    /// Maximal number of hops a message can take. Messages that reach it are dropped instead of
+    /// relayed further.
+    pub max_hops: u8,
+    /// Whether to trace the route of the messages we send. Each node sending a traced message on
+    /// appends itself to the trace, which the recipient gets in `Event::MessageReceived`.
+    pub trace: bool,
+}
+
+impl Default for RouteConfig {
+    fn default() -> Self {
+        Self {
+            max_hops: DEFAULT_MAX_HOPS,
+            trace: false,
+        }
+    }
+}
+
+/// Routing information of a message, updated by every node sending it on. Not covered by the
+/// signature of the message.
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+pub struct Route {
+    /// Number of nodes that sent the message on so far, including its source.
+    pub hop_count: u8,
+    /// The nodes that sent the message on so far, if its source asked to trace its route.
+    pub trace: Option<Vec<RouteHop>>,
+    /// Priority class given to the message by its source. The relays queue the message by it
+    /// without deserializing the whole message.
+    pub priority: Priority,
+}
+
+impl Route {
+    /// Returns the route of a message not sent yet.
+    pub fn new(priority: Priority) -> Self {
+        Self {
+            hop_count: 0,
+            trace: None,
+            priority,
+        }
+    }
+
+    /// Returns the priority class to relay the message with, read from the route without
+    /// deserializing or verifying the message. The priority isn't signed, so it's taken as is only
+    /// from a trusted sender - an elder, which did the same check when it received the message.
+    /// From anyone else it is capped at `Priority::Routing`, which any node can claim by signing
+    /// the message itself anyway.
+    pub fn relay_priority(&self, trusted_sender: bool) -> Priority {
+        if trusted_sender {
+            self.priority
+        } else {
+            cmp::min(self.priority, Priority::Routing)
+        }
+    }
+
+    /// Returns the route after a hop through the given node.
+    pub fn next_hop(&self, name: XorName, prefix: Prefix<XorName>) -> Self {
+        Self {
+            hop_count: self.hop_count.saturating_add(1),
+            trace: self.trace.as_ref().map(|trace| {
+                let mut trace = trace.clone();
+                trace.push(RouteHop { name, prefix });
+                trace
+            }),
+            priority: self.priority,
+        }
+    }
+}
+
+/// Node that sent a message on, as recorded in the route trace.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+pub struct RouteHop {
+    /// Name of the node.
+    pub name: XorName,
+    /// Prefix of the section of the node.
+    pub prefix: Prefix<XorName>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relay_priority_trusts_only_elders() {
+        let route = Route::new(Priority::Churn);
+
+        // An elder already checked the priority when it received the message.
+        assert_eq!(route.relay_priority(true), Priority::Churn);
+
+        // Anyone else can't send anything above `Priority::Routing`, whatever its route says.
+        assert_eq!(route.relay_priority(false), Priority::Routing);
+
+        // But picks among the user classes freely.
+        let route = Route::new(Priority::UserLow);
+        assert_eq!(route.relay_priority(false), Priority::UserLow);
+    }
+}
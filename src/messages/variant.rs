@@ -6,13 +6,20 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{AccumulatingMessage, MessageHash};
+use super::{AccumulatingMessage, MessageHash, Priority};
 use crate::{
-    consensus::{GenesisPrefixInfo, ParsecRequest, ParsecResponse},
+    consensus::{ConsensusRequest, ConsensusResponse, GenesisPrefixInfo},
+    crypto::signing::Signature,
+    error::RoutingError,
+    id::{FullId, PublicId},
+    reachability::ReachabilityProof,
     relocation::{RelocateDetails, RelocatePayload},
-    section::EldersInfo,
+    resource_proof::{ResourceProof, ResourceProofChallenge},
+    section::{BanDetails, EldersInfo, MemberInfo, MemberState},
+    time::Duration,
     xor_space::XorName,
 };
+use bincode::serialize;
 use bytes::Bytes;
 use hex_fmt::HexFmt;
 use serde::Serialize;
@@ -35,6 +42,18 @@ pub enum Variant {
     },
     /// User-facing message
     UserMessage(Vec<u8>),
+    /// User-facing message whose destination acknowledges receiving it with `Ack`.
+    AckedUserMessage {
+        /// The content of the message.
+        content: Vec<u8>,
+        /// Id of the message, the same for all its send attempts.
+        id: MessageHash,
+        /// Number of the send attempt, so the resends aren't filtered out as duplicates.
+        attempt: u8,
+    },
+    /// Sent from the destination of an `AckedUserMessage` to its source, acknowledging the
+    /// message with the given id.
+    Ack(MessageHash),
     /// Approves the joining node as a routing node.
     /// Section X -> Node joining X
     NodeApproval(Box<GenesisPrefixInfo>),
@@ -54,15 +73,41 @@ pub enum Variant {
     /// Sent from a bootstrapping peer to the section that responded with a
     /// `BootstrapResponse::Join` to its `BootstrapRequest`.
     JoinRequest(Box<JoinRequest>),
+    /// Sent from an elder to a peer whose `JoinRequest` didn't contain a valid resource proof.
+    /// The peer has to solve the challenge and send its `JoinRequest` again with the solution.
+    ResourceChallenge(ResourceProofChallenge),
+    /// Sent from an elder to a peer whose `JoinRequest` is missing its reachability proof, over a
+    /// new connection to the peer's address. The peer has to reply with `EchoResponse` carrying
+    /// the same nonce.
+    EchoRequest(u64),
+    /// Sent from a joining peer to the elder in response to `EchoRequest`.
+    EchoResponse(u64),
+    /// Sent from an elder to a peer that replied to its `EchoRequest`. The peer has to include it
+    /// in its `JoinRequest`.
+    ReachabilityProof(ReachabilityProof),
+    /// Sent from an elder to a peer whose `JoinRequest` was rejected.
+    JoinRejected(JoinRejection),
     /// Sent from Adults and Infants to Elders. Updates Elders about the sender's knowledge of its
     /// own section.
     MemberKnowledge(MemberKnowledge),
-    /// Parsec request message
-    ParsecRequest(u64, ParsecRequest),
-    /// Parsec response message
-    ParsecResponse(u64, ParsecResponse),
-    /// Message sent to a disconnected peer to trigger lost peer detection.
-    Ping,
+    /// Consensus request message
+    ConsensusRequest(u64, ConsensusRequest),
+    /// Consensus response message
+    ConsensusResponse(u64, ConsensusResponse),
+    /// Sent from a section that became too small to its sibling section, asking the sibling to
+    /// absorb it.
+    MergeRequest(Box<MergeRequest>),
+    /// Sent from a section to its neighbour sections after it banned one of its peers for
+    /// misbehaviour, so they refuse the peer too.
+    Ban(BanDetails),
+    /// Sent periodically from the elders of a section to the elders of its neighbour sections,
+    /// telling them the number of members of the section.
+    SectionSize(usize),
+    /// Message sent to a peer to measure the round-trip time to it, or to a disconnected peer to
+    /// trigger lost peer detection. The peer replies with `Pong` carrying the same nonce.
+    Ping(u64),
+    /// Response to `Ping`.
+    Pong(u64),
     /// Response to a message that the recipient cannot handle at this time but might be able to
     /// handle it later. For example, an adult receiving a message intended for elders will be
     /// able to handle it after being promoted.
@@ -75,6 +120,36 @@ pub enum Variant {
     },
 }
 
+impl Variant {
+    /// Priority class of messages with this variant. User messages get `Priority::UserNormal`
+    /// unless their sender chooses otherwise.
+    pub(crate) fn priority(&self) -> Priority {
+        match self {
+            Self::NodeApproval(_) | Self::GenesisUpdate(_) | Self::Relocate(_) => Priority::Churn,
+            Self::ConsensusRequest(..) | Self::ConsensusResponse(..) => Priority::Consensus,
+            Self::UserMessage(_) | Self::AckedUserMessage { .. } => Priority::UserNormal,
+            Self::MessageSignature(accumulating_msg) => accumulating_msg.content.priority,
+            Self::NeighbourInfo { .. }
+            | Self::Ack(_)
+            | Self::BootstrapRequest(_)
+            | Self::BootstrapResponse(_)
+            | Self::JoinRequest(_)
+            | Self::ResourceChallenge(_)
+            | Self::EchoRequest(_)
+            | Self::EchoResponse(_)
+            | Self::ReachabilityProof(_)
+            | Self::JoinRejected(_)
+            | Self::MemberKnowledge(_)
+            | Self::MergeRequest(_)
+            | Self::Ban(_)
+            | Self::SectionSize(_)
+            | Self::Ping(_)
+            | Self::Pong(_)
+            | Self::Bounce { .. } => Priority::Routing,
+        }
+    }
+}
+
 impl Debug for Variant {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
@@ -84,6 +159,17 @@ impl Debug for Variant {
                 .field("nonce", nonce)
                 .finish(),
             Self::UserMessage(payload) => write!(f, "UserMessage({})", HexFmt(payload)),
+            Self::AckedUserMessage {
+                content,
+                id,
+                attempt,
+            } => f
+                .debug_struct("AckedUserMessage")
+                .field("content", &format_args!("{}", HexFmt(content)))
+                .field("id", id)
+                .field("attempt", attempt)
+                .finish(),
+            Self::Ack(id) => write!(f, "Ack({:?})", id),
             Self::NodeApproval(payload) => write!(f, "NodeApproval({:?})", payload),
             Self::GenesisUpdate(payload) => write!(f, "GenesisUpdate({:?})", payload),
             Self::Relocate(payload) => write!(f, "Relocate({:?})", payload),
@@ -91,10 +177,19 @@ impl Debug for Variant {
             Self::BootstrapRequest(payload) => write!(f, "BootstrapRequest({})", payload),
             Self::BootstrapResponse(payload) => write!(f, "BootstrapResponse({:?})", payload),
             Self::JoinRequest(payload) => write!(f, "JoinRequest({:?})", payload),
+            Self::ResourceChallenge(payload) => write!(f, "ResourceChallenge({:?})", payload),
+            Self::EchoRequest(nonce) => write!(f, "EchoRequest({})", nonce),
+            Self::EchoResponse(nonce) => write!(f, "EchoResponse({})", nonce),
+            Self::ReachabilityProof(payload) => write!(f, "{:?}", payload),
+            Self::JoinRejected(payload) => write!(f, "JoinRejected({:?})", payload),
             Self::MemberKnowledge(payload) => write!(f, "MemberKnowledge({:?})", payload),
-            Self::ParsecRequest(version, _) => write!(f, "ParsecRequest({}, ..)", version),
-            Self::ParsecResponse(version, _) => write!(f, "ParsecResponse({}, ..)", version),
-            Self::Ping => write!(f, "Ping"),
+            Self::ConsensusRequest(version, _) => write!(f, "ConsensusRequest({}, ..)", version),
+            Self::ConsensusResponse(version, _) => write!(f, "ConsensusResponse({}, ..)", version),
+            Self::MergeRequest(payload) => write!(f, "MergeRequest({:?})", payload),
+            Self::Ban(payload) => write!(f, "Ban({:?})", payload),
+            Self::SectionSize(member_count) => write!(f, "SectionSize({})", member_count),
+            Self::Ping(nonce) => write!(f, "Ping({})", nonce),
+            Self::Pong(nonce) => write!(f, "Pong({})", nonce),
             Self::Bounce {
                 elders_version,
                 message,
@@ -116,6 +211,18 @@ pub enum BootstrapResponse {
     /// The new peer should retry bootstrapping with another section. The set of connection infos
     /// of the members of that section is provided.
     Rebootstrap(Vec<SocketAddr>),
+    /// The section queued the new peer to join it once it accepts new members and the peers queued
+    /// before it have joined. The new peer should keep waiting and send its `JoinRequest` again
+    /// after the given delay.
+    Wait(Duration),
+}
+
+/// Reason a section rejected a peer trying to join it.
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Debug, Hash)]
+pub enum JoinRejection {
+    /// The elders couldn't reach the peer at the given address. The peer needs an address that is
+    /// reachable from the rest of the network (e.g. a forwarded port) to join.
+    Unreachable(SocketAddr),
 }
 
 /// Request to join a section
@@ -125,6 +232,13 @@ pub struct JoinRequest {
     pub elders_version: u64,
     /// If the peer is being relocated, contains `RelocatePayload`. Otherwise contains `None`.
     pub relocate_payload: Option<RelocatePayload>,
+    /// If the peer is rejoining our section after a temporary absence, contains `RejoinPayload`.
+    /// Otherwise contains `None`.
+    pub rejoin_payload: Option<RejoinPayload>,
+    /// Solution of the `ResourceChallenge` the section sent to the peer, if any.
+    pub resource_proof: Option<ResourceProof>,
+    /// Proofs of the elders that checked the peer is reachable at its address.
+    pub reachability_proofs: Vec<ReachabilityProof>,
 }
 
 impl Debug for JoinRequest {
@@ -139,6 +253,78 @@ impl Debug for JoinRequest {
                     .as_ref()
                     .map(|payload| payload.relocate_details()),
             )
+            .field(
+                "rejoin_payload",
+                &self
+                    .rejoin_payload
+                    .as_ref()
+                    .map(|payload| payload.previous_id.name()),
+            )
+            .field("resource_proof", &self.resource_proof)
+            .field("reachability_proofs", &self.reachability_proofs)
+            .finish()
+    }
+}
+
+/// Proof that a peer rejoining a section under a new id is the member that left it under its
+/// previous id.
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct RejoinPayload {
+    /// The id the peer had before it left the section.
+    pub previous_id: PublicId,
+    /// The new id of the peer signed using its previous id.
+    pub signature_of_new_id_with_previous_id: Signature,
+}
+
+impl RejoinPayload {
+    pub fn new(new_pub_id: &PublicId, previous_full_id: &FullId) -> Result<Self, RoutingError> {
+        let new_id_serialised = serialize(new_pub_id)?;
+        let signature_of_new_id_with_previous_id = previous_full_id.sign(&new_id_serialised);
+
+        Ok(Self {
+            previous_id: *previous_full_id.public_id(),
+            signature_of_new_id_with_previous_id,
+        })
+    }
+
+    pub fn verify_identity(&self, new_pub_id: &PublicId) -> bool {
+        let new_id_serialised = match serialize(new_pub_id) {
+            Ok(buf) => buf,
+            Err(_) => return false,
+        };
+
+        self.previous_id.verify(
+            &new_id_serialised,
+            &self.signature_of_new_id_with_previous_id,
+        )
+    }
+}
+
+/// Request of a section to be merged into its sibling section.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct MergeRequest {
+    /// `EldersInfo` of the section to be merged.
+    pub elders_info: EldersInfo,
+    /// Members of the section to be merged.
+    pub members: Vec<MemberInfo>,
+}
+
+impl MergeRequest {
+    /// Returns the number of joined members of the section to be merged.
+    pub fn member_count(&self) -> usize {
+        self.members
+            .iter()
+            .filter(|info| info.state == MemberState::Joined)
+            .count()
+    }
+}
+
+impl Debug for MergeRequest {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("MergeRequest")
+            .field("elders_info", &self.elders_info)
+            .field("members", &self.members.len())
             .finish()
     }
 }
@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{DstLocation, Message, MessageHash, PartialMessage};
+use super::{DstLocation, Message, MessageHash, PartialMessage, Route};
 use crate::error::Result;
 use bytes::Bytes;
 use std::fmt::{self, Debug, Formatter};
@@ -16,11 +16,13 @@ use std::fmt::{self, Debug, Formatter};
 pub struct MessageWithBytes {
     /// Wrapped message.
     full_content: Option<Message>,
-    /// Partial message (just the destination location)
+    /// Partial message (the destination location and the route)
     partial_content: PartialMessage,
     /// Serialized full message as received or sent to quic_p2p.
     full_bytes: Bytes,
-    /// Crypto hash of the full message.
+    /// Length of the serialized partial message at the start of `full_bytes`.
+    partial_len: usize,
+    /// Crypto hash of the full message, except the route which changes on every hop.
     full_crypto_hash: MessageHash,
 }
 
@@ -29,7 +31,7 @@ impl MessageWithBytes {
     pub fn new(full_content: Message) -> Result<Self> {
         let full_bytes = full_content.to_bytes()?;
         let partial_content = full_content.to_partial();
-        let result = Self::new_from_parts(Some(full_content), partial_content, full_bytes);
+        let result = Self::new_from_parts(Some(full_content), partial_content, full_bytes)?;
 
         trace!("Creating {:?}", result);
 
@@ -38,7 +40,7 @@ impl MessageWithBytes {
 
     pub fn partial_from_bytes(bytes: Bytes) -> Result<Self> {
         let partial_content = PartialMessage::from_bytes(&bytes)?;
-        Ok(Self::new_from_parts(None, partial_content, bytes))
+        Self::new_from_parts(None, partial_content, bytes)
     }
 
     // Precondition: `full_bytes == serialize(&full_content)`
@@ -46,15 +48,42 @@ impl MessageWithBytes {
         full_content: Option<Message>,
         partial_content: PartialMessage,
         full_bytes: Bytes,
-    ) -> Self {
-        let full_crypto_hash = MessageHash::from_bytes(&full_bytes);
+    ) -> Result<Self> {
+        let partial_len = bincode::serialized_size(&partial_content)? as usize;
+        let full_crypto_hash = MessageHash::from_bytes(&full_bytes[partial_len..]);
 
-        Self {
+        Ok(Self {
             full_content,
             partial_content,
             full_bytes,
+            partial_len,
             full_crypto_hash,
-        }
+        })
+    }
+
+    /// Returns the same message with the given route. Only the serialized partial message is
+    /// replaced, the rest of the message including its signature stays intact.
+    pub fn with_route(&self, route: Route) -> Result<Self> {
+        let partial_content = PartialMessage {
+            dst: self.partial_content.dst,
+            route,
+        };
+        let mut full_bytes = bincode::serialize(&partial_content)?;
+        let partial_len = full_bytes.len();
+        full_bytes.extend_from_slice(&self.full_bytes[self.partial_len..]);
+
+        let full_content = self.full_content.clone().map(|mut content| {
+            content.route = partial_content.route.clone();
+            content
+        });
+
+        Ok(Self {
+            full_content,
+            partial_content,
+            full_bytes: full_bytes.into(),
+            partial_len,
+            full_crypto_hash: self.full_crypto_hash,
+        })
     }
 
     pub fn take_or_deserialize_message(&mut self) -> Result<Message> {
@@ -75,6 +104,10 @@ impl MessageWithBytes {
         &self.partial_content.dst
     }
 
+    pub fn route(&self) -> &Route {
+        &self.partial_content.route
+    }
+
     fn deserialize_message(&self) -> Result<Message> {
         Message::from_bytes(&self.full_bytes)
     }
@@ -96,9 +129,13 @@ impl Debug for MessageWithBytes {
 
 #[cfg(test)]
 mod tests {
-    use super::{super::Variant, *};
-    use crate::{id::FullId, rng, unwrap};
+    use super::{
+        super::{Priority, Variant, VerifyStatus},
+        *,
+    };
+    use crate::{id::FullId, rng, unwrap, xor_space::Prefix};
     use rand::{distributions::Standard, Rng};
+    use std::iter;
 
     #[test]
     fn serialise_and_partial_at_message() {
@@ -114,12 +151,56 @@ mod tests {
 
         let full_msg = unwrap!(Message::from_bytes(bytes));
         let partial_msg = unwrap!(PartialMessage::from_bytes(bytes));
-        let partial_msg_head = unwrap!(PartialMessage::from_bytes(&bytes.slice(0..40)));
+        let partial_msg_head = unwrap!(PartialMessage::from_bytes(&bytes.slice(0..42)));
 
-        let expected_partial = PartialMessage { dst: msg.dst };
+        let expected_partial = PartialMessage {
+            dst: msg.dst,
+            route: Route::new(Priority::UserNormal),
+        };
 
         assert_eq!(partial_msg, expected_partial);
         assert_eq!(partial_msg_head, expected_partial);
         assert_eq!(full_msg, msg);
     }
+
+    #[test]
+    fn update_route() {
+        let mut rng = rng::new();
+        let full_id = FullId::gen(&mut rng);
+
+        let dst = DstLocation::Section(rng.gen());
+        let variant = Variant::UserMessage(rng.sample_iter(Standard).take(6).collect());
+        let msg = unwrap!(Message::single_src(&full_id, dst, variant));
+        let msg_with_bytes = unwrap!(MessageWithBytes::new(msg.clone()));
+
+        let route = Route {
+            hop_count: 0,
+            trace: Some(Vec::new()),
+            priority: Priority::UserNormal,
+        }
+        .next_hop(rng.gen(), Prefix::default());
+        let relayed = unwrap!(msg_with_bytes.with_route(route.clone()));
+
+        // The route doesn't affect the hash, so the relayed message is still recognised as the
+        // same message.
+        assert_eq!(
+            relayed.full_crypto_hash(),
+            msg_with_bytes.full_crypto_hash()
+        );
+        assert_eq!(relayed.route(), &route);
+
+        let relayed_msg = unwrap!(Message::from_bytes(relayed.full_bytes()));
+        assert_eq!(relayed_msg.route, route);
+        assert_eq!(
+            unwrap!(relayed_msg.verify(iter::empty())),
+            VerifyStatus::Full
+        );
+        assert_eq!(
+            relayed_msg,
+            Message {
+                route: route.clone(),
+                ..msg
+            }
+        );
+    }
 }
@@ -12,13 +12,17 @@ use crate::{
     network_params::NetworkParams,
     quic_p2p::Network,
     rng::{self, MainRng, Seed, SeedPrinter},
+    section::{
+        AdmissionPolicy, DefaultAdmissionPolicy, DefaultRelocationPolicy, DefaultSplitPolicy,
+        RelocationPolicy, SplitPolicy,
+    },
 };
 use rand::SeedableRng;
 use std::{
     cell::RefCell,
     io::Write,
     ops::{Deref, DerefMut},
-    sync::Once,
+    sync::{Arc, Once},
 };
 
 static LOG_INIT: Once = Once::new();
@@ -28,6 +32,9 @@ pub struct Environment {
     rng: RefCell<MainRng>,
     network: Network,
     network_params: NetworkParams,
+    split_policy: Arc<dyn SplitPolicy>,
+    relocation_policy: Arc<dyn RelocationPolicy>,
+    admission_policy: Arc<dyn AdmissionPolicy>,
     seed_printer: Option<SeedPrinter>,
 }
 
@@ -62,15 +69,57 @@ impl Environment {
             rng: RefCell::new(MainRng::from_seed(seed)),
             network,
             network_params,
+            split_policy: Arc::new(DefaultSplitPolicy),
+            relocation_policy: Arc::new(DefaultRelocationPolicy::default()),
+            admission_policy: Arc::new(DefaultAdmissionPolicy),
             seed_printer: Some(SeedPrinter::on_failure(seed)),
         }
     }
 
+    /// Use the given split policy for the nodes in this environment.
+    pub fn with_split_policy<P: SplitPolicy + 'static>(mut self, split_policy: P) -> Self {
+        self.split_policy = Arc::new(split_policy);
+        self
+    }
+
+    /// Use the given relocation policy for the nodes in this environment.
+    pub fn with_relocation_policy<P: RelocationPolicy + 'static>(
+        mut self,
+        relocation_policy: P,
+    ) -> Self {
+        self.relocation_policy = Arc::new(relocation_policy);
+        self
+    }
+
+    /// Use the given admission policy for the nodes in this environment.
+    pub fn with_admission_policy<P: AdmissionPolicy + 'static>(
+        mut self,
+        admission_policy: P,
+    ) -> Self {
+        self.admission_policy = Arc::new(admission_policy);
+        self
+    }
+
     /// Get the network params.
     pub fn network_params(&self) -> NetworkParams {
         self.network_params
     }
 
+    /// Get the split policy.
+    pub fn split_policy(&self) -> Arc<dyn SplitPolicy> {
+        Arc::clone(&self.split_policy)
+    }
+
+    /// Get the relocation policy.
+    pub fn relocation_policy(&self) -> Arc<dyn RelocationPolicy> {
+        Arc::clone(&self.relocation_policy)
+    }
+
+    /// Get the admission policy.
+    pub fn admission_policy(&self) -> Arc<dyn AdmissionPolicy> {
+        Arc::clone(&self.admission_policy)
+    }
+
     /// Get the number of elders
     pub fn elder_size(&self) -> usize {
         self.network_params.elder_size
@@ -16,15 +16,28 @@ use std::collections::{BTreeMap, BTreeSet};
 
 pub(super) struct KeyGen<P: PublicId> {
     instances: BTreeMap<BTreeSet<P>, bls::SecretKeySet>,
+    // Participants that never respond during DKG. Any DKG they take part in never completes.
+    unresponsive: BTreeSet<P>,
 }
 
 impl<P: PublicId> KeyGen<P> {
     pub fn new() -> Self {
         Self {
             instances: BTreeMap::new(),
+            unresponsive: BTreeSet::new(),
         }
     }
 
+    pub fn set_unresponsive(&mut self, participant: P) {
+        let _ = self.unresponsive.insert(participant);
+    }
+
+    pub fn is_stalled(&self, participants: &BTreeSet<P>) -> bool {
+        participants
+            .iter()
+            .any(|participant| self.unresponsive.contains(participant))
+    }
+
     pub fn get_or_generate(
         &mut self,
         rng: &mut impl Rng,
@@ -147,6 +147,14 @@ where
         )
     }
 
+    #[cfg(test)]
+    pub fn set_dkg_unresponsive(&mut self, participant: S::PublicId) {
+        state::with(
+            self.section_hash,
+            |state: &mut SectionState<T, S::PublicId>| state.set_dkg_unresponsive(participant),
+        )
+    }
+
     pub fn gossip_recipients(&self) -> impl Iterator<Item = &S::PublicId> {
         trace!(
             "gossip_recipients: {:?} -- {:?}",
@@ -192,16 +200,13 @@ where
     }
 
     pub fn poll(&mut self) -> Option<Block<T, S::PublicId>> {
-        // Make sure we only return blocks for which we reached consensus.
-        if self.first_unpolled >= self.first_unconsensused {
-            return None;
-        }
-
         state::with(self.section_hash, |state| {
-            if let Some((block, holder)) = state
-                .get_block(self.first_unpolled)
-                .map(|(block, holder)| (block.clone(), holder.clone()))
-            {
+            // Make sure we only return blocks for which we reached consensus.
+            while self.first_unpolled < self.first_unconsensused {
+                let (block, holder) = state
+                    .get_block(self.first_unpolled)
+                    .map(|(block, holder)| (block.clone(), holder.clone()))?;
+
                 self.first_unpolled += 1;
                 self.observations
                     .entry(holder)
@@ -209,22 +214,27 @@ where
                     .state = ConsensusState::Polled;
 
                 // Simulate DKG: if the consensused payload is `StartDkg`, transform it into
-                // `DkgResult` using trusted dealer.
+                // `DkgResult` using trusted dealer. If any of the participants is unresponsive,
+                // the DKG never completes so there is no block to return.
                 match block.payload() {
                     Observation::StartDkg(participants) => {
+                        if state.is_dkg_stalled(participants) {
+                            continue;
+                        }
+
                         let dkg_result = state.get_or_generate_keys(
                             &mut self.rng,
                             self.our_id.public_id(),
                             participants.clone(),
                         );
 
-                        Some(Block::new_dkg(participants.clone(), dkg_result))
+                        return Some(Block::new_dkg(participants.clone(), dkg_result));
                     }
-                    _ => Some(block),
+                    _ => return Some(block),
                 }
-            } else {
-                None
             }
+
+            None
         })
     }
 
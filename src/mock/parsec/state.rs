@@ -117,6 +117,17 @@ impl<T: NetworkEvent, P: PublicId> SectionState<T, P> {
     pub fn contains_dkg_participant(&self, our_id: &P) -> bool {
         self.key_gen.contains_participant(our_id)
     }
+
+    /// Make the given participant unresponsive during DKG, so any DKG involving it stalls.
+    #[cfg(test)]
+    pub fn set_dkg_unresponsive(&mut self, participant: P) {
+        self.key_gen.set_unresponsive(participant)
+    }
+
+    /// Returns whether the DKG with the given participants stalls.
+    pub fn is_dkg_stalled(&self, participants: &BTreeSet<P>) -> bool {
+        self.key_gen.is_stalled(participants)
+    }
 }
 
 pub(super) type BlockInfo<'a, T, P> = (&'a Block<T, P>, &'a ObservationHolder<T, P>);
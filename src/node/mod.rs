@@ -10,12 +10,15 @@ mod stage;
 #[cfg(all(test, feature = "mock"))]
 mod tests;
 
+pub use self::stage::BootstrapConfig;
 #[cfg(feature = "mock_base")]
 pub use self::stage::{BOOTSTRAP_TIMEOUT, JOIN_TIMEOUT};
 
-use self::stage::{Approved, Bootstrapping, JoinParams, Joining, RelocateParams, Stage};
+use self::stage::{
+    Approved, Bootstrapping, JoinParams, Joining, RebootstrapParams, RelocateParams, Stage,
+};
 use crate::{
-    consensus::GenesisPrefixInfo,
+    consensus::{ConsensusConfig, GenesisPrefixInfo},
     core::Core,
     error::{Result, RoutingError},
     event::{Connected, Event},
@@ -23,22 +26,28 @@ use crate::{
     location::{DstLocation, SrcLocation},
     log_utils,
     messages::{
-        BootstrapResponse, Message, MessageAction, MessageHash, MessageWithBytes, QueuedMessage,
-        Variant,
+        BootstrapResponse, Message, MessageAction, MessageHash, MessageWithBytes, PartialMessage,
+        Priority, QueuedMessage, RouteConfig, UserPriority, Variant,
     },
     network_params::NetworkParams,
     pause::PausedState,
     quic_p2p::{EventSenders, Peer, Token},
     relocation::SignedRelocateDetails,
+    resource_proof::ResourceProofParams,
     rng::{self, MainRng},
+    section::{
+        AdmissionPolicy, BanDetails, DefaultAdmissionPolicy, DefaultElderSelection,
+        DefaultRelocationPolicy, DefaultSplitPolicy, DiversityRules, DiversityViolation,
+        ElderSelection, RelocationPolicy, SplitPolicy, DEFAULT_BAN_DURATION,
+    },
     time::Duration,
-    transport::PeerStatus,
+    transport::{BandwidthConfig, PeerStats, TransportKind},
     xor_space::{Prefix, XorName, Xorable},
     TransportConfig, TransportEvent,
 };
 use bytes::Bytes;
 use crossbeam_channel::{Receiver, RecvError, Select};
-use std::net::SocketAddr;
+use std::{collections::BTreeMap, net::SocketAddr, path::PathBuf, sync::Arc};
 
 #[cfg(all(test, feature = "mock"))]
 use crate::{
@@ -48,7 +57,7 @@ use crate::{
 #[cfg(feature = "mock_base")]
 use {
     crate::section::{EldersInfo, SectionProofChain, SharedState},
-    std::collections::{BTreeMap, BTreeSet},
+    std::collections::BTreeSet,
 };
 
 /// Delay after which a bounced message is resent.
@@ -60,10 +69,51 @@ pub struct NodeConfig {
     pub first: bool,
     /// The ID of the node or `None` for randomly generated one.
     pub full_id: Option<FullId>,
+    /// The ID the node had before it left the network, if it is returning after a temporary
+    /// absence. The node then rejoins its former section, which restores the age it had there as
+    /// long as it returns within the grace period of the section's relocation policy.
+    pub previous_id: Option<FullId>,
     /// Configuration for the underlying network transport.
     pub transport_config: TransportConfig,
+    /// Which network transport to use. `transport_config` applies to `TransportKind::Quic` only.
+    pub transport_kind: TransportKind,
+    /// File to persist the addresses of the elders we've seen in, or `None` to keep them in memory
+    /// only. When set, the next start of the node contacts the best of the cached elders in
+    /// parallel with bootstrapping off the hard-coded contacts of the transport.
+    pub bootstrap_cache_file: Option<PathBuf>,
+    /// How to bootstrap off the network: how many peers to contact in parallel and how to retry.
+    pub bootstrap: BootstrapConfig,
+    /// Hop limit of the messages we relay and whether to trace the route of the messages we send.
+    pub route: RouteConfig,
+    /// Limits of the traffic exchanged with each peer.
+    pub bandwidth: BandwidthConfig,
     /// Global network parameters. Must be identical for all nodes in the network.
     pub network_params: NetworkParams,
+    /// Consensus configuration. The algorithm must be identical for all nodes in the network.
+    pub consensus: ConsensusConfig,
+    /// Policy deciding when a section splits. Should be identical for all nodes in the network.
+    pub split_policy: Arc<dyn SplitPolicy>,
+    /// Policy deciding which members of a section become its elders. Should be identical for all
+    /// nodes in the network.
+    pub elder_selection: Arc<dyn ElderSelection>,
+    /// Policy deciding how nodes age and where they get relocated to. Should be identical for all
+    /// nodes in the network.
+    pub relocation_policy: Arc<dyn RelocationPolicy>,
+    /// Policy deciding whether a section accepts new members. Should be identical for all nodes in
+    /// the network.
+    pub admission_policy: Arc<dyn AdmissionPolicy>,
+    /// Resource proof our section requires from new nodes joining it. With the `mock_base`
+    /// feature the default is `ResourceProofParams::cheap()`.
+    pub resource_proof: ResourceProofParams,
+    /// Limits on how many elders and members of a section can share a network address. Should be
+    /// identical for all nodes in the network.
+    pub diversity: DiversityRules,
+    /// Number of membership changes (joins and leaves) of a section during which the peers it
+    /// banned for misbehaviour are refused. Should be identical for all nodes in the network.
+    pub ban_duration: u64,
+    /// Whether to also refuse all the peers sharing the IP address of a banned peer. Off by
+    /// default, as many honest peers can share an IP address behind a NAT.
+    pub ban_by_ip: bool,
     /// Random number generator to be used by the node. Can be used to achieve repeatable tests by
     /// providing a pre-seeded RNG. By default uses a random seed provided by the OS.
     pub rng: MainRng,
@@ -74,8 +124,23 @@ impl Default for NodeConfig {
         Self {
             first: false,
             full_id: None,
+            previous_id: None,
             transport_config: TransportConfig::default(),
+            transport_kind: TransportKind::default(),
+            bootstrap_cache_file: None,
+            bootstrap: BootstrapConfig::default(),
+            route: RouteConfig::default(),
+            bandwidth: BandwidthConfig::default(),
             network_params: NetworkParams::default(),
+            consensus: ConsensusConfig::default(),
+            split_policy: Arc::new(DefaultSplitPolicy),
+            elder_selection: Arc::new(DefaultElderSelection::default()),
+            relocation_policy: Arc::new(DefaultRelocationPolicy::default()),
+            admission_policy: Arc::new(DefaultAdmissionPolicy),
+            resource_proof: ResourceProofParams::default(),
+            diversity: DiversityRules::default(),
+            ban_duration: DEFAULT_BAN_DURATION,
+            ban_by_ip: false,
             rng: rng::new(),
         }
     }
@@ -113,6 +178,7 @@ impl Node {
         let (user_event_tx, user_event_rx) = crossbeam_channel::unbounded();
 
         let first = config.first;
+        let previous_id = config.previous_id.clone();
         let mut core = Core::new(config, timer_tx, transport_tx, user_event_tx);
 
         let stage = if first {
@@ -134,8 +200,9 @@ impl Node {
             }
         } else {
             info!("{} Bootstrapping a new node.", core.name());
-            core.transport.bootstrap();
-            Stage::Bootstrapping(Bootstrapping::new(None))
+            let mut stage = Bootstrapping::new(None, previous_id);
+            stage.start(&mut core);
+            Stage::Bootstrapping(stage)
         };
 
         let node = Self {
@@ -235,6 +302,8 @@ impl Node {
             stage.finish_handle_input(&mut self.core);
         }
 
+        self.handle_refused_sends();
+
         Ok(())
     }
 
@@ -361,12 +430,71 @@ impl Node {
         }
     }
 
+    /// Vote for relocating the member of our section with the given name to the section whose
+    /// prefix matches `destination`. The relocation happens only once a quorum of our elders votes
+    /// for it, so this is meant to be called by the operators of all the elders, for example to
+    /// drain a section or to rebalance the network. The relocated node keeps its age: unlike the
+    /// relocations triggered by churn, which move the node one age up, this one isn't earned by
+    /// the node, so it mustn't let the operators age nodes faster than the churn would.
+    /// Returns `InvalidState` error if we are not an elder, `PeerNotFound` error if there is no
+    /// such member and `InvalidRelocation` error if `destination` matches our own prefix.
+    pub fn vote_for_relocation(&mut self, name: XorName, destination: XorName) -> Result<()> {
+        let our_id = self.core.id();
+        if let Some(stage) = self
+            .stage
+            .approved_mut()
+            .filter(|stage| stage.is_our_elder(our_id))
+        {
+            stage.vote_for_relocation(name, destination)
+        } else {
+            Err(RoutingError::InvalidState)
+        }
+    }
+
+    /// Signal whether our section should accept new members. Our section stops accepting new
+    /// members once a quorum of its elders wants so, either because of this signal or because of
+    /// the configured `AdmissionPolicy`, and starts accepting them again once a quorum changes its
+    /// mind. Nodes that try to join in the meantime are asked to wait and retry later. Relocated
+    /// and rejoining nodes are always accepted. The signal takes effect only while we are an elder.
+    /// Returns `InvalidState` error if we are not a member of any section yet.
+    pub fn set_accepting_members(&mut self, accepting: bool) -> Result<()> {
+        if let Some(stage) = self.stage.approved_mut() {
+            stage.set_accepting_members(accepting);
+            Ok(())
+        } else {
+            Err(RoutingError::InvalidState)
+        }
+    }
+
+    /// Returns the violations of the address diversity rules by the elders and members of our
+    /// section, or `None` if we are not a member of any section yet.
+    pub fn diversity_violations(&self) -> Option<Vec<DiversityViolation>> {
+        self.stage.approved().map(|stage| {
+            stage
+                .shared_state
+                .diversity_violations(&self.core.diversity)
+        })
+    }
+
     /// Send a message.
     pub fn send_message(
         &mut self,
         src: SrcLocation,
         dst: DstLocation,
         content: Vec<u8>,
+    ) -> Result<(), RoutingError> {
+        self.send_message_with_priority(src, dst, content, UserPriority::Normal)
+    }
+
+    /// Send a message with the given priority. It is queued behind the routing-internal messages
+    /// and the user messages of a higher priority, on this node as well as on the nodes relaying
+    /// it.
+    pub fn send_message_with_priority(
+        &mut self,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Vec<u8>,
+        priority: UserPriority,
     ) -> Result<(), RoutingError> {
         if let DstLocation::Direct = dst {
             return Err(RoutingError::BadLocation);
@@ -378,16 +506,44 @@ impl Node {
             Stage::Bootstrapping(_) | Stage::Joining(_) | Stage::Terminated => {
                 Err(RoutingError::InvalidState)
             }
-            Stage::Approved(stage) => stage.send_routing_message(
+            Stage::Approved(stage) => stage.send_routing_message_with_priority(
                 &mut self.core,
                 src,
                 dst,
                 Variant::UserMessage(content),
+                priority.into(),
                 None,
             ),
         }
     }
 
+    /// Send a message whose destination acknowledges receiving it. Returns the hash identifying
+    /// the message in the `Event::Delivered` or `Event::DeliveryFailed` reporting the outcome. The
+    /// message is resent if the acknowledgement doesn't arrive in time. The destination reports
+    /// the message only once, even if it receives it several times. Sending the same content again
+    /// sends a new message, with a different hash, which is reported again.
+    pub fn send_message_with_ack(
+        &mut self,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Vec<u8>,
+    ) -> Result<MessageHash, RoutingError> {
+        if let DstLocation::Direct = dst {
+            return Err(RoutingError::BadLocation);
+        }
+
+        let _log_ident = self.set_log_ident();
+
+        match &mut self.stage {
+            Stage::Bootstrapping(_) | Stage::Joining(_) | Stage::Terminated => {
+                Err(RoutingError::InvalidState)
+            }
+            Stage::Approved(stage) => {
+                stage.send_user_message_with_ack(&mut self.core, src, dst, content)
+            }
+        }
+    }
+
     /// Send a message to a client peer.
     pub fn send_message_to_client(
         &mut self,
@@ -397,10 +553,20 @@ impl Node {
     ) -> Result<()> {
         self.core
             .transport
-            .send_message_to_client(peer_addr, msg, token);
+            .send_message_to_client(peer_addr, msg, token, &self.core.timer);
         Ok(())
     }
 
+    /// Returns the traffic exchanged with each peer we are connected to, as accounted against the
+    /// limits of `NodeConfig::bandwidth`.
+    pub fn peer_stats(&self) -> BTreeMap<SocketAddr, PeerStats> {
+        self.core
+            .transport
+            .peer_stats()
+            .map(|(addr, stats)| (*addr, *stats))
+            .collect()
+    }
+
     /// Disconnect form a client peer.
     pub fn disconnect_from_client(&mut self, peer_addr: SocketAddr) -> Result<()> {
         self.core.transport.disconnect(peer_addr);
@@ -436,21 +602,27 @@ impl Node {
             BootstrappedTo { node } => self.handle_bootstrapped_to(node),
             BootstrapFailure => self.handle_bootstrap_failure(),
             ConnectedTo { .. } => (),
-            ConnectionFailure { peer, .. } => match peer {
-                Peer::Client(_) => (),
-                Peer::Node(peer_addr) => self.handle_connection_failure(peer_addr),
-            },
+            ConnectionFailure { peer, .. } => {
+                self.core
+                    .transport
+                    .handle_connection_failure(&peer.peer_addr());
+
+                match peer {
+                    Peer::Client(_) => (),
+                    Peer::Node(peer_addr) => self.handle_connection_failure(peer_addr),
+                }
+            }
             NewMessage { peer, msg } => match peer {
                 Peer::Client(_) => (),
                 Peer::Node(peer_addr) => self.handle_new_message(peer_addr, msg),
             },
             UnsentUserMessage { peer, msg, token } => match peer {
                 Peer::Client(_) => (),
-                Peer::Node(peer_addr) => self.handle_unsent_message(peer_addr, msg, token),
+                Peer::Node(peer_addr) => self.handle_unsent_message(peer_addr, token),
             },
             SentUserMessage { peer, msg, token } => match peer {
                 Peer::Client(_) => (),
-                Peer::Node(peer_addr) => self.handle_sent_message(peer_addr, msg, token),
+                Peer::Node(peer_addr) => self.handle_sent_message(peer_addr, token),
             },
             Finish => {
                 self.stage = Stage::Terminated;
@@ -460,7 +632,7 @@ impl Node {
 
     fn handle_bootstrapped_to(&mut self, addr: SocketAddr) {
         match &mut self.stage {
-            Stage::Bootstrapping(stage) => stage.send_bootstrap_request(&mut self.core, addr),
+            Stage::Bootstrapping(stage) => stage.handle_bootstrapped_to(&mut self.core, addr),
             Stage::Joining(_) | Stage::Approved(_) => {
                 // A bootstrapped node doesn't need another bootstrap connection
                 self.core.transport.disconnect(addr);
@@ -470,10 +642,19 @@ impl Node {
     }
 
     fn handle_bootstrap_failure(&mut self) {
-        assert!(matches!(self.stage, Stage::Bootstrapping(_)));
+        let gave_up = match &mut self.stage {
+            Stage::Bootstrapping(stage) => stage.handle_bootstrap_failure(&mut self.core),
+            _ => false,
+        };
 
-        info!("Failed to bootstrap. Terminating.");
-        self.core.send_event(Event::Terminated);
+        if gave_up {
+            self.give_up_bootstrapping()
+        }
+    }
+
+    fn give_up_bootstrapping(&mut self) {
+        info!("Failed to bootstrap - giving up, restart required.");
+        self.core.send_event(Event::RestartRequired);
         self.stage = Stage::Terminated;
     }
 
@@ -486,6 +667,23 @@ impl Node {
     }
 
     fn handle_new_message(&mut self, sender: SocketAddr, bytes: Bytes) {
+        // We take part in consensus with our elders and the messages relayed between the sections
+        // pass through the elders of our neighbours, so the traffic of every elder we know of is
+        // never dropped.
+        let from_known_elder = self
+            .stage
+            .approved()
+            .into_iter()
+            .flat_map(|stage| stage.shared_state.sections.elders())
+            .any(|p2p_node| *p2p_node.peer_addr() == sender);
+        if !self
+            .core
+            .transport
+            .handle_received(sender, &bytes, from_known_elder)
+        {
+            return;
+        }
+
         let msg = match MessageWithBytes::partial_from_bytes(bytes) {
             Ok(msg) => msg,
             Err(error) => {
@@ -499,16 +697,52 @@ impl Node {
         }
     }
 
-    fn handle_unsent_message(&mut self, addr: SocketAddr, msg: Bytes, msg_token: Token) {
-        match self.core.handle_unsent_message(addr, msg, msg_token) {
-            PeerStatus::Normal => (),
-            PeerStatus::Lost => self.handle_peer_lost(addr),
+    fn handle_unsent_message(&mut self, addr: SocketAddr, msg_token: Token) {
+        let failure = self.core.handle_unsent_message(addr, msg_token);
+
+        if failure.peer_lost {
+            self.handle_peer_lost(addr);
+        }
+
+        if let Some(msg) = failure.undelivered {
+            self.handle_undelivered_message(msg);
         }
     }
 
-    fn handle_sent_message(&mut self, addr: SocketAddr, _msg: Bytes, token: Token) {
+    // Moves the messages whose sends were refused for exceeding the outbound limit on to their
+    // other targets.
+    fn handle_refused_sends(&mut self) {
+        for msg in self.core.handle_refused_sends() {
+            self.handle_undelivered_message(msg);
+        }
+    }
+
+    fn handle_sent_message(&mut self, addr: SocketAddr, token: Token) {
         trace!("Successfully sent message with ID {} to {:?}", token, addr);
-        self.core.transport.target_succeeded(token, addr);
+        self.core.handle_sent_message(addr, token);
+    }
+
+    // Notifies the user about their message that failed to reach any of its targets. The messages
+    // we only relay are not reported.
+    fn handle_undelivered_message(&mut self, msg: Bytes) {
+        let msg = match Message::from_bytes(&msg) {
+            Ok(msg) => msg,
+            Err(error) => {
+                debug!("Failed to deserialize undelivered message: {:?}", error);
+                return;
+            }
+        };
+
+        if !msg.src.location().contains(self.core.name()) {
+            return;
+        }
+
+        if let Variant::UserMessage(content) = msg.variant {
+            self.core.send_event(Event::MessageUndelivered {
+                content,
+                dst: msg.dst,
+            });
+        }
     }
 
     fn handle_timeout(&mut self, token: u64) {
@@ -517,10 +751,15 @@ impl Node {
         }
 
         match &mut self.stage {
-            Stage::Bootstrapping(stage) => stage.handle_timeout(&mut self.core, token),
+            Stage::Bootstrapping(stage) => {
+                if stage.handle_timeout(&mut self.core, token) {
+                    self.give_up_bootstrapping()
+                }
+            }
             Stage::Joining(stage) => {
                 if stage.handle_timeout(&mut self.core, token) {
-                    self.rebootstrap()
+                    let params = stage.rebootstrap_params().clone();
+                    self.rebootstrap(params)
                 }
             }
             Stage::Approved(stage) => stage.handle_timeout(&mut self.core, token),
@@ -600,7 +839,10 @@ impl Node {
                 self.send_bounce(&sender, msg.full_bytes().clone());
                 Ok(())
             }
-            Stage::Approved(stage) => stage.send_signed_message(&mut self.core, msg),
+            Stage::Approved(stage) => {
+                let priority = stage.relay_priority(&sender, msg);
+                stage.send_signed_message(&mut self.core, msg, priority)
+            }
             Stage::Terminated => unreachable!(),
         }
     }
@@ -667,10 +909,38 @@ impl Node {
                         msg.src.to_sender_node(sender)?,
                         elders_info,
                     )?,
+                Variant::BootstrapResponse(BootstrapResponse::Wait(retry_after)) => {
+                    stage.handle_wait(&mut self.core, msg.src.to_sender_node(sender)?, retry_after)
+                }
+                Variant::ResourceChallenge(challenge) => stage.handle_resource_challenge(
+                    &mut self.core,
+                    msg.src.to_sender_node(sender)?,
+                    challenge,
+                ),
                 Variant::NodeApproval(genesis_prefix_info) => {
                     let connect_type = stage.connect_type();
                     self.approve(connect_type, *genesis_prefix_info)?
                 }
+                Variant::EchoRequest(nonce) => stage.handle_echo_request(
+                    &mut self.core,
+                    msg.src.to_sender_node(sender)?,
+                    nonce,
+                ),
+                Variant::ReachabilityProof(proof) => stage.handle_reachability_proof(
+                    &mut self.core,
+                    msg.src.to_sender_node(sender)?,
+                    proof,
+                ),
+                Variant::JoinRejected(rejection) => {
+                    if stage.handle_join_rejected(
+                        &self.core,
+                        msg.src.to_sender_node(sender)?,
+                        rejection,
+                    ) {
+                        self.core.send_event(Event::Terminated);
+                        self.stage = Stage::Terminated;
+                    }
+                }
                 Variant::Bounce {
                     elders_version,
                     message,
@@ -687,6 +957,16 @@ impl Node {
                     msg.src.check_is_section()?;
                     stage.handle_genesis_update(&mut self.core, *info)?;
                 }
+                Variant::MergeRequest(request) => {
+                    msg.dst.check_is_section()?;
+                    let (src_prefix, _) = msg.src.as_section_prefix_and_key()?;
+                    stage.handle_merge_request(*src_prefix, *request);
+                }
+                Variant::Ban(details) => {
+                    msg.dst.check_is_section()?;
+                    let (src_prefix, _) = msg.src.as_section_prefix_and_key()?;
+                    stage.handle_ban(&self.core, *src_prefix, details);
+                }
                 Variant::Relocate(_) => {
                     msg.src.check_is_section()?;
                     let signed_relocate = SignedRelocateDetails::new(msg)?;
@@ -711,41 +991,75 @@ impl Node {
                     msg.src.to_sender_node(sender)?,
                     *join_request,
                 ),
+                Variant::SectionSize(member_count) => {
+                    stage.handle_section_size(&msg.src.to_sender_node(sender)?, member_count)
+                }
                 Variant::MemberKnowledge(payload) => stage.handle_member_knowledge(
                     &mut self.core,
                     msg.src.to_sender_node(sender)?,
                     payload,
                 ),
-                Variant::ParsecRequest(version, request) => {
-                    stage.handle_parsec_request(
+                Variant::EchoResponse(nonce) => stage.handle_echo_response(
+                    &mut self.core,
+                    msg.src.to_sender_node(sender)?,
+                    nonce,
+                ),
+                Variant::ConsensusRequest(version, request) => {
+                    stage.handle_consensus_request(
                         &mut self.core,
                         version,
                         request,
                         msg.src.to_sender_node(sender)?,
                     )?;
                 }
-                Variant::ParsecResponse(version, response) => {
-                    stage.handle_parsec_response(
+                Variant::ConsensusResponse(version, response) => {
+                    stage.handle_consensus_response(
                         &mut self.core,
                         version,
                         response,
                         *msg.src.as_node()?,
                     )?;
                 }
+                Variant::Ping(nonce) => {
+                    let sender = msg.src.to_sender_node(sender)?;
+                    self.core
+                        .send_direct_message(sender.peer_addr(), Variant::Pong(nonce));
+                }
+                Variant::Pong(nonce) => {
+                    let sender = msg.src.to_sender_node(sender)?;
+                    self.core
+                        .transport
+                        .pong_received(*sender.peer_addr(), nonce);
+                }
                 Variant::UserMessage(content) => {
                     self.core.send_event(Event::MessageReceived {
                         content,
                         src: msg.src.location(),
                         dst: msg.dst,
+                        trace: msg.route.trace,
                     });
                 }
+                Variant::AckedUserMessage { content, id, .. } => {
+                    stage.handle_acked_user_message(
+                        &mut self.core,
+                        msg.src.location(),
+                        msg.dst,
+                        content,
+                        id,
+                        msg.route.trace,
+                    )?;
+                }
+                Variant::Ack(id) => stage.handle_ack(&self.core, msg.src.location(), id),
                 Variant::Bounce {
                     elders_version,
                     message,
                 } => self.handle_bounce(msg.src.to_sender_node(sender)?, elders_version, message),
-                Variant::NodeApproval(_) | Variant::BootstrapResponse(_) | Variant::Ping => {
-                    unreachable!()
-                }
+                Variant::NodeApproval(_)
+                | Variant::BootstrapResponse(_)
+                | Variant::ResourceChallenge(_)
+                | Variant::EchoRequest(_)
+                | Variant::ReachabilityProof(_)
+                | Variant::JoinRejected(_) => unreachable!(),
             },
             Stage::Terminated => unreachable!(),
         }
@@ -754,6 +1068,10 @@ impl Node {
     }
 
     fn handle_bounce(&mut self, sender: P2pNode, sender_version: Option<u64>, msg_bytes: Bytes) {
+        let priority = PartialMessage::from_bytes(&msg_bytes)
+            .map(|msg| msg.route.priority)
+            .unwrap_or(Priority::Routing);
+
         let known_version = match &self.stage {
             Stage::Bootstrapping(_) | Stage::Joining(_) => {
                 trace!(
@@ -764,6 +1082,7 @@ impl Node {
                 self.core.send_message_to_target_later(
                     sender.peer_addr(),
                     msg_bytes,
+                    priority,
                     BOUNCE_RESEND_DELAY,
                 );
                 return;
@@ -789,6 +1108,7 @@ impl Node {
                 self.core.send_message_to_target_later(
                     sender.peer_addr(),
                     msg_bytes,
+                    priority,
                     BOUNCE_RESEND_DELAY,
                 );
             } else {
@@ -827,9 +1147,17 @@ impl Node {
         let JoinParams {
             elders_info,
             relocate_payload,
+            rejoin_payload,
+            rebootstrap_params,
         } = params;
 
-        self.stage = Stage::Joining(Joining::new(&mut self.core, elders_info, relocate_payload));
+        self.stage = Stage::Joining(Joining::new(
+            &mut self.core,
+            elders_info,
+            relocate_payload,
+            rejoin_payload,
+            rebootstrap_params,
+        ));
     }
 
     // Transition from Joining to Approved
@@ -857,7 +1185,7 @@ impl Node {
             details,
         } = params;
 
-        let mut stage = Bootstrapping::new(Some(details));
+        let mut stage = Bootstrapping::new(Some(details), None);
 
         for conn_info in conn_infos {
             stage.send_bootstrap_request(&mut self.core, conn_info)
@@ -867,11 +1195,25 @@ impl Node {
     }
 
     // Transition from Joining to Bootstrapping on join failure
-    fn rebootstrap(&mut self) {
-        // TODO: preserve relocation details
-        self.stage = Stage::Bootstrapping(Bootstrapping::new(None));
-        self.core.full_id = FullId::gen(&mut self.core.rng);
-        self.core.transport.bootstrap();
+    fn rebootstrap(&mut self, params: RebootstrapParams) {
+        let RebootstrapParams {
+            relocate,
+            previous_id,
+        } = params;
+
+        // A relocating node has to go back to the id its relocation details were issued for, so
+        // it can prove it is the relocated node again. Any other node starts over with a new id.
+        let relocate_details = if let Some((details, old_full_id)) = relocate {
+            self.core.full_id = old_full_id;
+            Some(details)
+        } else {
+            self.core.full_id = FullId::gen(&mut self.core.rng);
+            None
+        };
+
+        let mut stage = Bootstrapping::new(relocate_details, previous_id);
+        stage.start(&mut self.core);
+        self.stage = Stage::Bootstrapping(stage);
     }
 
     fn set_log_ident(&self) -> log_utils::Guard {
@@ -913,12 +1255,7 @@ impl Node {
     pub fn has_unpolled_observations(&self) -> bool {
         self.stage
             .approved()
-            .map(|stage| {
-                stage
-                    .consensus_engine
-                    .parsec_map()
-                    .has_unpolled_observations()
-            })
+            .map(|stage| stage.consensus_engine.has_unpolled_observations())
             .unwrap_or(false)
     }
 
@@ -929,12 +1266,19 @@ impl Node {
         delivery_group_size: usize,
         message: Message,
     ) -> Result<(), RoutingError> {
+        let priority = message.route.priority;
         let message = message.to_bytes()?;
         self.core
-            .send_message_to_targets(dst_targets, delivery_group_size, message);
+            .send_message_to_targets(dst_targets, delivery_group_size, message, priority);
         Ok(())
     }
 
+    /// Sets the hop limit of the messages we relay and whether to trace the route of the messages
+    /// we send.
+    pub fn set_route_config(&mut self, config: RouteConfig) {
+        self.core.route_config = config;
+    }
+
     /// Returns the version of the latest Parsec instance of this node.
     pub fn parsec_last_version(&self) -> u64 {
         self.stage
@@ -1062,6 +1406,20 @@ impl Node {
             .map(|info| info.age_counter_value())
     }
 
+    /// Returns whether our section accepts new members.
+    pub fn is_accepting_members(&self) -> bool {
+        self.shared_state()
+            .map(|state| state.accepting_members)
+            .unwrap_or(false)
+    }
+
+    /// Returns the peers currently banned by our section or by our neighbour sections.
+    pub fn banned_peers(&self) -> Vec<BanDetails> {
+        self.shared_state()
+            .map(|state| state.active_bans(self.core.ban_duration).cloned().collect())
+            .unwrap_or_default()
+    }
+
     /// Returns the latest BLS public key of our section or `None` if we are not joined yet.
     pub fn section_key(&self) -> Option<&bls::PublicKey> {
         self.stage
@@ -8,29 +8,36 @@
 
 use crate::{
     consensus::{
-        self, AccumulatingEvent, AccumulatingProof, ConsensusEngine, DkgResultWrapper,
-        GenesisPrefixInfo, NetworkEvent, OnlinePayload, ParsecRequest, ParsecResponse,
+        self, AccumulatingEvent, AccumulatingProof, ConsensusEngine, ConsensusRequest,
+        ConsensusResponse, DkgResultWrapper, DkgRound, DkgTracker, GenesisPrefixInfo, NetworkEvent,
+        OnlinePayload, Restored, DKG_MAX_ATTEMPTS, DKG_TIMEOUT, JOURNAL_SYNC_INTERVAL,
     },
-    core::Core,
+    core::{Core, ResumeParams},
+    delivery_tracker::{DeliveryTracker, PendingDelivery, ACK_MAX_ATTEMPTS, ACK_TIMEOUT},
     error::{Result, RoutingError},
     event::Event,
     id::{P2pNode, PublicId},
+    join_queue::{JoinAdmission, JoinQueue},
     location::{DstLocation, SrcLocation},
     messages::{
-        self, AccumulatingMessage, BootstrapResponse, JoinRequest, MemberKnowledge, Message,
-        MessageAction, MessageHash, MessageWithBytes, PlainMessage, Variant, VerifyStatus,
+        self, AccumulatingMessage, BootstrapResponse, JoinRejection, JoinRequest, MemberKnowledge,
+        MergeRequest, Message, MessageAction, MessageHash, MessageWithBytes, PlainMessage,
+        Priority, Route, RouteHop, Variant, VerifyStatus,
     },
     pause::PausedState,
+    reachability::{self, ReachabilityProof, REACHABILITY_TIMEOUT},
     relocation::{RelocateDetails, SignedRelocateDetails},
+    resource_proof::ResourceProofChallenge,
     rng::MainRng,
     routing_table,
     section::{
-        EldersInfo, IndexedSecretKeyShare, MemberState, NeighbourEldersRemoved,
-        SectionKeysProvider, SharedState, SplitCache, MIN_AGE,
+        BanDetails, BanReason, EldersInfo, IndexedSecretKeyShare, MemberState,
+        NeighbourEldersRemoved, SectionKeysProvider, SharedState, SplitCache, MIN_AGE,
     },
     signature_accumulator::SignatureAccumulator,
     time::Duration,
     xor_space::{Prefix, XorName},
+    QUORUM_DENOMINATOR, QUORUM_NUMERATOR,
 };
 use bytes::Bytes;
 use crossbeam_channel::Sender;
@@ -50,6 +57,16 @@ const KNOWLEDGE_TIMEOUT: Duration = Duration::from_secs(2);
 /// This helps avoid relocated node receiving message they need to process from previous section.
 const RELOCATE_COOL_DOWN_STEPS: i32 = 10;
 
+/// Interval at which the elders tell the neighbour sections the number of members of our section.
+const SECTION_SIZE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Interval at which we ping the known elders to measure the round-trip time to them.
+const LATENCY_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Number of consecutive stall timeouts after which the responsive elders stop waiting for the
+/// unresponsive ones and vote them offline.
+const STALL_RECOVERY_CHECKS: u32 = 2;
+
 // The approved stage - node is a full member of a section and is performing its duties according
 // to its persona (infant, adult or elder).
 pub struct Approved {
@@ -59,8 +76,10 @@ pub struct Approved {
     sig_accumulator: SignatureAccumulator,
     genesis_prefix_info: GenesisPrefixInfo,
     timer_token: u64,
-    // DKG cache
-    dkg_cache: BTreeMap<BTreeSet<PublicId>, EldersInfo>,
+    section_size_timer_token: u64,
+    latency_probe_timer_token: u64,
+    // The DKG rounds we started and are waiting for the results of.
+    dkg_tracker: DkgTracker,
     // The accumulated info during a split.
     split_cache: Option<SplitCache>,
     // Marker indicating we are processing churn event
@@ -70,6 +89,28 @@ pub struct Approved {
     members_changed: bool,
     /// The knowledge of the non-elder members about our section.
     members_knowledge: BTreeMap<XorName, MemberKnowledge>,
+    // Version of our elders info at the time we last voted to ask our sibling to absorb us.
+    merge_requested: Option<u64>,
+    // Whether the user wants our section to accept new members.
+    accepting_members: bool,
+    // Admission state and version we last voted for.
+    admission_vote: Option<(bool, u64)>,
+    // New nodes waiting to join our section.
+    join_queue: JoinQueue,
+    // Reachability checks of joining nodes we are performing, by the names of the nodes.
+    reachability_checks: BTreeMap<XorName, ReachabilityCheck>,
+    // Joining nodes that failed our reachability check, with the address we couldn't reach them
+    // at.
+    unreachable_peers: BTreeMap<XorName, SocketAddr>,
+    // Acknowledgements of the user messages we sent and received.
+    delivery_tracker: DeliveryTracker,
+    // The responsive elders we reach consensus with while recovering from a stall, until the
+    // unresponsive ones are voted offline and the new elders take over.
+    stall_recovery_group: Option<EldersInfo>,
+    // Consensus messages waiting for the consensus journal to be synced to disk, and the timer of
+    // the sync.
+    unsynced_messages: Vec<(SocketAddr, Variant)>,
+    journal_sync_timer_token: Option<u64>,
 }
 
 impl Approved {
@@ -121,7 +162,8 @@ impl Approved {
         }
 
         let serialised_state = bincode::serialize(&shared_state)?;
-        let consensus_engine = ConsensusEngine::new(
+        let mut consensus_engine = ConsensusEngine::new(
+            core.consensus_config.clone(),
             &mut core.rng,
             core.full_id.clone(),
             &genesis_prefix_info.elders_info,
@@ -129,24 +171,62 @@ impl Approved {
             genesis_prefix_info.parsec_version,
         );
 
-        Ok(Self {
+        let restored = consensus_engine.take_restored();
+        if let Some(Restored::SharedState(serialised_state)) = &restored {
+            shared_state = bincode::deserialize(serialised_state)?;
+            // Checkpoints are only written after polling, so the genesis event was handled.
+            shared_state.handled_genesis_event = true;
+        }
+
+        let mut stage = Self {
             consensus_engine,
             shared_state,
             section_keys_provider,
             sig_accumulator: Default::default(),
             genesis_prefix_info,
             timer_token,
-            dkg_cache: Default::default(),
+            section_size_timer_token: core.timer.schedule(SECTION_SIZE_INTERVAL),
+            latency_probe_timer_token: core.timer.schedule(LATENCY_PROBE_INTERVAL),
+            dkg_tracker: Default::default(),
             split_cache: None,
             churn_in_progress: false,
             members_changed: false,
             members_knowledge: Default::default(),
-        })
+            merge_requested: None,
+            accepting_members: true,
+            admission_vote: None,
+            join_queue: Default::default(),
+            reachability_checks: Default::default(),
+            unreachable_peers: Default::default(),
+            delivery_tracker: DeliveryTracker::new(),
+            stall_recovery_group: None,
+            unsynced_messages: Vec::new(),
+            journal_sync_timer_token: None,
+        };
+        stage.update_bootstrap_cache(core);
+
+        if let Some(Restored::Votes(events)) = restored {
+            stage.vote_again_after_restart(core, events);
+        }
+
+        Ok(stage)
     }
 
-    pub fn pause(self, core: Core) -> PausedState {
+    pub fn pause(mut self, mut core: Core) -> PausedState {
+        self.send_unsynced_messages(&mut core);
+
         PausedState {
             network_params: core.network_params,
+            split_policy: core.split_policy,
+            elder_selection: core.elder_selection,
+            relocation_policy: core.relocation_policy,
+            admission_policy: core.admission_policy,
+            resource_proof: core.resource_proof,
+            diversity: core.diversity,
+            ban_duration: core.ban_duration,
+            ban_by_ip: core.ban_by_ip,
+            bootstrap_config: core.bootstrap_config,
+            route_config: core.route_config,
             consensus_engine: self.consensus_engine,
             shared_state: self.shared_state,
             section_keys_provider: self.section_keys_provider,
@@ -168,11 +248,24 @@ impl Approved {
         user_event_tx: Sender<Event>,
     ) -> (Self, Core) {
         let core = Core::resume(
-            state.network_params,
-            state.full_id,
-            state.transport,
-            state.msg_filter,
-            state.msg_queue,
+            ResumeParams {
+                network_params: state.network_params,
+                consensus_config: state.consensus_engine.config().clone(),
+                split_policy: state.split_policy,
+                elder_selection: state.elder_selection,
+                relocation_policy: state.relocation_policy,
+                admission_policy: state.admission_policy,
+                resource_proof: state.resource_proof,
+                diversity: state.diversity,
+                ban_duration: state.ban_duration,
+                ban_by_ip: state.ban_by_ip,
+                bootstrap_config: state.bootstrap_config,
+                route_config: state.route_config,
+                full_id: state.full_id,
+                transport: state.transport,
+                msg_filter: state.msg_filter,
+                msg_queue: state.msg_queue,
+            },
             timer_tx,
             user_event_tx,
         );
@@ -196,12 +289,24 @@ impl Approved {
             sig_accumulator: state.sig_accumulator,
             genesis_prefix_info: state.genesis_prefix_info,
             timer_token,
+            section_size_timer_token: core.timer.schedule(SECTION_SIZE_INTERVAL),
+            latency_probe_timer_token: core.timer.schedule(LATENCY_PROBE_INTERVAL),
             split_cache: state.split_cache,
             // TODO: these fields should come from PausedState too
-            dkg_cache: Default::default(),
+            dkg_tracker: Default::default(),
             churn_in_progress: false,
             members_changed: false,
             members_knowledge: Default::default(),
+            merge_requested: None,
+            accepting_members: true,
+            admission_vote: None,
+            join_queue: Default::default(),
+            reachability_checks: Default::default(),
+            unreachable_peers: Default::default(),
+            delivery_tracker: DeliveryTracker::new(),
+            stall_recovery_group: None,
+            unsynced_messages: Vec::new(),
+            journal_sync_timer_token: None,
         };
 
         (stage, core)
@@ -224,7 +329,7 @@ impl Approved {
 
             // Ping the peer to trigger lost peer detection.
             let addr = *node.peer_addr();
-            core.send_direct_message(&addr, Variant::Ping);
+            core.send_ping(&addr);
         } else {
             trace!("ConnectionFailure from non-member {}", addr);
         }
@@ -239,6 +344,8 @@ impl Approved {
             return;
         };
 
+        self.dkg_tracker.handle_peer_lost(&pub_id);
+
         if self.is_our_elder(core.id()) && self.shared_state.our_members.contains(pub_id.name()) {
             self.vote_for_event(AccumulatingEvent::Offline(pub_id));
         }
@@ -247,13 +354,34 @@ impl Approved {
     pub fn handle_timeout(&mut self, core: &mut Core, token: u64) {
         if self.timer_token == token {
             if self.is_our_elder(core.id()) {
-                self.timer_token = core.timer.schedule(self.consensus_engine.gossip_period());
                 self.consensus_engine.reset_gossip_period();
+                self.timer_token = core.timer.schedule(self.consensus_engine.gossip_period());
+                self.check_consensus_stalled(core);
             } else {
                 // TODO: send this only when the knowledge changes, not periodically.
                 self.send_member_knowledge(core);
                 self.timer_token = core.timer.schedule(KNOWLEDGE_TIMEOUT);
             }
+        } else if self.section_size_timer_token == token {
+            self.section_size_timer_token = core.timer.schedule(SECTION_SIZE_INTERVAL);
+            self.send_section_size(core);
+        } else if self.latency_probe_timer_token == token {
+            self.latency_probe_timer_token = core.timer.schedule(LATENCY_PROBE_INTERVAL);
+            self.send_latency_probes(core);
+        } else if let Some(round) = self.dkg_tracker.handle_timeout(token) {
+            self.handle_dkg_timeout(core, round);
+        } else if let Some(name) = self
+            .reachability_checks
+            .iter()
+            .find(|(_, check)| check.timer_token == token)
+            .map(|(name, _)| *name)
+        {
+            self.handle_reachability_timeout(core, name);
+        } else if let Some(delivery) = self.delivery_tracker.handle_timeout(token) {
+            self.handle_ack_timeout(core, delivery);
+        } else if self.journal_sync_timer_token == Some(token) {
+            self.journal_sync_timer_token = None;
+            self.send_unsynced_messages(core);
         }
     }
 
@@ -266,6 +394,13 @@ impl Approved {
         }
 
         self.consensus_engine.prune_if_needed();
+
+        // Don't wait for the rest of a long idle gossip period when there is work to do.
+        if self.is_our_elder(core.id()) && self.consensus_engine.speed_up_gossip() {
+            self.consensus_engine.reset_gossip_period();
+            self.timer_token = core.timer.schedule(self.consensus_engine.gossip_period());
+        }
+
         self.send_parsec_gossip(core, None);
     }
 
@@ -274,6 +409,29 @@ impl Approved {
         self.vote_for_event(AccumulatingEvent::User(event));
     }
 
+    pub fn vote_for_relocation(
+        &mut self,
+        name: XorName,
+        destination: XorName,
+    ) -> Result<(), RoutingError> {
+        if !self.shared_state.our_members.contains(&name) {
+            return Err(RoutingError::PeerNotFound(name));
+        }
+
+        if self.shared_state.our_prefix().matches(&destination) {
+            return Err(RoutingError::InvalidRelocation);
+        }
+
+        self.vote_for_event(AccumulatingEvent::RelocateRequest { name, destination });
+        Ok(())
+    }
+
+    /// Set whether we want our section to accept new members, in addition to what the admission
+    /// policy decides.
+    pub fn set_accepting_members(&mut self, accepting: bool) {
+        self.accepting_members = accepting;
+    }
+
     /// Is the node with the given id an elder in our section?
     pub fn is_our_elder(&self, id: &PublicId) -> bool {
         self.shared_state
@@ -304,14 +462,14 @@ impl Approved {
         let is_self_elder = self.is_our_elder(our_id);
 
         match &msg.variant {
-            Variant::NeighbourInfo { .. } => {
+            Variant::NeighbourInfo { .. } | Variant::MergeRequest(_) | Variant::Ban(_) => {
                 if is_self_elder && self.verify_message(msg)? {
                     Ok(MessageAction::Handle)
                 } else {
                     Ok(MessageAction::Bounce)
                 }
             }
-            Variant::UserMessage(_) => {
+            Variant::UserMessage(_) | Variant::AckedUserMessage { .. } | Variant::Ack(_) => {
                 if self.should_handle_user_message(our_id, &msg.dst) && self.verify_message(msg)? {
                     Ok(MessageAction::Handle)
                 } else {
@@ -345,7 +503,11 @@ impl Approved {
                 match &accumulating_msg.content.variant {
                     Variant::NeighbourInfo { .. }
                     | Variant::UserMessage(_)
+                    | Variant::AckedUserMessage { .. }
+                    | Variant::Ack(_)
                     | Variant::NodeApproval(_)
+                    | Variant::MergeRequest(_)
+                    | Variant::Ban(_)
                     | Variant::Relocate(_) => Ok(MessageAction::Handle),
 
                     Variant::GenesisUpdate(info) => {
@@ -363,10 +525,17 @@ impl Approved {
                     | Variant::BootstrapRequest(_)
                     | Variant::BootstrapResponse(_)
                     | Variant::JoinRequest(_)
+                    | Variant::ResourceChallenge(_)
+                    | Variant::EchoRequest(_)
+                    | Variant::EchoResponse(_)
+                    | Variant::ReachabilityProof(_)
+                    | Variant::JoinRejected(_)
                     | Variant::MemberKnowledge(_)
-                    | Variant::ParsecRequest(..)
-                    | Variant::ParsecResponse(..)
-                    | Variant::Ping
+                    | Variant::SectionSize(_)
+                    | Variant::ConsensusRequest(..)
+                    | Variant::ConsensusResponse(..)
+                    | Variant::Ping(_)
+                    | Variant::Pong(_)
                     | Variant::Bounce { .. } => Ok(MessageAction::Discard),
                 }
             }
@@ -383,8 +552,12 @@ impl Approved {
             }
             Variant::BootstrapRequest(_)
             | Variant::MemberKnowledge(_)
-            | Variant::ParsecRequest(..)
-            | Variant::ParsecResponse(..)
+            | Variant::SectionSize(_)
+            | Variant::EchoResponse(_)
+            | Variant::ConsensusRequest(..)
+            | Variant::ConsensusResponse(..)
+            | Variant::Ping(_)
+            | Variant::Pong(_)
             | Variant::Bounce { .. } => {
                 if self.verify_message(msg)? {
                     Ok(MessageAction::Handle)
@@ -392,7 +565,11 @@ impl Approved {
                     Ok(MessageAction::Discard)
                 }
             }
-            Variant::BootstrapResponse(_) | Variant::Ping => Ok(MessageAction::Discard),
+            Variant::BootstrapResponse(_)
+            | Variant::ResourceChallenge(_)
+            | Variant::EchoRequest(_)
+            | Variant::ReachabilityProof(_)
+            | Variant::JoinRejected(_) => Ok(MessageAction::Discard),
         }
     }
 
@@ -403,6 +580,65 @@ impl Approved {
         })
     }
 
+    pub fn handle_section_size(&mut self, sender: &P2pNode, member_count: usize) {
+        let prefix = self
+            .shared_state
+            .sections
+            .find_neighbour_by_elder(sender.name())
+            .filter(|info| info.elders.get(sender.name()) == Some(sender))
+            .map(|info| info.prefix);
+
+        if let Some(prefix) = prefix {
+            self.shared_state
+                .sections
+                .update_neighbour_size(prefix, member_count);
+        } else {
+            trace!("ignore SectionSize from {} - not a neighbour elder", sender);
+        }
+    }
+
+    pub fn handle_merge_request(&mut self, src_prefix: Prefix<XorName>, request: MergeRequest) {
+        if src_prefix != request.elders_info.prefix
+            || src_prefix != self.shared_state.our_prefix().sibling()
+        {
+            debug!(
+                "ignore MergeRequest from {:?} - not our sibling: {:?}",
+                src_prefix, request
+            );
+            return;
+        }
+
+        if self.shared_state.merging.is_some() {
+            debug!("ignore MergeRequest {:?} - already merging", request);
+            return;
+        }
+
+        self.vote_for_event(AccumulatingEvent::Merge(Box::new(request)));
+    }
+
+    pub fn handle_ban(&mut self, core: &Core, src_prefix: Prefix<XorName>, details: BanDetails) {
+        // A section can only tell us about the peers it banned itself.
+        if !self.shared_state.our_prefix().is_neighbour(&src_prefix)
+            || !src_prefix.matches(details.pub_id.name())
+        {
+            debug!(
+                "ignore Ban from {:?} - not our neighbour's peer: {:?}",
+                src_prefix, details
+            );
+            return;
+        }
+
+        if self
+            .find_ban(core, details.pub_id.name(), &details.peer_addr)
+            .is_some()
+        {
+            trace!("ignore Ban {:?} - already banned", details);
+            return;
+        }
+
+        self.vote_for_event(AccumulatingEvent::Ban(details));
+    }
+
     pub fn handle_neighbour_info(
         &mut self,
         elders_info: EldersInfo,
@@ -423,8 +659,17 @@ impl Approved {
     ) -> Result<()> {
         info!("Received GenesisUpdate: {:?}", genesis_prefix_info);
 
+        // We can only be elder here if our section was merged into our sibling section.
+        let was_elder = self.is_our_elder(core.id());
+
         core.msg_filter.reset();
         self.handle_elders_update(core, genesis_prefix_info);
+
+        if was_elder {
+            info!("Demoted");
+            core.send_event(Event::Demoted);
+        }
+
         Ok(())
     }
 
@@ -444,7 +689,7 @@ impl Approved {
             signed_msg.relocate_details().destination
         );
 
-        if !self.check_signed_relocation_details(&signed_msg) {
+        if self.check_signed_relocation_details(&signed_msg).is_err() {
             return None;
         }
 
@@ -508,6 +753,14 @@ impl Approved {
             destination, p2p_node
         );
 
+        if let Some(ban) = self.find_ban(core, p2p_node.name(), p2p_node.peer_addr()) {
+            debug!(
+                "Ignoring BootstrapRequest from {} - banned: {:?}.",
+                p2p_node, ban
+            );
+            return;
+        }
+
         let response = if self.shared_state.our_prefix().matches(&destination) {
             let our_info = self.shared_state.our_info().clone();
             debug!(
@@ -548,6 +801,11 @@ impl Approved {
         }
 
         let pub_id = *p2p_node.public_id();
+        if let Some(ban) = self.find_ban(core, pub_id.name(), p2p_node.peer_addr()) {
+            debug!("Ignoring JoinRequest from {} - banned: {:?}.", pub_id, ban);
+            return;
+        }
+
         if !self.shared_state.our_prefix().matches(pub_id.name()) {
             debug!(
                 "Ignoring JoinRequest from {} - name doesn't match our prefix {:?}.",
@@ -570,6 +828,8 @@ impl Approved {
             return;
         }
 
+        let relocating = join_request.relocate_payload.is_some();
+
         // This joining node is being relocated to us.
         let (age, their_knowledge) = if let Some(payload) = join_request.relocate_payload {
             if !payload.verify_identity(&pub_id) {
@@ -593,8 +853,17 @@ impl Approved {
                 return;
             }
 
-            if !self.check_signed_relocation_details(&payload.details) {
-                return;
+            match self.check_signed_relocation_details(&payload.details) {
+                Ok(()) => (),
+                Err(RoutingError::FailedSignature) => {
+                    warn!(
+                        "Relocation JoinRequest from {} has forged relocation details.",
+                        pub_id
+                    );
+                    self.vote_for_ban(core, p2p_node, BanReason::ForgedRelocation);
+                    return;
+                }
+                Err(_) => return,
             }
 
             (details.age, Some(details.destination_key))
@@ -602,13 +871,220 @@ impl Approved {
             (MIN_AGE, None)
         };
 
+        // This joining node is returning to us after a temporary absence.
+        let (age, previous_name) = match join_request.rejoin_payload {
+            Some(payload) if !relocating => {
+                if !payload.verify_identity(&pub_id) {
+                    debug!(
+                        "Ignoring rejoin JoinRequest from {} - invalid signature.",
+                        pub_id
+                    );
+                    return;
+                }
+
+                let previous_name = *payload.previous_id.name();
+                if let Some(age) = self
+                    .shared_state
+                    .rejoin_age(&previous_name, &*core.relocation_policy)
+                {
+                    (age, Some(previous_name))
+                } else {
+                    debug!(
+                        "JoinRequest from {} can't rejoin as {} - joining as a new node instead.",
+                        pub_id, previous_name
+                    );
+                    (age, None)
+                }
+            }
+            _ => (age, None),
+        };
+
+        // Relocated nodes can't choose their destination, so the diversity rules apply only to the
+        // other nodes.
+        if !relocating
+            && !self
+                .shared_state
+                .allows_new_member(&p2p_node, &core.diversity)
+        {
+            info!(
+                "Ignoring JoinRequest from {} - address diversity rules violated by {}.",
+                pub_id,
+                p2p_node.peer_addr()
+            );
+            return;
+        }
+
+        // Relocated and rejoining nodes are admitted even when we don't accept new members. New
+        // nodes wait their turn in the join queue.
+        if !relocating && previous_name.is_none() {
+            match self
+                .join_queue
+                .handle_request(&pub_id, self.shared_state.accepting_members)
+            {
+                JoinAdmission::Admit => (),
+                JoinAdmission::Wait(retry_after) => {
+                    debug!(
+                        "Deferring JoinRequest from {} - queued for admission.",
+                        pub_id
+                    );
+                    core.send_direct_message(
+                        p2p_node.peer_addr(),
+                        Variant::BootstrapResponse(BootstrapResponse::Wait(retry_after)),
+                    );
+                    return;
+                }
+                JoinAdmission::Full => {
+                    debug!("Ignoring JoinRequest from {} - join queue full.", pub_id);
+                    return;
+                }
+            }
+        }
+
+        // New nodes have to prove they spent resources on joining, to make flooding our section
+        // with freshly generated ids expensive.
+        if !relocating && previous_name.is_none() {
+            let challenge = ResourceProofChallenge::new(
+                core.resource_proof,
+                self.shared_state.our_history.last_key(),
+                pub_id.name(),
+            );
+
+            if !join_request
+                .resource_proof
+                .map(|proof| challenge.verify(&proof))
+                .unwrap_or(false)
+            {
+                debug!("Sending ResourceChallenge to {}", pub_id);
+                core.send_direct_message(
+                    p2p_node.peer_addr(),
+                    Variant::ResourceChallenge(challenge),
+                );
+                return;
+            }
+        }
+
+        // Relocated nodes were reachable in their previous section already.
+        if !relocating
+            && !self.check_reachability(core, &p2p_node, &join_request.reachability_proofs)
+        {
+            return;
+        }
+
         self.vote_for_event(AccumulatingEvent::Online(OnlinePayload {
             p2p_node,
             age,
             their_knowledge,
+            previous_name,
         }))
     }
 
+    // Returns whether the joining node has the proofs of all the elders responsible for checking
+    // it is reachable. If not and we are one of them, starts checking it.
+    fn check_reachability(
+        &mut self,
+        core: &mut Core,
+        p2p_node: &P2pNode,
+        proofs: &[ReachabilityProof],
+    ) -> bool {
+        let section_key = self.shared_state.our_history.last_key();
+        let we_are_missing = reachability::checkers(self.shared_state.our_info(), p2p_node.name())
+            .into_iter()
+            .filter(|checker| {
+                !proofs.iter().any(|proof| {
+                    proof.elder() == checker.public_id()
+                        && proof.verify(section_key, p2p_node.name(), p2p_node.peer_addr())
+                })
+            })
+            .map(|checker| checker.public_id() == core.id())
+            .collect::<Vec<_>>();
+
+        if we_are_missing.is_empty() {
+            return true;
+        }
+
+        if !we_are_missing.contains(&true) {
+            trace!("Waiting for {} to prove it's reachable.", p2p_node);
+            return false;
+        }
+
+        if self.unreachable_peers.get(p2p_node.name()) == Some(p2p_node.peer_addr()) {
+            debug!("Rejecting JoinRequest from {} - unreachable.", p2p_node);
+            core.send_direct_message(
+                p2p_node.peer_addr(),
+                Variant::JoinRejected(JoinRejection::Unreachable(*p2p_node.peer_addr())),
+            );
+            return false;
+        }
+
+        if !self.reachability_checks.contains_key(p2p_node.name()) {
+            self.start_reachability_check(core, p2p_node);
+        }
+
+        false
+    }
+
+    fn start_reachability_check(&mut self, core: &mut Core, p2p_node: &P2pNode) {
+        let check = ReachabilityCheck {
+            addr: *p2p_node.peer_addr(),
+            nonce: core.rng.gen(),
+            timer_token: core.timer.schedule(REACHABILITY_TIMEOUT),
+        };
+
+        debug!("Checking {} is reachable.", p2p_node);
+
+        // Close the connection the node opened to us, so the echo goes over a new connection
+        // opened by us, the same way any other node would reach it.
+        core.transport.disconnect(check.addr);
+        core.send_direct_message(&check.addr, Variant::EchoRequest(check.nonce));
+
+        let _ = self.reachability_checks.insert(*p2p_node.name(), check);
+    }
+
+    pub fn handle_echo_response(&mut self, core: &mut Core, p2p_node: P2pNode, nonce: u64) {
+        match self.reachability_checks.get(p2p_node.name()) {
+            Some(check) if check.nonce == nonce && check.addr == *p2p_node.peer_addr() => (),
+            _ => {
+                debug!("Ignoring unexpected EchoResponse from {}.", p2p_node);
+                return;
+            }
+        }
+
+        let _ = self.reachability_checks.remove(p2p_node.name());
+        let _ = self.unreachable_peers.remove(p2p_node.name());
+
+        match ReachabilityProof::new(
+            &core.full_id,
+            self.shared_state.our_history.last_key(),
+            p2p_node.name(),
+            p2p_node.peer_addr(),
+        ) {
+            Ok(proof) => {
+                debug!("{} is reachable.", p2p_node);
+                core.send_direct_message(p2p_node.peer_addr(), Variant::ReachabilityProof(proof));
+            }
+            Err(error) => error!("Failed to create reachability proof: {:?}", error),
+        }
+    }
+
+    fn handle_reachability_timeout(&mut self, core: &mut Core, name: XorName) {
+        let check = if let Some(check) = self.reachability_checks.remove(&name) {
+            check
+        } else {
+            return;
+        };
+
+        info!(
+            "Rejecting joining node {} - unreachable at {}.",
+            name, check.addr
+        );
+
+        let _ = self.unreachable_peers.insert(name, check.addr);
+        core.send_direct_message(
+            &check.addr,
+            Variant::JoinRejected(JoinRejection::Unreachable(check.addr)),
+        );
+    }
+
     pub fn handle_member_knowledge(
         &mut self,
         core: &mut Core,
@@ -631,29 +1107,29 @@ impl Approved {
         self.send_parsec_gossip(core, Some((version, p2p_node)))
     }
 
-    pub fn handle_parsec_request(
+    pub fn handle_consensus_request(
         &mut self,
         core: &mut Core,
         msg_version: u64,
-        parsec_request: ParsecRequest,
+        request: ConsensusRequest,
         p2p_node: P2pNode,
     ) -> Result<()> {
         trace!(
-            "handle parsec request v{} from {} (last: v{})",
+            "handle consensus request v{} from {} (last: v{})",
             msg_version,
             p2p_node.public_id(),
             self.consensus_engine.parsec_version(),
         );
 
-        let response = self.consensus_engine.handle_parsec_request(
+        let response = self.consensus_engine.handle_consensus_request(
             msg_version,
-            parsec_request,
+            request,
             *p2p_node.public_id(),
         );
 
         if let Some(response) = response {
-            trace!("send parsec response v{} to {:?}", msg_version, p2p_node,);
-            core.send_direct_message(p2p_node.peer_addr(), response);
+            trace!("send consensus response v{} to {:?}", msg_version, p2p_node,);
+            self.send_consensus_message(core, p2p_node.peer_addr(), response);
         }
 
         match msg_version.cmp(&self.consensus_engine.parsec_version()) {
@@ -670,17 +1146,17 @@ impl Approved {
         }
     }
 
-    pub fn handle_parsec_response(
+    pub fn handle_consensus_response(
         &mut self,
         core: &mut Core,
         msg_version: u64,
-        parsec_response: ParsecResponse,
+        response: ConsensusResponse,
         pub_id: PublicId,
     ) -> Result<()> {
-        trace!("handle parsec response v{} from {}", msg_version, pub_id);
+        trace!("handle consensus response v{} from {}", msg_version, pub_id);
 
         self.consensus_engine
-            .handle_parsec_response(msg_version, parsec_response, pub_id);
+            .handle_consensus_response(msg_version, response, pub_id);
 
         if msg_version == self.consensus_engine.parsec_version() {
             self.poll_all(core)
@@ -695,8 +1171,9 @@ impl Approved {
             .contains(core.name(), self.shared_state.our_prefix())
             || msg.message_dst().is_section()
         {
-            // Relay closer to the destination or broadcast to the rest of our section.
-            self.send_signed_message(core, msg)
+            // Relay closer to the destination or broadcast to the rest of our section. This is our
+            // own message, so its route priority can be trusted.
+            self.send_signed_message(core, msg, msg.route().priority)
         } else {
             Ok(())
         }
@@ -709,6 +1186,15 @@ impl Approved {
     ) -> Result<()> {
         trace!("accumulated message {:?}", msg_with_bytes);
 
+        // We are the source of the message, so this is where tracing its route starts.
+        if core.route_config.trace {
+            msg_with_bytes = msg_with_bytes.with_route(Route {
+                hop_count: 0,
+                trace: Some(Vec::new()),
+                priority: msg_with_bytes.route().priority,
+            })?;
+        }
+
         // TODO: this is almost the same as `Node::try_handle_message` - find a way
         // to avoid the duplication.
         self.try_relay_message(core, &msg_with_bytes)?;
@@ -752,7 +1238,16 @@ impl Approved {
     // Polls and processes all accumulated events.
     fn poll_all(&mut self, core: &mut Core) -> Result<()> {
         while self.poll_one(core)? {}
+
+        if self.consensus_engine.needs_checkpoint() {
+            let serialised_state = bincode::serialize(&self.shared_state)?;
+            self.consensus_engine.checkpoint(serialised_state);
+        }
+
         self.vote_for_remove_unresponsive_peers();
+        self.vote_for_ban_accused(core);
+        self.vote_for_merge_request(core);
+        self.vote_for_admission(core);
 
         Ok(())
     }
@@ -776,7 +1271,11 @@ impl Approved {
             return Ok(true);
         }
 
-        let (event, proof) = match self.consensus_engine.poll(self.shared_state.sections.our()) {
+        let our_elders = match &self.stall_recovery_group {
+            Some(group) => group,
+            None => self.shared_state.sections.our(),
+        };
+        let (event, proof) = match self.consensus_engine.poll(our_elders) {
             None => return Ok(false),
             Some((event, proof)) => (event, proof),
         };
@@ -819,7 +1318,8 @@ impl Approved {
         let is_churn_trigger = match event {
             AccumulatingEvent::Online(_)
             | AccumulatingEvent::Offline(_)
-            | AccumulatingEvent::Relocate(_) => true,
+            | AccumulatingEvent::Relocate(_)
+            | AccumulatingEvent::Merge(_) => true,
             _ => false,
         };
 
@@ -845,10 +1345,17 @@ impl Approved {
 
         self.members_changed = false;
 
-        let new_infos = if let Some(new_infos) = self
-            .shared_state
-            .promote_and_demote_elders(&core.network_params, core.name())
-        {
+        for violation in self.shared_state.diversity_violations(&core.diversity) {
+            warn!("Address diversity violation: {}", violation);
+        }
+
+        let new_infos = if let Some(new_infos) = self.shared_state.promote_and_demote_elders(
+            &core.network_params,
+            &*core.split_policy,
+            &*core.elder_selection,
+            &core.diversity,
+            core.name(),
+        ) {
             self.churn_in_progress = true;
             new_infos
         } else {
@@ -861,14 +1368,238 @@ impl Approved {
         }
 
         for info in new_infos {
-            let participants: BTreeSet<_> = info.elder_ids().copied().collect();
-            let _ = self.dkg_cache.insert(participants.clone(), info);
-            self.vote_for_event(AccumulatingEvent::StartDkg(participants));
+            self.start_dkg(core, info, 1);
+        }
+
+        true
+    }
+
+    // Vote for starting a DKG round for the given elders info and start tracking it.
+    fn start_dkg(&mut self, core: &Core, elders_info: EldersInfo, attempt: u8) {
+        let participants: BTreeSet<_> = elders_info.elder_ids().copied().collect();
+        let timer_token = core.timer.schedule(DKG_TIMEOUT);
+        self.dkg_tracker.start(elders_info, attempt, timer_token);
+        self.vote_for_event(AccumulatingEvent::StartDkg(participants));
+    }
+
+    // Reports a consensus stall and tries to recover from it, if consensus stalled.
+    fn check_consensus_stalled(&mut self, core: &mut Core) {
+        let our_elders = match &self.stall_recovery_group {
+            Some(group) => group,
+            None => self.shared_state.our_info(),
+        };
+        let unresponsive = if let Some(unresponsive) =
+            self.consensus_engine.check_stalled(our_elders, core.id())
+        {
+            unresponsive
+        } else {
+            return;
+        };
+
+        warn!(
+            "Consensus stalled - unresponsive elders: {:?}",
+            unresponsive
+        );
+
+        if self.consensus_engine.stall_checks() == 1 {
+            core.send_event(Event::ConsensusStalled {
+                unresponsive: unresponsive.iter().map(|id| *id.name()).collect(),
+            });
+        }
+
+        if self.consensus_engine.stall_checks() >= STALL_RECOVERY_CHECKS
+            && self.recover_from_stall(core, &unresponsive)
+        {
+            return;
+        }
+
+        // First make sure the stall isn't caused by us: ping the unresponsive elders so the ones
+        // that are really gone get detected as lost (and voted offline), and gossip to all the
+        // others right away instead of one by one.
+        let version = self.consensus_engine.parsec_version();
+        let elders: Vec<_> = self
+            .shared_state
+            .our_info()
+            .elders
+            .values()
+            .filter(|p2p_node| p2p_node.public_id() != core.id())
+            .cloned()
+            .collect();
+
+        for p2p_node in elders {
+            if unresponsive.contains(p2p_node.public_id()) {
+                core.send_ping(p2p_node.peer_addr());
+            } else {
+                self.send_parsec_gossip(core, Some((version, p2p_node)));
+            }
+        }
+    }
+
+    // Restarts consensus with only the responsive elders and votes the unresponsive ones offline,
+    // which makes the section go through a new DKG and elders info round without them. Returns
+    // whether the recovery was started.
+    //
+    // Only done if the responsive elders are at least two thirds of all our elders. That way they
+    // still hold enough shares of the section key to sign the new elders info, and at most one
+    // side of a network partition can recover. Like the DKG restart, this relies on the responsive
+    // elders agreeing on who is unresponsive; if they don't, the consensus stalls again and the
+    // next recovery is attempted with the then current knowledge.
+    fn recover_from_stall(&mut self, core: &mut Core, unresponsive: &BTreeSet<PublicId>) -> bool {
+        if unresponsive.is_empty() || !self.is_our_elder(core.id()) {
+            return false;
+        }
+
+        let our_info = self.shared_state.our_info();
+        let responsive: BTreeMap<_, _> = self
+            .stall_recovery_group
+            .as_ref()
+            .unwrap_or(our_info)
+            .elders
+            .iter()
+            .filter(|(_, p2p_node)| !unresponsive.contains(p2p_node.public_id()))
+            .map(|(name, p2p_node)| (*name, p2p_node.clone()))
+            .collect();
+
+        if responsive.len() * QUORUM_DENOMINATOR < our_info.elders.len() * QUORUM_NUMERATOR {
+            debug!(
+                "Not recovering from the consensus stall - only {}/{} elders responsive",
+                responsive.len(),
+                our_info.elders.len()
+            );
+            return false;
+        }
+
+        let group = EldersInfo::new(responsive, our_info.prefix, our_info.version);
+
+        info!(
+            "Recovering from the consensus stall - voting offline: {:?}",
+            unresponsive
+        );
+
+        let complete_data = match self.prepare_parsec_reset(core.id()) {
+            Ok(complete_data) => complete_data,
+            Err(error) => {
+                error!("Failed to recover from the consensus stall: {}", error);
+                return false;
+            }
+        };
+
+        let to_vote_again = complete_data.to_vote_again;
+        let mut to_vote_offline = unresponsive.clone();
+        for event in &to_vote_again {
+            if let AccumulatingEvent::Offline(pub_id) = &event.payload {
+                let _ = to_vote_offline.remove(pub_id);
+            }
+        }
+
+        // Our elders info stays as it is, as does the section key that vouches for it. Only the
+        // consensus is restarted with the responsive elders.
+        if let Err(error) = self.finalise_parsec_reset(
+            core,
+            complete_data.genesis_prefix_info,
+            Some(group),
+            to_vote_again,
+        ) {
+            error!("Failed to recover from the consensus stall: {}", error);
+            return false;
+        }
+
+        self.process_post_reset_events(core, complete_data.to_process);
+
+        for pub_id in to_vote_offline {
+            if self.shared_state.our_members.contains(pub_id.name()) {
+                self.vote_for_event(AccumulatingEvent::Offline(pub_id));
+            }
         }
 
         true
     }
 
+    // Handles a DKG round that didn't produce a result in time. If some of its participants are
+    // unresponsive, restart the round without them. Otherwise keep waiting for it.
+    //
+    // Note: the restarted round only reaches consensus if enough elders agree on which
+    // participants are unresponsive. If they don't, the new round times out as well and another
+    // attempt is made with the then current knowledge, up to `DKG_MAX_ATTEMPTS` times. The last
+    // round is then waited for without timing it out again.
+    fn handle_dkg_timeout(&mut self, core: &mut Core, round: DkgRound) {
+        if !self.is_our_elder(core.id()) {
+            return;
+        }
+
+        // A round we keep waiting for is checked again on every timeout, but reported only once.
+        if !round.timed_out {
+            warn!(
+                "DKG for {:?} timed out (attempt {})",
+                round.participants, round.attempt
+            );
+            core.send_event(Event::DkgTimedOut {
+                participants: round.participants.iter().map(|id| *id.name()).collect(),
+                attempt: round.attempt,
+            });
+        }
+
+        if round.attempt >= DKG_MAX_ATTEMPTS {
+            debug!(
+                "Not restarting DKG for {:?} - no attempts left, waiting for the current round",
+                round.participants
+            );
+            self.dkg_tracker.wait(round);
+            return;
+        }
+
+        let unresponsive = self
+            .consensus_engine
+            .detect_unresponsive(&round.participants);
+        let excluded: BTreeSet<_> = round
+            .participants
+            .iter()
+            .filter(|id| *id != core.id())
+            .filter(|id| {
+                unresponsive.contains(*id)
+                    || self.dkg_tracker.is_lost(id)
+                    || !self.shared_state.our_members.is_active(id.name())
+            })
+            .copied()
+            .collect();
+
+        if excluded.is_empty() {
+            debug!(
+                "Not restarting DKG for {:?} - waiting for the current round",
+                round.participants
+            );
+            let timer_token = core.timer.schedule(DKG_TIMEOUT);
+            self.dkg_tracker.resume(round, timer_token);
+            return;
+        }
+
+        let elders_info = EldersInfo::new(
+            round
+                .elders_info
+                .elders
+                .iter()
+                .filter(|(_, p2p_node)| !excluded.contains(p2p_node.public_id()))
+                .map(|(name, p2p_node)| (*name, p2p_node.clone()))
+                .collect(),
+            round.elders_info.prefix,
+            round.elders_info.version,
+        );
+
+        info!(
+            "Restarting DKG for {:?} without {:?} (attempt {})",
+            elders_info,
+            excluded,
+            round.attempt + 1
+        );
+        core.send_event(Event::DkgRestarted {
+            participants: elders_info.elders.keys().copied().collect(),
+            excluded: excluded.iter().map(|id| *id.name()).collect(),
+            attempt: round.attempt + 1,
+        });
+
+        self.start_dkg(core, elders_info, round.attempt + 1);
+    }
+
     /// Polls and handles the next scheduled relocation, if any.
     fn poll_relocation(&mut self, our_id: &PublicId) -> bool {
         // Delay relocation until no additional churn is in progress.
@@ -933,6 +1664,17 @@ impl Approved {
             AccumulatingEvent::RelocatePrepare(pub_id, count) => {
                 self.handle_relocate_prepare_event(core, pub_id, count);
             }
+            AccumulatingEvent::RelocateRequest { name, destination } => {
+                self.handle_relocate_request_event(name, destination)
+            }
+            AccumulatingEvent::Admission { accepting, version } => {
+                self.handle_admission_event(accepting, version)
+            }
+            AccumulatingEvent::Ban(details) => self.handle_ban_event(core, details)?,
+            AccumulatingEvent::RequestMerge(version) => {
+                self.handle_request_merge_event(core, version)?
+            }
+            AccumulatingEvent::Merge(request) => self.handle_merge_event(core, *request),
             AccumulatingEvent::User(payload) => self.handle_user_event(core, payload)?,
         }
 
@@ -969,11 +1711,14 @@ impl Approved {
         if self.shared_state.add_member(
             payload.p2p_node.clone(),
             payload.age,
-            core.network_params.recommended_section_size,
+            payload.previous_name.as_ref(),
+            &core.network_params,
+            &*core.relocation_policy,
         ) {
             info!("handle Online: {:?}.", payload);
 
             self.members_changed = true;
+            self.join_queue.remove(payload.p2p_node.public_id());
 
             if self.is_our_elder(core.id()) {
                 core.send_event(Event::MemberJoined {
@@ -997,9 +1742,9 @@ impl Approved {
             return;
         }
 
-        if let Some(info) = self
-            .shared_state
-            .remove_member(&pub_id, core.network_params.recommended_section_size)
+        if let Some(info) =
+            self.shared_state
+                .remove_member(&pub_id, &core.network_params, &*core.relocation_policy)
         {
             info!("handle Offline: {}", pub_id);
 
@@ -1015,7 +1760,77 @@ impl Approved {
                 });
             }
         } else {
-            info!("ignore Offline: {}", pub_id);
+            info!("ignore Offline: {}", pub_id);
+        }
+    }
+
+    fn handle_request_merge_event(&mut self, core: &mut Core, version: u64) -> Result<()> {
+        if version != self.shared_state.our_info().version {
+            debug!("ignore RequestMerge({}) - outdated", version);
+            return Ok(());
+        }
+
+        if !self.is_our_elder(core.id()) {
+            return Ok(());
+        }
+
+        info!("handle RequestMerge({})", version);
+
+        let our_prefix = *self.shared_state.our_prefix();
+        let request = MergeRequest {
+            elders_info: self.shared_state.our_info().clone(),
+            members: self.shared_state.our_members.joined().cloned().collect(),
+        };
+
+        self.send_routing_message(
+            core,
+            SrcLocation::Section(our_prefix),
+            DstLocation::Section(our_prefix.sibling().name()),
+            Variant::MergeRequest(Box::new(request)),
+            None,
+        )
+    }
+
+    fn handle_merge_event(&mut self, core: &mut Core, request: MergeRequest) {
+        if self.churn_in_progress {
+            log_or_panic!(
+                log::Level::Error,
+                "can't handle Merge when churn is in progress"
+            );
+            return;
+        }
+
+        // The request tells us how big our sibling is now.
+        self.shared_state
+            .sections
+            .update_neighbour_size(request.elders_info.prefix, request.member_count());
+
+        // If both we and our sibling became too small, they ask each other to merge. Only one of
+        // the requests can be accepted, so the section whose prefix ends with 1 lets the other one
+        // absorb it.
+        let our_prefix = self.shared_state.our_prefix();
+        if self.shared_state.should_request_merge(&core.network_params)
+            && our_prefix.lower_bound() > our_prefix.sibling().lower_bound()
+        {
+            info!("ignore Merge: {:?} - we are merging into them", request);
+            return;
+        }
+
+        let sibling_prefix = request.elders_info.prefix;
+        if self
+            .shared_state
+            .merge_sibling(&core.network_params, request.clone())
+        {
+            info!("handle Merge: {:?}", request);
+            self.members_changed = true;
+
+            // Bring the members of our sibling into our consensus right away, so they can take
+            // part in the DKG for the elders of the merged section.
+            if self.is_our_elder(core.id()) {
+                self.send_genesis_updates_matching(core, &sibling_prefix);
+            }
+        } else {
+            info!("ignore Merge: {:?}", request);
         }
     }
 
@@ -1036,6 +1851,71 @@ impl Approved {
         }
     }
 
+    fn handle_admission_event(&mut self, accepting: bool, version: u64) {
+        if self.shared_state.update_admission(accepting, version) {
+            info!("handle Admission: accepting: {}, v{}", accepting, version);
+        } else {
+            info!("ignore Admission: accepting: {}, v{}", accepting, version);
+        }
+    }
+
+    fn handle_ban_event(&mut self, core: &mut Core, details: BanDetails) -> Result<()> {
+        if !self.shared_state.ban(details.clone(), core.ban_duration) {
+            info!("ignore Ban: {:?} - already banned", details);
+            return Ok(());
+        }
+
+        info!("handle Ban: {:?}", details);
+
+        if !self.is_our_elder(core.id()) {
+            return Ok(());
+        }
+
+        let our_prefix = *self.shared_state.our_prefix();
+        if !our_prefix.matches(details.pub_id.name()) {
+            // Banned by a neighbour section.
+            return Ok(());
+        }
+
+        // Vote the banned peer out of our section.
+        if self
+            .shared_state
+            .our_members
+            .is_active(details.pub_id.name())
+        {
+            self.vote_for_event(AccumulatingEvent::Offline(details.pub_id));
+        }
+
+        // Tell our neighbours so they refuse the peer too.
+        let neighbours: Vec<_> = self
+            .shared_state
+            .sections
+            .other()
+            .map(|(prefix, _)| *prefix)
+            .filter(|prefix| our_prefix.is_neighbour(prefix))
+            .collect();
+        for prefix in neighbours {
+            self.send_routing_message(
+                core,
+                SrcLocation::Section(our_prefix),
+                DstLocation::Section(prefix.name()),
+                Variant::Ban(details.clone()),
+                None,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_relocate_request_event(&mut self, name: XorName, destination: XorName) {
+        if self.shared_state.relocate_member(&name, destination) {
+            info!("handle RelocateRequest: {} to {}", name, destination);
+            self.members_changed = true;
+        } else {
+            info!("ignore RelocateRequest: {} to {}", name, destination);
+        }
+    }
+
     fn handle_relocate_event(
         &mut self,
         core: &mut Core,
@@ -1045,7 +1925,8 @@ impl Approved {
             .shared_state
             .remove_member(
                 &details.pub_id,
-                core.network_params.recommended_section_size,
+                &core.network_params,
+                &*core.relocation_policy,
             )
             .map(|info| info.state)
         {
@@ -1053,7 +1934,7 @@ impl Approved {
                 info!("handle Relocate: {:?}", details);
                 node_knowledge
             }
-            Some(MemberState::Left) | None => {
+            Some(MemberState::Left { .. }) | None => {
                 info!("ignore Relocate: {:?} - not a member", details);
                 return Ok(());
             }
@@ -1111,15 +1992,20 @@ impl Approved {
             return Ok(());
         }
 
-        if let Some(info) = self.dkg_cache.remove(participants) {
+        if let Some(info) = self.dkg_tracker.complete(participants) {
             info!("handle DkgResult: {:?}", participants);
             self.vote_for_section_info(info, dkg_result.0.public_key_set.public_key())?;
+        } else if self.dkg_tracker.forget_abandoned(participants) {
+            debug!(
+                "Ignoring DKG result of an abandoned round {:?}",
+                participants
+            );
         } else {
             log_or_panic!(
                 log::Level::Error,
                 "DKG for an unexpected info {:?} (expected: {{{:?}}})",
                 participants,
-                self.dkg_cache.keys().format(", ")
+                self.dkg_tracker.pending_participants().format(", ")
             );
         }
         Ok(())
@@ -1143,6 +2029,10 @@ impl Approved {
                 return Ok(());
             };
 
+        self.update_bootstrap_cache(core);
+        // The joining nodes have to prove they are reachable again with our new key anyway.
+        self.unreachable_peers.clear();
+
         let elders_info = self.shared_state.our_info();
         let info_prefix = elders_info.prefix;
         let is_elder = elders_info.elders.contains_key(core.name());
@@ -1159,7 +2049,7 @@ impl Approved {
         if info_prefix.is_extension_of(&old_prefix) {
             info!("Split");
         } else if old_prefix.is_extension_of(&info_prefix) {
-            panic!("Merge not supported: {:?} -> {:?}", old_prefix, info_prefix);
+            info!("Merge");
         }
 
         let complete_data = self.prepare_parsec_reset(core.id())?;
@@ -1179,6 +2069,7 @@ impl Approved {
         self.finalise_parsec_reset(
             core,
             complete_data.genesis_prefix_info,
+            None,
             complete_data.to_vote_again,
         )?;
         self.process_post_reset_events(core, complete_data.to_process);
@@ -1274,6 +2165,7 @@ impl Approved {
             .finalise_dkg(our_name, &elders_info)?;
         self.shared_state
             .update_our_section(elders_info, section_key, signature);
+        self.dkg_tracker.prune(self.shared_state.our_info().version);
         self.churn_in_progress = false;
         Ok(())
     }
@@ -1282,6 +2174,10 @@ impl Approved {
         let prefix = elders_info.prefix;
         self.shared_state.sections.add_neighbour(elders_info);
         self.shared_state.sections.update_keys(prefix, section_key);
+        self.shared_state.sections.update_neighbour_size(
+            prefix,
+            self.shared_state.our_members.post_split_sibling_count(),
+        );
 
         // We can update their knowledge already because we know they also reached consensus on
         // our `SectionInfo` so they know our latest key.
@@ -1299,11 +2195,16 @@ impl Approved {
         info!("handle NeighbourInfo: {:?}", elders_info);
 
         let neighbour_elders_removed = NeighbourEldersRemoved::builder(&self.shared_state.sections);
-        self.shared_state
-            .sections
-            .update_keys(elders_info.prefix, key);
+        if self.shared_state.sections.is_merged_neighbour(&elders_info) {
+            self.shared_state.sections.merge(elders_info.prefix, key);
+        } else {
+            self.shared_state
+                .sections
+                .update_keys(elders_info.prefix, key);
+        }
         self.shared_state.sections.add_neighbour(elders_info);
         let neighbour_elders_removed = neighbour_elders_removed.build(&self.shared_state.sections);
+        self.update_bootstrap_cache(core);
 
         if !self.is_our_elder(core.id()) {
             return Ok(());
@@ -1313,6 +2214,41 @@ impl Approved {
         Ok(())
     }
 
+    // Tells the elders of our neighbour sections how many members our section has. This isn't
+    // voted on: the member count keeps changing and the neighbours only need an estimate, for
+    // example to decide whether to merge with us or to relocate nodes to us.
+    fn send_section_size(&mut self, core: &mut Core) {
+        if !self.is_our_elder(core.id()) {
+            return;
+        }
+
+        let member_count = self.shared_state.our_members.joined().count();
+        let recipients: Vec<_> = self
+            .shared_state
+            .sections
+            .neighbour_elders()
+            .map(|p2p_node| *p2p_node.peer_addr())
+            .collect();
+
+        for recipient in recipients {
+            core.send_direct_message(&recipient, Variant::SectionSize(member_count));
+        }
+    }
+
+    // Pings the elders we might route messages through, so that the delivery targets with the
+    // lowest round-trip time can be preferred.
+    fn send_latency_probes(&self, core: &mut Core) {
+        let our_name = *core.name();
+        for p2p_node in self
+            .shared_state
+            .sections
+            .elders()
+            .filter(|p2p_node| *p2p_node.name() != our_name)
+        {
+            core.send_ping(p2p_node.peer_addr());
+        }
+    }
+
     fn handle_send_neighbour_info_event(
         &mut self,
         core: &mut Core,
@@ -1358,9 +2294,11 @@ impl Approved {
 
         info!("handle ParsecPrune");
         let complete_data = self.prepare_parsec_reset(core.id())?;
+        let stall_recovery_group = self.stall_recovery_group.clone();
         self.finalise_parsec_reset(
             core,
             complete_data.genesis_prefix_info,
+            stall_recovery_group,
             complete_data.to_vote_again,
         )?;
         self.send_genesis_updates(core);
@@ -1404,11 +2342,16 @@ impl Approved {
                 | AccumulatingEvent::ParsecPrune
                 | AccumulatingEvent::Relocate(_)
                 | AccumulatingEvent::RelocatePrepare(_, _)
+                | AccumulatingEvent::RelocateRequest { .. }
+                | AccumulatingEvent::Admission { .. }
+                | AccumulatingEvent::Ban(_)
                 | AccumulatingEvent::SectionInfo(_, _)
                 | AccumulatingEvent::NeighbourInfo { .. }
                 | AccumulatingEvent::SendNeighbourInfo { .. }
                 | AccumulatingEvent::TheirKeyInfo { .. }
                 | AccumulatingEvent::TheirKnowledge { .. }
+                | AccumulatingEvent::RequestMerge(_)
+                | AccumulatingEvent::Merge(_)
                 | AccumulatingEvent::User(_) => false,
             })
             .cloned()
@@ -1416,40 +2359,7 @@ impl Approved {
 
         let to_vote_again = cached_events
             .into_iter()
-            .filter(|event| {
-                match event.payload {
-                    // Only re-vote if still relevant to our new prefix.
-                    AccumulatingEvent::Online(ref payload) => {
-                        our_prefix.matches(payload.p2p_node.name())
-                    }
-                    AccumulatingEvent::Offline(pub_id) => our_prefix.matches(pub_id.name()),
-                    AccumulatingEvent::Relocate(ref details)
-                    | AccumulatingEvent::RelocatePrepare(ref details, _) => {
-                        our_prefix.matches(details.pub_id.name())
-                    }
-                    // Drop: no longer relevant after prefix change.
-                    AccumulatingEvent::Genesis { .. }
-                    | AccumulatingEvent::StartDkg(_)
-                    | AccumulatingEvent::DkgResult { .. }
-                    | AccumulatingEvent::ParsecPrune => false,
-
-                    // Keep: Additional signatures for neighbours for sec-msg-relay.
-                    AccumulatingEvent::SectionInfo(ref elders_info, _)
-                    | AccumulatingEvent::NeighbourInfo(ref elders_info, _) => {
-                        our_prefix.is_neighbour(&elders_info.prefix)
-                    }
-
-                    // Only revote if the recipient is still our neighbour
-                    AccumulatingEvent::SendNeighbourInfo { ref dst, .. } => {
-                        self.shared_state.sections.is_in_neighbour(dst)
-                    }
-
-                    // Keep: Still relevant after prefix change.
-                    AccumulatingEvent::TheirKeyInfo { .. }
-                    | AccumulatingEvent::TheirKnowledge { .. }
-                    | AccumulatingEvent::User(_) => true,
-                }
-            })
+            .filter(|event| self.should_vote_again(&our_prefix, event))
             .collect();
 
         Ok(ParsecResetData {
@@ -1459,6 +2369,71 @@ impl Approved {
         })
     }
 
+    // Returns whether the event we voted for and which didn't accumulate before the consensus reset
+    // is still relevant after it, given our new prefix.
+    fn should_vote_again(&self, our_prefix: &Prefix<XorName>, event: &NetworkEvent) -> bool {
+        match event.payload {
+            // Only re-vote if still relevant to our new prefix.
+            AccumulatingEvent::Online(ref payload) => our_prefix.matches(payload.p2p_node.name()),
+            AccumulatingEvent::Offline(pub_id) => our_prefix.matches(pub_id.name()),
+            AccumulatingEvent::Relocate(ref details)
+            | AccumulatingEvent::RelocatePrepare(ref details, _) => {
+                our_prefix.matches(details.pub_id.name())
+            }
+            AccumulatingEvent::RelocateRequest {
+                ref name,
+                ref destination,
+            } => our_prefix.matches(name) && !our_prefix.matches(destination),
+            // Drop: no longer relevant after prefix change.
+            AccumulatingEvent::Genesis { .. }
+            | AccumulatingEvent::StartDkg(_)
+            | AccumulatingEvent::DkgResult { .. }
+            | AccumulatingEvent::ParsecPrune
+            | AccumulatingEvent::Admission { .. }
+            | AccumulatingEvent::RequestMerge(_) => false,
+
+            // Only re-vote if they are still our sibling.
+            AccumulatingEvent::Merge(ref request) => {
+                request.elders_info.prefix == our_prefix.sibling()
+            }
+
+            // Keep: Additional signatures for neighbours for sec-msg-relay.
+            AccumulatingEvent::SectionInfo(ref elders_info, _)
+            | AccumulatingEvent::NeighbourInfo(ref elders_info, _) => {
+                our_prefix.is_neighbour(&elders_info.prefix)
+            }
+
+            // Only revote if the recipient is still our neighbour
+            AccumulatingEvent::SendNeighbourInfo { ref dst, .. } => {
+                self.shared_state.sections.is_in_neighbour(dst)
+            }
+
+            // Keep: Still relevant after prefix change.
+            AccumulatingEvent::TheirKeyInfo { .. }
+            | AccumulatingEvent::TheirKnowledge { .. }
+            | AccumulatingEvent::Ban(_)
+            | AccumulatingEvent::User(_) => true,
+        }
+    }
+
+    // Votes again for the events we voted for before a restart which didn't accumulate in the
+    // consensus instance that ended meanwhile.
+    fn vote_again_after_restart(&mut self, core: &Core, events: Vec<NetworkEvent>) {
+        if !self.is_our_elder(core.id()) {
+            return;
+        }
+
+        let our_prefix = *self.shared_state.our_prefix();
+        let events: BTreeSet<_> = events
+            .into_iter()
+            .filter(|event| self.should_vote_again(&our_prefix, event))
+            .collect();
+
+        for event in events {
+            self.consensus_engine.vote_for(event);
+        }
+    }
+
     fn process_post_reset_events(&mut self, core: &mut Core, to_process: BTreeSet<NetworkEvent>) {
         to_process.iter().for_each(|event| match &event.payload {
             AccumulatingEvent::Genesis { .. }
@@ -1468,11 +2443,16 @@ impl Approved {
             | AccumulatingEvent::ParsecPrune
             | AccumulatingEvent::Relocate(_)
             | AccumulatingEvent::RelocatePrepare(_, _)
+            | AccumulatingEvent::RelocateRequest { .. }
+            | AccumulatingEvent::Admission { .. }
+            | AccumulatingEvent::Ban(_)
             | AccumulatingEvent::SectionInfo(_, _)
             | AccumulatingEvent::NeighbourInfo { .. }
             | AccumulatingEvent::SendNeighbourInfo { .. }
             | AccumulatingEvent::TheirKeyInfo { .. }
             | AccumulatingEvent::TheirKnowledge { .. }
+            | AccumulatingEvent::RequestMerge(_)
+            | AccumulatingEvent::Merge(_)
             | AccumulatingEvent::User(_) => {
                 log_or_panic!(log::Level::Error, "unexpected event {:?}", event.payload);
             }
@@ -1482,20 +2462,25 @@ impl Approved {
         });
     }
 
-    // Finalise parsec reset and revotes for all previously unaccumulated events.
+    // Finalise parsec reset and revotes for all previously unaccumulated events. The new
+    // consensus is run by our elders, or by `stall_recovery_group` if given.
     fn finalise_parsec_reset(
         &mut self,
         core: &mut Core,
         genesis_prefix_info: GenesisPrefixInfo,
+        stall_recovery_group: Option<EldersInfo>,
         to_vote_again: BTreeSet<NetworkEvent>,
     ) -> Result<()> {
         let serialised_state = bincode::serialize(&self.shared_state)?;
 
         self.genesis_prefix_info = genesis_prefix_info;
+        self.stall_recovery_group = stall_recovery_group;
         self.consensus_engine.finalise_reset(
             &mut core.rng,
             core.full_id.clone(),
-            &self.genesis_prefix_info.elders_info,
+            self.stall_recovery_group
+                .as_ref()
+                .unwrap_or(&self.genesis_prefix_info.elders_info),
             serialised_state,
             self.genesis_prefix_info.parsec_version,
         );
@@ -1504,6 +2489,9 @@ impl Approved {
             self.consensus_engine.vote_for(event.clone());
         });
 
+        // Admission votes are dropped on reset, so allow voting for them again.
+        self.admission_vote = None;
+
         Ok(())
     }
 
@@ -1523,6 +2511,20 @@ impl Approved {
             genesis_prefix_info.public_keys.public_key(),
         );
         self.genesis_prefix_info = genesis_prefix_info;
+        self.stall_recovery_group = None;
+        self.update_bootstrap_cache(core);
+    }
+
+    // Remembers the elders we know of as bootstrap contacts for the next start.
+    fn update_bootstrap_cache(&self, core: &mut Core) {
+        let our_name = *core.name();
+        core.transport.bootstrap_cache.insert_elders(
+            self.shared_state
+                .sections
+                .elders()
+                .filter(|p2p_node| *p2p_node.name() != our_name)
+                .map(|p2p_node| *p2p_node.peer_addr()),
+        );
     }
 
     // Detect non-responsive peers and vote them out.
@@ -1536,6 +2538,100 @@ impl Approved {
         }
     }
 
+    // Vote to ban the peers our consensus algorithm proved malicious.
+    fn vote_for_ban_accused(&mut self, core: &Core) {
+        for pub_id in self.consensus_engine.take_accused() {
+            if let Some(p2p_node) = self
+                .shared_state
+                .our_members
+                .get_p2p_node(pub_id.name())
+                .cloned()
+            {
+                self.vote_for_ban(core, p2p_node, BanReason::Equivocation);
+            }
+        }
+    }
+
+    // Vote to ban the given peer if we are elder and it isn't banned yet. Only for misbehaviour
+    // every elder can check for itself, like a forged relocation in a join request sent to all of
+    // them, so that the ban accumulates only once a quorum of the elders confirmed it.
+    fn vote_for_ban(&mut self, core: &Core, p2p_node: P2pNode, reason: BanReason) {
+        if !self.is_our_elder(core.id()) {
+            return;
+        }
+
+        if self
+            .find_ban(core, p2p_node.name(), p2p_node.peer_addr())
+            .is_some()
+        {
+            return;
+        }
+
+        info!("Voting for banning {} - {:?}", p2p_node, reason);
+        self.vote_for_event(AccumulatingEvent::Ban(BanDetails {
+            pub_id: *p2p_node.public_id(),
+            peer_addr: *p2p_node.peer_addr(),
+            reason,
+        }));
+    }
+
+    // Returns the ban in force against the peer with the given name, or with the IP address of
+    // `peer_addr` if we refuse whole IP addresses.
+    fn find_ban(&self, core: &Core, name: &XorName, peer_addr: &SocketAddr) -> Option<&BanDetails> {
+        let ip = if core.ban_by_ip {
+            Some(peer_addr.ip())
+        } else {
+            None
+        };
+        self.shared_state.find_ban(name, ip, core.ban_duration)
+    }
+
+    // Ask our sibling to absorb us if we became too small.
+    fn vote_for_merge_request(&mut self, core: &Core) {
+        if !self.is_our_elder(core.id()) || !self.is_ready_to_churn() {
+            return;
+        }
+
+        let version = self.shared_state.our_info().version;
+        if self.merge_requested == Some(version)
+            || !self.shared_state.should_request_merge(&core.network_params)
+        {
+            return;
+        }
+
+        info!("Voting for merge request");
+        self.merge_requested = Some(version);
+        self.vote_for_event(AccumulatingEvent::RequestMerge(version));
+    }
+
+    // Vote to change whether our section accepts new members if it no longer matches our admission
+    // policy and the wish of the user.
+    fn vote_for_admission(&mut self, core: &Core) {
+        if !self.is_our_elder(core.id()) || !self.is_ready_to_churn() {
+            return;
+        }
+
+        let accepting = self.accepting_members
+            && core
+                .admission_policy
+                .is_accepting(&core.network_params, &self.shared_state.occupancy());
+        if accepting == self.shared_state.accepting_members {
+            return;
+        }
+
+        let version = self.shared_state.admission_version + 1;
+        if self.admission_vote == Some((accepting, version)) {
+            return;
+        }
+
+        info!(
+            "Voting for admission: accepting: {}, v{}",
+            accepting, version
+        );
+        self.admission_vote = Some((accepting, version));
+        self.vote_for_event(AccumulatingEvent::Admission { accepting, version });
+    }
+
     fn vote_for_relocate(&mut self, details: RelocateDetails) {
         self.consensus_engine
             .vote_for(AccumulatingEvent::Relocate(details).into_network_event())
@@ -1596,7 +2692,16 @@ impl Approved {
 
     // Send `GenesisUpdate` message to all non-elders.
     fn send_genesis_updates(&mut self, core: &mut Core) {
+        self.send_genesis_updates_matching(core, &Prefix::default())
+    }
+
+    // Send `GenesisUpdate` message to the non-elders whose names match `prefix`.
+    fn send_genesis_updates_matching(&mut self, core: &mut Core, prefix: &Prefix<XorName>) {
         for (recipient, msg) in self.create_genesis_updates() {
+            if !prefix.matches(recipient.name()) {
+                continue;
+            }
+
             trace!(
                 "Send GenesisUpdate({:?}) to {}",
                 self.genesis_prefix_info,
@@ -1617,6 +2722,7 @@ impl Approved {
             .cloned()
             .filter_map(|recipient| {
                 let variant = Variant::GenesisUpdate(Box::new(self.genesis_prefix_info.clone()));
+                let priority = variant.priority();
                 let dst = DstLocation::Node(*recipient.name());
                 let index = self
                     .members_knowledge
@@ -1628,7 +2734,7 @@ impl Approved {
                     })
                     .unwrap_or(0);
 
-                match self.to_accumulating_message(dst, variant, Some(index)) {
+                match self.to_accumulating_message(dst, variant, priority, Some(index)) {
                     Ok(msg) => Some((recipient, msg)),
                     Err(error) => {
                         error!("Failed to create signed message: {:?}", error);
@@ -1662,7 +2768,7 @@ impl Approved {
         {
             Ok(msg) => {
                 trace!("send parsec request v{} to {:?}", version, gossip_target,);
-                core.send_direct_message(gossip_target.peer_addr(), msg);
+                self.send_consensus_message(core, gossip_target.peer_addr(), msg);
             }
             Err(error) => {
                 trace!(
@@ -1675,6 +2781,35 @@ impl Approved {
         }
     }
 
+    // Sends the consensus request or response once the consensus journal it was derived from is on
+    // disk. The journal is synced in batches, at most `JOURNAL_SYNC_INTERVAL` after the message
+    // was created.
+    fn send_consensus_message(
+        &mut self,
+        core: &mut Core,
+        recipient: &SocketAddr,
+        variant: Variant,
+    ) {
+        if self.unsynced_messages.is_empty() && !self.consensus_engine.needs_journal_sync() {
+            core.send_direct_message(recipient, variant);
+            return;
+        }
+
+        self.unsynced_messages.push((*recipient, variant));
+
+        if self.journal_sync_timer_token.is_none() {
+            self.journal_sync_timer_token = Some(core.timer.schedule(JOURNAL_SYNC_INTERVAL));
+        }
+    }
+
+    fn send_unsynced_messages(&mut self, core: &mut Core) {
+        self.consensus_engine.sync_journal();
+
+        for (recipient, variant) in self.unsynced_messages.drain(..) {
+            core.send_direct_message(&recipient, variant);
+        }
+    }
+
     fn choose_gossip_recipient(&mut self, rng: &mut MainRng) -> Option<P2pNode> {
         let recipients = self.consensus_engine.gossip_recipients();
         if recipients.is_empty() {
@@ -1746,13 +2881,45 @@ impl Approved {
         }
     }
 
-    // Send message over the network.
-    pub fn send_signed_message(&mut self, core: &mut Core, msg: &MessageWithBytes) -> Result<()> {
+    // Returns the priority class to relay the message received from the given peer with. See
+    // `Route::relay_priority`.
+    pub fn relay_priority(&self, sender: &SocketAddr, msg: &MessageWithBytes) -> Priority {
+        let trusted_sender = self
+            .shared_state
+            .sections
+            .elders()
+            .any(|p2p_node| p2p_node.peer_addr() == sender);
+        msg.route().relay_priority(trusted_sender)
+    }
+
+    // Send message over the network with the given priority class.
+    pub fn send_signed_message(
+        &mut self,
+        core: &mut Core,
+        msg: &MessageWithBytes,
+        priority: Priority,
+    ) -> Result<()> {
+        if msg.route().hop_count >= core.route_config.max_hops {
+            debug!(
+                "Not sending {:?} - hop limit of {} reached.",
+                msg, core.route_config.max_hops
+            );
+            return Ok(());
+        }
+
+        let msg = &msg.with_route(Route {
+            priority,
+            ..msg
+                .route()
+                .next_hop(*core.name(), *self.shared_state.our_prefix())
+        })?;
+
         let (targets, dg_size) = routing_table::delivery_targets(
             msg.message_dst(),
             core.id(),
             &self.shared_state.our_members,
             &self.shared_state.sections,
+            |node| core.transport.rtt(node.peer_addr()),
         )?;
 
         let targets: Vec<_> = targets
@@ -1772,11 +2939,121 @@ impl Approved {
 
         let targets: Vec<_> = targets.into_iter().map(|node| *node.peer_addr()).collect();
         let cheap_bytes_clone = msg.full_bytes().clone();
-        core.send_message_to_targets(&targets, dg_size, cheap_bytes_clone);
+        core.send_message_to_targets(&targets, dg_size, cheap_bytes_clone, priority);
+
+        Ok(())
+    }
+
+    // Sends a user message whose destination acknowledges it. Returns the id of the message.
+    pub fn send_user_message_with_ack(
+        &mut self,
+        core: &mut Core,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Vec<u8>,
+    ) -> Result<MessageHash> {
+        let id = self.delivery_tracker.next_id(&src, &dst, &content)?;
+        self.send_acked_user_message(
+            core,
+            PendingDelivery {
+                id,
+                src,
+                dst,
+                content,
+                attempt: 0,
+            },
+        )?;
+
+        Ok(id)
+    }
+
+    fn send_acked_user_message(
+        &mut self,
+        core: &mut Core,
+        delivery: PendingDelivery,
+    ) -> Result<()> {
+        let variant = Variant::AckedUserMessage {
+            content: delivery.content.clone(),
+            id: delivery.id,
+            attempt: delivery.attempt,
+        };
+        self.send_routing_message(core, delivery.src, delivery.dst, variant, None)?;
+
+        let timer_token = core.timer.schedule(ACK_TIMEOUT);
+        self.delivery_tracker.insert(delivery, timer_token);
+
+        Ok(())
+    }
+
+    fn handle_ack_timeout(&mut self, core: &mut Core, mut delivery: PendingDelivery) {
+        if delivery.attempt + 1 >= ACK_MAX_ATTEMPTS {
+            debug!(
+                "Message {:?} to {:?} not acknowledged after {} attempts - giving up.",
+                delivery.id, delivery.dst, ACK_MAX_ATTEMPTS
+            );
+            core.send_event(Event::DeliveryFailed(delivery.id));
+            return;
+        }
+
+        delivery.attempt += 1;
+        trace!(
+            "Message {:?} to {:?} not acknowledged - resending (attempt {}).",
+            delivery.id,
+            delivery.dst,
+            delivery.attempt
+        );
+
+        let id = delivery.id;
+        if let Err(error) = self.send_acked_user_message(core, delivery) {
+            debug!("Failed to resend message {:?}: {:?}", id, error);
+            core.send_event(Event::DeliveryFailed(id));
+        }
+    }
+
+    pub fn handle_acked_user_message(
+        &mut self,
+        core: &mut Core,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Vec<u8>,
+        id: MessageHash,
+        trace: Option<Vec<RouteHop>>,
+    ) -> Result<()> {
+        // Acknowledge with the authority the message was addressed to.
+        let ack_src = match dst {
+            DstLocation::Node(_) => SrcLocation::Node(*core.name()),
+            DstLocation::Section(_) => SrcLocation::Section(*self.shared_state.our_prefix()),
+            DstLocation::Direct => return Err(RoutingError::BadLocation),
+        };
+        let ack_dst = match src {
+            SrcLocation::Node(name) => DstLocation::Node(name),
+            SrcLocation::Section(prefix) => DstLocation::Section(prefix.name()),
+        };
+
+        // Acknowledge every attempt, as the previous acks might have been lost, but report the
+        // message only once.
+        self.send_routing_message(core, ack_src, ack_dst, Variant::Ack(id), None)?;
+
+        if self.delivery_tracker.insert_received(id) {
+            core.send_event(Event::MessageReceived {
+                content,
+                src,
+                dst,
+                trace,
+            });
+        }
 
         Ok(())
     }
 
+    pub fn handle_ack(&mut self, core: &Core, src: SrcLocation, id: MessageHash) {
+        if self.delivery_tracker.handle_ack(&src, &id) {
+            core.send_event(Event::Delivered(id));
+        } else {
+            trace!("Ignoring unexpected Ack({:?}) from {:?}", id, src);
+        }
+    }
+
     // Constructs a message, finds the nodes responsible for accumulation, and either sends
     // these nodes a signature or tries to accumulate signatures for this message (on success, the
     // accumulator handles or forwards the message).
@@ -1790,6 +3067,27 @@ impl Approved {
         dst: DstLocation,
         variant: Variant,
         proof_start_index_override: Option<u64>,
+    ) -> Result<()> {
+        let priority = variant.priority();
+        self.send_routing_message_with_priority(
+            core,
+            src,
+            dst,
+            variant,
+            priority,
+            proof_start_index_override,
+        )
+    }
+
+    // Same as `send_routing_message`, but overrides the priority given by the variant.
+    pub fn send_routing_message_with_priority(
+        &mut self,
+        core: &mut Core,
+        src: SrcLocation,
+        dst: DstLocation,
+        variant: Variant,
+        priority: Priority,
+        proof_start_index_override: Option<u64>,
     ) -> Result<()> {
         if !src.contains(core.name()) {
             log_or_panic!(
@@ -1805,13 +3103,14 @@ impl Approved {
         // If the source is a single node, we don't even need to send signatures, so let's cut this
         // short
         if !src.is_section() {
-            let msg = Message::single_src(&core.full_id, dst, variant)?;
+            let mut msg = Message::single_src(&core.full_id, dst, variant)?;
+            msg.route.priority = priority;
             let msg = MessageWithBytes::new(msg)?;
             return self.handle_accumulated_message(core, msg);
         }
 
         let accumulating_msg =
-            self.to_accumulating_message(dst, variant, proof_start_index_override)?;
+            self.to_accumulating_message(dst, variant, priority, proof_start_index_override)?;
 
         let targets = routing_table::signature_targets(
             &dst,
@@ -1845,6 +3144,7 @@ impl Approved {
         &self,
         dst: DstLocation,
         variant: Variant,
+        priority: Priority,
         proof_start_index_override: Option<u64>,
     ) -> Result<AccumulatingMessage> {
         let proof = self.shared_state.prove(&dst, proof_start_index_override);
@@ -1862,6 +3162,7 @@ impl Approved {
             dst,
             dst_key,
             variant,
+            priority,
         };
 
         AccumulatingMessage::new(content, sk_share, pk_set, proof)
@@ -1877,6 +3178,14 @@ impl Approved {
         our_id: &PublicId,
         genesis_prefix_info: &GenesisPrefixInfo,
     ) -> bool {
+        // Our sibling is absorbing us: we follow their consensus until the merged section is
+        // formed, or we missed that and the merged section is already formed.
+        let our_prefix = self.shared_state.our_prefix();
+        let genesis_prefix = &genesis_prefix_info.elders_info.prefix;
+        if *genesis_prefix == our_prefix.sibling() || our_prefix.is_extension_of(genesis_prefix) {
+            return true;
+        }
+
         !self.is_our_elder(our_id)
             && genesis_prefix_info.parsec_version > self.genesis_prefix_info.parsec_version
     }
@@ -1936,11 +3245,17 @@ impl Approved {
         }
     }
 
-    fn check_signed_relocation_details(&self, msg: &SignedRelocateDetails) -> bool {
+    // A failed signature means the peer that sent the details forged them. That's misbehaviour of
+    // the peer rather than a failure of ours, so it isn't logged as one.
+    fn check_signed_relocation_details(&self, msg: &SignedRelocateDetails) -> Result<()> {
         msg.signed_msg()
             .verify(self.shared_state.sections.keys())
             .and_then(VerifyStatus::require_full)
             .map_err(|error| {
+                if let RoutingError::FailedSignature = error {
+                    return error;
+                }
+
                 messages::log_verify_failure(
                     msg.signed_msg(),
                     &error,
@@ -1948,7 +3263,6 @@ impl Approved {
                 );
                 error
             })
-            .is_ok()
     }
 
     fn print_network_stats(&self) {
@@ -1979,3 +3293,10 @@ fn create_first_elders_info(p2p_node: P2pNode) -> EldersInfo {
     let node = (name, p2p_node);
     EldersInfo::new(iter::once(node).collect(), Prefix::default(), 0)
 }
+
+// Check of a joining node being reachable at its address.
+struct ReachabilityCheck {
+    addr: SocketAddr,
+    nonce: u64,
+    timer_token: u64,
+}
@@ -9,56 +9,168 @@
 use crate::{
     core::Core,
     error::Result,
+    event::{BootstrapProgress, Event},
     id::{FullId, P2pNode},
-    messages::{BootstrapResponse, Message, MessageAction, Variant, VerifyStatus},
+    messages::{BootstrapResponse, Message, MessageAction, RejoinPayload, Variant, VerifyStatus},
     relocation::{RelocatePayload, SignedRelocateDetails},
     section::EldersInfo,
     time::Duration,
-    xor_space::Prefix,
+    xor_space::{Prefix, XorName},
 };
 use bytes::Bytes;
-use fxhash::FxHashSet;
+use fxhash::FxHashMap;
 use std::{collections::HashMap, iter, net::SocketAddr};
 
 /// Time after which bootstrap is cancelled (and possibly retried).
 pub const BOOTSTRAP_TIMEOUT: Duration = Duration::from_secs(20);
 
+/// Configuration of how a node bootstraps off the network.
+///
+/// A bootstrap attempt sends `BootstrapRequest` to several known peers in parallel. The attempt
+/// fails when none of them responded within `BOOTSTRAP_TIMEOUT`, and is then retried after a delay
+/// starting at `initial_retry_delay` and doubling with every retry, up to `max_retry_delay`.
+#[derive(Clone, Copy, Debug)]
+pub struct BootstrapConfig {
+    /// Maximum number of peers to send `BootstrapRequest` to in parallel.
+    pub parallel_requests: usize,
+    /// Number of failed attempts to retry before giving up. The node then emits
+    /// `Event::RestartRequired`, not `Event::Terminated` as it used to, because the failure is
+    /// likely temporary and a restart, possibly with other contacts, can succeed.
+    pub max_retries: u32,
+    /// Delay before the first retry.
+    pub initial_retry_delay: Duration,
+    /// Longest delay between retries.
+    pub max_retry_delay: Duration,
+}
+
+impl BootstrapConfig {
+    // Returns the delay before the given retry (starting from 0).
+    fn retry_delay(&self, retry: u32) -> Duration {
+        self.initial_retry_delay
+            .checked_mul(1 << retry.min(31))
+            .unwrap_or(self.max_retry_delay)
+            .min(self.max_retry_delay)
+    }
+}
+
+impl Default for BootstrapConfig {
+    fn default() -> Self {
+        Self {
+            parallel_requests: 3,
+            max_retries: 5,
+            initial_retry_delay: Duration::from_secs(5),
+            max_retry_delay: Duration::from_secs(80),
+        }
+    }
+}
+
 // The bootstrapping stage - node is trying to find the section to join.
 pub struct Bootstrapping {
-    // Using `FxHashSet` for deterministic iteration order.
-    pending_requests: FxHashSet<SocketAddr>,
+    // Peers we sent `BootstrapRequest` to and are waiting for the response of, with the number of
+    // redirects that led us to them. Using `FxHashMap` for deterministic iteration order.
+    pending_requests: FxHashMap<SocketAddr, usize>,
     timeout_tokens: HashMap<u64, SocketAddr>,
+    // Number of redirects that led to the peers we are currently following.
+    redirect_depth: usize,
+    // Whether we are waiting for the transport to bootstrap off one of its hard-coded contacts.
+    transport_bootstrapping: bool,
+    // Number of failed attempts so far.
+    retries: u32,
+    retry_token: Option<u64>,
     relocate_details: Option<SignedRelocateDetails>,
+    // The id we had before leaving the network, if we are rejoining.
+    previous_id: Option<FullId>,
 }
 
 impl Bootstrapping {
-    pub fn new(relocate_details: Option<SignedRelocateDetails>) -> Self {
+    pub fn new(
+        relocate_details: Option<SignedRelocateDetails>,
+        previous_id: Option<FullId>,
+    ) -> Self {
         Self {
             pending_requests: Default::default(),
             timeout_tokens: Default::default(),
+            redirect_depth: 0,
+            transport_bootstrapping: false,
+            retries: 0,
+            retry_token: None,
             relocate_details,
+            previous_id,
+        }
+    }
+
+    /// Starts a bootstrap attempt: sends `BootstrapRequest` to the best elders from the bootstrap
+    /// cache and bootstraps off the hard-coded contacts of the transport at the same time.
+    pub fn start(&mut self, core: &mut Core) {
+        self.redirect_depth = 0;
+
+        let contacts: Vec<_> = core
+            .transport
+            .bootstrap_cache
+            .contacts()
+            .into_iter()
+            .take(core.bootstrap_config.parallel_requests)
+            .collect();
+        if !contacts.is_empty() {
+            core.send_event(Event::Bootstrapping(BootstrapProgress::Contacting(
+                contacts.clone(),
+            )));
+        }
+
+        for addr in contacts {
+            self.send_bootstrap_request(core, addr);
+        }
+
+        self.transport_bootstrapping = true;
+        core.transport.bootstrap();
+    }
+
+    pub fn handle_bootstrapped_to(&mut self, core: &mut Core, addr: SocketAddr) {
+        self.transport_bootstrapping = false;
+
+        if self.pending_requests.contains_key(&addr) {
+            return;
         }
+
+        core.send_event(Event::Bootstrapping(BootstrapProgress::Contacting(vec![
+            addr,
+        ])));
+        self.send_bootstrap_request(core, addr);
     }
 
-    pub fn handle_timeout(&mut self, core: &mut Core, token: u64) {
+    /// Handles the transport failing to connect to any of its hard-coded contacts. Returns `true`
+    /// if we gave up on bootstrapping.
+    pub fn handle_bootstrap_failure(&mut self, core: &mut Core) -> bool {
+        debug!("Failed to bootstrap off the hard-coded contacts.");
+        self.transport_bootstrapping = false;
+        self.handle_attempt_failure(core)
+    }
+
+    /// Returns `true` if we gave up on bootstrapping.
+    pub fn handle_timeout(&mut self, core: &mut Core, token: u64) -> bool {
+        if self.retry_token == Some(token) {
+            self.retry_token = None;
+            info!("Retrying bootstrap (retry {}).", self.retries);
+            self.start(core);
+            return false;
+        }
+
         let peer_addr = if let Some(peer_addr) = self.timeout_tokens.remove(&token) {
             peer_addr
         } else {
-            return;
+            return false;
         };
 
-        debug!("Timeout when trying to bootstrap against {}.", peer_addr);
-
-        if !self.pending_requests.remove(&peer_addr) {
-            return;
+        if self.pending_requests.remove(&peer_addr).is_none() {
+            return false;
         }
 
+        debug!("Timeout when trying to bootstrap against {}.", peer_addr);
+
         core.transport.disconnect(peer_addr);
+        core.transport.bootstrap_cache.record_failure(peer_addr);
 
-        if self.pending_requests.is_empty() {
-            // Rebootstrap
-            core.transport.bootstrap();
-        }
+        self.handle_attempt_failure(core)
     }
 
     pub fn decide_message_action(&self, msg: &Message) -> Result<MessageAction> {
@@ -70,17 +182,28 @@ impl Bootstrapping {
 
             Variant::NeighbourInfo { .. }
             | Variant::UserMessage(_)
+            | Variant::AckedUserMessage { .. }
+            | Variant::Ack(_)
             | Variant::NodeApproval(_)
             | Variant::GenesisUpdate(_)
+            | Variant::MergeRequest(_)
+            | Variant::Ban(_)
             | Variant::Relocate(_)
             | Variant::MessageSignature(_)
             | Variant::BootstrapRequest(_)
             | Variant::JoinRequest(_) => Ok(MessageAction::Bounce),
 
             Variant::MemberKnowledge { .. }
-            | Variant::ParsecRequest(..)
-            | Variant::ParsecResponse(..)
-            | Variant::Ping => Ok(MessageAction::Discard),
+            | Variant::SectionSize(_)
+            | Variant::ResourceChallenge(_)
+            | Variant::EchoRequest(_)
+            | Variant::EchoResponse(_)
+            | Variant::ReachabilityProof(_)
+            | Variant::JoinRejected(_)
+            | Variant::ConsensusRequest(..)
+            | Variant::ConsensusResponse(..)
+            | Variant::Ping(_)
+            | Variant::Pong(_) => Ok(MessageAction::Discard),
         }
     }
 
@@ -98,14 +221,20 @@ impl Bootstrapping {
         response: BootstrapResponse,
     ) -> Result<Option<JoinParams>> {
         // Ignore messages from peers we didn't send `BootstrapRequest` to.
-        if !self.pending_requests.contains(sender.peer_addr()) {
+        let depth = if let Some(depth) = self.pending_requests.remove(sender.peer_addr()) {
+            depth
+        } else {
             debug!(
                 "Ignoring BootstrapResponse from unexpected peer: {}",
                 sender,
             );
             core.transport.disconnect(*sender.peer_addr());
             return Ok(None);
-        }
+        };
+
+        core.transport
+            .bootstrap_cache
+            .record_success(*sender.peer_addr());
 
         match response {
             BootstrapResponse::Join(elders_info) => {
@@ -114,62 +243,143 @@ impl Bootstrapping {
                     elders_info, sender
                 );
 
-                let relocate_payload = self.join_section(core, &elders_info)?;
-                Ok(Some(JoinParams {
-                    elders_info,
-                    relocate_payload,
-                }))
+                core.transport.bootstrap_cache.insert_elders(
+                    elders_info
+                        .elders
+                        .values()
+                        .map(|p2p_node| *p2p_node.peer_addr()),
+                );
+
+                Ok(Some(self.join_section(core, elders_info)?))
             }
             BootstrapResponse::Rebootstrap(new_conn_infos) => {
-                info!(
-                    "Bootstrapping redirected to another set of peers: {:?}",
-                    new_conn_infos
+                core.transport.disconnect(*sender.peer_addr());
+                core.transport
+                    .bootstrap_cache
+                    .insert_elders(new_conn_infos.iter().copied());
+
+                // Every redirect leads to a section closer to our destination, so the redirect
+                // reached through the most redirects is the best one. Of the equally good ones we
+                // follow the first.
+                if depth < self.redirect_depth {
+                    debug!(
+                        "Ignoring redirect to {:?} - already following a better one",
+                        new_conn_infos
+                    );
+                } else {
+                    info!(
+                        "Bootstrapping redirected to another set of peers: {:?}",
+                        new_conn_infos
+                    );
+                    self.follow_redirect(core, depth + 1, new_conn_infos);
+                }
+
+                // The peers responding means the network is reachable, so if we are left with
+                // nobody to wait for, we retry even if we ran out of retries.
+                if self.is_idle() {
+                    self.schedule_retry(core);
+                }
+
+                Ok(None)
+            }
+            BootstrapResponse::Wait(_) => {
+                debug!(
+                    "Ignoring BootstrapResponse::Wait from {} - we haven't sent JoinRequest yet",
+                    sender
                 );
-                self.reconnect_to_new_section(core, new_conn_infos);
                 Ok(None)
             }
         }
     }
 
     pub fn send_bootstrap_request(&mut self, core: &mut Core, dst: SocketAddr) {
-        if !self.pending_requests.insert(dst) {
+        self.send_bootstrap_request_at(core, dst, self.redirect_depth)
+    }
+
+    fn send_bootstrap_request_at(&mut self, core: &mut Core, dst: SocketAddr, depth: usize) {
+        if self.pending_requests.contains_key(&dst) {
             return;
         }
 
+        let _ = self.pending_requests.insert(dst, depth);
+
         let token = core.timer.schedule(BOOTSTRAP_TIMEOUT);
         let _ = self.timeout_tokens.insert(token, dst);
 
-        let destination = match &self.relocate_details {
-            Some(details) => *details.destination(),
-            None => *core.name(),
-        };
+        let destination = self.destination(core);
 
         debug!("Sending BootstrapRequest to {}.", dst);
         core.send_direct_message(&dst, Variant::BootstrapRequest(destination));
     }
 
-    fn reconnect_to_new_section(&mut self, core: &mut Core, new_conn_infos: Vec<SocketAddr>) {
-        for addr in self.pending_requests.drain() {
-            core.transport.disconnect(addr);
+    fn follow_redirect(&mut self, core: &mut Core, depth: usize, new_conn_infos: Vec<SocketAddr>) {
+        self.redirect_depth = depth;
+
+        let contacts: Vec<_> = new_conn_infos
+            .into_iter()
+            .filter(|addr| !self.pending_requests.contains_key(addr))
+            .take(core.bootstrap_config.parallel_requests)
+            .collect();
+        core.send_event(Event::Bootstrapping(BootstrapProgress::Redirected(
+            contacts.clone(),
+        )));
+
+        for addr in contacts {
+            self.send_bootstrap_request_at(core, addr, depth);
+        }
+    }
+
+    // Checks whether the current attempt failed and if so, schedules a retry. Returns `true` if we
+    // ran out of retries and gave up.
+    fn handle_attempt_failure(&mut self, core: &mut Core) -> bool {
+        if !self.is_idle() {
+            return false;
+        }
+
+        if self.retries >= core.bootstrap_config.max_retries {
+            info!("Failed to bootstrap after {} retries.", self.retries);
+            return true;
         }
 
-        self.timeout_tokens.clear();
+        self.schedule_retry(core);
+        false
+    }
+
+    // Whether we are not waiting for anything - the current attempt failed.
+    fn is_idle(&self) -> bool {
+        self.pending_requests.is_empty()
+            && !self.transport_bootstrapping
+            && self.retry_token.is_none()
+    }
+
+    fn schedule_retry(&mut self, core: &mut Core) {
+        let delay = core.bootstrap_config.retry_delay(self.retries);
+        self.retries += 1;
+        self.retry_token = Some(core.timer.schedule(delay));
+
+        info!("Failed to bootstrap - retrying in {:?}.", delay);
+        core.send_event(Event::Bootstrapping(BootstrapProgress::Retrying {
+            attempt: self.retries,
+            delay,
+        }));
+    }
 
-        for conn_info in new_conn_infos {
-            self.send_bootstrap_request(core, conn_info);
+    // The name of the section we want to join: the relocation destination if we are being
+    // relocated, the section we left if we are rejoining, otherwise the section matching our name.
+    fn destination(&self, core: &Core) -> XorName {
+        if let Some(details) = &self.relocate_details {
+            *details.destination()
+        } else if let Some(previous_id) = &self.previous_id {
+            *previous_id.public_id().name()
+        } else {
+            *core.name()
         }
     }
 
-    fn join_section(
-        &mut self,
-        core: &mut Core,
-        elders_info: &EldersInfo,
-    ) -> Result<Option<RelocatePayload>> {
+    fn join_section(&mut self, core: &mut Core, elders_info: EldersInfo) -> Result<JoinParams> {
+        let destination = self.destination(core);
         let relocate_details = self.relocate_details.take();
-        let destination = match &relocate_details {
-            Some(details) => *details.destination(),
-            None => *core.name(),
-        };
+        let previous_id = self.previous_id.take();
         let old_full_id = core.full_id.clone();
 
         // Use a name that will match the destination even after multiple splits
@@ -185,18 +395,43 @@ impl Bootstrapping {
             core.full_id = new_full_id;
         }
 
-        if let Some(details) = relocate_details {
-            let payload = RelocatePayload::new(details, core.full_id.public_id(), &old_full_id)?;
-            Ok(Some(payload))
-        } else {
-            Ok(None)
-        }
+        let relocate_payload = relocate_details
+            .as_ref()
+            .map(|details| {
+                RelocatePayload::new(details.clone(), core.full_id.public_id(), &old_full_id)
+            })
+            .transpose()?;
+        let rejoin_payload = previous_id
+            .as_ref()
+            .map(|previous_id| RejoinPayload::new(core.full_id.public_id(), previous_id))
+            .transpose()?;
+
+        Ok(JoinParams {
+            elders_info,
+            relocate_payload,
+            rejoin_payload,
+            rebootstrap_params: RebootstrapParams {
+                relocate: relocate_details.map(|details| (details, old_full_id)),
+                previous_id,
+            },
+        })
     }
 }
 
 pub struct JoinParams {
     pub elders_info: EldersInfo,
     pub relocate_payload: Option<RelocatePayload>,
+    pub rejoin_payload: Option<RejoinPayload>,
+    pub rebootstrap_params: RebootstrapParams,
+}
+
+// What we need to bootstrap again towards the same destination if joining fails.
+#[derive(Clone)]
+pub struct RebootstrapParams {
+    // The details of our relocation along with the id they were issued for, if we are relocating.
+    pub relocate: Option<(SignedRelocateDetails, FullId)>,
+    // The id we had before leaving the network, if we are rejoining.
+    pub previous_id: Option<FullId>,
 }
 
 fn verify_message(msg: &Message) -> Result<()> {
@@ -6,30 +6,48 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::RebootstrapParams;
 use crate::{
     core::Core,
     error::Result,
     event::Connected,
     id::P2pNode,
     messages::{
-        self, BootstrapResponse, JoinRequest, Message, MessageAction, Variant, VerifyStatus,
+        self, BootstrapResponse, JoinRejection, JoinRequest, Message, MessageAction, RejoinPayload,
+        Variant, VerifyStatus,
     },
+    reachability::{self, ReachabilityProof},
     relocation::RelocatePayload,
+    resource_proof::{ResourceProof, ResourceProofChallenge},
     section::EldersInfo,
-    xor_space::Prefix,
+    xor_space::{Prefix, XorName},
 };
 use bytes::Bytes;
-use std::time::Duration;
+use std::{collections::BTreeSet, time::Duration};
 
 /// Time after which an attempt to joining a section is cancelled (and possibly retried).
 pub const JOIN_TIMEOUT: Duration = Duration::from_secs(600);
+/// Total time a section not accepting new members can postpone the join timeout by.
+pub const MAX_JOIN_WAIT: Duration = Duration::from_secs(1800);
 
 // The joining stage - node is waiting to be approved by the section.
 pub struct Joining {
     // EldersInfo of the section we are joining.
     elders_info: EldersInfo,
-    // Whether we are joining as infant or relocating.
+    // Whether we are joining as infant, relocating or rejoining.
     join_type: JoinType,
+    // What we need to bootstrap again if this join fails.
+    rebootstrap_params: RebootstrapParams,
+    // Timer token for resending the join requests after the section told us to wait.
+    retry_token: Option<u64>,
+    // How long the section told us to wait so far.
+    waited: Duration,
+    // Our solution of the latest resource proof challenge of the section.
+    resource_proof: Option<ResourceProof>,
+    // Proofs of the elders that checked we are reachable.
+    reachability_proofs: Vec<ReachabilityProof>,
+    // Names of the elders that checked we are reachable and rejected us.
+    reachability_rejections: BTreeSet<XorName>,
 }
 
 impl Joining {
@@ -37,10 +55,25 @@ impl Joining {
         core: &mut Core,
         elders_info: EldersInfo,
         relocate_payload: Option<RelocatePayload>,
+        rejoin_payload: Option<RejoinPayload>,
+        rebootstrap_params: RebootstrapParams,
     ) -> Self {
-        let join_type = match relocate_payload {
-            Some(payload) => JoinType::Relocate(payload),
-            None => {
+        let join_type = match (relocate_payload, rejoin_payload) {
+            (Some(payload), _) => {
+                let timeout_token = core.timer.schedule(JOIN_TIMEOUT);
+                JoinType::Relocate {
+                    payload,
+                    timeout_token,
+                }
+            }
+            (None, Some(payload)) => {
+                let timeout_token = core.timer.schedule(JOIN_TIMEOUT);
+                JoinType::Rejoin {
+                    payload,
+                    timeout_token,
+                }
+            }
+            (None, None) => {
                 let timeout_token = core.timer.schedule(JOIN_TIMEOUT);
                 JoinType::First { timeout_token }
             }
@@ -49,6 +82,12 @@ impl Joining {
         let stage = Self {
             elders_info,
             join_type,
+            rebootstrap_params,
+            retry_token: None,
+            waited: Duration::from_secs(0),
+            resource_proof: None,
+            reachability_proofs: Vec::new(),
+            reachability_rejections: BTreeSet::new(),
         };
         stage.send_join_requests(core);
         stage
@@ -56,9 +95,16 @@ impl Joining {
 
     // Returns whether the timeout was handled.
     pub fn handle_timeout(&mut self, core: &mut Core, token: u64) -> bool {
+        if self.retry_token == Some(token) {
+            self.retry_token = None;
+            self.send_join_requests(core);
+            return false;
+        }
+
         let join_token = match self.join_type {
-            JoinType::First { timeout_token } => timeout_token,
-            JoinType::Relocate(_) => return false,
+            JoinType::First { timeout_token }
+            | JoinType::Relocate { timeout_token, .. }
+            | JoinType::Rejoin { timeout_token, .. } => timeout_token,
         };
 
         if join_token == token {
@@ -83,11 +129,11 @@ impl Joining {
         match msg.variant {
             Variant::NodeApproval(_) => {
                 match &self.join_type {
-                    JoinType::Relocate(payload) => {
+                    JoinType::Relocate { payload, .. } => {
                         let details = payload.relocate_details();
                         verify_message(msg, Some(&details.destination_key))?;
                     }
-                    JoinType::First { .. } => {
+                    JoinType::First { .. } | JoinType::Rejoin { .. } => {
                         // We don't have any trusted keys to verify this message, but we still need to
                         // handle it.
                     }
@@ -95,14 +141,24 @@ impl Joining {
                 Ok(MessageAction::Handle)
             }
 
-            Variant::BootstrapResponse(BootstrapResponse::Join(_)) | Variant::Bounce { .. } => {
+            Variant::BootstrapResponse(BootstrapResponse::Join(_))
+            | Variant::BootstrapResponse(BootstrapResponse::Wait(_))
+            | Variant::ResourceChallenge(_)
+            | Variant::EchoRequest(_)
+            | Variant::ReachabilityProof(_)
+            | Variant::JoinRejected(_)
+            | Variant::Bounce { .. } => {
                 verify_message(msg, None)?;
                 Ok(MessageAction::Handle)
             }
 
             Variant::NeighbourInfo { .. }
             | Variant::UserMessage(_)
+            | Variant::AckedUserMessage { .. }
+            | Variant::Ack(_)
             | Variant::GenesisUpdate(_)
+            | Variant::MergeRequest(_)
+            | Variant::Ban(_)
             | Variant::Relocate(_)
             | Variant::MessageSignature(_)
             | Variant::BootstrapRequest(_)
@@ -110,9 +166,12 @@ impl Joining {
             | Variant::JoinRequest(_) => Ok(MessageAction::Bounce),
 
             Variant::MemberKnowledge { .. }
-            | Variant::ParsecRequest(..)
-            | Variant::ParsecResponse(..)
-            | Variant::Ping => Ok(MessageAction::Discard),
+            | Variant::SectionSize(_)
+            | Variant::EchoResponse(_)
+            | Variant::ConsensusRequest(..)
+            | Variant::ConsensusResponse(..)
+            | Variant::Ping(_)
+            | Variant::Pong(_) => Ok(MessageAction::Discard),
         }
     }
 
@@ -139,6 +198,7 @@ impl Joining {
                 new_elders_info, sender
             );
             self.elders_info = new_elders_info;
+            self.reachability_rejections.clear();
             self.send_join_requests(core);
         } else {
             log_or_panic!(
@@ -152,6 +212,186 @@ impl Joining {
         Ok(())
     }
 
+    // Handles the section telling us we are queued to join it: postpones the join timeout, unless
+    // we already waited `MAX_JOIN_WAIT`, and resends the join requests after `retry_after`.
+    pub fn handle_wait(&mut self, core: &mut Core, sender: P2pNode, retry_after: Duration) {
+        if !self.elders_info.elders.contains_key(sender.name()) {
+            debug!(
+                "Ignoring Wait from {} - not an elder of our target section",
+                sender
+            );
+            return;
+        }
+
+        if self.retry_token.is_some() {
+            return;
+        }
+
+        let timeout_token = match &mut self.join_type {
+            JoinType::First { timeout_token } | JoinType::Rejoin { timeout_token, .. } => {
+                timeout_token
+            }
+            JoinType::Relocate { .. } => {
+                debug!("Ignoring Wait from {} - we are being relocated", sender);
+                return;
+            }
+        };
+
+        let retry_after = retry_after.min(JOIN_TIMEOUT);
+
+        info!(
+            "Queued to join section {:?} - retrying in {:?}",
+            self.elders_info.prefix, retry_after
+        );
+
+        // Don't let the section postpone our join indefinitely: once we waited long enough, the
+        // join times out and we try again from bootstrapping.
+        if self.waited < MAX_JOIN_WAIT {
+            self.waited += retry_after;
+            *timeout_token = core.timer.schedule(retry_after + JOIN_TIMEOUT);
+        }
+
+        self.retry_token = Some(core.timer.schedule(retry_after));
+    }
+
+    // Handles the resource proof challenge of an elder of the section we are joining: solves it
+    // (unless we already did) and sends the solution to the elder.
+    pub fn handle_resource_challenge(
+        &mut self,
+        core: &mut Core,
+        sender: P2pNode,
+        challenge: ResourceProofChallenge,
+    ) {
+        if !self.elders_info.elders.contains_key(sender.name()) {
+            debug!(
+                "Ignoring ResourceChallenge from {} - not an elder of our target section",
+                sender
+            );
+            return;
+        }
+
+        if let JoinType::Relocate { .. } = self.join_type {
+            debug!(
+                "Ignoring ResourceChallenge from {} - we are being relocated",
+                sender
+            );
+            return;
+        }
+
+        if !challenge.is_acceptable() {
+            debug!(
+                "Ignoring ResourceChallenge from {} - too expensive: {:?}",
+                sender, challenge
+            );
+            return;
+        }
+
+        if !self
+            .resource_proof
+            .as_ref()
+            .map(|proof| challenge.is_solved_by(proof))
+            .unwrap_or(false)
+        {
+            info!("Solving {:?}", challenge);
+            self.resource_proof = Some(challenge.solve());
+        }
+
+        self.send_join_request(core, &sender);
+    }
+
+    // Handles the elder of the section we are joining checking that we are reachable.
+    pub fn handle_echo_request(&self, core: &mut Core, sender: P2pNode, nonce: u64) {
+        if !self.elders_info.elders.contains_key(sender.name()) {
+            debug!(
+                "Ignoring EchoRequest from {} - not an elder of our target section",
+                sender
+            );
+            return;
+        }
+
+        core.send_direct_message(sender.peer_addr(), Variant::EchoResponse(nonce));
+    }
+
+    // Handles the proof of an elder of the section we are joining that we are reachable. Once we
+    // have the proofs of all the elders checking us, sends the join requests again with them.
+    pub fn handle_reachability_proof(
+        &mut self,
+        core: &mut Core,
+        sender: P2pNode,
+        proof: ReachabilityProof,
+    ) {
+        if !self.elders_info.elders.contains_key(sender.name())
+            || proof.elder() != sender.public_id()
+        {
+            debug!(
+                "Ignoring {:?} from {} - not an elder of our target section",
+                proof, sender
+            );
+            return;
+        }
+
+        self.reachability_proofs
+            .retain(|old_proof| old_proof.elder() != proof.elder());
+        self.reachability_proofs.push(proof);
+
+        let complete = reachability::checkers(&self.elders_info, core.name())
+            .into_iter()
+            .all(|checker| {
+                self.reachability_proofs
+                    .iter()
+                    .any(|proof| proof.elder() == checker.public_id())
+            });
+        if complete {
+            self.send_join_requests(core);
+        }
+    }
+
+    // Handles an elder of the section rejecting us. A single elder failing to reach us might be
+    // a problem on its side, so returns whether we should give up joining only once all the elders
+    // checking us rejected us.
+    pub fn handle_join_rejected(
+        &mut self,
+        core: &Core,
+        sender: P2pNode,
+        rejection: JoinRejection,
+    ) -> bool {
+        let checkers = reachability::checkers(&self.elders_info, core.name());
+        if !checkers
+            .iter()
+            .any(|checker| checker.public_id() == sender.public_id())
+        {
+            debug!(
+                "Ignoring JoinRejected from {} - not checking our reachability",
+                sender
+            );
+            return false;
+        }
+
+        let _ = self.reachability_rejections.insert(*sender.name());
+
+        let rejected = checkers
+            .iter()
+            .all(|checker| self.reachability_rejections.contains(checker.name()));
+
+        match rejection {
+            JoinRejection::Unreachable(addr) if rejected => error!(
+                "Section {:?} rejected us - we are not reachable at {}",
+                self.elders_info.prefix, addr
+            ),
+            JoinRejection::Unreachable(addr) => warn!(
+                "{} couldn't reach us at {} - waiting for the other elders checking us",
+                sender, addr
+            ),
+        }
+
+        rejected
+    }
+
+    // What we need to bootstrap again towards the same section if this join fails.
+    pub fn rebootstrap_params(&self) -> &RebootstrapParams {
+        &self.rebootstrap_params
+    }
+
     // The EldersInfo of the section we are joining.
     pub fn target_section_elders_info(&self) -> &EldersInfo {
         &self.elders_info
@@ -160,37 +400,55 @@ impl Joining {
     // Are we relocating or joining for the first time?
     pub fn connect_type(&self) -> Connected {
         match self.join_type {
-            JoinType::First { .. } => Connected::First,
-            JoinType::Relocate(_) => Connected::Relocate,
+            JoinType::First { .. } | JoinType::Rejoin { .. } => Connected::First,
+            JoinType::Relocate { .. } => Connected::Relocate,
         }
     }
 
     fn send_join_requests(&self, core: &mut Core) {
-        let relocate_payload = match &self.join_type {
-            JoinType::First { .. } => None,
-            JoinType::Relocate(payload) => Some(payload),
+        for dst in self.elders_info.elders.values() {
+            self.send_join_request(core, dst);
+        }
+    }
+
+    fn send_join_request(&self, core: &mut Core, dst: &P2pNode) {
+        let (relocate_payload, rejoin_payload) = match &self.join_type {
+            JoinType::First { .. } => (None, None),
+            JoinType::Relocate { payload, .. } => (Some(payload), None),
+            JoinType::Rejoin { payload, .. } => (None, Some(payload)),
         };
 
-        for dst in self.elders_info.elders.values() {
-            let join_request = JoinRequest {
-                elders_version: self.elders_info.version,
-                relocate_payload: relocate_payload.cloned(),
-            };
+        let join_request = JoinRequest {
+            elders_version: self.elders_info.version,
+            relocate_payload: relocate_payload.cloned(),
+            rejoin_payload: rejoin_payload.cloned(),
+            resource_proof: self.resource_proof.clone(),
+            reachability_proofs: self.reachability_proofs.clone(),
+        };
 
-            let variant = Variant::JoinRequest(Box::new(join_request));
+        let variant = Variant::JoinRequest(Box::new(join_request));
 
-            info!("Sending JoinRequest to {}", dst);
-            core.send_direct_message(dst.peer_addr(), variant);
-        }
+        info!("Sending JoinRequest to {}", dst);
+        core.send_direct_message(dst.peer_addr(), variant);
     }
 }
 
 #[allow(clippy::large_enum_variant)]
 enum JoinType {
     // Node joining the network for the first time.
-    First { timeout_token: u64 },
+    First {
+        timeout_token: u64,
+    },
     // Node being relocated.
-    Relocate(RelocatePayload),
+    Relocate {
+        payload: RelocatePayload,
+        timeout_token: u64,
+    },
+    // Node returning to its former section after a temporary absence.
+    Rejoin {
+        payload: RejoinPayload,
+        timeout_token: u64,
+    },
 }
 
 fn verify_message(msg: &Message, trusted_key: Option<&bls::PublicKey>) -> Result<()> {
@@ -12,7 +12,7 @@ mod joining;
 
 pub use self::{
     approved::{Approved, RelocateParams},
-    bootstrapping::{Bootstrapping, JoinParams},
+    bootstrapping::{BootstrapConfig, Bootstrapping, JoinParams, RebootstrapParams},
     joining::Joining,
 };
 
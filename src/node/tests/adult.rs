@@ -12,7 +12,7 @@ use crate::{
     error::Result,
     id::FullId,
     location::DstLocation,
-    messages::{AccumulatingMessage, Message, PlainMessage, Variant},
+    messages::{AccumulatingMessage, Message, PlainMessage, Priority, Variant},
     network_params::NetworkParams,
     node::{Node, NodeConfig},
     rng::{self, MainRng},
@@ -157,6 +157,7 @@ fn genesis_update_accumulating_message(
         dst: DstLocation::Node(dst),
         dst_key: public_key_set.public_key(),
         variant: Variant::GenesisUpdate(Box::new(genesis_prefix_info)),
+        priority: Priority::Churn,
     };
 
     let proof = sender.state.prove(&content.dst, None);
@@ -7,12 +7,12 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    event::Event,
+    event::{BootstrapProgress, Event},
     messages::{Message, Variant},
     mock::Environment,
-    node::{Node, NodeConfig, BOOTSTRAP_TIMEOUT},
+    node::{BootstrapConfig, Node, NodeConfig, BOOTSTRAP_TIMEOUT},
     quic_p2p::{EventSenders, Peer},
-    transport::Transport,
+    transport::{BandwidthConfig, Transport, TransportKind},
     TransportConfig, TransportEvent,
 };
 use crossbeam_channel::{self as mpmc, TryRecvError};
@@ -20,7 +20,7 @@ use fake_clock::FakeClock;
 
 #[test]
 // Check that losing our proxy connection while in the bootstrapping stage doesn't stall
-// and instead triggers a re-bootstrap attempt..
+// and instead triggers a re-bootstrap attempt after a delay.
 fn lose_proxy_connection() {
     let env = Environment::new(Default::default());
 
@@ -32,7 +32,14 @@ fn lose_proxy_connection() {
     };
     let node_a_endpoint = env.gen_addr();
     let node_a_config = TransportConfig::node().with_endpoint(node_a_endpoint);
-    let node_a_network_service = Transport::new(node_a_event_tx, node_a_config).unwrap();
+    let node_a_network_service = Transport::new(
+        node_a_event_tx,
+        node_a_config,
+        TransportKind::Quic,
+        None,
+        BandwidthConfig::default(),
+    )
+    .unwrap();
 
     // Construct a node "B" which will start in the bootstrapping stage and bootstrap off the
     // network service above.
@@ -41,8 +48,13 @@ fn lose_proxy_connection() {
         .with_hard_coded_contact(node_a_endpoint)
         .with_endpoint(node_b_endpoint);
 
+    let bootstrap_config = BootstrapConfig {
+        max_retries: 1,
+        ..Default::default()
+    };
     let (mut node_b, node_b_event_rx, _) = Node::new(NodeConfig {
         transport_config: node_b_config,
+        bootstrap: bootstrap_config,
         ..Default::default()
     });
 
@@ -77,12 +89,36 @@ fn lose_proxy_connection() {
         panic!("Should have received `NewMessage` event.");
     }
 
+    match node_b_event_rx.try_recv() {
+        Ok(Event::Bootstrapping(BootstrapProgress::Contacting(contacts))) => {
+            assert_eq!(contacts, vec![node_a_endpoint])
+        }
+        event => panic!("Unexpected event {:?}", event),
+    }
+
     // Drop A and let some time pass...
     drop(node_a_network_service);
     FakeClock::advance_time(BOOTSTRAP_TIMEOUT.as_secs() * 1000 + 1);
     env.poll();
 
-    // ...which causes the bootstrap request to timeout and B then attempts to rebootstrap..
+    // ...which causes the bootstrap request to timeout and B then schedules a retry.
+    step_at_least_once(&mut node_b);
+    let delay = bootstrap_config.initial_retry_delay;
+    assert_eq!(
+        node_b_event_rx.try_recv(),
+        Ok(Event::Bootstrapping(BootstrapProgress::Retrying {
+            attempt: 1,
+            delay
+        }))
+    );
+    assert!(matches!(
+        node_b_event_rx.try_recv(),
+        Err(TryRecvError::Empty)
+    ));
+
+    // After the delay, B attempts to rebootstrap...
+    FakeClock::advance_time(delay.as_secs() * 1000 + 1);
+    env.poll();
     step_at_least_once(&mut node_b);
     assert!(matches!(
         node_b_event_rx.try_recv(),
@@ -90,9 +126,12 @@ fn lose_proxy_connection() {
     ));
     env.poll();
 
-    // ... but there is no one to bootstrap to, so the bootstrap fails which causes B to terminate.
+    // ... but there is no one to bootstrap to and B ran out of retries, so it gives up.
     step_at_least_once(&mut node_b);
-    assert!(matches!(node_b_event_rx.try_recv(), Ok(Event::Terminated)));
+    assert!(matches!(
+        node_b_event_rx.try_recv(),
+        Ok(Event::RestartRequired)
+    ));
     assert!(matches!(
         node_b_event_rx.try_recv(),
         Err(TryRecvError::Empty)
@@ -9,20 +9,26 @@
 use super::utils as test_utils;
 use crate::{
     consensus::{
-        generate_bls_threshold_secret_key, AccumulatingEvent, OnlinePayload, ParsecRequest,
+        generate_bls_threshold_secret_key, AccumulatingEvent, ConsensusRequest, OnlinePayload,
+        ParsecRequest, DKG_TIMEOUT,
     },
     error::Result,
+    event::Event,
     id::{FullId, P2pNode, PublicId},
     location::DstLocation,
-    messages::{BootstrapResponse, MemberKnowledge, Message, Variant},
+    messages::{BootstrapResponse, JoinRejection, JoinRequest, MemberKnowledge, Message, Variant},
     node::{Node, NodeConfig},
     quic_p2p,
+    reachability::{self, ReachabilityProof, REACHABILITY_TIMEOUT},
+    resource_proof::{ResourceProofChallenge, ResourceProofParams},
     rng::{self, MainRng},
     section::EldersInfo,
     section::MIN_AGE,
+    time::Duration,
     utils, ELDER_SIZE,
 };
-use crossbeam_channel::Receiver;
+use crossbeam_channel::{Receiver, Select};
+use fake_clock::FakeClock;
 use itertools::Itertools;
 use mock_quic_p2p::Network;
 use std::{collections::BTreeSet, iter, net::SocketAddr};
@@ -43,6 +49,7 @@ struct Env {
     pub rng: MainRng,
     pub network: Network,
     pub subject: Node,
+    pub event_rx: Receiver<Event>,
     pub other_ids: Vec<(FullId, bls::SecretKeyShare)>,
     pub elders_info: EldersInfo,
     pub candidate: P2pNode,
@@ -71,7 +78,7 @@ impl Env {
         let (full_id, secret_key_share) = full_and_bls_ids.remove(0);
         let other_ids = full_and_bls_ids;
 
-        let (subject, ..) = Node::approved(
+        let (subject, event_rx, _) = Node::approved(
             NodeConfig {
                 full_id: Some(full_id),
                 ..Default::default()
@@ -86,6 +93,7 @@ impl Env {
             rng,
             network,
             subject,
+            event_rx,
             other_ids,
             elders_info,
             candidate,
@@ -157,7 +165,7 @@ impl Env {
         let message = Message::single_src(
             other_full_id,
             DstLocation::Direct,
-            Variant::ParsecRequest(parsec_version, request),
+            Variant::ConsensusRequest(parsec_version, ConsensusRequest::Parsec(request)),
         )
         .unwrap();
 
@@ -180,6 +188,7 @@ impl Env {
                 p2p_node,
                 age: MIN_AGE,
                 their_knowledge: None,
+                previous_name: None,
             })),
         );
     }
@@ -334,6 +343,30 @@ impl Env {
         self.updated_other_ids(new_elders_info)
     }
 
+    fn set_dkg_unresponsive(&mut self, pub_id: PublicId) {
+        self.subject
+            .consensus_engine_mut()
+            .unwrap()
+            .parsec_map_mut()
+            .set_dkg_unresponsive(pub_id)
+    }
+
+    // Advance the time by `duration` and handle all the timeouts that expired.
+    fn advance_time_and_handle_timeouts(&mut self, duration: Duration) {
+        FakeClock::advance_time(duration.as_millis() as u64 + 1);
+
+        loop {
+            let mut sel = Select::new();
+            self.subject.register(&mut sel);
+
+            if let Ok(op_index) = sel.try_ready() {
+                let _ = self.subject.handle_selected_operation(op_index);
+            } else {
+                break;
+            }
+        }
+    }
+
     fn has_unpolled_observations(&self) -> bool {
         self.subject.has_unpolled_observations()
     }
@@ -350,6 +383,36 @@ impl Env {
         Peer::gen(&mut self.rng, &self.network)
     }
 
+    // Generate a joining peer whose reachability the subject is responsible for checking.
+    fn gen_joining_peer_checked_by_subject(&mut self) -> JoiningPeer {
+        loop {
+            let full_id = FullId::gen(&mut self.rng);
+            if reachability::checkers(&self.elders_info, full_id.public_id().name())
+                .into_iter()
+                .any(|checker| checker.public_id() == self.subject.id())
+            {
+                return JoiningPeer::with_full_id(full_id);
+            }
+        }
+    }
+
+    // Send the `JoinRequest` of the peer to the subject and answer its resource challenge, so the
+    // subject proceeds to check the peer is reachable.
+    fn send_join_request_with_resource_proof(&mut self, peer: &mut JoiningPeer) {
+        let addr = peer.our_connection_info();
+
+        let msg = peer.join_request(self.elders_info.version, None).unwrap();
+        test_utils::handle_message(&mut self.subject, addr, msg).unwrap();
+        self.network.poll(&mut self.rng);
+        let challenge = peer.expect_resource_challenge();
+
+        let msg = peer
+            .join_request(self.elders_info.version, Some(&challenge))
+            .unwrap();
+        test_utils::handle_message(&mut self.subject, addr, msg).unwrap();
+        self.network.poll(&mut self.rng);
+    }
+
     // Drop an existing elder and promote an adult to take its place. Drive the whole process to
     // completion by casting all necessary votes and letting them accumulate.
     fn perform_offline_and_promote(
@@ -463,6 +526,84 @@ fn when_accumulate_offline_and_start_dkg_and_section_info_then_node_is_removed_f
     assert!(!env.is_candidate_elder());
 }
 
+#[test]
+fn when_dkg_times_out_then_it_is_restarted_without_unresponsive_participant() {
+    let mut env = Env::new(ELDER_SIZE - 1);
+    let new_info = env.new_elders_info_with_candidate();
+    env.set_dkg_unresponsive(*env.candidate.public_id());
+
+    env.accumulate_online(env.candidate.clone());
+    env.accumulate_start_dkg(&new_info);
+
+    // The DKG with the candidate stalls and then the candidate goes away.
+    env.subject.handle_peer_lost(*env.candidate.peer_addr());
+    env.advance_time_and_handle_timeouts(DKG_TIMEOUT);
+
+    let events: Vec<_> = env.event_rx.try_iter().collect();
+    assert!(events.iter().any(|event| match event {
+        Event::DkgTimedOut {
+            participants,
+            attempt: 1,
+        } => *participants == new_info.new_elders_info.elders.keys().copied().collect(),
+        _ => false,
+    }));
+    assert!(events.iter().any(|event| match event {
+        Event::DkgRestarted {
+            participants,
+            excluded,
+            attempt: 2,
+        } => {
+            *participants == env.elders_info.elders.keys().copied().collect()
+                && *excluded == iter::once(*env.candidate.name()).collect()
+        }
+        _ => false,
+    }));
+
+    // The restarted DKG completes and the section info without the candidate gets voted for.
+    let restarted_info = env.updated_other_ids(EldersInfo::new(
+        env.elders_info.elders.clone(),
+        env.elders_info.prefix,
+        env.elders_info.version + 1,
+    ));
+    env.accumulate_start_dkg(&restarted_info);
+    env.accumulate_section_info_if_vote(&restarted_info);
+    env.accumulate_voted_unconsensused_events();
+
+    assert!(env.is_candidate_member());
+    assert!(!env.is_candidate_elder());
+    assert_eq!(
+        env.subject.our_section().map(|info| info.version),
+        Some(restarted_info.new_elders_info.version)
+    );
+}
+
+#[test]
+fn when_dkg_times_out_with_no_unresponsive_participant_then_it_is_not_restarted() {
+    let mut env = Env::new(ELDER_SIZE - 1);
+    let new_info = env.new_elders_info_with_candidate();
+    env.set_dkg_unresponsive(*env.candidate.public_id());
+
+    env.accumulate_online(env.candidate.clone());
+    env.accumulate_start_dkg(&new_info);
+
+    // The round keeps being waited for, but its timeout is reported only once.
+    for _ in 0..3 {
+        env.advance_time_and_handle_timeouts(DKG_TIMEOUT);
+    }
+
+    let events: Vec<_> = env.event_rx.try_iter().collect();
+    assert_eq!(
+        events
+            .iter()
+            .filter(|event| matches!(event, Event::DkgTimedOut { attempt: 1, .. }))
+            .count(),
+        1
+    );
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, Event::DkgRestarted { .. })));
+}
+
 #[test]
 fn handle_bootstrap() {
     let mut env = Env::new(ELDER_SIZE);
@@ -478,6 +619,7 @@ fn handle_bootstrap() {
     match response {
         BootstrapResponse::Join(elders_info) => assert_eq!(elders_info, env.elders_info),
         BootstrapResponse::Rebootstrap(_) => panic!("Unexpected Rebootstrap response"),
+        BootstrapResponse::Wait(_) => panic!("Unexpected Wait response"),
     }
 }
 
@@ -522,6 +664,89 @@ fn send_genesis_update() {
     assert!(!proof.has_key(&orig_section_key));
 }
 
+#[test]
+fn resource_challenge_round_trip() {
+    let mut env = Env::new(ELDER_SIZE);
+    let mut new_node = env.gen_joining_peer_checked_by_subject();
+    let addr = new_node.our_connection_info();
+    let section_key = *env.subject.section_key().expect("subject is not approved");
+
+    let msg = new_node
+        .join_request(env.elders_info.version, None)
+        .unwrap();
+    test_utils::handle_message(&mut env.subject, addr, msg).unwrap();
+    env.network.poll(&mut env.rng);
+    let challenge = new_node.expect_resource_challenge();
+
+    // A proof solving a challenge for another node is rejected and the challenge sent again.
+    let other_name = *FullId::gen(&mut env.rng).public_id().name();
+    let wrong_challenge =
+        ResourceProofChallenge::new(ResourceProofParams::default(), &section_key, &other_name);
+    let msg = new_node
+        .join_request(env.elders_info.version, Some(&wrong_challenge))
+        .unwrap();
+    test_utils::handle_message(&mut env.subject, addr, msg).unwrap();
+    env.network.poll(&mut env.rng);
+
+    let variants: Vec<_> = new_node.recv_messages().map(|msg| msg.variant).collect();
+    assert!(variants
+        .iter()
+        .any(|variant| *variant == Variant::ResourceChallenge(challenge)));
+    assert!(!variants
+        .iter()
+        .any(|variant| matches!(variant, Variant::EchoRequest(_))));
+
+    // The right proof lets the node proceed to the reachability check.
+    let msg = new_node
+        .join_request(env.elders_info.version, Some(&challenge))
+        .unwrap();
+    test_utils::handle_message(&mut env.subject, addr, msg).unwrap();
+    env.network.poll(&mut env.rng);
+    let _ = new_node.expect_echo_request();
+}
+
+#[test]
+fn reachable_joining_node_receives_reachability_proof() {
+    let mut env = Env::new(ELDER_SIZE);
+    let mut new_node = env.gen_joining_peer_checked_by_subject();
+    let addr = new_node.our_connection_info();
+
+    env.send_join_request_with_resource_proof(&mut new_node);
+    let nonce = new_node.expect_echo_request();
+
+    let msg = new_node.echo_response(nonce).unwrap();
+    test_utils::handle_message(&mut env.subject, addr, msg).unwrap();
+    env.network.poll(&mut env.rng);
+
+    let proof = new_node.expect_reachability_proof();
+    assert_eq!(proof.elder(), env.subject.id());
+    assert!(proof.verify(
+        env.subject.section_key().expect("subject is not approved"),
+        new_node.public_id().name(),
+        &addr
+    ));
+}
+
+#[test]
+fn unreachable_joining_node_is_rejected() {
+    let mut env = Env::new(ELDER_SIZE);
+    let mut new_node = env.gen_joining_peer_checked_by_subject();
+    let addr = new_node.our_connection_info();
+
+    env.send_join_request_with_resource_proof(&mut new_node);
+    let _ = new_node.expect_echo_request();
+
+    // The node never answers the echo.
+    env.advance_time_and_handle_timeouts(REACHABILITY_TIMEOUT);
+    env.network.poll(&mut env.rng);
+
+    assert_eq!(
+        new_node.expect_join_rejected(),
+        JoinRejection::Unreachable(addr)
+    );
+    assert!(!env.has_unpolled_observations());
+}
+
 struct JoiningPeer {
     network_service: quic_p2p::QuicP2p,
     network_event_rx: Receiver<quic_p2p::Event>,
@@ -530,6 +755,10 @@ struct JoiningPeer {
 
 impl JoiningPeer {
     fn new(rng: &mut MainRng) -> Self {
+        Self::with_full_id(FullId::gen(rng))
+    }
+
+    fn with_full_id(full_id: FullId) -> Self {
         let (network_event_tx, network_event_rx) = {
             let (node_tx, node_rx) = crossbeam_channel::unbounded();
             let (client_tx, _) = crossbeam_channel::unbounded();
@@ -540,7 +769,7 @@ impl JoiningPeer {
         Self {
             network_service,
             network_event_rx,
-            full_id: FullId::gen(rng),
+            full_id,
         }
     }
 
@@ -557,6 +786,65 @@ impl JoiningPeer {
         Message::single_src(&self.full_id, DstLocation::Direct, variant)
     }
 
+    fn join_request(
+        &self,
+        elders_version: u64,
+        challenge: Option<&ResourceProofChallenge>,
+    ) -> Result<Message> {
+        let variant = Variant::JoinRequest(Box::new(JoinRequest {
+            elders_version,
+            relocate_payload: None,
+            rejoin_payload: None,
+            resource_proof: challenge.map(ResourceProofChallenge::solve),
+            reachability_proofs: Vec::new(),
+        }));
+        Message::single_src(&self.full_id, DstLocation::Direct, variant)
+    }
+
+    fn echo_response(&self, nonce: u64) -> Result<Message> {
+        Message::single_src(
+            &self.full_id,
+            DstLocation::Direct,
+            Variant::EchoResponse(nonce),
+        )
+    }
+
+    fn expect_resource_challenge(&self) -> ResourceProofChallenge {
+        self.recv_messages()
+            .find_map(|msg| match msg.variant {
+                Variant::ResourceChallenge(challenge) => Some(challenge),
+                _ => None,
+            })
+            .expect("ResourceChallenge not received")
+    }
+
+    fn expect_echo_request(&self) -> u64 {
+        self.recv_messages()
+            .find_map(|msg| match msg.variant {
+                Variant::EchoRequest(nonce) => Some(nonce),
+                _ => None,
+            })
+            .expect("EchoRequest not received")
+    }
+
+    fn expect_reachability_proof(&self) -> ReachabilityProof {
+        self.recv_messages()
+            .find_map(|msg| match msg.variant {
+                Variant::ReachabilityProof(proof) => Some(proof),
+                _ => None,
+            })
+            .expect("ReachabilityProof not received")
+    }
+
+    fn expect_join_rejected(&self) -> JoinRejection {
+        self.recv_messages()
+            .find_map(|msg| match msg.variant {
+                Variant::JoinRejected(rejection) => Some(rejection),
+                _ => None,
+            })
+            .expect("JoinRejected not received")
+    }
+
     fn expect_bootstrap_response(&self) -> BootstrapResponse {
         self.recv_messages()
             .find_map(|msg| match msg.variant {
@@ -10,15 +10,20 @@ use crate::{
     consensus::{ConsensusEngine, GenesisPrefixInfo},
     id::FullId,
     message_filter::MessageFilter,
-    messages::QueuedMessage,
+    messages::{QueuedMessage, RouteConfig},
     network_params::NetworkParams,
-    section::{SectionKeysProvider, SharedState, SplitCache},
+    node::BootstrapConfig,
+    resource_proof::ResourceProofParams,
+    section::{
+        AdmissionPolicy, DiversityRules, ElderSelection, RelocationPolicy, SectionKeysProvider,
+        SharedState, SplitCache, SplitPolicy,
+    },
     signature_accumulator::SignatureAccumulator,
     transport::Transport,
     TransportEvent,
 };
 use crossbeam_channel as mpmc;
-use std::collections::VecDeque;
+use std::{collections::VecDeque, sync::Arc};
 
 /// A type that wraps the internal state of a node while it is paused in order to be upgraded and/or
 /// restarted. A value of this type is obtained by pausing a node and can be then used to resume
@@ -30,6 +35,16 @@ use std::collections::VecDeque;
 // version >= X.
 pub struct PausedState {
     pub(super) network_params: NetworkParams,
+    pub(super) split_policy: Arc<dyn SplitPolicy>,
+    pub(super) elder_selection: Arc<dyn ElderSelection>,
+    pub(super) relocation_policy: Arc<dyn RelocationPolicy>,
+    pub(super) admission_policy: Arc<dyn AdmissionPolicy>,
+    pub(super) resource_proof: ResourceProofParams,
+    pub(super) diversity: DiversityRules,
+    pub(super) ban_duration: u64,
+    pub(super) ban_by_ip: bool,
+    pub(super) bootstrap_config: BootstrapConfig,
+    pub(super) route_config: RouteConfig,
     pub(super) consensus_engine: ConsensusEngine,
     pub(super) shared_state: SharedState,
     pub(super) section_keys_provider: SectionKeysProvider,
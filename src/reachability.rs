@@ -0,0 +1,116 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Reachability check of new nodes joining a section.
+//!
+//! A couple of the elders of the section - the ones closest to the name of the joining node -
+//! open new connections back to the address of the node and exchange a signed echo with it. Each
+//! of them that succeeds sends the node a proof, which the node then includes in its
+//! `JoinRequest`. The elders vote the node online only with proofs from all these elders, so a node
+//! the rest of the network can't reach (e.g. one behind a NAT) doesn't join only to fail every
+//! delivery. An elder that fails to reach the node sends it a rejection, and the node gives up
+//! joining once all these elders rejected it.
+
+use crate::{
+    crypto::signing::Signature,
+    error::Result,
+    id::{FullId, P2pNode, PublicId},
+    section::EldersInfo,
+    time::Duration,
+    xor_space::{XorName, Xorable},
+};
+use bincode::serialize;
+use std::{
+    fmt::{self, Debug, Formatter},
+    net::SocketAddr,
+};
+
+/// Number of elders that check the reachability of a joining node.
+pub const REACHABILITY_CHECKERS: usize = 2;
+/// Time an elder waits for the echo of a joining node before rejecting it as unreachable.
+pub const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Returns the elders that check the reachability of the joining node with the given name.
+pub fn checkers<'a>(elders_info: &'a EldersInfo, name: &XorName) -> Vec<&'a P2pNode> {
+    let mut elders: Vec<_> = elders_info.elders.values().collect();
+    elders.sort_by(|lhs, rhs| name.cmp_distance(lhs.name(), rhs.name()));
+    elders.truncate(REACHABILITY_CHECKERS);
+    elders
+}
+
+/// Proof given by an elder to a joining node that it could reach the node at its address.
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ReachabilityProof {
+    elder: PublicId,
+    signature: Signature,
+}
+
+impl ReachabilityProof {
+    /// Creates the proof that the node with the given name was reachable at `addr`. The proof is
+    /// bound to the current key of the section so it can't be reused once the key changes.
+    pub fn new(
+        full_id: &FullId,
+        section_key: &bls::PublicKey,
+        name: &XorName,
+        addr: &SocketAddr,
+    ) -> Result<Self> {
+        let signature = full_id.sign(&serialize(&(section_key, name, addr))?);
+
+        Ok(Self {
+            elder: *full_id.public_id(),
+            signature,
+        })
+    }
+
+    /// The elder that gave the proof.
+    pub fn elder(&self) -> &PublicId {
+        &self.elder
+    }
+
+    /// Verifies that the proof is for the node with the given name reachable at `addr`.
+    pub fn verify(&self, section_key: &bls::PublicKey, name: &XorName, addr: &SocketAddr) -> bool {
+        match serialize(&(section_key, name, addr)) {
+            Ok(bytes) => self.elder.verify(&bytes, &self.signature),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Debug for ReachabilityProof {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "ReachabilityProof({})", self.elder.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rng, section::gen_secret_key};
+    use rand::Rng;
+
+    #[test]
+    fn create_and_verify() {
+        let mut rng = rng::new();
+        let full_id = FullId::gen(&mut rng);
+        let section_key = gen_secret_key(&mut rng).public_key();
+        let name: XorName = rng.gen();
+        let addr = SocketAddr::from(([192, 0, 2, 1], 1234));
+
+        let proof = ReachabilityProof::new(&full_id, &section_key, &name, &addr).unwrap();
+        assert_eq!(proof.elder(), full_id.public_id());
+        assert!(proof.verify(&section_key, &name, &addr));
+
+        // The proof is bound to the name, the address and the section key.
+        let other_name: XorName = rng.gen();
+        assert!(!proof.verify(&section_key, &other_name, &addr));
+        let other_addr = SocketAddr::from(([192, 0, 2, 2], 1234));
+        assert!(!proof.verify(&section_key, &name, &other_addr));
+        let other_key = gen_secret_key(&mut rng).public_key();
+        assert!(!proof.verify(&other_key, &name, &addr));
+    }
+}
@@ -127,31 +127,9 @@ impl RelocatePayload {
     }
 }
 
-#[cfg(not(feature = "mock_base"))]
-pub fn compute_destination(
-    _src_prefix: &Prefix<XorName>,
-    relocated_name: &XorName,
-    trigger_name: &XorName,
-) -> XorName {
-    compute_destination_without_override(relocated_name, trigger_name)
-}
-
-#[cfg(feature = "mock_base")]
-pub fn compute_destination(
-    src_prefix: &Prefix<XorName>,
-    relocated_name: &XorName,
-    trigger_name: &XorName,
-) -> XorName {
-    self::overrides::get(
-        src_prefix,
-        compute_destination_without_override(relocated_name, trigger_name),
-    )
-}
-
-fn compute_destination_without_override(
-    relocated_name: &XorName,
-    trigger_name: &XorName,
-) -> XorName {
+/// Computes the relocation destination by hashing the names of the relocated and the trigger
+/// nodes.
+pub fn compute_destination(relocated_name: &XorName, trigger_name: &XorName) -> XorName {
     let mut buffer = [0; 2 * XOR_NAME_LEN];
     buffer[..XOR_NAME_LEN].copy_from_slice(&relocated_name.0);
     buffer[XOR_NAME_LEN..].copy_from_slice(&trigger_name.0);
@@ -159,6 +137,16 @@ fn compute_destination_without_override(
     XorName(crypto::sha3_256(&buffer))
 }
 
+#[cfg(not(feature = "mock_base"))]
+pub fn override_destination(_src_prefix: &Prefix<XorName>, destination: XorName) -> XorName {
+    destination
+}
+
+#[cfg(feature = "mock_base")]
+pub fn override_destination(src_prefix: &Prefix<XorName>, destination: XorName) -> XorName {
+    self::overrides::get(src_prefix, destination)
+}
+
 #[cfg(feature = "mock_base")]
 mod overrides {
     use crate::{Prefix, XorName};
@@ -0,0 +1,212 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Resource proof required from new nodes joining a section.
+//!
+//! The proof consists of a computation (finding a nonce whose hash together with the challenge
+//! seed has enough leading zero bits) and of a payload derived from the seed that the joining
+//! node must send, proving its bandwidth. It makes flooding a section with freshly generated ids
+//! expensive.
+
+use crate::{
+    crypto::{self, Digest256},
+    xor_space::XorName,
+};
+use hex_fmt::HexFmt;
+use std::fmt::{self, Debug, Formatter};
+
+/// Maximum difficulty a joining node accepts to solve. The node solves the challenge on its event
+/// loop, so this also bounds how long it stops handling other messages: about a million hashes,
+/// which is around a second.
+const MAX_DIFFICULTY: u8 = 20;
+/// Maximum payload size a joining node accepts to send.
+const MAX_DATA_SIZE: usize = 1024 * 1024;
+
+/// Parameters of the resource proof the elders require from new nodes joining their section.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResourceProofParams {
+    /// Number of leading zero bits the hash of the solution must have. Every additional bit doubles
+    /// the expected amount of computation. Joining nodes refuse challenges above 20.
+    pub difficulty: u8,
+    /// Size in bytes of the payload the joining node must send along with the solution. Joining
+    /// nodes refuse challenges above 1 MiB.
+    pub data_size: usize,
+}
+
+impl ResourceProofParams {
+    /// Parameters that make the proof trivial to produce, for tests which join many nodes.
+    pub fn cheap() -> Self {
+        Self {
+            difficulty: 1,
+            data_size: 64,
+        }
+    }
+}
+
+impl Default for ResourceProofParams {
+    #[cfg(not(feature = "mock_base"))]
+    fn default() -> Self {
+        Self {
+            difficulty: 16,
+            data_size: 256 * 1024,
+        }
+    }
+
+    #[cfg(feature = "mock_base")]
+    fn default() -> Self {
+        Self::cheap()
+    }
+}
+
+/// Challenge sent by an elder to a node trying to join its section.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ResourceProofChallenge {
+    seed: Digest256,
+    difficulty: u8,
+    data_size: usize,
+}
+
+impl ResourceProofChallenge {
+    /// Creates the challenge for the joining node with the given name. The seed is derived from
+    /// the current key of the section so that all its elders issue the same challenge and a proof
+    /// can't be reused once the key changes.
+    pub fn new(params: ResourceProofParams, section_key: &bls::PublicKey, name: &XorName) -> Self {
+        let mut buffer = section_key.to_bytes().to_vec();
+        buffer.extend_from_slice(&name.0);
+
+        Self {
+            seed: crypto::sha3_256(&buffer),
+            difficulty: params.difficulty,
+            data_size: params.data_size,
+        }
+    }
+
+    /// Returns whether the challenge is within the limits we are willing to solve.
+    pub fn is_acceptable(&self) -> bool {
+        self.difficulty <= MAX_DIFFICULTY && self.data_size <= MAX_DATA_SIZE
+    }
+
+    /// Returns whether the challenge has the same seed as the one `proof` solves.
+    pub fn is_solved_by(&self, proof: &ResourceProof) -> bool {
+        self.seed == proof.seed
+    }
+
+    /// Solves the challenge. This is deliberately expensive.
+    pub fn solve(&self) -> ResourceProof {
+        let nonce = (0..)
+            .find(|nonce| leading_zero_bits(&self.hash(*nonce)) >= u32::from(self.difficulty))
+            .expect("no resource proof solution found");
+
+        ResourceProof {
+            seed: self.seed,
+            nonce,
+            data: self.data(),
+        }
+    }
+
+    /// Verifies that `proof` solves the challenge.
+    pub fn verify(&self, proof: &ResourceProof) -> bool {
+        self.is_solved_by(proof)
+            && leading_zero_bits(&self.hash(proof.nonce)) >= u32::from(self.difficulty)
+            && proof.data == self.data()
+    }
+
+    fn hash(&self, nonce: u64) -> Digest256 {
+        let mut buffer = self.seed.to_vec();
+        buffer.extend_from_slice(&nonce.to_le_bytes());
+        crypto::sha3_256(&buffer)
+    }
+
+    // The payload the joining node has to send: a chain of hashes starting at the seed.
+    fn data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.data_size);
+        let mut block = self.seed;
+
+        while data.len() < self.data_size {
+            block = crypto::sha3_256(&block);
+            let len = block.len().min(self.data_size - data.len());
+            data.extend_from_slice(&block[..len]);
+        }
+
+        data
+    }
+}
+
+impl Debug for ResourceProofChallenge {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("ResourceProofChallenge")
+            .field("seed", &HexFmt(&self.seed))
+            .field("difficulty", &self.difficulty)
+            .field("data_size", &self.data_size)
+            .finish()
+    }
+}
+
+/// Solution of a `ResourceProofChallenge`, sent by the joining node in its `JoinRequest`.
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ResourceProof {
+    seed: Digest256,
+    nonce: u64,
+    data: Vec<u8>,
+}
+
+impl Debug for ResourceProof {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("ResourceProof")
+            .field("seed", &HexFmt(&self.seed))
+            .field("nonce", &self.nonce)
+            .field("data_size", &self.data.len())
+            .finish()
+    }
+}
+
+fn leading_zero_bits(digest: &Digest256) -> u32 {
+    let mut count = 0;
+    for byte in digest {
+        count += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rng, section::gen_secret_key};
+    use rand::Rng;
+
+    #[test]
+    fn solve_and_verify() {
+        let mut rng = rng::new();
+        let section_key = gen_secret_key(&mut rng).public_key();
+        let name: XorName = rng.gen();
+        let params = ResourceProofParams {
+            difficulty: 8,
+            data_size: 100,
+        };
+
+        let challenge = ResourceProofChallenge::new(params, &section_key, &name);
+        let proof = challenge.solve();
+        assert_eq!(proof.data.len(), 100);
+        assert!(challenge.verify(&proof));
+
+        // The proof is bound to the name of the joining node...
+        let other_name: XorName = rng.gen();
+        let other_challenge = ResourceProofChallenge::new(params, &section_key, &other_name);
+        assert!(!other_challenge.verify(&proof));
+
+        // ...and requires the whole payload.
+        let mut truncated = proof;
+        let _ = truncated.data.pop();
+        assert!(!challenge.verify(&truncated));
+    }
+}
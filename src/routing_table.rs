@@ -13,9 +13,15 @@ use crate::{
     id::{P2pNode, PublicId},
     location::DstLocation,
     section::{SectionMap, SectionMembers},
+    time::Duration,
     xor_space::{XorName, Xorable},
 };
 use itertools::Itertools;
+use std::cmp::Ordering;
+
+// Latencies in the same multiple of this are considered equal when choosing delivery targets, so
+// the choice doesn't flap on measurement noise.
+const RTT_RESOLUTION: Duration = Duration::from_millis(10);
 
 /// Returns the delivery group size based on the section size `n`
 pub const fn delivery_group_size(n: usize) -> usize {
@@ -37,12 +43,19 @@ pub const fn delivery_group_size(n: usize) -> usize {
 ///     - if our name *is* the destination, returns an empty set; otherwise
 ///     - if the destination name is an entry in the routing table, returns it; otherwise
 ///     - returns the `N/3` closest members of the RT to the target
-pub fn delivery_targets(
+///
+/// Among the members of the same section, the ones with the lowest latency according to `rtt` are
+/// preferred, so they make up the initial targets.
+pub fn delivery_targets<R>(
     dst: &DstLocation,
     our_id: &PublicId,
     our_members: &SectionMembers,
     sections: &SectionMap,
-) -> Result<(Vec<P2pNode>, usize)> {
+    rtt: R,
+) -> Result<(Vec<P2pNode>, usize)>
+where
+    R: Fn(&P2pNode) -> Option<Duration>,
+{
     if !sections.is_elder(our_id.name()) {
         // We are not Elder - return all the elders of our section, so the message can be properly
         // relayed through them.
@@ -60,7 +73,7 @@ pub fn delivery_targets(
                 return Ok((vec![node.clone()], 1));
             }
 
-            candidates(target_name, our_id, sections)?
+            candidates(target_name, our_id, sections, &rtt)?
         }
         DstLocation::Section(target_name) => {
             let (prefix, section) = sections.closest(target_name);
@@ -79,7 +92,7 @@ pub fn delivery_targets(
                 return Ok((section, dg_size));
             }
 
-            candidates(target_name, our_id, sections)?
+            candidates(target_name, our_id, sections, &rtt)?
         }
         DstLocation::Direct => return Err(RoutingError::CannotRoute),
     };
@@ -88,11 +101,15 @@ pub fn delivery_targets(
 }
 
 // Obtain the delivery group candidates for this target
-fn candidates(
+fn candidates<R>(
     target_name: &XorName,
     our_id: &PublicId,
     sections: &SectionMap,
-) -> Result<(Vec<P2pNode>, usize)> {
+    rtt: R,
+) -> Result<(Vec<P2pNode>, usize)>
+where
+    R: Fn(&P2pNode) -> Option<Duration>,
+{
     let filtered_sections = sections
         .sorted_by_distance_to(target_name)
         .into_iter()
@@ -101,12 +118,12 @@ fn candidates(
     let mut dg_size = 0;
     let mut nodes_to_send = Vec::new();
     for (idx, (prefix, len, connected)) in filtered_sections.enumerate() {
-        nodes_to_send.extend(connected.cloned());
+        nodes_to_send.extend(connected.map(|node| (idx, node.clone())));
         dg_size = delivery_group_size(len);
 
         if *prefix == sections.our().prefix {
             // Send to all connected targets so they can forward the message
-            nodes_to_send.retain(|node| node.name() != our_id.name());
+            nodes_to_send.retain(|(_, node)| node.name() != our_id.name());
             dg_size = nodes_to_send.len();
             break;
         }
@@ -115,7 +132,15 @@ fn candidates(
             break;
         }
     }
-    nodes_to_send.sort_by(|lhs, rhs| target_name.cmp_distance(lhs.name(), rhs.name()));
+
+    // Closer sections first, then the lowest latency within each section, then the closest names.
+    nodes_to_send.sort_by(|(lhs_idx, lhs), (rhs_idx, rhs)| {
+        lhs_idx
+            .cmp(rhs_idx)
+            .then_with(|| cmp_rtt(rtt(lhs), rtt(rhs)))
+            .then_with(|| target_name.cmp_distance(lhs.name(), rhs.name()))
+    });
+    let nodes_to_send: Vec<_> = nodes_to_send.into_iter().map(|(_, node)| node).collect();
 
     if dg_size > 0 && nodes_to_send.len() >= dg_size {
         Ok((nodes_to_send, dg_size))
@@ -124,6 +149,19 @@ fn candidates(
     }
 }
 
+// Compares latencies at `RTT_RESOLUTION`. The unmeasured ones come last.
+fn cmp_rtt(lhs: Option<Duration>, rhs: Option<Duration>) -> Ordering {
+    let resolution = RTT_RESOLUTION.as_micros();
+    match (lhs, rhs) {
+        (Some(lhs), Some(rhs)) => {
+            (lhs.as_micros() / resolution).cmp(&(rhs.as_micros() / resolution))
+        }
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
 // Returns a `P2pNode` for a known node.
 fn get_p2p_node<'a>(
     name: &XorName,
@@ -160,3 +198,89 @@ where
     list.truncate(delivery_group_size(list.len()));
     list
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        id::FullId,
+        rng::{self, MainRng},
+        section::{gen_secret_key, EldersInfo},
+        xor_space::Prefix,
+    };
+    use rand::Rng;
+    use std::{collections::HashMap, iter};
+
+    #[test]
+    fn cmp_rtt_at_resolution() {
+        let ms = Duration::from_millis;
+
+        assert_eq!(cmp_rtt(Some(ms(3)), Some(ms(7))), Ordering::Equal);
+        assert_eq!(cmp_rtt(Some(ms(7)), Some(ms(13))), Ordering::Less);
+        assert_eq!(cmp_rtt(Some(ms(25)), Some(ms(13))), Ordering::Greater);
+        assert_eq!(cmp_rtt(Some(ms(500)), None), Ordering::Less);
+        assert_eq!(cmp_rtt(None, Some(ms(500))), Ordering::Greater);
+        assert_eq!(cmp_rtt(None, None), Ordering::Equal);
+    }
+
+    #[test]
+    fn candidates_prefer_lowest_latency_then_closest_names() {
+        let mut rng = rng::new();
+        let our_prefix: Prefix<XorName> = "0".parse().unwrap();
+        let their_prefix: Prefix<XorName> = "1".parse().unwrap();
+
+        let our_info = gen_elders_info(&mut rng, our_prefix);
+        let our_id = *our_info.elders.values().next().unwrap().public_id();
+        let their_info = gen_elders_info(&mut rng, their_prefix);
+
+        let mut sections = SectionMap::new(our_info, gen_secret_key(&mut rng).public_key());
+        sections.add_neighbour(their_info.clone());
+
+        let target = their_prefix.substituted_in(rng.gen());
+
+        // Their elders sorted by distance to the target. Measure the two farthest ones, the
+        // farthest one lower than the other, and keep the rest unmeasured.
+        let by_distance: Vec<_> = their_info
+            .elders
+            .values()
+            .sorted_by(|lhs, rhs| target.cmp_distance(lhs.name(), rhs.name()))
+            .collect();
+        let farthest = by_distance[by_distance.len() - 1];
+        let second_farthest = by_distance[by_distance.len() - 2];
+        let rtts: HashMap<_, _> = vec![
+            (*farthest.name(), Duration::from_millis(5)),
+            (*second_farthest.name(), Duration::from_millis(45)),
+        ]
+        .into_iter()
+        .collect();
+
+        let (targets, dg_size) = candidates(&target, &our_id, &sections, |node| {
+            rtts.get(node.name()).copied()
+        })
+        .unwrap();
+
+        assert_eq!(dg_size, delivery_group_size(their_info.elders.len()));
+
+        let expected: Vec<_> = iter::once(farthest)
+            .chain(iter::once(second_farthest))
+            .chain(by_distance[..by_distance.len() - 2].iter().copied())
+            .map(P2pNode::name)
+            .collect();
+        let actual: Vec<_> = targets.iter().map(P2pNode::name).collect();
+        assert_eq!(actual, expected);
+    }
+
+    fn gen_elders_info(rng: &mut MainRng, prefix: Prefix<XorName>) -> EldersInfo {
+        let members = (0..5)
+            .map(|index| {
+                let pub_id = *FullId::within_range(rng, &prefix.range_inclusive()).public_id();
+                (
+                    *pub_id.name(),
+                    P2pNode::new(pub_id, ([127, 0, 0, 1], 9000 + index).into()),
+                )
+            })
+            .collect();
+
+        EldersInfo::new(members, prefix, 0)
+    }
+}
@@ -0,0 +1,89 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    network_params::NetworkParams,
+    xor_space::{Prefix, XorName},
+};
+use std::fmt::Debug;
+
+/// Decides whether a section accepts new members.
+///
+/// Every elder evaluates the policy on its own and votes when its outcome differs from the current
+/// admission state of the section. The state changes only once a quorum of the elders agrees, so
+/// elders that temporarily disagree (for example because they haven't processed the latest churn
+/// yet) don't cause the section to flip back and forth. The new nodes asking to join meanwhile are
+/// queued by the elders and admitted in order once the section accepts new members again.
+pub trait AdmissionPolicy: Debug + Send + Sync {
+    /// Returns whether the section described by `occupancy` should accept new members.
+    fn is_accepting(&self, network_params: &NetworkParams, occupancy: &SectionOccupancy) -> bool;
+}
+
+/// Occupancy of a section considered for admitting new members.
+#[derive(Clone, Copy, Debug)]
+pub struct SectionOccupancy {
+    /// Prefix of the section.
+    pub prefix: Prefix<XorName>,
+    /// Number of joined members of the section, including infants.
+    pub members: usize,
+    /// Number of joined members of the section that are not mature yet.
+    pub infants: usize,
+}
+
+/// The default admission policy: always accept new members.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultAdmissionPolicy;
+
+impl AdmissionPolicy for DefaultAdmissionPolicy {
+    fn is_accepting(&self, _network_params: &NetworkParams, _occupancy: &SectionOccupancy) -> bool {
+        true
+    }
+}
+
+/// Admission policy that stops accepting new members once the section has `max_members` members
+/// or `max_infants` infants. The section normally splits before reaching `max_members`, so the
+/// limit applies mostly to sections that can't split yet because their members are not mature.
+#[derive(Clone, Copy, Debug)]
+pub struct LimitedAdmissionPolicy {
+    /// Maximum number of joined members of the section.
+    pub max_members: usize,
+    /// Maximum number of joined infants of the section.
+    pub max_infants: usize,
+}
+
+impl AdmissionPolicy for LimitedAdmissionPolicy {
+    fn is_accepting(&self, _network_params: &NetworkParams, occupancy: &SectionOccupancy) -> bool {
+        occupancy.members < self.max_members && occupancy.infants < self.max_infants
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn occupancy(members: usize, infants: usize) -> SectionOccupancy {
+        SectionOccupancy {
+            prefix: Prefix::default(),
+            members,
+            infants,
+        }
+    }
+
+    #[test]
+    fn limited_policy() {
+        let params = NetworkParams::default();
+        let policy = LimitedAdmissionPolicy {
+            max_members: 10,
+            max_infants: 3,
+        };
+
+        assert!(policy.is_accepting(&params, &occupancy(9, 2)));
+        assert!(!policy.is_accepting(&params, &occupancy(10, 2)));
+        assert!(!policy.is_accepting(&params, &occupancy(9, 3)));
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{id::PublicId, xor_space::XorName};
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, SocketAddr},
+};
+
+/// Default number of membership changes of a section during which the peers it banned are refused.
+pub const DEFAULT_BAN_DURATION: u64 = 256;
+
+/// Why a peer was banned.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum BanReason {
+    /// The consensus algorithm proved the peer malicious, for example for voting for conflicting
+    /// events.
+    Equivocation,
+    /// The peer tried to join with relocation details not signed by the section it claims to be
+    /// relocated from.
+    ForgedRelocation,
+}
+
+/// A peer banned by a section.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct BanDetails {
+    /// The id of the banned peer.
+    pub pub_id: PublicId,
+    /// The address the banned peer was connected from. With `NodeConfig::ban_by_ip`, all the peers
+    /// with the same IP address are refused too.
+    pub peer_addr: SocketAddr,
+    /// Why the peer was banned.
+    pub reason: BanReason,
+}
+
+/// List of the peers banned by our section, agreed on by its elders.
+///
+/// Bans expire after a number of membership changes (joins and leaves) of our section rather than
+/// after some time, so that all the elders agree on when they expire.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct BanList {
+    // Banned peers by name, together with the membership version of our section when they were
+    // banned.
+    entries: BTreeMap<XorName, (BanDetails, u64)>,
+}
+
+impl BanList {
+    /// Adds a ban at the given membership version and forgets the bans that expired by then.
+    /// Returns whether the peer wasn't banned yet.
+    pub fn insert(&mut self, details: BanDetails, version: u64, duration: u64) -> bool {
+        self.prune(version, duration);
+
+        if self.entries.contains_key(details.pub_id.name()) {
+            return false;
+        }
+
+        let _ = self
+            .entries
+            .insert(*details.pub_id.name(), (details, version));
+        true
+    }
+
+    /// Returns the ban of the peer with the given name or, if given, IP address, if it is still in
+    /// force at the given membership version.
+    pub fn find(
+        &self,
+        name: &XorName,
+        ip: Option<IpAddr>,
+        version: u64,
+        duration: u64,
+    ) -> Option<&BanDetails> {
+        self.active(version, duration)
+            .find(|details| details.pub_id.name() == name || Some(details.peer_addr.ip()) == ip)
+    }
+
+    /// Returns the bans still in force at the given membership version.
+    pub fn active(&self, version: u64, duration: u64) -> impl Iterator<Item = &BanDetails> {
+        self.entries
+            .values()
+            .filter(move |(_, since)| version.wrapping_sub(*since) < duration)
+            .map(|(details, _)| details)
+    }
+
+    fn prune(&mut self, version: u64, duration: u64) {
+        self.entries
+            .retain(|_, (_, since)| version.wrapping_sub(*since) < duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{id::FullId, rng};
+
+    fn gen_details(ip: [u8; 4]) -> BanDetails {
+        let mut rng = rng::new();
+        BanDetails {
+            pub_id: *FullId::gen(&mut rng).public_id(),
+            peer_addr: (ip, 9000).into(),
+            reason: BanReason::Equivocation,
+        }
+    }
+
+    #[test]
+    fn find_by_name_or_ip_until_expired() {
+        let mut list = BanList::default();
+        let banned = gen_details([10, 0, 0, 1]);
+        let other = gen_details([10, 0, 0, 2]);
+        let other_ip = other.peer_addr.ip();
+
+        assert!(list.insert(banned.clone(), 5, 3));
+        assert!(!list.insert(banned.clone(), 6, 3));
+
+        let name = banned.pub_id.name();
+        assert_eq!(list.find(name, Some(other_ip), 7, 3), Some(&banned));
+        assert_eq!(
+            list.find(other.pub_id.name(), Some(banned.peer_addr.ip()), 7, 3),
+            Some(&banned)
+        );
+        assert_eq!(list.find(other.pub_id.name(), Some(other_ip), 7, 3), None);
+
+        // Other peers behind the banned IP address are refused only if asked to.
+        assert_eq!(list.find(other.pub_id.name(), None, 7, 3), None);
+
+        // The ban expires three membership changes after it was issued.
+        assert_eq!(list.find(name, Some(other_ip), 8, 3), None);
+        assert!(list.insert(banned, 8, 3));
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::id::P2pNode;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+/// Limits on how many nodes of a section can share a network address, so that a single operator
+/// can't easily control a quorum of the elders.
+///
+/// A subnet is a /24 network for IPv4 addresses and a /48 network for IPv6 addresses. `None`
+/// means no limit.
+///
+/// The rules are applied independently by every elder of the section, so they should be identical
+/// for all the nodes in the network, otherwise the elders won't agree on the elder selection.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DiversityRules {
+    /// Maximum number of elders of a section with the same IP address.
+    pub max_elders_per_ip: Option<usize>,
+    /// Maximum number of elders of a section in the same subnet.
+    pub max_elders_per_subnet: Option<usize>,
+    /// Maximum number of members of a section with the same IP address. New nodes exceeding it are
+    /// not admitted.
+    pub max_members_per_ip: Option<usize>,
+    /// Maximum number of members of a section in the same subnet. New nodes exceeding it are not
+    /// admitted.
+    pub max_members_per_subnet: Option<usize>,
+}
+
+impl DiversityRules {
+    /// Returns whether `candidate` can be an elder together with `elders`.
+    pub fn allows_elder<'a, I>(&self, elders: I, candidate: &P2pNode) -> bool
+    where
+        I: IntoIterator<Item = &'a P2pNode>,
+    {
+        allows(
+            elders,
+            candidate,
+            self.max_elders_per_ip,
+            self.max_elders_per_subnet,
+        )
+    }
+
+    /// Returns whether `candidate` can be a member together with `members`.
+    pub fn allows_member<'a, I>(&self, members: I, candidate: &P2pNode) -> bool
+    where
+        I: IntoIterator<Item = &'a P2pNode>,
+    {
+        allows(
+            members,
+            candidate,
+            self.max_members_per_ip,
+            self.max_members_per_subnet,
+        )
+    }
+
+    /// Returns all the violations of the rules by the given elders and members.
+    pub fn violations<'a, E, M>(&self, elders: E, members: M) -> Vec<DiversityViolation>
+    where
+        E: IntoIterator<Item = &'a P2pNode>,
+        M: IntoIterator<Item = &'a P2pNode>,
+    {
+        let mut violations = Vec::new();
+        let scopes = [
+            (
+                DiversityScope::Elders,
+                count_groups(elders),
+                self.max_elders_per_ip,
+                self.max_elders_per_subnet,
+            ),
+            (
+                DiversityScope::Members,
+                count_groups(members),
+                self.max_members_per_ip,
+                self.max_members_per_subnet,
+            ),
+        ];
+
+        for (scope, counts, max_per_ip, max_per_subnet) in &scopes {
+            for (group, count) in counts {
+                let limit = match group {
+                    AddressGroup::Ip(_) => max_per_ip,
+                    AddressGroup::Subnet(_) => max_per_subnet,
+                };
+
+                match limit {
+                    Some(limit) if count > limit => violations.push(DiversityViolation {
+                        scope: *scope,
+                        group: *group,
+                        count: *count,
+                        limit: *limit,
+                    }),
+                    _ => (),
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+/// Which nodes of a section a `DiversityViolation` concerns.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DiversityScope {
+    /// The elders of the section.
+    Elders,
+    /// All the joined members of the section.
+    Members,
+}
+
+/// Set of network addresses the `DiversityRules` limit the number of nodes in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum AddressGroup {
+    /// Single IP address.
+    Ip(IpAddr),
+    /// Subnet, represented by its network address.
+    Subnet(IpAddr),
+}
+
+impl AddressGroup {
+    fn subnet(ip: IpAddr) -> Self {
+        let network = match ip {
+            IpAddr::V4(ip) => {
+                let [a, b, c, _] = ip.octets();
+                IpAddr::V4(Ipv4Addr::new(a, b, c, 0))
+            }
+            IpAddr::V6(ip) => {
+                let segments = ip.segments();
+                IpAddr::V6(Ipv6Addr::new(
+                    segments[0],
+                    segments[1],
+                    segments[2],
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                ))
+            }
+        };
+
+        Self::Subnet(network)
+    }
+}
+
+impl Display for AddressGroup {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Ip(ip) => write!(formatter, "{}", ip),
+            Self::Subnet(IpAddr::V4(network)) => write!(formatter, "{}/24", network),
+            Self::Subnet(IpAddr::V6(network)) => write!(formatter, "{}/48", network),
+        }
+    }
+}
+
+/// Group of network addresses shared by more nodes of a section than the `DiversityRules` allow.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DiversityViolation {
+    /// Which nodes of the section violate the rules.
+    pub scope: DiversityScope,
+    /// The shared addresses.
+    pub group: AddressGroup,
+    /// Number of nodes with an address in the group.
+    pub count: usize,
+    /// Maximum number of nodes allowed by the rules.
+    pub limit: usize,
+}
+
+impl Display for DiversityViolation {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} {:?} in {} (limit {})",
+            self.count, self.scope, self.group, self.limit
+        )
+    }
+}
+
+fn allows<'a, I>(
+    nodes: I,
+    candidate: &P2pNode,
+    max_per_ip: Option<usize>,
+    max_per_subnet: Option<usize>,
+) -> bool
+where
+    I: IntoIterator<Item = &'a P2pNode>,
+{
+    let ip = candidate.peer_addr().ip();
+    let subnet = AddressGroup::subnet(ip);
+
+    let (same_ip, same_subnet) = nodes
+        .into_iter()
+        .filter(|node| node.name() != candidate.name())
+        .map(|node| node.peer_addr().ip())
+        .fold((0, 0), |(same_ip, same_subnet), other| {
+            (
+                same_ip + usize::from(other == ip),
+                same_subnet + usize::from(AddressGroup::subnet(other) == subnet),
+            )
+        });
+
+    max_per_ip.map_or(true, |max| same_ip < max)
+        && max_per_subnet.map_or(true, |max| same_subnet < max)
+}
+
+fn count_groups<'a, I>(nodes: I) -> BTreeMap<AddressGroup, usize>
+where
+    I: IntoIterator<Item = &'a P2pNode>,
+{
+    let mut counts = BTreeMap::new();
+    for node in nodes {
+        let ip = node.peer_addr().ip();
+        *counts.entry(AddressGroup::Ip(ip)).or_insert(0) += 1;
+        *counts.entry(AddressGroup::subnet(ip)).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{id::FullId, rng};
+
+    fn gen_nodes(ips: &[[u8; 4]]) -> Vec<P2pNode> {
+        let mut rng = rng::new();
+        ips.iter()
+            .map(|ip| P2pNode::new(*FullId::gen(&mut rng).public_id(), (*ip, 9000).into()))
+            .collect()
+    }
+
+    #[test]
+    fn limits_per_ip_and_subnet() {
+        let rules = DiversityRules {
+            max_elders_per_ip: Some(1),
+            max_elders_per_subnet: Some(2),
+            ..Default::default()
+        };
+        let nodes = gen_nodes(&[
+            [10, 0, 0, 1],
+            [10, 0, 0, 2],
+            [10, 0, 0, 1],
+            [10, 0, 0, 3],
+            [10, 0, 1, 1],
+        ]);
+
+        assert!(rules.allows_elder(&nodes[..1], &nodes[1]));
+        assert!(!rules.allows_elder(&nodes[..1], &nodes[2]));
+        assert!(!rules.allows_elder(&nodes[..2], &nodes[3]));
+        assert!(rules.allows_elder(&nodes[..2], &nodes[4]));
+        assert!(rules.allows_member(&nodes[..4], &nodes[2]));
+
+        let violations = rules.violations(&nodes[..3], &nodes);
+        assert_eq!(
+            violations,
+            vec![
+                DiversityViolation {
+                    scope: DiversityScope::Elders,
+                    group: AddressGroup::Ip([10, 0, 0, 1].into()),
+                    count: 2,
+                    limit: 1,
+                },
+                DiversityViolation {
+                    scope: DiversityScope::Elders,
+                    group: AddressGroup::Subnet([10, 0, 0, 0].into()),
+                    count: 3,
+                    limit: 2,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::member_info::MemberInfo;
+use crate::{id::P2pNode, xor_space::XorName};
+use std::{cmp::Ordering, fmt::Debug};
+
+/// Decides which members of a section become its elders.
+///
+/// The decision is made independently by every elder of the section, so it must depend only on
+/// its arguments (and on data that is identical for all the elders), otherwise the elders won't
+/// agree on the new elders.
+pub trait ElderSelection: Debug + Send + Sync {
+    /// Compares two candidates for elders. The one comparing `Less` is preferred.
+    fn cmp_candidates(&self, lhs: &ElderCandidate, rhs: &ElderCandidate) -> Ordering;
+
+    /// Returns whether the current `elder` should be replaced with `candidate`, which is not an
+    /// elder currently. Only called when there are no free elder slots. By default, the elder is
+    /// replaced whenever the candidate is preferred.
+    fn should_replace(&self, elder: &ElderCandidate, candidate: &ElderCandidate) -> bool {
+        self.cmp_candidates(candidate, elder) == Ordering::Less
+    }
+}
+
+/// Member of a section considered for elder.
+#[derive(Clone, Copy, Debug)]
+pub struct ElderCandidate<'a> {
+    info: &'a MemberInfo,
+}
+
+impl<'a> ElderCandidate<'a> {
+    pub(crate) fn new(info: &'a MemberInfo) -> Self {
+        Self { info }
+    }
+
+    /// Returns the candidate node.
+    pub fn p2p_node(&self) -> &'a P2pNode {
+        &self.info.p2p_node
+    }
+
+    /// Returns the candidate name.
+    pub fn name(&self) -> &'a XorName {
+        self.info.p2p_node.name()
+    }
+
+    /// Returns the candidate age.
+    pub fn age(&self) -> u8 {
+        self.info.age()
+    }
+
+    /// Returns the version of our section members at the time the candidate joined. Lower means
+    /// joined earlier.
+    pub fn joined_at(&self) -> u64 {
+        self.info.section_version
+    }
+}
+
+/// The default elder selection: older nodes are preferred, and in case of a tie, nodes that joined
+/// earlier.
+///
+/// To avoid changing elders (and running DKG) for a negligible gain, a current elder is replaced
+/// only by a candidate at least `age_margin` older than it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultElderSelection {
+    /// How much older than an elder a candidate must be to replace it.
+    pub age_margin: u8,
+}
+
+impl ElderSelection for DefaultElderSelection {
+    fn cmp_candidates(&self, lhs: &ElderCandidate, rhs: &ElderCandidate) -> Ordering {
+        rhs.info
+            .age_counter
+            .cmp(&lhs.info.age_counter)
+            .then(lhs.joined_at().cmp(&rhs.joined_at()))
+    }
+
+    fn should_replace(&self, elder: &ElderCandidate, candidate: &ElderCandidate) -> bool {
+        self.cmp_candidates(candidate, elder) == Ordering::Less
+            && candidate.age() >= elder.age().saturating_add(self.age_margin)
+    }
+}
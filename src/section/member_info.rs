@@ -51,7 +51,7 @@ pub const MIN_AGE: u8 = 4;
 const MAX_INFANT_AGE: u32 = MIN_AGE as u32;
 
 /// Information about a member of our section.
-#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, Debug)]
 pub struct MemberInfo {
     pub age_counter: AgeCounter,
     pub state: MemberState,
@@ -98,16 +98,24 @@ impl MemberInfo {
     }
 }
 
-#[derive(Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, Debug)]
 pub enum MemberState {
     Joined,
     Relocating {
         // The latest index of our section key that this node knows about.
         node_knowledge: u64,
     },
-    // TODO: we should track how long the node has been away. If longer than some limit, remove it
-    // from the list. Otherwise we allow it to return.
-    Left,
+    Left {
+        // Version of our section members at the time this node left. Used to determine how long
+        // the node has been away and thus whether it can still rejoin with its previous age.
+        since: u64,
+    },
+}
+
+impl MemberState {
+    pub fn is_left(&self) -> bool {
+        matches!(self, Self::Left { .. })
+    }
 }
 
 #[cfg(test)]
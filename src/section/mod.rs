@@ -6,24 +6,41 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod admission_policy;
+mod ban_list;
+mod diversity;
+mod elder_selection;
 mod elders_info;
 mod member_info;
 mod network_stats;
+mod relocation_policy;
 mod section_keys;
 mod section_map;
 mod section_members;
 mod section_proof_chain;
 mod shared_state;
+mod split_policy;
 
 pub use self::{
+    admission_policy::{
+        AdmissionPolicy, DefaultAdmissionPolicy, LimitedAdmissionPolicy, SectionOccupancy,
+    },
+    ban_list::{BanDetails, BanList, BanReason, DEFAULT_BAN_DURATION},
+    diversity::{AddressGroup, DiversityRules, DiversityScope, DiversityViolation},
+    elder_selection::{DefaultElderSelection, ElderCandidate, ElderSelection},
     elders_info::{quorum_count, EldersInfo},
     member_info::{AgeCounter, MemberInfo, MemberState, MIN_AGE, MIN_AGE_COUNTER},
     network_stats::NetworkStats,
+    relocation_policy::{
+        Ageing, Churn, DefaultRelocationPolicy, KnownSection, RebalancingRelocationPolicy,
+        RejoinRules, RelocationCandidate, RelocationPolicy,
+    },
     section_keys::{IndexedSecretKeyShare, SectionKeys, SectionKeysProvider},
     section_map::{NeighbourEldersRemoved, SectionMap},
     section_members::SectionMembers,
     section_proof_chain::{SectionProofChain, TrustStatus},
     shared_state::SharedState,
+    split_policy::{DefaultSplitPolicy, MaxSizeSplitPolicy, SplitCandidate, SplitPolicy},
 };
 
 #[cfg(test)]
@@ -0,0 +1,313 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::MIN_AGE;
+use crate::{
+    network_params::NetworkParams,
+    relocation,
+    xor_space::{Prefix, XorName},
+};
+use std::{cmp::Ordering, fmt::Debug};
+
+/// Decides how the members of a section age and where they get relocated to.
+///
+/// The decisions are made independently by every elder of the section, so they must depend only
+/// on their arguments (and on data that is identical for all the elders), otherwise the elders
+/// won't agree on the relocations.
+pub trait RelocationPolicy: Debug + Send + Sync {
+    /// Returns how the other members of the section age when the churn described by `churn`
+    /// happens.
+    fn ageing(&self, network_params: &NetworkParams, churn: &Churn) -> Ageing;
+
+    /// Returns whether a member whose age has just increased to `age` should be relocated.
+    fn should_relocate(&self, age: u8) -> bool;
+
+    /// Returns the relocation destination of the member described by `candidate`. The member is
+    /// relocated to the section whose prefix matches the returned name. If the name matches our
+    /// own prefix, the relocation is skipped.
+    fn destination(&self, candidate: &RelocationCandidate) -> XorName;
+
+    /// Returns the age to restore a member with when it rejoins our section after having left it
+    /// `absence` membership changes ago, or `None` if it has been away for too long to rejoin.
+    /// Members that can no longer rejoin are forgotten.
+    fn rejoin_age(&self, age: u8, absence: u64) -> Option<u8>;
+}
+
+/// Churn event (a member joining or leaving) in our section.
+#[derive(Clone, Copy, Debug)]
+pub struct Churn {
+    /// Prefix of our section.
+    pub prefix: Prefix<XorName>,
+    /// Number of joined members of our section.
+    pub section_size: usize,
+    /// Name of the member that joined or left.
+    pub trigger: XorName,
+    /// Whether the member that joined or left is mature or an elder.
+    pub trigger_mature: bool,
+}
+
+impl Churn {
+    /// Returns whether the network is still in its startup phase: there is only one section and it
+    /// has less than `recommended_section_size` members.
+    pub fn is_startup(&self, network_params: &NetworkParams) -> bool {
+        self.prefix.is_empty() && self.section_size < network_params.recommended_section_size
+    }
+}
+
+/// How the members of a section age on churn.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Ageing {
+    /// The age counters don't change.
+    None,
+    /// The age counters are incremented. Members whose age increases as a result are considered
+    /// for relocation.
+    Counter,
+    /// The ages are incremented directly, without relocating anyone.
+    Accelerated,
+}
+
+/// Member of a section considered for relocation.
+#[derive(Clone, Copy, Debug)]
+pub struct RelocationCandidate<'a> {
+    /// Prefix of the section the member is being relocated from.
+    pub src_prefix: &'a Prefix<XorName>,
+    /// Name of the member being relocated.
+    pub name: &'a XorName,
+    /// Name of the member whose churn triggered the relocation.
+    pub trigger: &'a XorName,
+    /// Sections known to the source section, other than itself.
+    pub known_sections: &'a [KnownSection],
+}
+
+/// Section known to the section relocating a member.
+#[derive(Clone, Copy, Debug)]
+pub struct KnownSection {
+    /// Prefix of the section.
+    pub prefix: Prefix<XorName>,
+    /// Number of elders of the section.
+    pub elder_count: usize,
+    /// Number of members of the section, as last reported by it, if known.
+    pub member_count: Option<usize>,
+}
+
+/// Rules for members rejoining our section after a temporary absence.
+#[derive(Clone, Copy, Debug)]
+pub struct RejoinRules {
+    /// Maximum number of membership changes (joins and leaves) of our section that can happen
+    /// while a member is away for it to still be able to rejoin with its previous age.
+    pub grace_period: u64,
+    /// By how much the age of a rejoining member is decreased.
+    pub age_penalty: u8,
+}
+
+impl RejoinRules {
+    /// Returns the age to restore a member with, as described in `RelocationPolicy::rejoin_age`.
+    pub fn rejoin_age(&self, age: u8, absence: u64) -> Option<u8> {
+        if absence > self.grace_period {
+            None
+        } else {
+            Some(age.saturating_sub(self.age_penalty).max(MIN_AGE))
+        }
+    }
+}
+
+impl Default for RejoinRules {
+    fn default() -> Self {
+        Self {
+            grace_period: 32,
+            age_penalty: 1,
+        }
+    }
+}
+
+/// The default relocation policy: during network startup the members age on every churn, after
+/// that the counters are incremented only on churn of mature members. Every age increase triggers
+/// a relocation, to a destination computed by hashing the names of the relocated and the trigger
+/// nodes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultRelocationPolicy {
+    /// Rules for members rejoining after a temporary absence.
+    pub rejoin: RejoinRules,
+}
+
+impl RelocationPolicy for DefaultRelocationPolicy {
+    fn ageing(&self, network_params: &NetworkParams, churn: &Churn) -> Ageing {
+        default_ageing(network_params, churn)
+    }
+
+    fn should_relocate(&self, _age: u8) -> bool {
+        true
+    }
+
+    fn destination(&self, candidate: &RelocationCandidate) -> XorName {
+        relocation::compute_destination(candidate.name, candidate.trigger)
+    }
+
+    fn rejoin_age(&self, age: u8, absence: u64) -> Option<u8> {
+        self.rejoin.rejoin_age(age, absence)
+    }
+}
+
+/// Relocation policy that ages the members the same way as `DefaultRelocationPolicy`, but
+/// relocates them only from `min_relocation_age` on, and to the smallest known section in order to
+/// rebalance the network.
+///
+/// The smallest section is the one with the fewest members. The sections whose member count is not
+/// known yet are considered bigger than all the others and compared by their number of elders. In
+/// case of a tie, the one covering the largest part of the name space (that is, with the shortest
+/// prefix) is chosen, as it has the fewest members relative to its share of the network. The
+/// remaining ties are broken by the hashed destination.
+#[derive(Clone, Copy, Debug)]
+pub struct RebalancingRelocationPolicy {
+    /// Minimum age at which members get relocated.
+    pub min_relocation_age: u8,
+    /// Rules for members rejoining after a temporary absence.
+    pub rejoin: RejoinRules,
+}
+
+impl RelocationPolicy for RebalancingRelocationPolicy {
+    fn ageing(&self, network_params: &NetworkParams, churn: &Churn) -> Ageing {
+        default_ageing(network_params, churn)
+    }
+
+    fn should_relocate(&self, age: u8) -> bool {
+        age >= self.min_relocation_age
+    }
+
+    fn destination(&self, candidate: &RelocationCandidate) -> XorName {
+        let hashed = relocation::compute_destination(candidate.name, candidate.trigger);
+
+        candidate
+            .known_sections
+            .iter()
+            .min_by(|lhs, rhs| cmp_by_size(lhs, rhs, &hashed))
+            .map(|section| section.prefix.substituted_in(hashed))
+            .unwrap_or(hashed)
+    }
+
+    fn rejoin_age(&self, age: u8, absence: u64) -> Option<u8> {
+        self.rejoin.rejoin_age(age, absence)
+    }
+}
+
+fn default_ageing(network_params: &NetworkParams, churn: &Churn) -> Ageing {
+    if churn.is_startup(network_params) {
+        Ageing::Accelerated
+    } else if churn.trigger_mature {
+        Ageing::Counter
+    } else {
+        // As a measure against sybil attacks, we don't increment the age counters on infant churn
+        // once we completed the startup phase.
+        Ageing::None
+    }
+}
+
+// Compares the sections so that the smallest one compares `Less`.
+fn cmp_by_size(lhs: &KnownSection, rhs: &KnownSection, target: &XorName) -> Ordering {
+    let by_count = match (lhs.member_count, rhs.member_count) {
+        (Some(lhs), Some(rhs)) => lhs.cmp(&rhs),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => lhs.elder_count.cmp(&rhs.elder_count),
+    };
+
+    by_count
+        .then(lhs.prefix.bit_count().cmp(&rhs.prefix.bit_count()))
+        .then_with(|| lhs.prefix.cmp_distance(&rhs.prefix, target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn churn(prefix: &str, section_size: usize, trigger_mature: bool) -> Churn {
+        Churn {
+            prefix: Prefix::from_str(prefix).unwrap(),
+            section_size,
+            trigger: XorName::default(),
+            trigger_mature,
+        }
+    }
+
+    fn known_section(
+        prefix: &str,
+        elder_count: usize,
+        member_count: Option<usize>,
+    ) -> KnownSection {
+        KnownSection {
+            prefix: Prefix::from_str(prefix).unwrap(),
+            elder_count,
+            member_count,
+        }
+    }
+
+    #[test]
+    fn default_policy_ageing() {
+        let params = NetworkParams {
+            elder_size: 4,
+            recommended_section_size: 10,
+        };
+        let policy = DefaultRelocationPolicy::default();
+
+        assert_eq!(
+            policy.ageing(&params, &churn("", 9, false)),
+            Ageing::Accelerated
+        );
+        assert_eq!(policy.ageing(&params, &churn("", 10, false)), Ageing::None);
+        assert_eq!(
+            policy.ageing(&params, &churn("", 10, true)),
+            Ageing::Counter
+        );
+        assert_eq!(
+            policy.ageing(&params, &churn("0", 5, true)),
+            Ageing::Counter
+        );
+    }
+
+    #[test]
+    fn rebalancing_policy_destination() {
+        let policy = RebalancingRelocationPolicy {
+            min_relocation_age: 6,
+            rejoin: RejoinRules::default(),
+        };
+        let src_prefix = Prefix::from_str("00").unwrap();
+        let name = XorName::default();
+        let trigger = XorName::default();
+        let known_sections = [
+            known_section("01", 5, Some(12)),
+            known_section("10", 5, Some(9)),
+            known_section("110", 4, Some(9)),
+            known_section("111", 3, None),
+        ];
+        let candidate = RelocationCandidate {
+            src_prefix: &src_prefix,
+            name: &name,
+            trigger: &trigger,
+            known_sections: &known_sections,
+        };
+
+        assert!(!policy.should_relocate(5));
+        assert!(policy.should_relocate(6));
+
+        let destination = policy.destination(&candidate);
+        assert!(known_sections[1].prefix.matches(&destination));
+    }
+
+    #[test]
+    fn rejoin_rules() {
+        let rules = RejoinRules {
+            grace_period: 10,
+            age_penalty: 2,
+        };
+
+        assert_eq!(rules.rejoin_age(8, 10), Some(6));
+        assert_eq!(rules.rejoin_age(MIN_AGE + 1, 0), Some(MIN_AGE));
+        assert_eq!(rules.rejoin_age(8, 11), None);
+    }
+}
@@ -38,6 +38,8 @@ pub struct SectionMap {
     recent_keys: VecDeque<(Prefix<XorName>, bls::PublicKey)>,
     // Indices of our section keys that are trusted by other sections.
     knowledge: BTreeMap<Prefix<XorName>, u64>,
+    // Number of members of the neighbour sections, as last reported by them.
+    neighbour_sizes: BTreeMap<Prefix<XorName>, usize>,
 }
 
 impl SectionMap {
@@ -50,6 +52,7 @@ impl SectionMap {
             keys: iter::once((prefix, our_key)).collect(),
             recent_keys: Default::default(),
             knowledge: Default::default(),
+            neighbour_sizes: Default::default(),
         }
     }
 
@@ -212,13 +215,13 @@ impl SectionMap {
                     return Some(*prefix);
                 }
 
-                // Remove older compatible neighbour prefixes.
-                // DO NOT SUPPORT MERGE: Not consider newer if the older one was extension (split).
+                // Remove older compatible neighbour prefixes. This also removes the sections that
+                // merged into a newer section with their parent prefix, as a merged section always
+                // has higher version than any of the sections it was merged from.
                 let is_newer =
                     |(other_prefix, other_elders_info): (&Prefix<XorName>, &EldersInfo)| {
                         other_prefix.is_compatible(prefix)
                             && other_elders_info.version > elders_info.version
-                            && !prefix.is_extension_of(other_prefix)
                     };
 
                 if self.neighbours.iter().any(is_newer) {
@@ -232,6 +235,22 @@ impl SectionMap {
         for prefix in to_remove {
             let _ = self.neighbours.remove(&prefix);
         }
+
+        let neighbours = &self.neighbours;
+        self.neighbour_sizes
+            .retain(|prefix, _| neighbours.contains_key(prefix));
+    }
+
+    /// Returns the number of members of the neighbour section with the given prefix, if known.
+    pub fn neighbour_size(&self, prefix: &Prefix<XorName>) -> Option<usize> {
+        self.neighbour_sizes.get(prefix).copied()
+    }
+
+    /// Updates the number of members of the neighbour section with the given prefix.
+    pub fn update_neighbour_size(&mut self, prefix: Prefix<XorName>, size: usize) {
+        if self.neighbours.contains_key(&prefix) {
+            let _ = self.neighbour_sizes.insert(prefix, size);
+        }
     }
 
     /// Returns the known section keys and any recent keys we still hold.
@@ -308,6 +327,52 @@ impl SectionMap {
         let _ = self.keys.insert(prefix, new_key);
     }
 
+    /// Returns whether the given neighbour `EldersInfo` is of a section that resulted from merging
+    /// some of the neighbours we know of.
+    pub fn is_merged_neighbour(&self, elders_info: &EldersInfo) -> bool {
+        self.neighbours.iter().any(|(prefix, known_info)| {
+            prefix.is_extension_of(&elders_info.prefix) && known_info.version < elders_info.version
+        })
+    }
+
+    /// Replaces the keys of all the sections that merged into the section with `prefix` with the
+    /// key of the merged section. Also replaces their knowledge of our section with the lowest of
+    /// them, unless `prefix` is our own prefix in which case the knowledge is dropped.
+    #[cfg_attr(feature = "mock_base", allow(clippy::trivially_copy_pass_by_ref))]
+    pub fn merge(&mut self, prefix: Prefix<XorName>, new_key: bls::PublicKey) {
+        trace!("merge keys for {:?}: {:?}", prefix, new_key);
+
+        let merged: Vec<_> = self
+            .keys
+            .keys()
+            .filter(|old_prefix| old_prefix.is_extension_of(&prefix))
+            .copied()
+            .collect();
+        for old_prefix in merged {
+            if let Some(old_key) = self.keys.remove(&old_prefix) {
+                self.recent_keys.push_front((old_prefix, old_key));
+            }
+        }
+        self.recent_keys.truncate(MAX_RECENT_KEYS);
+        let _ = self.keys.insert(prefix, new_key);
+
+        let merged: Vec<_> = self
+            .knowledge
+            .keys()
+            .filter(|old_prefix| old_prefix.is_extension_of(&prefix))
+            .copied()
+            .collect();
+        let index = merged
+            .iter()
+            .filter_map(|old_prefix| self.knowledge.remove(old_prefix))
+            .min();
+        if let Some(index) = index {
+            if prefix != self.our.prefix {
+                let _ = self.knowledge.insert(prefix, index);
+            }
+        }
+    }
+
     /// Returns the index of the public key in our_history that will be trusted by the given
     /// section.
     pub fn knowledge_by_section(&self, prefix: &Prefix<XorName>) -> u64 {
@@ -580,6 +645,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn merge_keys_and_knowledge() {
+        let mut rng = rng::new();
+        let k0 = gen_key(&mut rng);
+        let k1 = gen_key(&mut rng);
+        let k2 = gen_key(&mut rng);
+        let k3 = gen_key(&mut rng);
+
+        let mut map = SectionMap::new(gen_elders_info(&mut rng, "0".parse().unwrap()), k0);
+        map.update_keys("10".parse().unwrap(), k1);
+        map.update_keys("11".parse().unwrap(), k2);
+        map.update_knowledge("10".parse().unwrap(), 3);
+        map.update_knowledge("11".parse().unwrap(), 2);
+
+        map.merge("1".parse().unwrap(), k3);
+
+        let actual: Vec<_> = map.keys().map(|(prefix, key)| (*prefix, *key)).collect();
+        let expected: Vec<(Prefix<_>, _)> = vec![
+            ("0".parse().unwrap(), k0),
+            ("1".parse().unwrap(), k3),
+            ("11".parse().unwrap(), k2),
+            ("10".parse().unwrap(), k1),
+        ];
+        assert_eq!(actual, expected);
+        assert_eq!(map.knowledge_by_section(&"1".parse().unwrap()), 2);
+    }
+
     #[test]
     fn update_their_knowledge_after_split_from_one_sibling() {
         let mut rng = rng::new();
@@ -6,14 +6,16 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::member_info::{MemberInfo, MemberState};
+use super::{
+    diversity::DiversityRules,
+    elder_selection::{ElderCandidate, ElderSelection},
+    member_info::{MemberInfo, MemberState},
+};
 use crate::{
     id::P2pNode,
     xor_space::{Prefix, XorName},
 };
-use itertools::Itertools;
 use std::{
-    cmp::Ordering,
     collections::{btree_map::Entry, BTreeMap},
     mem,
 };
@@ -37,7 +39,7 @@ impl SectionMembers {
     pub fn active(&self) -> impl Iterator<Item = &MemberInfo> {
         self.members
             .values()
-            .filter(|member| member.state != MemberState::Left)
+            .filter(|member| !member.state.is_left())
     }
 
     /// Returns an iterator over the members that have state == `Joined`.
@@ -54,6 +56,14 @@ impl SectionMembers {
             .filter(|member| member.state == MemberState::Joined)
     }
 
+    /// Returns the number of joined members that went to our sibling section in the last split.
+    pub fn post_split_sibling_count(&self) -> usize {
+        self.post_split_siblings
+            .values()
+            .filter(|member| member.state == MemberState::Joined)
+            .count()
+    }
+
     /// Returns nodes from our section with age greater than `MIN_AGE`
     pub fn mature(&self) -> impl Iterator<Item = &P2pNode> {
         self.joined()
@@ -75,8 +85,20 @@ impl SectionMembers {
     }
 
     /// Returns the candidates for elders out of all the nodes in this section.
-    pub fn elder_candidates(&self, elder_size: usize) -> BTreeMap<XorName, P2pNode> {
-        elder_candidates(elder_size, self.joined())
+    pub fn elder_candidates(
+        &self,
+        elder_size: usize,
+        selection: &dyn ElderSelection,
+        diversity: &DiversityRules,
+        current_elders: &BTreeMap<XorName, P2pNode>,
+    ) -> BTreeMap<XorName, P2pNode> {
+        elder_candidates(
+            elder_size,
+            selection,
+            diversity,
+            current_elders,
+            self.joined(),
+        )
     }
 
     /// Returns the candidates for elders out of all nodes matching the prefix.
@@ -84,9 +106,15 @@ impl SectionMembers {
         &self,
         prefix: &Prefix<XorName>,
         elder_size: usize,
+        selection: &dyn ElderSelection,
+        diversity: &DiversityRules,
+        current_elders: &BTreeMap<XorName, P2pNode>,
     ) -> BTreeMap<XorName, P2pNode> {
         elder_candidates(
             elder_size,
+            selection,
+            diversity,
+            current_elders,
             self.joined()
                 .filter(|info| prefix.matches(info.p2p_node.name())),
         )
@@ -96,7 +124,7 @@ impl SectionMembers {
     pub fn contains(&self, name: &XorName) -> bool {
         self.members
             .get(name)
-            .map(|info| info.state != MemberState::Left)
+            .map(|info| !info.state.is_left())
             .unwrap_or(false)
     }
 
@@ -104,7 +132,7 @@ impl SectionMembers {
     pub fn is_active(&self, name: &XorName) -> bool {
         self.members
             .get(name)
-            .map(|info| info.state != MemberState::Left)
+            .map(|info| !info.state.is_left())
             .unwrap_or(false)
     }
 
@@ -120,10 +148,10 @@ impl SectionMembers {
     pub fn add(&mut self, p2p_node: P2pNode, age: u8) {
         match self.members.entry(*p2p_node.name()) {
             Entry::Occupied(mut entry) => {
-                if entry.get().state == MemberState::Left {
-                    // Node rejoining
-                    // TODO: To properly support rejoining, either keep the previous age or set the
-                    // new age to max(old_age, new_age)
+                if entry.get().state.is_left() {
+                    // Node rejoining under its previous name. The age it had before is restored
+                    // only if it rejoins under a new name proving its previous identity (see
+                    // `RejoinPayload`), so here it starts over with the given age.
                     entry.get_mut().state = MemberState::Joined;
                     entry.get_mut().set_age(age);
                     entry.get_mut().section_version = self.version;
@@ -153,11 +181,12 @@ impl SectionMembers {
         if let Some(info) = self
             .members
             .get_mut(name)
-            // TODO: Probably should actually remove them
-            .filter(|info| info.state != MemberState::Left)
+            .filter(|info| !info.state.is_left())
         {
             let output = info.clone();
-            info.state = MemberState::Left;
+            info.state = MemberState::Left {
+                since: self.version,
+            };
             self.increment_version();
             Some(output)
         } else {
@@ -171,6 +200,58 @@ impl SectionMembers {
         }
     }
 
+    /// Returns the number of membership changes since the member with the given name left our
+    /// section, or `None` if it is not a member that left.
+    pub fn absence(&self, name: &XorName) -> Option<u64> {
+        match self.members.get(name)?.state {
+            MemberState::Left { since } => Some(self.version.wrapping_sub(since)),
+            MemberState::Joined | MemberState::Relocating { .. } => None,
+        }
+    }
+
+    /// Returns the number of membership changes of our section so far.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Forgets the member with the given name if it left our section, so it can no longer rejoin
+    /// with its previous age.
+    pub fn forget(&mut self, name: &XorName) {
+        if self.absence(name).is_some() {
+            let _ = self.members.remove(name);
+        }
+    }
+
+    /// Forgets all the members that left our section and for which `can_rejoin` returns `false`.
+    /// `can_rejoin` is called with the member and its absence (see `absence`).
+    pub fn forget_left<F>(&mut self, mut can_rejoin: F)
+    where
+        F: FnMut(&MemberInfo, u64) -> bool,
+    {
+        let version = self.version;
+        self.members.retain(|_, info| match info.state {
+            MemberState::Left { since } => can_rejoin(info, version.wrapping_sub(since)),
+            MemberState::Joined | MemberState::Relocating { .. } => true,
+        })
+    }
+
+    /// Adds the members of a sibling section that is being merged into our section. Their ages and
+    /// states are retained.
+    pub fn merge<I>(&mut self, members: I)
+    where
+        I: IntoIterator<Item = MemberInfo>,
+    {
+        for mut info in members {
+            if self.contains(info.p2p_node.name()) {
+                continue;
+            }
+
+            info.section_version = self.version;
+            let _ = self.members.insert(*info.p2p_node.name(), info);
+            self.increment_version();
+        }
+    }
+
     /// Remove all members whose name does not match our prefix and assigns them to
     /// `post_split_siblings`.
     pub fn remove_not_matching_our_prefix(&mut self, prefix: &Prefix<XorName>) {
@@ -193,24 +274,160 @@ impl PartialEq for SectionMembers {
 }
 
 // Returns the nodes that should become the next elders out of the given members.
-fn elder_candidates<'a, I>(elder_size: usize, members: I) -> BTreeMap<XorName, P2pNode>
+//
+// The current elders keep their slots unless `selection` decides they should be replaced by some
+// of the other members. Free slots are filled with the most preferred of the other members.
+fn elder_candidates<'a, I>(
+    elder_size: usize,
+    selection: &dyn ElderSelection,
+    diversity: &DiversityRules,
+    current_elders: &BTreeMap<XorName, P2pNode>,
+    members: I,
+) -> BTreeMap<XorName, P2pNode>
 where
     I: IntoIterator<Item = &'a MemberInfo>,
 {
-    members
+    let (mut current, mut others): (Vec<_>, Vec<_>) = members
         .into_iter()
-        .sorted_by(|info1, info2| cmp_elder_candidates(info1, info2))
+        .map(ElderCandidate::new)
+        .partition(|candidate| current_elders.contains_key(candidate.name()));
+
+    // Keep the most preferred current elders, demoting those that violate the diversity rules.
+    current.sort_by(|lhs, rhs| selection.cmp_candidates(lhs, rhs));
+    let mut elders: Vec<ElderCandidate> = Vec::with_capacity(elder_size);
+    for candidate in current {
+        if elders.len() < elder_size && allows(diversity, &elders, &candidate) {
+            elders.push(candidate);
+        }
+    }
+
+    // Fill the free slots. Candidates violating the diversity rules are still considered for
+    // replacing the least preferred elders below.
+    others.sort_by(|lhs, rhs| selection.cmp_candidates(lhs, rhs));
+    let mut skipped = Vec::new();
+    let mut others = others.into_iter();
+    while elders.len() < elder_size {
+        if let Some(candidate) = others.next() {
+            if allows(diversity, &elders, &candidate) {
+                elders.push(candidate);
+            } else {
+                skipped.push(candidate);
+            }
+        } else {
+            break;
+        }
+    }
+
+    let mut others: Vec<_> = skipped.into_iter().chain(others).collect();
+    others.sort_by(|lhs, rhs| selection.cmp_candidates(lhs, rhs));
+    let mut others = others.into_iter().peekable();
+
+    // Replace the least preferred elders while there are candidates good enough to replace them.
+    elders.sort_by(|lhs, rhs| selection.cmp_candidates(lhs, rhs));
+    while let (Some(elder), Some(candidate)) = (elders.last().copied(), others.peek().copied()) {
+        if !selection.should_replace(&elder, &candidate) {
+            break;
+        }
+
+        let _ = others.next();
+        if !allows(diversity, &elders[..elders.len() - 1], &candidate) {
+            continue;
+        }
+
+        let _ = elders.pop();
+        elders.push(candidate);
+        elders.sort_by(|lhs, rhs| selection.cmp_candidates(lhs, rhs));
+    }
+
+    elders
         .into_iter()
-        .map(|info| (*info.p2p_node.name(), info.p2p_node.clone()))
-        .take(elder_size)
+        .map(|candidate| (*candidate.name(), candidate.p2p_node().clone()))
         .collect()
 }
 
-// Compare candidates for the next elders. The one comparing `Less` is more likely to become
-// elder.
-fn cmp_elder_candidates(lhs: &MemberInfo, rhs: &MemberInfo) -> Ordering {
-    // Older nodes are preferred. In case of a tie, nodes joining earlier are preferred.
-    rhs.age_counter
-        .cmp(&lhs.age_counter)
-        .then(lhs.section_version.cmp(&rhs.section_version))
+fn allows(
+    diversity: &DiversityRules,
+    elders: &[ElderCandidate],
+    candidate: &ElderCandidate,
+) -> bool {
+    diversity.allows_elder(
+        elders.iter().map(|elder| elder.p2p_node()),
+        candidate.p2p_node(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{id::FullId, rng, section::DefaultElderSelection};
+
+    fn gen_members(ages: &[u8]) -> Vec<MemberInfo> {
+        let mut rng = rng::new();
+        ages.iter()
+            .enumerate()
+            .map(|(index, age)| {
+                let id = FullId::gen(&mut rng);
+                let addr = ([127, 0, 0, 1], 9000 + index as u16).into();
+                MemberInfo::new(*age, P2pNode::new(*id.public_id(), addr), index as u64)
+            })
+            .collect()
+    }
+
+    fn to_elders(members: &[MemberInfo]) -> BTreeMap<XorName, P2pNode> {
+        members
+            .iter()
+            .map(|info| (*info.p2p_node.name(), info.p2p_node.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn default_selection_prefers_older_members() {
+        let members = gen_members(&[5, 6, 7, 8]);
+        let selection = DefaultElderSelection::default();
+
+        let elders = elder_candidates(
+            2,
+            &selection,
+            &DiversityRules::default(),
+            &to_elders(&members[..2]),
+            &members,
+        );
+        assert_eq!(elders, to_elders(&members[2..]));
+    }
+
+    #[test]
+    fn age_margin_keeps_current_elders() {
+        let members = gen_members(&[5, 6, 6, 8]);
+        let selection = DefaultElderSelection { age_margin: 2 };
+
+        // Only the member of age 8 is older than the elder of age 5 by at least the margin.
+        let elders = elder_candidates(
+            2,
+            &selection,
+            &DiversityRules::default(),
+            &to_elders(&members[..2]),
+            &members,
+        );
+        assert_eq!(elders, to_elders(&[members[1].clone(), members[3].clone()]));
+    }
+
+    #[test]
+    fn diversity_rules_limit_elders_per_ip() {
+        // All the members share the same IP address.
+        let members = gen_members(&[5, 6, 7, 8]);
+        let selection = DefaultElderSelection::default();
+        let diversity = DiversityRules {
+            max_elders_per_ip: Some(1),
+            ..Default::default()
+        };
+
+        let elders = elder_candidates(
+            2,
+            &selection,
+            &diversity,
+            &to_elders(&members[..2]),
+            &members,
+        );
+        assert_eq!(elders, to_elders(&members[3..]));
+    }
 }
@@ -6,20 +6,26 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{EldersInfo, MemberInfo, MemberState, SectionMap, SectionMembers, SectionProofChain};
+use super::{
+    Ageing, BanDetails, BanList, Churn, DiversityRules, DiversityViolation, ElderSelection,
+    EldersInfo, KnownSection, MemberInfo, MemberState, RelocationCandidate, RelocationPolicy,
+    SectionMap, SectionMembers, SectionOccupancy, SectionProofChain, SplitCandidate, SplitPolicy,
+    MIN_AGE,
+};
 use crate::{
     consensus::AccumulatingEvent,
     id::{P2pNode, PublicId},
     location::DstLocation,
-    messages::{MessageHash, SrcAuthority},
+    messages::{MergeRequest, MessageHash, SrcAuthority},
     network_params::NetworkParams,
     relocation::{self, RelocateDetails},
     xor_space::{Prefix, XorName, Xorable},
 };
 use std::{
+    cmp,
     collections::{BTreeMap, BTreeSet, VecDeque},
     fmt::Debug,
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
 };
 
 /// Section state that is shared among all elders of a section via Parsec consensus.
@@ -38,6 +44,14 @@ pub struct SharedState {
     pub churn_event_backlog: VecDeque<AccumulatingEvent>,
     /// Queue of pending relocations.
     pub relocate_queue: VecDeque<RelocateDetails>,
+    /// Info of our sibling section that is being merged into our section, if any.
+    pub merging: Option<EldersInfo>,
+    /// Whether our section currently accepts new members.
+    pub accepting_members: bool,
+    /// Number of times our section changed whether it accepts new members.
+    pub admission_version: u64,
+    /// Peers banned by our section or by our neighbours for misbehaviour.
+    pub ban_list: BanList,
 }
 
 impl SharedState {
@@ -49,6 +63,10 @@ impl SharedState {
             our_members: SectionMembers::default(),
             churn_event_backlog: Default::default(),
             relocate_queue: VecDeque::new(),
+            merging: None,
+            accepting_members: true,
+            admission_version: 0,
+            ban_list: BanList::default(),
         }
     }
 
@@ -127,13 +145,16 @@ impl SharedState {
             .find(|p2p_node| p2p_node.peer_addr() == socket_addr)
     }
 
-    /// Adds new member if its name matches our prefix and it's not already joined.
+    /// Adds new member if its name matches our prefix and it's not already joined. If the member
+    /// is rejoining under a new name, `previous_name` is the name it had before it left.
     /// Returns whether the member was actually added.
     pub fn add_member(
         &mut self,
         p2p_node: P2pNode,
         age: u8,
-        recommended_section_size: usize,
+        previous_name: Option<&XorName>,
+        network_params: &NetworkParams,
+        relocation_policy: &dyn RelocationPolicy,
     ) -> bool {
         if !self.our_prefix().matches(p2p_node.name()) {
             trace!("not adding node {} - not matching our prefix", p2p_node);
@@ -147,8 +168,26 @@ impl SharedState {
 
         let name = *p2p_node.name();
 
+        let age = match previous_name {
+            Some(previous_name) if self.our_members.absence(previous_name).is_some() => {
+                self.our_members.forget(previous_name);
+                age
+            }
+            Some(previous_name) => {
+                // The previous identity was already used to rejoin or has been forgotten meanwhile.
+                trace!(
+                    "not restoring age of node {} - {} can't rejoin",
+                    p2p_node,
+                    previous_name
+                );
+                MIN_AGE
+            }
+            None => age,
+        };
+
         self.our_members.add(p2p_node, age);
-        self.increment_age_counters(&name, recommended_section_size);
+        self.increment_age_counters(&name, network_params, relocation_policy);
+        self.forget_expired_left_members(relocation_policy);
 
         true
     }
@@ -158,22 +197,172 @@ impl SharedState {
     pub fn remove_member(
         &mut self,
         pub_id: &PublicId,
-        recommended_section_size: usize,
+        network_params: &NetworkParams,
+        relocation_policy: &dyn RelocationPolicy,
     ) -> Option<MemberInfo> {
         match self.our_members.get(pub_id.name()).map(|info| &info.state) {
-            Some(MemberState::Left) | None => {
+            Some(MemberState::Left { .. }) | None => {
                 trace!("not removing node {} - not a member", pub_id);
                 return None;
             }
             Some(MemberState::Relocating { .. }) => (),
             Some(MemberState::Joined) => {
-                self.increment_age_counters(pub_id.name(), recommended_section_size)
+                self.increment_age_counters(pub_id.name(), network_params, relocation_policy)
             }
         }
 
         self.relocate_queue
             .retain(|details| &details.pub_id != pub_id);
-        self.our_members.remove(pub_id.name())
+        let removed = self.our_members.remove(pub_id.name());
+        self.forget_expired_left_members(relocation_policy);
+        removed
+    }
+
+    /// Returns the age the member that left our section under the given name should be restored
+    /// with if it rejoins now, or `None` if it can't rejoin with its previous age.
+    pub fn rejoin_age(
+        &self,
+        previous_name: &XorName,
+        relocation_policy: &dyn RelocationPolicy,
+    ) -> Option<u8> {
+        let absence = self.our_members.absence(previous_name)?;
+        let info = self.our_members.get(previous_name)?;
+        relocation_policy.rejoin_age(info.age(), absence)
+    }
+
+    /// Returns the occupancy of our section, to decide whether it should accept new members.
+    pub fn occupancy(&self) -> SectionOccupancy {
+        let (members, infants) = self
+            .our_members
+            .joined()
+            .fold((0, 0), |(members, infants), info| {
+                (members + 1, infants + usize::from(!info.is_mature()))
+            });
+
+        SectionOccupancy {
+            prefix: *self.our_prefix(),
+            members,
+            infants,
+        }
+    }
+
+    /// Changes whether our section accepts new members. The change is applied only if `version`
+    /// immediately follows the current admission version, so that of the concurrent votes only the
+    /// first one to accumulate takes effect. Returns whether the change was applied.
+    pub fn update_admission(&mut self, accepting: bool, version: u64) -> bool {
+        if version != self.admission_version + 1 {
+            return false;
+        }
+
+        self.accepting_members = accepting;
+        self.admission_version = version;
+        true
+    }
+
+    /// Returns whether `p2p_node` can join our section without violating the diversity rules.
+    pub fn allows_new_member(&self, p2p_node: &P2pNode, diversity: &DiversityRules) -> bool {
+        diversity.allows_member(
+            self.our_members.joined().map(|info| &info.p2p_node),
+            p2p_node,
+        )
+    }
+
+    /// Returns the violations of the diversity rules by our elders and members.
+    pub fn diversity_violations(&self, diversity: &DiversityRules) -> Vec<DiversityViolation> {
+        diversity.violations(
+            self.our_info().elders.values(),
+            self.our_members.joined().map(|info| &info.p2p_node),
+        )
+    }
+
+    /// Bans the given peer for `duration` membership changes of our section. Returns whether the
+    /// peer wasn't banned yet.
+    pub fn ban(&mut self, details: BanDetails, duration: u64) -> bool {
+        self.ban_list
+            .insert(details, self.our_members.version(), duration)
+    }
+
+    /// Returns the ban in force against the peer with the given name or, if given, IP address.
+    pub fn find_ban(
+        &self,
+        name: &XorName,
+        ip: Option<IpAddr>,
+        duration: u64,
+    ) -> Option<&BanDetails> {
+        self.ban_list
+            .find(name, ip, self.our_members.version(), duration)
+    }
+
+    /// Returns the bans still in force.
+    pub fn active_bans(&self, duration: u64) -> impl Iterator<Item = &BanDetails> {
+        self.ban_list.active(self.our_members.version(), duration)
+    }
+
+    /// Returns whether our section became too small and should ask our sibling to absorb it: we
+    /// can't staff a full set of elders anymore and, as far as we know, our sibling has fewer than
+    /// `recommended_section_size` members, so the split that separated us is no longer justified.
+    pub fn should_request_merge(&self, network_params: &NetworkParams) -> bool {
+        let our_prefix = self.our_prefix();
+        if our_prefix.bit_count() == 0 {
+            return false;
+        }
+
+        // We can only merge into our sibling if it is a single section, not split further.
+        let sibling_size = if let Some(size) = self.sections.neighbour_size(&our_prefix.sibling()) {
+            size
+        } else {
+            return false;
+        };
+
+        self.our_members.joined().count() < network_params.elder_size
+            && sibling_size < network_params.recommended_section_size
+    }
+
+    /// Starts merging our sibling section into our section. Returns whether the merge was started.
+    /// The sibling must be unable to staff a full set of elders and we must have fewer than
+    /// `recommended_section_size` members ourselves.
+    pub fn merge_sibling(&mut self, network_params: &NetworkParams, request: MergeRequest) -> bool {
+        if request.elders_info.prefix != self.our_prefix().sibling() {
+            trace!(
+                "not merging {:?} - not our sibling",
+                request.elders_info.prefix
+            );
+            return false;
+        }
+
+        let sibling_size = request.member_count();
+        if sibling_size >= network_params.elder_size
+            || self.our_members.joined().count() >= network_params.recommended_section_size
+        {
+            trace!(
+                "not merging {:?} - sections too big: {} + {}",
+                request.elders_info.prefix,
+                self.our_members.joined().count(),
+                sibling_size
+            );
+            return false;
+        }
+
+        if let Some(merging) = &self.merging {
+            trace!(
+                "not merging {:?} - already merging {:?}",
+                request.elders_info.prefix,
+                merging.prefix
+            );
+            return false;
+        }
+
+        let prefix = request.elders_info.prefix;
+        self.our_members.merge(
+            request
+                .members
+                .into_iter()
+                .filter(|info| !info.state.is_left())
+                .filter(|info| prefix.matches(info.p2p_node.name())),
+        );
+        self.merging = Some(request.elders_info);
+
+        true
     }
 
     /// Find section which has member with the given id
@@ -198,36 +387,39 @@ impl SharedState {
     pub fn promote_and_demote_elders(
         &mut self,
         network_params: &NetworkParams,
+        split_policy: &dyn SplitPolicy,
+        elder_selection: &dyn ElderSelection,
+        diversity: &DiversityRules,
         our_name: &XorName,
     ) -> Option<Vec<EldersInfo>> {
-        if let Some((our_info, other_info)) = self.try_split(network_params, our_name) {
+        if let Some(merged_info) = self.try_merge(network_params, elder_selection, diversity) {
+            return Some(vec![merged_info]);
+        }
+
+        if let Some((our_info, other_info)) = self.try_split(
+            network_params,
+            split_policy,
+            elder_selection,
+            diversity,
+            our_name,
+        ) {
             return Some(vec![our_info, other_info]);
         }
 
-        let expected_elders_map = self.elder_candidates(network_params.elder_size);
+        let expected_elders_map =
+            self.elder_candidates(network_params.elder_size, elder_selection, diversity);
         let expected_elders: BTreeSet<_> = expected_elders_map.values().cloned().collect();
         let current_elders: BTreeSet<_> = self.our_info().elders.values().cloned().collect();
 
         if expected_elders == current_elders {
             None
         } else {
-            let old_size = self.our_info().elders.len();
-
             let new_info = EldersInfo::new(
                 expected_elders_map,
                 self.our_info().prefix,
                 self.our_info().version + 1,
             );
 
-            if self.our_info().elders.len() < network_params.elder_size
-                && old_size >= network_params.elder_size
-            {
-                panic!(
-                    "Merging situation encountered! Not supported: {:?}",
-                    self.our_info()
-                );
-            }
-
             Some(vec![new_info])
         }
     }
@@ -238,12 +430,64 @@ impl SharedState {
         section_key: bls::PublicKey,
         signature: bls::Signature,
     ) {
+        let is_merge = self.our_prefix().is_extension_of(&elders_info.prefix);
+
         self.our_members
             .remove_not_matching_our_prefix(&elders_info.prefix);
         self.our_history.push(section_key, signature);
         self.sections.set_our(elders_info);
-        self.sections
-            .update_keys(self.sections.our().prefix, section_key);
+
+        if is_merge {
+            self.merging = None;
+            self.sections.merge(self.sections.our().prefix, section_key);
+        } else {
+            self.sections
+                .update_keys(self.sections.our().prefix, section_key);
+        }
+    }
+
+    /// Schedules the relocation of the member with the given name to the given destination, as
+    /// requested by the node operators. Returns whether the relocation was scheduled.
+    ///
+    /// The member keeps its age, rather than getting the `age + 1` of the relocations triggered by
+    /// churn (see `increment_age_counters`). Those are earned by the churn the member witnessed,
+    /// while this one is only the operators' decision, and ageing the member here would let them
+    /// age it faster than the churn does.
+    pub fn relocate_member(&mut self, name: &XorName, destination: XorName) -> bool {
+        if self.our_prefix().matches(&destination) {
+            trace!("not relocating {} - destination inside our section", name);
+            return false;
+        }
+
+        let relocating_state = self.create_relocating_state();
+        let destination_key = *self
+            .sections
+            .key_by_name(&destination)
+            .unwrap_or_else(|| self.our_history.first_key());
+
+        let member_info = if let Some(member_info) = self
+            .our_members
+            .joined_mut()
+            .find(|info| info.p2p_node.name() == name)
+        {
+            member_info
+        } else {
+            trace!("not relocating {} - not a joined member", name);
+            return false;
+        };
+
+        member_info.state = relocating_state;
+
+        let details = RelocateDetails {
+            pub_id: *member_info.p2p_node.public_id(),
+            destination,
+            destination_key,
+            // Not `age() + 1` - see above.
+            age: member_info.age(),
+        };
+
+        self.relocate_queue.push_front(details);
+        true
     }
 
     pub fn poll_relocation(&mut self) -> Option<RelocateDetails> {
@@ -376,45 +620,90 @@ impl SharedState {
         events
     }
 
+    // Tries to merge our sibling into our section.
+    // If a merge is in progress, returns the elders info of the merged section. Its elders are
+    // chosen out of the members of both sections, so the DKG for them is a joint one. The members
+    // of our sibling follow our consensus since we accepted the merge, so they take part in it.
+    fn try_merge(
+        &self,
+        network_params: &NetworkParams,
+        elder_selection: &dyn ElderSelection,
+        diversity: &DiversityRules,
+    ) -> Option<EldersInfo> {
+        let sibling_info = self.merging.as_ref()?;
+        let merged_prefix = self.our_prefix().popped();
+        let current_elders = self
+            .our_info()
+            .elders
+            .iter()
+            .chain(&sibling_info.elders)
+            .map(|(name, p2p_node)| (*name, p2p_node.clone()))
+            .collect();
+
+        let elders = self.our_members.elder_candidates_matching_prefix(
+            &merged_prefix,
+            network_params.elder_size,
+            elder_selection,
+            diversity,
+            &current_elders,
+        );
+        let version = cmp::max(self.our_info().version, sibling_info.version) + 1;
+
+        Some(EldersInfo::new(elders, merged_prefix, version))
+    }
+
     // Tries to split our section.
-    // If we have enough mature nodes for both subsections, returns the elders infos of the two
+    // If the split policy decides we should split, returns the elders infos of the two
     // subsections. Otherwise returns `None`.
     fn try_split(
         &self,
         network_params: &NetworkParams,
+        split_policy: &dyn SplitPolicy,
+        elder_selection: &dyn ElderSelection,
+        diversity: &DiversityRules,
         our_name: &XorName,
     ) -> Option<(EldersInfo, EldersInfo)> {
         let next_bit_index = self.our_prefix().bit_count();
         let next_bit = our_name.bit(next_bit_index);
 
-        let (our_new_size, sibling_new_size) = self
+        let mature = self
             .our_members
             .mature()
-            .map(|p2p_node| p2p_node.name().bit(next_bit_index) == next_bit)
-            .fold((0, 0), |(ours, siblings), is_our_prefix| {
-                if is_our_prefix {
-                    (ours + 1, siblings)
+            .map(|p2p_node| p2p_node.name().bit(next_bit_index))
+            .fold((0, 0), |(zeros, ones), bit| {
+                if bit {
+                    (zeros, ones + 1)
                 } else {
-                    (ours, siblings + 1)
+                    (zeros + 1, ones)
                 }
             });
+        let candidate = SplitCandidate {
+            prefix: *self.our_prefix(),
+            members: self.our_members.joined().count(),
+            mature,
+        };
 
-        // If either of the two new sections will not contain enough entries, return `false`.
-        if our_new_size < network_params.recommended_section_size
-            || sibling_new_size < network_params.recommended_section_size
-        {
+        if !split_policy.should_split(network_params, &candidate) {
             return None;
         }
 
         let our_prefix = self.our_prefix().pushed(next_bit);
         let other_prefix = self.our_prefix().pushed(!next_bit);
 
-        let our_elders = self
-            .our_members
-            .elder_candidates_matching_prefix(&our_prefix, network_params.elder_size);
-        let other_elders = self
-            .our_members
-            .elder_candidates_matching_prefix(&other_prefix, network_params.elder_size);
+        let our_elders = self.our_members.elder_candidates_matching_prefix(
+            &our_prefix,
+            network_params.elder_size,
+            elder_selection,
+            diversity,
+            &self.our_info().elders,
+        );
+        let other_elders = self.our_members.elder_candidates_matching_prefix(
+            &other_prefix,
+            network_params.elder_size,
+            elder_selection,
+            diversity,
+            &self.our_info().elders,
+        );
 
         let our_info = EldersInfo::new(our_elders, our_prefix, self.our_info().version + 1);
         let other_info = EldersInfo::new(other_elders, other_prefix, self.our_info().version + 1);
@@ -424,8 +713,18 @@ impl SharedState {
 
     // Returns the candidates for elders out of all the nodes in the section, even out of the
     // relocating nodes if there would not be enough instead.
-    fn elder_candidates(&self, elder_size: usize) -> BTreeMap<XorName, P2pNode> {
-        let mut elders = self.our_members.elder_candidates(elder_size);
+    fn elder_candidates(
+        &self,
+        elder_size: usize,
+        elder_selection: &dyn ElderSelection,
+        diversity: &DiversityRules,
+    ) -> BTreeMap<XorName, P2pNode> {
+        let mut elders = self.our_members.elder_candidates(
+            elder_size,
+            elder_selection,
+            diversity,
+            &self.our_info().elders,
+        );
 
         // Ensure that we can still handle one node lost when relocating.
         // Ensure that the node we eject are the one we want to relocate first.
@@ -444,43 +743,55 @@ impl SharedState {
             .iter()
             .map(|details| details.pub_id.name())
             .filter_map(move |name| self.our_members.get(name))
-            .filter(|info| info.state != MemberState::Left)
+            .filter(|info| !info.state.is_left())
             .take(count)
             .map(|info| (*info.p2p_node.name(), info.p2p_node.clone()))
     }
 
     // Increment the age counters of the members.
-    fn increment_age_counters(&mut self, trigger_node: &XorName, recommended_section_size: usize) {
-        let our_section_size = self.our_members.joined().count();
-        let our_prefix = &self.sections.our().prefix;
-
-        // Is network startup in progress?
-        let startup =
-            *our_prefix == Prefix::default() && our_section_size < recommended_section_size;
-
-        // As a measure against sybil attacks, we don't increment the age counters on infant churn
-        // once we completed the startup phase.
-        if !startup
-            && !self.our_members.is_mature(trigger_node)
-            && !self.is_peer_our_elder(trigger_node)
-        {
+    fn increment_age_counters(
+        &mut self,
+        trigger_node: &XorName,
+        network_params: &NetworkParams,
+        relocation_policy: &dyn RelocationPolicy,
+    ) {
+        let our_prefix = *self.our_prefix();
+        let churn = Churn {
+            prefix: our_prefix,
+            section_size: self.our_members.joined().count(),
+            trigger: *trigger_node,
+            trigger_mature: self.our_members.is_mature(trigger_node)
+                || self.is_peer_our_elder(trigger_node),
+        };
+
+        let ageing = relocation_policy.ageing(network_params, &churn);
+        if ageing == Ageing::None {
             trace!(
-                "Not incrementing age counters on infant churn (section size: {})",
-                our_section_size,
+                "Not incrementing age counters on churn of {} (section size: {})",
+                trigger_node,
+                churn.section_size,
             );
             return;
         }
 
         let relocating_state = self.create_relocating_state();
         let first_key = self.our_history.first_key();
+        let known_sections: Vec<_> = self
+            .sections
+            .other()
+            .map(|(prefix, info)| KnownSection {
+                prefix: *prefix,
+                elder_count: info.elders.len(),
+                member_count: self.sections.neighbour_size(prefix),
+            })
+            .collect();
 
         for member_info in self.our_members.joined_mut() {
             if member_info.p2p_node.name() == trigger_node {
                 continue;
             }
 
-            // During network startup we go through accelerated ageing.
-            if startup {
+            if ageing == Ageing::Accelerated {
                 member_info.increment_age();
                 continue;
             }
@@ -489,11 +800,17 @@ impl SharedState {
                 continue;
             }
 
-            let destination = relocation::compute_destination(
-                our_prefix,
-                member_info.p2p_node.name(),
-                trigger_node,
-            );
+            if !relocation_policy.should_relocate(member_info.age()) {
+                continue;
+            }
+
+            let destination = relocation_policy.destination(&RelocationCandidate {
+                src_prefix: &our_prefix,
+                name: member_info.p2p_node.name(),
+                trigger: trigger_node,
+                known_sections: &known_sections,
+            });
+            let destination = relocation::override_destination(&our_prefix, destination);
             if our_prefix.matches(&destination) {
                 // Relocation destination inside the current section - ignoring.
                 trace!(
@@ -524,6 +841,13 @@ impl SharedState {
         trace!("increment_age_counters: {:?}", self.our_members);
     }
 
+    // Forget the members that left our section and have been away for too long to rejoin.
+    fn forget_expired_left_members(&mut self, relocation_policy: &dyn RelocationPolicy) {
+        self.our_members.forget_left(|info, absence| {
+            relocation_policy.rejoin_age(info.age(), absence).is_some()
+        })
+    }
+
     // Return a relocating state of a node relocating now.
     // Ensure that node knows enough to trust node_knowledge proving index.
     fn create_relocating_state(&self) -> MemberState {
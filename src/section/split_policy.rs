@@ -0,0 +1,107 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    network_params::NetworkParams,
+    xor_space::{Prefix, XorName},
+};
+use std::fmt::Debug;
+
+/// Decides when a section splits.
+///
+/// The decision is made independently by every elder of the section, so it must depend only on
+/// its arguments (and on data that is identical for all the elders), otherwise the elders won't
+/// agree on the split.
+pub trait SplitPolicy: Debug + Send + Sync {
+    /// Returns whether the section described by `candidate` should split.
+    fn should_split(&self, network_params: &NetworkParams, candidate: &SplitCandidate) -> bool;
+}
+
+/// Section considered for a split.
+#[derive(Clone, Copy, Debug)]
+pub struct SplitCandidate {
+    /// Prefix of the section.
+    pub prefix: Prefix<XorName>,
+    /// Number of joined members of the section, including infants.
+    pub members: usize,
+    /// Number of mature members that would end up in the sub-section with prefix
+    /// `prefix.pushed(false)` and `prefix.pushed(true)` respectively.
+    pub mature: (usize, usize),
+}
+
+impl SplitCandidate {
+    /// Returns whether both sub-sections would have at least `count` mature members.
+    pub fn both_have_mature(&self, count: usize) -> bool {
+        self.mature.0 >= count && self.mature.1 >= count
+    }
+}
+
+/// The default split policy: split once both sub-sections would have at least
+/// `recommended_section_size` mature members.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultSplitPolicy;
+
+impl SplitPolicy for DefaultSplitPolicy {
+    fn should_split(&self, network_params: &NetworkParams, candidate: &SplitCandidate) -> bool {
+        candidate.both_have_mature(network_params.recommended_section_size)
+    }
+}
+
+/// Split policy that splits a section once it has more than `max_section_size` members, as long as
+/// both sub-sections would have enough mature members to fill all their elder slots.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxSizeSplitPolicy {
+    /// Maximum number of joined members a section can have without splitting.
+    pub max_section_size: usize,
+}
+
+impl SplitPolicy for MaxSizeSplitPolicy {
+    fn should_split(&self, network_params: &NetworkParams, candidate: &SplitCandidate) -> bool {
+        candidate.members > self.max_section_size
+            && candidate.both_have_mature(network_params.elder_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(members: usize, mature: (usize, usize)) -> SplitCandidate {
+        SplitCandidate {
+            prefix: Prefix::default(),
+            members,
+            mature,
+        }
+    }
+
+    #[test]
+    fn default_policy() {
+        let params = NetworkParams {
+            elder_size: 4,
+            recommended_section_size: 10,
+        };
+
+        assert!(!DefaultSplitPolicy.should_split(&params, &candidate(30, (9, 20))));
+        assert!(DefaultSplitPolicy.should_split(&params, &candidate(20, (10, 10))));
+    }
+
+    #[test]
+    fn max_size_policy() {
+        let params = NetworkParams {
+            elder_size: 4,
+            recommended_section_size: 10,
+        };
+        let policy = MaxSizeSplitPolicy {
+            max_section_size: 12,
+        };
+
+        assert!(!policy.should_split(&params, &candidate(12, (6, 6))));
+        assert!(!policy.should_split(&params, &candidate(13, (3, 10))));
+        assert!(policy.should_split(&params, &candidate(13, (4, 5))));
+    }
+}
@@ -77,7 +77,7 @@ mod tests {
         consensus::generate_bls_threshold_secret_key,
         id::{FullId, P2pNode},
         location::{DstLocation, SrcLocation},
-        messages::{Message, PlainMessage, Variant},
+        messages::{Message, PlainMessage, Priority, Variant},
         rng::{self, MainRng},
         section::{gen_secret_key, IndexedSecretKeyShare, SectionProofChain},
         Prefix, XorName,
@@ -103,6 +103,7 @@ mod tests {
                 dst: DstLocation::Section(rng.gen()),
                 dst_key: gen_secret_key(rng).public_key(),
                 variant: Variant::UserMessage(rng.sample_iter(Standard).take(3).collect()),
+                priority: Priority::UserNormal,
             };
 
             let msg_sender_secret_key_share = secret_key_shares
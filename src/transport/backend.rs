@@ -0,0 +1,53 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    error::Result,
+    quic_p2p::{Peer, QuicP2p, Token},
+};
+use bytes::Bytes;
+use std::net::SocketAddr;
+
+/// Network backend that `Transport` exchanges messages with other peers over.
+///
+/// The outcome of the operations is reported asynchronously as `TransportEvent`s sent to the event
+/// channels the backend was created with:
+/// - `bootstrap` results in `BootstrappedTo` or `BootstrapFailure`,
+/// - `send` results in `SentUserMessage` or `UnsentUserMessage` carrying the given token,
+/// - messages from other peers are reported as `NewMessage`.
+pub trait TransportBackend {
+    /// Connects to one of the bootstrap contacts.
+    fn bootstrap(&mut self);
+
+    /// Sends the message to the peer, connecting to it first if necessary.
+    fn send(&mut self, peer: Peer, msg: Bytes, token: Token);
+
+    /// Returns the address other peers can reach us at.
+    fn our_connection_info(&mut self) -> Result<SocketAddr>;
+
+    /// Closes the connection to the peer, if any.
+    fn disconnect_from(&mut self, addr: SocketAddr);
+}
+
+impl TransportBackend for QuicP2p {
+    fn bootstrap(&mut self) {
+        QuicP2p::bootstrap(self)
+    }
+
+    fn send(&mut self, peer: Peer, msg: Bytes, token: Token) {
+        QuicP2p::send(self, peer, msg, token)
+    }
+
+    fn our_connection_info(&mut self) -> Result<SocketAddr> {
+        Ok(QuicP2p::our_connection_info(self)?)
+    }
+
+    fn disconnect_from(&mut self, addr: SocketAddr) {
+        QuicP2p::disconnect_from(self, addr)
+    }
+}
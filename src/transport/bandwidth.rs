@@ -0,0 +1,375 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Per-peer accounting and rate limiting of the traffic.
+
+use crate::time::{Duration, Instant};
+use std::{collections::HashMap, net::SocketAddr};
+
+/// Longest time a message can be delayed to keep within the outbound limit of its recipient.
+/// Messages that would have to wait longer are dropped instead, so the messages waiting for a peer
+/// that can't keep up don't pile up without bound.
+pub const MAX_OUTBOUND_DELAY: Duration = Duration::from_secs(30);
+
+/// Token-bucket limit of the traffic exchanged with a single peer. The peer can exchange up to the
+/// burst sizes at once, after which it's held to the rates.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RateLimit {
+    /// Sustained number of bytes per second.
+    pub bytes_per_sec: u64,
+    /// Maximal number of bytes in a burst. Should be at least the size of the largest message,
+    /// otherwise such messages never fit in the limit.
+    pub burst_bytes: u64,
+    /// Sustained number of messages per second.
+    pub msgs_per_sec: u64,
+    /// Maximal number of messages in a burst.
+    pub burst_msgs: u64,
+}
+
+/// Configuration of the per-peer traffic limits. Unlimited by default.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BandwidthConfig {
+    /// Limit of the traffic received from each node. Messages over it are dropped. The traffic
+    /// from the elders of our and the neighbouring sections is exempt, as it carries the consensus
+    /// and the messages relayed between the sections.
+    pub inbound: Option<RateLimit>,
+    /// Limit of the user traffic sent to each peer. Messages over it are delayed until they fit in
+    /// it, or dropped if that would take longer than `MAX_OUTBOUND_DELAY`. The routing-internal
+    /// traffic is exempt, so it's never held up behind the user traffic.
+    pub outbound: Option<RateLimit>,
+    /// Number of messages over the inbound limit in a row after which we disconnect from the node,
+    /// or `None` to only drop them.
+    pub disconnect_after: Option<u32>,
+}
+
+/// Traffic exchanged with a single peer while connected to it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PeerStats {
+    /// Bytes received from the peer, including the dropped messages.
+    pub bytes_received: u64,
+    /// Messages received from the peer, including the dropped ones.
+    pub msgs_received: u64,
+    /// Bytes handed over to the network to send to the peer.
+    pub bytes_sent: u64,
+    /// Messages handed over to the network to send to the peer.
+    pub msgs_sent: u64,
+    /// Received messages dropped for exceeding the inbound limit.
+    pub msgs_dropped: u64,
+    /// Messages to the peer delayed for exceeding the outbound limit.
+    pub msgs_delayed: u64,
+    /// Messages to the peer dropped for exceeding the outbound limit by more than
+    /// `MAX_OUTBOUND_DELAY`.
+    pub msgs_refused: u64,
+}
+
+/// What to do with a received message.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Reception {
+    /// The message is within the limit.
+    Accept,
+    /// The message exceeds the limit and should be dropped.
+    Drop,
+    /// The message exceeds the limit and the sender did so too many times in a row. The message
+    /// should be dropped and the sender disconnected.
+    Disconnect,
+}
+
+/// What to do with a message to send.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Reservation {
+    /// The message is within the limit and can be sent right away.
+    Send,
+    /// The message exceeds the limit and should be delayed by the given time.
+    Delay(Duration),
+    /// The message would have to be delayed longer than `MAX_OUTBOUND_DELAY` and should be
+    /// dropped.
+    Refuse,
+}
+
+/// Traffic accounting and limits of all the peers.
+pub struct Bandwidth {
+    config: BandwidthConfig,
+    peers: HashMap<SocketAddr, Entry>,
+}
+
+impl Bandwidth {
+    pub fn new(config: BandwidthConfig) -> Self {
+        Self {
+            config,
+            peers: HashMap::new(),
+        }
+    }
+
+    pub fn stats(&self) -> impl Iterator<Item = (&SocketAddr, &PeerStats)> {
+        self.peers.iter().map(|(addr, entry)| (addr, &entry.stats))
+    }
+
+    /// Accounts a message of `len` bytes received from the peer and decides whether it's within
+    /// the inbound limit. Messages from `exempt` peers are accounted but always accepted.
+    pub fn handle_received(&mut self, addr: SocketAddr, len: usize, exempt: bool) -> Reception {
+        let config = self.config;
+        let entry = self.peers.entry(addr).or_default();
+        entry.stats.bytes_received += len as u64;
+        entry.stats.msgs_received += 1;
+
+        let limit = match &config.inbound {
+            Some(limit) if !exempt => limit,
+            _ => return Reception::Accept,
+        };
+
+        if entry
+            .inbound
+            .get_or_insert_with(|| Bucket::new(limit))
+            .try_take(limit, len)
+        {
+            entry.violations = 0;
+            return Reception::Accept;
+        }
+
+        entry.stats.msgs_dropped += 1;
+        entry.violations += 1;
+
+        match config.disconnect_after {
+            Some(max_violations) if entry.violations >= max_violations => Reception::Disconnect,
+            _ => Reception::Drop,
+        }
+    }
+
+    /// Reserves room for a message of `len` bytes to the peer in the outbound limit, unless it's
+    /// refused.
+    pub fn reserve_send(&mut self, addr: SocketAddr, len: usize) -> Reservation {
+        let limit = if let Some(limit) = &self.config.outbound {
+            limit
+        } else {
+            return Reservation::Send;
+        };
+
+        let entry = self.peers.entry(addr).or_default();
+        let reservation = entry
+            .outbound
+            .get_or_insert_with(|| Bucket::new(limit))
+            .reserve(limit, len);
+
+        match reservation {
+            Reservation::Send => (),
+            Reservation::Delay(_) => entry.stats.msgs_delayed += 1,
+            Reservation::Refuse => entry.stats.msgs_refused += 1,
+        }
+
+        reservation
+    }
+
+    /// Accounts a message of `len` bytes handed over to the network to send to the peer.
+    pub fn handle_sent(&mut self, addr: SocketAddr, len: usize) {
+        let entry = self.peers.entry(addr).or_default();
+        entry.stats.bytes_sent += len as u64;
+        entry.stats.msgs_sent += 1;
+    }
+
+    /// Forgets the peer, for example when disconnected from it.
+    pub fn remove(&mut self, addr: &SocketAddr) {
+        let _ = self.peers.remove(addr);
+    }
+}
+
+#[derive(Default)]
+struct Entry {
+    stats: PeerStats,
+    inbound: Option<Bucket>,
+    outbound: Option<Bucket>,
+    // Number of received messages over the limit since the last one within it.
+    violations: u32,
+}
+
+// Pair of token buckets, one for bytes and one for messages.
+struct Bucket {
+    bytes: f64,
+    msgs: f64,
+    last_update: Instant,
+}
+
+impl Bucket {
+    fn new(limit: &RateLimit) -> Self {
+        Self {
+            bytes: limit.burst_bytes as f64,
+            msgs: limit.burst_msgs as f64,
+            last_update: Instant::now(),
+        }
+    }
+
+    // Takes the tokens for a message of `len` bytes if there are enough. Returns whether it did.
+    fn try_take(&mut self, limit: &RateLimit, len: usize) -> bool {
+        self.refill(limit);
+
+        if self.bytes >= len as f64 && self.msgs >= 1.0 {
+            self.bytes -= len as f64;
+            self.msgs -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Takes the tokens for a message of `len` bytes, going into debt if there aren't enough, up
+    // to the debt repaid in `MAX_OUTBOUND_DELAY`. Returns how long until the debt is repaid, if
+    // there is any.
+    fn reserve(&mut self, limit: &RateLimit, len: usize) -> Reservation {
+        self.refill(limit);
+
+        let bytes = self.bytes - len as f64;
+        let msgs = self.msgs - 1.0;
+
+        let bytes_wait = -bytes / limit.bytes_per_sec.max(1) as f64;
+        let msgs_wait = -msgs / limit.msgs_per_sec.max(1) as f64;
+        let wait = bytes_wait.max(msgs_wait);
+
+        if wait > MAX_OUTBOUND_DELAY.as_secs_f64() {
+            return Reservation::Refuse;
+        }
+
+        self.bytes = bytes;
+        self.msgs = msgs;
+
+        if wait > 0.0 {
+            Reservation::Delay(Duration::from_secs_f64(wait))
+        } else {
+            Reservation::Send
+        }
+    }
+
+    fn refill(&mut self, limit: &RateLimit) {
+        let elapsed = self.last_update.elapsed().as_secs_f64();
+        self.last_update = Instant::now();
+
+        self.bytes =
+            (self.bytes + elapsed * limit.bytes_per_sec as f64).min(limit.burst_bytes as f64);
+        self.msgs = (self.msgs + elapsed * limit.msgs_per_sec as f64).min(limit.burst_msgs as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unwrap;
+
+    const LIMIT: RateLimit = RateLimit {
+        bytes_per_sec: 100,
+        burst_bytes: 1000,
+        msgs_per_sec: 1,
+        burst_msgs: 3,
+    };
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn drop_and_disconnect_over_inbound_limit() {
+        let mut bandwidth = Bandwidth::new(BandwidthConfig {
+            inbound: Some(LIMIT),
+            outbound: None,
+            disconnect_after: Some(2),
+        });
+
+        // Within the message burst.
+        for _ in 0..3 {
+            assert_eq!(
+                bandwidth.handle_received(addr(1), 10, false),
+                Reception::Accept
+            );
+        }
+        assert_eq!(
+            bandwidth.handle_received(addr(1), 10, false),
+            Reception::Drop
+        );
+        assert_eq!(
+            bandwidth.handle_received(addr(1), 10, false),
+            Reception::Disconnect
+        );
+
+        // The limit is per peer.
+        assert_eq!(
+            bandwidth.handle_received(addr(2), 1000, false),
+            Reception::Accept
+        );
+        assert_eq!(
+            bandwidth.handle_received(addr(2), 1, false),
+            Reception::Drop
+        );
+
+        // Exempt peers are only accounted.
+        for _ in 0..5 {
+            assert_eq!(
+                bandwidth.handle_received(addr(3), 1000, true),
+                Reception::Accept
+            );
+        }
+
+        let stats: HashMap<_, _> = bandwidth.stats().collect();
+        assert_eq!(
+            stats[&addr(1)],
+            &PeerStats {
+                bytes_received: 50,
+                msgs_received: 5,
+                msgs_dropped: 2,
+                ..PeerStats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn delay_over_outbound_limit() {
+        let mut bandwidth = Bandwidth::new(BandwidthConfig {
+            inbound: None,
+            outbound: Some(LIMIT),
+            disconnect_after: None,
+        });
+
+        assert_eq!(bandwidth.reserve_send(addr(1), 900), Reservation::Send);
+
+        // 200 bytes over the burst take two seconds to repay.
+        let delay = expect_delay(bandwidth.reserve_send(addr(1), 300));
+        assert!(delay > Duration::from_millis(1900) && delay <= Duration::from_secs(2));
+
+        // The next message waits for the previous ones.
+        let delay = expect_delay(bandwidth.reserve_send(addr(1), 100));
+        assert!(delay > Duration::from_millis(2900) && delay <= Duration::from_secs(3));
+
+        assert_eq!(unwrap!(bandwidth.stats().next()).1.msgs_delayed, 2);
+    }
+
+    #[test]
+    fn refuse_over_max_outbound_delay() {
+        let mut bandwidth = Bandwidth::new(BandwidthConfig {
+            inbound: None,
+            outbound: Some(LIMIT),
+            disconnect_after: None,
+        });
+
+        // The message rate allows a delay of up to `MAX_OUTBOUND_DELAY` seconds worth of messages
+        // past the burst.
+        let max_delayed = MAX_OUTBOUND_DELAY.as_secs() * LIMIT.msgs_per_sec;
+        for _ in 0..LIMIT.burst_msgs + max_delayed {
+            assert_ne!(bandwidth.reserve_send(addr(1), 1), Reservation::Refuse);
+        }
+        assert_eq!(bandwidth.reserve_send(addr(1), 1), Reservation::Refuse);
+
+        // Refused messages don't add to the debt.
+        assert_eq!(bandwidth.reserve_send(addr(1), 1), Reservation::Refuse);
+        let stats = *unwrap!(bandwidth.stats().next()).1;
+        assert_eq!(stats.msgs_delayed, max_delayed);
+        assert_eq!(stats.msgs_refused, 2);
+    }
+
+    fn expect_delay(reservation: Reservation) -> Duration {
+        match reservation {
+            Reservation::Delay(delay) => delay,
+            reservation => panic!("unexpected reservation {:?}", reservation),
+        }
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Cache of the elders we've seen, used as bootstrap contacts on the next start.
+
+use crate::{
+    error::Result,
+    time::{Duration, Instant},
+};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufReader, ErrorKind},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+// Maximum number of contacts kept in the cache. The lowest ranked ones are evicted first.
+const BOOTSTRAP_CACHE_SIZE: usize = 200;
+// Number of consecutive failures after which a contact is evicted from the cache.
+const MAX_FAILURES: u32 = 3;
+// Minimum time between two saves caused by new elders, which we can learn about often.
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    // When we last heard of the contact being an elder.
+    last_seen: u64,
+    // When we last successfully bootstrapped off the contact, if ever.
+    last_success: Option<u64>,
+    // Number of failed bootstrap attempts since the last success.
+    failures: u32,
+}
+
+impl Entry {
+    // Sort key, smallest first: fewest failures, then most recent success, then most recently seen.
+    fn rank(&self) -> (u32, Reverse<Option<u64>>, Reverse<u64>) {
+        (
+            self.failures,
+            Reverse(self.last_success),
+            Reverse(self.last_seen),
+        )
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Contents {
+    // Logical clock, incremented on every update. Used instead of the wall clock so the ranking
+    // doesn't depend on the system time and survives restarts.
+    clock: u64,
+    entries: HashMap<SocketAddr, Entry>,
+}
+
+/// Bootstrap contacts ranked by recency and success, optionally persisted to a file.
+pub struct BootstrapCache {
+    path: Option<PathBuf>,
+    contents: Contents,
+    // Whether there are new contacts not saved yet.
+    dirty: bool,
+    last_save: Option<Instant>,
+}
+
+impl BootstrapCache {
+    /// Creates a cache persisted to the given file, loading its current contents. `None` keeps
+    /// the cache in memory only.
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let contents = path
+            .as_ref()
+            .and_then(|path| match load(path) {
+                Ok(contents) => Some(contents),
+                Err(error) => {
+                    warn!(
+                        "Failed to load bootstrap cache from {:?}: {:?}",
+                        path, error
+                    );
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        Self {
+            path,
+            contents,
+            dirty: false,
+            last_save: None,
+        }
+    }
+
+    /// Returns the cached contacts, best first.
+    pub fn contacts(&self) -> Vec<SocketAddr> {
+        let mut entries: Vec<_> = self.contents.entries.iter().collect();
+        entries.sort_by_key(|(addr, entry)| (entry.rank(), **addr));
+        entries.into_iter().map(|(addr, _)| *addr).collect()
+    }
+
+    /// Records that the given peers are currently elders. Saves the cache only if some of them
+    /// are new to it, and then at most once per `SAVE_INTERVAL`. The rest is saved with the next
+    /// save or when the cache is dropped.
+    pub fn insert_elders(&mut self, addrs: impl IntoIterator<Item = SocketAddr>) {
+        let mut addrs = addrs.into_iter().peekable();
+        if addrs.peek().is_none() {
+            return;
+        }
+
+        let clock = self.tick();
+        for addr in addrs {
+            let entries = &mut self.contents.entries;
+            if !entries.contains_key(&addr) {
+                self.dirty = true;
+            }
+            entries.entry(addr).or_default().last_seen = clock;
+        }
+
+        self.evict();
+
+        let due = self
+            .last_save
+            .map(|last_save| last_save.elapsed() >= SAVE_INTERVAL)
+            .unwrap_or(true);
+        if self.dirty && due {
+            self.save();
+        }
+    }
+
+    /// Records that the peer responded to our bootstrap request.
+    pub fn record_success(&mut self, addr: SocketAddr) {
+        let clock = self.tick();
+        let entry = self.contents.entries.entry(addr).or_default();
+        entry.last_seen = clock;
+        entry.last_success = Some(clock);
+        entry.failures = 0;
+
+        self.evict();
+        self.save();
+    }
+
+    /// Records that the peer didn't respond to our bootstrap request.
+    pub fn record_failure(&mut self, addr: SocketAddr) {
+        let entry = if let Some(entry) = self.contents.entries.get_mut(&addr) {
+            entry
+        } else {
+            return;
+        };
+
+        entry.failures += 1;
+        if entry.failures >= MAX_FAILURES {
+            let _ = self.contents.entries.remove(&addr);
+        }
+
+        self.save();
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.contents.clock += 1;
+        self.contents.clock
+    }
+
+    // Removes the lowest ranked entries exceeding `BOOTSTRAP_CACHE_SIZE`.
+    fn evict(&mut self) {
+        for addr in self.contacts().into_iter().skip(BOOTSTRAP_CACHE_SIZE) {
+            let _ = self.contents.entries.remove(&addr);
+        }
+    }
+
+    fn save(&mut self) {
+        self.dirty = false;
+        self.last_save = Some(Instant::now());
+
+        if let Some(path) = &self.path {
+            if let Err(error) = save(path, &self.contents) {
+                warn!("Failed to save bootstrap cache to {:?}: {:?}", path, error);
+            }
+        }
+    }
+}
+
+impl Drop for BootstrapCache {
+    fn drop(&mut self) {
+        if self.dirty {
+            self.save();
+        }
+    }
+}
+
+fn load(path: &Path) -> Result<Contents> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Contents::default()),
+        Err(error) => return Err(error.into()),
+    };
+
+    Ok(bincode::deserialize_from(BufReader::new(file))?)
+}
+
+// Writes to a temporary file first and then renames it, so a crash never leaves a partially
+// written cache behind.
+fn save(path: &Path, contents: &Contents) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, bincode::serialize(contents)?)?;
+    fs::rename(&temp_path, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rng, unwrap};
+    use rand::Rng;
+    use std::env;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn ranking() {
+        let mut cache = BootstrapCache::new(None);
+        cache.insert_elders(vec![addr(1), addr(2)]);
+        cache.insert_elders(vec![addr(3)]);
+
+        // Most recently seen first.
+        assert_eq!(cache.contacts()[0], addr(3));
+
+        // Successful contacts rank above the merely seen ones.
+        cache.record_success(addr(1));
+        assert_eq!(cache.contacts()[0], addr(1));
+
+        // Failing contacts rank last and are evicted eventually.
+        cache.record_failure(addr(3));
+        assert_eq!(cache.contacts(), vec![addr(1), addr(2), addr(3)]);
+        for _ in 1..MAX_FAILURES {
+            cache.record_failure(addr(3));
+        }
+        assert_eq!(cache.contacts(), vec![addr(1), addr(2)]);
+    }
+
+    #[test]
+    fn persistence() {
+        let mut rng = rng::new();
+        let dir = env::temp_dir().join(format!("routing-bootstrap-{:016x}", rng.gen::<u64>()));
+        let path = dir.join("bootstrap.cache");
+
+        let mut cache = BootstrapCache::new(Some(path.clone()));
+        cache.insert_elders(vec![addr(1), addr(2)]);
+        cache.record_success(addr(2));
+
+        let cache = BootstrapCache::new(Some(path));
+        assert_eq!(cache.contacts(), vec![addr(2), addr(1)]);
+
+        unwrap!(fs::remove_dir_all(&dir));
+    }
+
+    #[test]
+    fn save_new_elders_debounced() {
+        let mut rng = rng::new();
+        let dir = env::temp_dir().join(format!("routing-bootstrap-{:016x}", rng.gen::<u64>()));
+        let path = dir.join("bootstrap.cache");
+
+        let mut cache = BootstrapCache::new(Some(path.clone()));
+        cache.insert_elders(vec![addr(1), addr(2)]);
+        assert!(path.exists());
+
+        // Known elders don't cause a save.
+        unwrap!(fs::remove_file(&path));
+        cache.insert_elders(vec![addr(1), addr(2)]);
+        assert!(!path.exists());
+
+        // New elders do, but not sooner than `SAVE_INTERVAL` after the previous save...
+        cache.insert_elders(vec![addr(3)]);
+        assert!(!path.exists());
+
+        // ...or when the cache is dropped.
+        drop(cache);
+        let cache = BootstrapCache::new(Some(path));
+        assert_eq!(cache.contacts().len(), 3);
+
+        unwrap!(fs::remove_dir_all(&dir));
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Round-trip time estimate of the peers.
+//!
+//! Measured by pinging the peers: every `Pong` answering the last `Ping` sent to the peer is a
+//! sample of the time between sending the ping and receiving the pong. The samples are smoothed
+//! the same way TCP smooths its round-trip time (RFC 6298).
+
+use crate::time::{Duration, Instant};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    net::SocketAddr,
+};
+
+// The smoothed round-trip time moves by `1 / SMOOTHING_DIVISOR` of the difference to each new
+// sample.
+const SMOOTHING_DIVISOR: u32 = 8;
+
+#[derive(Default)]
+pub struct Latencies {
+    // The nonce of the last ping sent to each peer that wasn't answered yet, and when it was sent.
+    pings: HashMap<SocketAddr, (u64, Instant)>,
+    rtts: HashMap<SocketAddr, Duration>,
+}
+
+impl Latencies {
+    /// Records a ping sent to the peer. Any previous unanswered ping to it won't be sampled.
+    pub fn ping_sent(&mut self, addr: SocketAddr, nonce: u64) {
+        let _ = self.pings.insert(addr, (nonce, Instant::now()));
+    }
+
+    /// Samples the round-trip time to the peer if the pong answers the last ping sent to it.
+    pub fn pong_received(&mut self, addr: SocketAddr, nonce: u64) {
+        if let Entry::Occupied(entry) = self.pings.entry(addr) {
+            if entry.get().0 == nonce {
+                let (_, start) = entry.remove();
+                self.add_sample(addr, start.elapsed());
+            }
+        }
+    }
+
+    /// Returns the smoothed round-trip time to the peer, if measured.
+    pub fn rtt(&self, addr: &SocketAddr) -> Option<Duration> {
+        self.rtts.get(addr).copied()
+    }
+
+    /// Forgets the round-trip time to the peer and its unanswered ping, for example when
+    /// disconnected from it.
+    pub fn remove(&mut self, addr: &SocketAddr) {
+        let _ = self.rtts.remove(addr);
+        let _ = self.pings.remove(addr);
+    }
+
+    fn add_sample(&mut self, addr: SocketAddr, sample: Duration) {
+        let _ = self
+            .rtts
+            .entry(addr)
+            .and_modify(|rtt| {
+                *rtt = (*rtt * (SMOOTHING_DIVISOR - 1) + sample) / SMOOTHING_DIVISOR;
+            })
+            .or_insert(sample);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn smoothing() {
+        let mut latencies = Latencies::default();
+
+        // The first sample is taken as is.
+        latencies.add_sample(addr(1), Duration::from_millis(80));
+        assert_eq!(latencies.rtt(&addr(1)), Some(Duration::from_millis(80)));
+
+        // A single outlier moves it only by a fraction of the difference.
+        latencies.add_sample(addr(1), Duration::from_millis(880));
+        assert_eq!(latencies.rtt(&addr(1)), Some(Duration::from_millis(180)));
+
+        assert_eq!(latencies.rtt(&addr(2)), None);
+    }
+
+    #[test]
+    fn sample_pongs_answering_last_ping_only() {
+        let mut latencies = Latencies::default();
+
+        latencies.ping_sent(addr(1), 1);
+        latencies.ping_sent(addr(2), 2);
+
+        // Wrong nonce, or from a peer we didn't ping with it.
+        latencies.pong_received(addr(1), 2);
+        latencies.pong_received(addr(3), 2);
+        assert_eq!(latencies.rtt(&addr(1)), None);
+        assert_eq!(latencies.rtt(&addr(3)), None);
+
+        latencies.pong_received(addr(1), 1);
+        assert!(latencies.rtt(&addr(1)).is_some());
+
+        // A newer ping supersedes the unanswered one.
+        latencies.ping_sent(addr(2), 3);
+        latencies.pong_received(addr(2), 2);
+        assert_eq!(latencies.rtt(&addr(2)), None);
+        latencies.pong_received(addr(2), 3);
+        assert!(latencies.rtt(&addr(2)).is_some());
+
+        assert!(latencies.pings.is_empty());
+    }
+
+    #[test]
+    fn remove_forgets_unanswered_ping() {
+        let mut latencies = Latencies::default();
+
+        latencies.ping_sent(addr(1), 1);
+        latencies.ping_sent(addr(2), 2);
+        latencies.remove(&addr(1));
+
+        assert_eq!(latencies.pings.len(), 1);
+        assert!(latencies.pings.contains_key(&addr(2)));
+    }
+}
@@ -0,0 +1,280 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::TransportBackend;
+use crate::{
+    error::Result,
+    quic_p2p::{EventSenders, Peer, Token},
+    TransportEvent,
+};
+use bytes::Bytes;
+use crossbeam_channel::Sender;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// In-process network connecting the nodes of a single process through channels, without any
+/// sockets. Cloning it gives another handle to the same network.
+#[derive(Clone, Default)]
+pub struct MemoryNetwork {
+    inner: Arc<Mutex<MemoryNetworkInner>>,
+}
+
+#[derive(Default)]
+struct MemoryNetworkInner {
+    nodes: HashMap<SocketAddr, MemoryNode>,
+    last_port: u16,
+}
+
+struct MemoryNode {
+    tx: Sender<TransportEvent>,
+    // Nodes we exchanged messages with, to notify when we leave the network.
+    peers: HashSet<SocketAddr>,
+}
+
+impl MemoryNetwork {
+    /// Creates a new, empty network.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adds a node to the network and returns its newly assigned address.
+    fn connect(&self, node_tx: Sender<TransportEvent>) -> SocketAddr {
+        let mut inner = self.lock();
+        inner.last_port = inner.last_port.wrapping_add(1);
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, inner.last_port));
+        let _ = inner.nodes.insert(
+            addr,
+            MemoryNode {
+                tx: node_tx,
+                peers: HashSet::new(),
+            },
+        );
+        addr
+    }
+
+    // Removes the node from the network, reporting a connection failure to its peers.
+    fn disconnect(&self, addr: &SocketAddr) {
+        let mut inner = self.lock();
+        let node = if let Some(node) = inner.nodes.remove(addr) {
+            node
+        } else {
+            return;
+        };
+
+        for peer in node.peers {
+            if let Some(peer) = inner.nodes.get_mut(&peer) {
+                let _ = peer.peers.remove(addr);
+                let _ = peer.tx.send(TransportEvent::ConnectionFailure {
+                    peer: Peer::Node(*addr),
+                    err: io::Error::new(io::ErrorKind::ConnectionReset, "peer left the network")
+                        .into(),
+                });
+            }
+        }
+    }
+
+    // Forgets the peers of each other, so they are no longer notified when the other leaves.
+    fn forget(&self, a: &SocketAddr, b: &SocketAddr) {
+        let mut inner = self.lock();
+        for &(node, peer) in &[(a, b), (b, a)] {
+            if let Some(node) = inner.nodes.get_mut(node) {
+                let _ = node.peers.remove(peer);
+            }
+        }
+    }
+
+    // Delivers the event from the node at `src` to the node at `dst`, making them peers. Returns
+    // whether the `dst` node is in the network.
+    fn deliver(&self, src: &SocketAddr, dst: &SocketAddr, event: TransportEvent) -> bool {
+        let mut inner = self.lock();
+        let delivered = inner
+            .nodes
+            .get(dst)
+            .map(|node| node.tx.send(event).is_ok())
+            .unwrap_or(false);
+
+        if delivered {
+            for &(node, peer) in &[(src, dst), (dst, src)] {
+                if let Some(node) = inner.nodes.get_mut(node) {
+                    let _ = node.peers.insert(*peer);
+                }
+            }
+        }
+
+        delivered
+    }
+
+    fn lock(&self) -> MutexGuard<MemoryNetworkInner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Configuration of the in-process transport.
+#[derive(Clone, Default)]
+pub struct MemoryConfig {
+    /// The network to join.
+    pub network: MemoryNetwork,
+    /// Addresses of the nodes of the network to bootstrap off.
+    pub hard_coded_contacts: Vec<SocketAddr>,
+}
+
+// Transport backend exchanging messages with the other nodes of a `MemoryNetwork`. The nodes we
+// exchanged messages with are reported a connection failure when we leave the network, as they
+// would be by a real transport. Only nodes are supported, sending to clients always fails.
+pub struct MemoryBackend {
+    network: MemoryNetwork,
+    contacts: Vec<SocketAddr>,
+    our_addr: SocketAddr,
+    event_tx: Sender<TransportEvent>,
+}
+
+impl MemoryBackend {
+    pub fn new(event_tx: EventSenders, config: MemoryConfig) -> Self {
+        let our_addr = config.network.connect(event_tx.node_tx.clone());
+
+        Self {
+            network: config.network,
+            contacts: config.hard_coded_contacts,
+            our_addr,
+            event_tx: event_tx.node_tx,
+        }
+    }
+
+    fn report(&self, event: TransportEvent) {
+        let _ = self.event_tx.send(event);
+    }
+}
+
+impl TransportBackend for MemoryBackend {
+    fn bootstrap(&mut self) {
+        let our_addr = self.our_addr;
+        let contact = self.contacts.iter().find(|contact| {
+            self.network.deliver(
+                &our_addr,
+                contact,
+                TransportEvent::ConnectedTo {
+                    peer: Peer::Node(our_addr),
+                },
+            )
+        });
+
+        if let Some(contact) = contact {
+            self.report(TransportEvent::BootstrappedTo { node: *contact })
+        } else {
+            self.report(TransportEvent::BootstrapFailure)
+        }
+    }
+
+    fn send(&mut self, peer: Peer, msg: Bytes, token: Token) {
+        let delivered = match &peer {
+            Peer::Node(addr) => self.network.deliver(
+                &self.our_addr,
+                addr,
+                TransportEvent::NewMessage {
+                    peer: Peer::Node(self.our_addr),
+                    msg: msg.clone(),
+                },
+            ),
+            Peer::Client(_) => false,
+        };
+
+        if delivered {
+            self.report(TransportEvent::SentUserMessage { peer, msg, token })
+        } else {
+            self.report(TransportEvent::UnsentUserMessage { peer, msg, token })
+        }
+    }
+
+    fn our_connection_info(&mut self) -> Result<SocketAddr> {
+        Ok(self.our_addr)
+    }
+
+    fn disconnect_from(&mut self, addr: SocketAddr) {
+        // There are no connections to close - the messages are delivered directly.
+        self.network.forget(&self.our_addr, &addr)
+    }
+}
+
+impl Drop for MemoryBackend {
+    fn drop(&mut self) {
+        self.network.disconnect(&self.our_addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::{self as mpmc, Receiver};
+
+    fn gen_backend(
+        network: &MemoryNetwork,
+        contacts: Vec<SocketAddr>,
+    ) -> (MemoryBackend, Receiver<TransportEvent>) {
+        let (node_tx, node_rx) = mpmc::unbounded();
+        let (client_tx, _) = mpmc::unbounded();
+        let config = MemoryConfig {
+            network: network.clone(),
+            hard_coded_contacts: contacts,
+        };
+
+        (
+            MemoryBackend::new(EventSenders { node_tx, client_tx }, config),
+            node_rx,
+        )
+    }
+
+    #[test]
+    fn bootstrap_and_send() {
+        let network = MemoryNetwork::new();
+        let (mut a, a_rx) = gen_backend(&network, vec![]);
+        let a_addr = a.our_connection_info().unwrap();
+        let (mut b, b_rx) = gen_backend(&network, vec![a_addr]);
+        let b_addr = b.our_connection_info().unwrap();
+
+        b.bootstrap();
+        match b_rx.try_recv().unwrap() {
+            TransportEvent::BootstrappedTo { node } => assert_eq!(node, a_addr),
+            event => panic!("unexpected event {:?}", event),
+        }
+        match a_rx.try_recv().unwrap() {
+            TransportEvent::ConnectedTo { peer } => assert_eq!(peer.peer_addr(), b_addr),
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        b.send(Peer::Node(a_addr), Bytes::from_static(b"hello"), 7);
+        match a_rx.try_recv().unwrap() {
+            TransportEvent::NewMessage { peer, msg } => {
+                assert_eq!(peer.peer_addr(), b_addr);
+                assert_eq!(&msg[..], b"hello");
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        match b_rx.try_recv().unwrap() {
+            TransportEvent::SentUserMessage { token, .. } => assert_eq!(token, 7),
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        // The peers of a node that left the network are notified and sending to it fails.
+        drop(a);
+        match b_rx.try_recv().unwrap() {
+            TransportEvent::ConnectionFailure { peer, .. } => assert_eq!(peer.peer_addr(), a_addr),
+            event => panic!("unexpected event {:?}", event),
+        }
+        b.send(Peer::Node(a_addr), Bytes::from_static(b"bye"), 8);
+        match b_rx.try_recv().unwrap() {
+            TransportEvent::UnsentUserMessage { token, .. } => assert_eq!(token, 8),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+}
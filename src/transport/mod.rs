@@ -6,47 +6,141 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod backend;
+mod bandwidth;
+mod bootstrap_cache;
+mod latency;
+mod memory;
 mod sending_targets_cache;
+mod tcp;
 
-pub use sending_targets_cache::{Resend, RESEND_DELAY, RESEND_MAX_ATTEMPTS};
+pub use self::{
+    backend::TransportBackend,
+    bandwidth::{BandwidthConfig, PeerStats, RateLimit, MAX_OUTBOUND_DELAY},
+    bootstrap_cache::BootstrapCache,
+    memory::{MemoryConfig, MemoryNetwork},
+    sending_targets_cache::{RESEND_DELAY, RESEND_MAX_ATTEMPTS},
+    tcp::TcpConfig,
+};
 
+use self::{
+    bandwidth::{Bandwidth, Reception, Reservation},
+    latency::Latencies,
+    memory::MemoryBackend,
+    tcp::TcpBackend,
+};
 use crate::{
-    quic_p2p::{EventSenders, Peer, QuicP2p, QuicP2pError, Token},
+    error::Result,
+    messages::Priority,
+    quic_p2p::{EventSenders, Peer, QuicP2p, Token},
+    rng::MainRng,
     time::Duration,
     timer::Timer,
-    TransportConfig,
+    TransportConfig, TransportEvent,
 };
 use bytes::Bytes;
+use crossbeam_channel::Sender;
 use hex_fmt::HexFmt;
-use std::{collections::HashMap, net::SocketAddr, slice};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    mem,
+    net::SocketAddr,
+    path::PathBuf,
+    slice,
+};
 
-use sending_targets_cache::SendingTargetsCache;
+use sending_targets_cache::{Resend, SendingTargetsCache};
+
+// Maximal number of sends to a node handed over to the backend and not completed yet. Further sends
+// to the node wait in the outbound queues, so a message queued later but with a higher priority
+// overtakes them. The limit is per node, so a slow node doesn't hold up the sends to the others.
+const MAX_IN_FLIGHT_SENDS: usize = 64;
+
+/// Which network transport a node uses to communicate with the other nodes.
+#[derive(Clone)]
+pub enum TransportKind {
+    /// QUIC over UDP, configured by `NodeConfig::transport_config`. With the `mock_base` feature
+    /// this is the mock network.
+    Quic,
+    /// Plain TCP, for environments where QUIC/UDP is blocked. Supports nodes only, not clients.
+    /// The traffic is neither encrypted nor authenticated: a peer is identified by the IP it
+    /// connects from and the port it claims to listen on, so any process at the same IP can pass
+    /// for it. Use it on trusted networks only.
+    Tcp(TcpConfig),
+    /// In-process network through channels, for running many nodes in one process. Supports
+    /// nodes only, not clients.
+    Memory(MemoryConfig),
+}
+
+impl Default for TransportKind {
+    fn default() -> Self {
+        Self::Quic
+    }
+}
 
 /// Struct that handles network operations: sending and receiving messages, as well as resending on
 /// failure.
+///
+/// Messages to nodes are sent through one outbound queue per `Priority`. The queues are served
+/// strictly in order of priority, so bulk user traffic never delays the routing-internal messages.
+/// The traffic exchanged with each peer is accounted and the user traffic is kept within the
+/// limits of the `BandwidthConfig`: it's delayed as needed, or dropped if it would have to wait
+/// longer than `MAX_OUTBOUND_DELAY`. The round-trip time to each node is measured by pings.
 pub struct Transport {
-    quic_p2p: QuicP2p,
+    backend: Box<dyn TransportBackend>,
     cache: SendingTargetsCache,
+    /// Elders we've seen, to bootstrap off on the next start.
+    pub bootstrap_cache: BootstrapCache,
     next_msg_token: Token,
     scheduled_messages: HashMap<u64, ScheduledMessage>,
+    queues: BTreeMap<Priority, VecDeque<ScheduledMessage>>,
+    // Sends handed over to the backend whose completion hasn't been reported yet, by target.
+    in_flight_sends: HashMap<SocketAddr, HashSet<Token>>,
+    // Sends to nodes dropped for exceeding the outbound limit, not handled yet.
+    refused_sends: Vec<(Token, SocketAddr)>,
+    // Where to report the messages to clients dropped for exceeding the outbound limit.
+    client_tx: Sender<TransportEvent>,
+    bandwidth: Bandwidth,
+    latencies: Latencies,
 }
 
 impl Transport {
-    pub fn new(event_tx: EventSenders, config: TransportConfig) -> Result<Self, QuicP2pError> {
-        {
-            let quic_p2p = QuicP2p::with_config(event_tx, Some(config), Default::default(), false)?;
-
-            Ok(Transport {
-                quic_p2p,
-                cache: Default::default(),
-                next_msg_token: 0,
-                scheduled_messages: Default::default(),
-            })
-        }
+    pub fn new(
+        event_tx: EventSenders,
+        config: TransportConfig,
+        kind: TransportKind,
+        bootstrap_cache_file: Option<PathBuf>,
+        bandwidth_config: BandwidthConfig,
+    ) -> Result<Self> {
+        let client_tx = event_tx.client_tx.clone();
+        let backend: Box<dyn TransportBackend> = match kind {
+            TransportKind::Quic => Box::new(QuicP2p::with_config(
+                event_tx,
+                Some(config),
+                Default::default(),
+                false,
+            )?),
+            TransportKind::Tcp(config) => Box::new(TcpBackend::new(event_tx, config)?),
+            TransportKind::Memory(config) => Box::new(MemoryBackend::new(event_tx, config)),
+        };
+
+        Ok(Transport {
+            backend,
+            cache: Default::default(),
+            bootstrap_cache: BootstrapCache::new(bootstrap_cache_file),
+            next_msg_token: 0,
+            scheduled_messages: Default::default(),
+            queues: Default::default(),
+            in_flight_sends: HashMap::new(),
+            refused_sends: Vec::new(),
+            client_tx,
+            bandwidth: Bandwidth::new(bandwidth_config),
+            latencies: Latencies::default(),
+        })
     }
 
     pub fn bootstrap(&mut self) {
-        self.quic_p2p.bootstrap()
+        self.backend.bootstrap()
     }
 
     pub fn send_message_to_targets(
@@ -54,6 +148,8 @@ impl Transport {
         conn_infos: &[SocketAddr],
         delivery_group_size: usize,
         msg: Bytes,
+        priority: Priority,
+        timer: &Timer,
     ) {
         if conn_infos.len() < delivery_group_size {
             warn!(
@@ -67,97 +163,252 @@ impl Transport {
         let token = self.next_msg_token();
 
         trace!(
-            "Sending message with token {} to {:?}",
+            "Sending message with token {} and priority {:?} to {:?}",
             token,
+            priority,
             &conn_infos[..delivery_group_size.min(conn_infos.len())]
         );
 
         // initially only send to delivery_group_size targets
         for addr in conn_infos.iter().take(delivery_group_size) {
             // NetworkBytes is refcounted and cheap to clone.
-            self.send_now(*addr, msg.clone(), token);
+            self.send_after(*addr, msg.clone(), token, priority, timer, None);
         }
 
         self.cache
-            .insert_message(token, conn_infos, delivery_group_size);
+            .insert_message(token, msg, priority, conn_infos, delivery_group_size);
     }
 
     pub fn send_message_to_target_later(
         &mut self,
         target: &SocketAddr,
         content: Bytes,
+        priority: Priority,
         timer: &Timer,
         delay: Duration,
     ) {
         let token = self.next_msg_token();
-        self.send_later(*target, content, token, timer, delay);
-        self.cache.insert_message(token, slice::from_ref(target), 1);
+        self.send_after(
+            *target,
+            content.clone(),
+            token,
+            priority,
+            timer,
+            Some(delay),
+        );
+        self.cache
+            .insert_message(token, content, priority, slice::from_ref(target), 1);
+    }
+
+    // Client messages don't go through the queues, because their completion is reported to the
+    // application, not to us. They are kept within the outbound limit all the same, and the ones
+    // refused are reported to the application as unsent.
+    pub fn send_message_to_client(
+        &mut self,
+        target: SocketAddr,
+        msg: Bytes,
+        token: Token,
+        timer: &Timer,
+    ) {
+        match self.bandwidth.reserve_send(target, msg.len()) {
+            Reservation::Send => self.send_to_client(target, msg, token),
+            Reservation::Delay(throttle) => {
+                trace!(
+                    "Delaying message with token {} to client {} by {:?} - outbound limit exceeded.",
+                    token,
+                    target,
+                    throttle
+                );
+
+                let timer_token = timer.schedule(throttle);
+                let _ = self.scheduled_messages.insert(
+                    timer_token,
+                    ScheduledMessage {
+                        content: msg,
+                        token,
+                        target,
+                        priority: Priority::UserNormal,
+                        to_client: true,
+                    },
+                );
+            }
+            Reservation::Refuse => {
+                debug!(
+                    "Dropping message with token {} to client {} - outbound limit exceeded for longer than {:?}.",
+                    token,
+                    target,
+                    MAX_OUTBOUND_DELAY
+                );
+
+                let _ = self.client_tx.send(TransportEvent::UnsentUserMessage {
+                    peer: Peer::Client(target),
+                    msg,
+                    token,
+                });
+            }
+        }
+    }
+
+    /// Accounts a message received from the node at `sender`. Returns whether it's within the
+    /// inbound limit and should be handled. Disconnects from the sender if it keeps exceeding it.
+    /// Messages from `exempt` senders are always handled.
+    pub fn handle_received(&mut self, sender: SocketAddr, msg: &Bytes, exempt: bool) -> bool {
+        match self.bandwidth.handle_received(sender, msg.len(), exempt) {
+            Reception::Accept => true,
+            Reception::Drop => {
+                trace!("Dropping message from {} - inbound limit exceeded.", sender);
+                false
+            }
+            Reception::Disconnect => {
+                debug!(
+                    "Disconnecting from {} - inbound limit exceeded too many times.",
+                    sender
+                );
+                self.disconnect(sender);
+                false
+            }
+        }
     }
 
-    pub fn send_message_to_client(&mut self, target: SocketAddr, msg: Bytes, token: Token) {
-        let client = Peer::Client(target);
-        self.quic_p2p.send(client, msg, token);
+    /// Returns the smoothed round-trip time to the node, if measured yet.
+    pub fn rtt(&self, addr: &SocketAddr) -> Option<Duration> {
+        self.latencies.rtt(addr)
     }
 
-    pub fn target_succeeded(&mut self, token: Token, target: SocketAddr) {
-        self.cache.target_succeeded(token, target)
+    /// Records a `Ping` with the given nonce sent to the node.
+    pub fn ping_sent(&mut self, addr: SocketAddr, nonce: u64) {
+        self.latencies.ping_sent(addr, nonce)
+    }
+
+    /// Records a `Pong` received from the node, measuring the round-trip time to it.
+    pub fn pong_received(&mut self, addr: SocketAddr, nonce: u64) {
+        self.latencies.pong_received(addr, nonce)
+    }
+
+    /// Returns the traffic exchanged with the peers we are connected to.
+    pub fn peer_stats(&self) -> impl Iterator<Item = (&SocketAddr, &PeerStats)> {
+        self.bandwidth.stats()
+    }
+
+    pub fn target_succeeded(
+        &mut self,
+        token: Token,
+        target: SocketAddr,
+        timer: &Timer,
+        rng: &mut MainRng,
+    ) {
+        self.complete_send(token, target);
+
+        for resend in self.cache.target_succeeded(token, target, rng) {
+            self.resend(resend, timer);
+        }
     }
 
     pub fn target_failed(
         &mut self,
-        msg: Bytes,
         msg_token: Token,
         failed_target: SocketAddr,
         timer: &Timer,
-    ) -> PeerStatus {
-        match self.cache.target_failed(msg_token, failed_target) {
-            Resend::Now(next_target) => {
-                trace!(
-                    "Sending message with token {} to {} failed - resending to {} now",
-                    msg_token,
-                    failed_target,
-                    next_target
-                );
+        rng: &mut MainRng,
+    ) -> SendFailure {
+        self.complete_send(msg_token, failed_target);
 
-                self.send_now(next_target, msg, msg_token);
-                PeerStatus::Normal
-            }
-            Resend::Later(next_target, delay) => {
-                trace!(
-                    "Sending message with token {} to {} failed - resending to {} in {:?}",
-                    msg_token,
-                    failed_target,
-                    next_target,
-                    delay
-                );
+        let outcome = self.cache.target_failed(msg_token, failed_target, rng);
+
+        if outcome.resends.is_empty() {
+            trace!(
+                "Sending message with token {} to {} failed - not resending now.",
+                msg_token,
+                failed_target,
+            );
+        }
+
+        for resend in outcome.resends {
+            trace!(
+                "Sending message with token {} to {} failed - resending message with token {} to {} {}",
+                msg_token,
+                failed_target,
+                resend.token,
+                resend.target,
+                resend
+                    .delay
+                    .map(|delay| format!("in {:?}", delay))
+                    .unwrap_or_else(|| "now".to_string()),
+            );
 
-                self.send_later(next_target, msg, msg_token, timer, delay);
-                PeerStatus::Normal
+            self.resend(resend, timer);
+        }
+
+        if outcome.target_lost {
+            trace!(
+                "Sending message with token {} to {} failed too many times - giving up on the target.",
+                msg_token,
+                failed_target,
+            );
+        }
+
+        if outcome.undelivered.is_some() {
+            debug!(
+                "Message with token {} failed to reach any of its targets.",
+                msg_token
+            );
+        }
+
+        SendFailure {
+            peer_lost: outcome.target_lost,
+            undelivered: outcome.undelivered,
+        }
+    }
+
+    /// Handles the sends to nodes dropped for exceeding the outbound limit since the last call,
+    /// moving on to the other targets of their messages. Returns the messages that failed to reach
+    /// any of their targets.
+    pub fn handle_refused_sends(&mut self, timer: &Timer, rng: &mut MainRng) -> Vec<Bytes> {
+        let mut undelivered = Vec::new();
+
+        for (token, target) in mem::take(&mut self.refused_sends) {
+            let outcome = self.cache.target_refused(token, target, rng);
+
+            for resend in outcome.resends {
+                self.resend(resend, timer);
             }
-            Resend::Never => {
-                trace!(
-                    "Sending message with token {} to {} failed too many times - giving up.",
-                    msg_token,
-                    failed_target,
-                );
 
-                PeerStatus::Lost
+            if let Some(msg) = outcome.undelivered {
+                debug!(
+                    "Message with token {} failed to reach any of its targets.",
+                    token
+                );
+                undelivered.push(msg);
             }
         }
+
+        undelivered
     }
 
-    pub fn our_connection_info(&mut self) -> Result<SocketAddr, QuicP2pError> {
-        self.quic_p2p.our_connection_info()
+    pub fn our_connection_info(&mut self) -> Result<SocketAddr> {
+        self.backend.our_connection_info()
     }
 
     pub fn disconnect(&mut self, addr: SocketAddr) {
-        self.quic_p2p.disconnect_from(addr)
+        self.bandwidth.remove(&addr);
+        self.latencies.remove(&addr);
+        self.backend.disconnect_from(addr);
+        self.abandon_sends(&addr);
+    }
+
+    pub fn handle_connection_failure(&mut self, addr: &SocketAddr) {
+        self.bandwidth.remove(addr);
+        self.latencies.remove(addr);
+        self.abandon_sends(addr);
     }
 
     pub fn handle_timeout(&mut self, timer_token: u64) -> bool {
         if let Some(msg) = self.scheduled_messages.remove(&timer_token) {
-            self.quic_p2p
-                .send(Peer::Node(msg.target), msg.content, msg.token);
+            if msg.to_client {
+                self.send_to_client(msg.target, msg.content, msg.token);
+            } else {
+                self.enqueue(msg);
+            }
             true
         } else {
             false
@@ -169,27 +420,138 @@ impl Transport {
         self.next_msg_token
     }
 
-    fn send_now(&mut self, target: SocketAddr, content: Bytes, token: Token) {
-        self.quic_p2p.send(Peer::Node(target), content, token)
+    fn send_to_client(&mut self, target: SocketAddr, msg: Bytes, token: Token) {
+        self.bandwidth.handle_sent(target, msg.len());
+        self.backend.send(Peer::Client(target), msg, token);
     }
 
-    fn send_later(
+    // Sends the message after the given delay, or immediately if `None`. The delay of user
+    // messages is extended as needed to keep within the outbound limit of the target, or the send
+    // is refused if it would take too long. The routing-internal messages are exempt from the
+    // limit: delaying them at queue time would hold them up behind the user messages queued
+    // before them.
+    fn send_after(
         &mut self,
         target: SocketAddr,
         content: Bytes,
         token: Token,
+        priority: Priority,
         timer: &Timer,
-        delay: Duration,
+        delay: Option<Duration>,
     ) {
-        let timer_token = timer.schedule(delay);
-        let _ = self.scheduled_messages.insert(
-            timer_token,
-            ScheduledMessage {
-                content,
-                token,
-                target,
-            },
-        );
+        let reservation = if priority.is_user() {
+            self.bandwidth.reserve_send(target, content.len())
+        } else {
+            Reservation::Send
+        };
+        let throttle = match reservation {
+            Reservation::Send => None,
+            Reservation::Delay(throttle) => {
+                trace!(
+                    "Delaying message with token {} to {} by {:?} - outbound limit exceeded.",
+                    token,
+                    target,
+                    throttle
+                );
+                Some(throttle)
+            }
+            Reservation::Refuse => {
+                debug!(
+                    "Dropping message with token {} to {} - outbound limit exceeded for longer than {:?}.",
+                    token,
+                    target,
+                    MAX_OUTBOUND_DELAY
+                );
+                // Handled later, as the message might not be in the cache yet.
+                self.refused_sends.push((token, target));
+                return;
+            }
+        };
+
+        let msg = ScheduledMessage {
+            content,
+            token,
+            target,
+            priority,
+            to_client: false,
+        };
+
+        if let Some(delay) = delay.max(throttle) {
+            let timer_token = timer.schedule(delay);
+            let _ = self.scheduled_messages.insert(timer_token, msg);
+        } else {
+            self.enqueue(msg)
+        }
+    }
+
+    fn resend(&mut self, resend: Resend, timer: &Timer) {
+        self.send_after(
+            resend.target,
+            resend.msg,
+            resend.token,
+            resend.priority,
+            timer,
+            resend.delay,
+        )
+    }
+
+    fn enqueue(&mut self, msg: ScheduledMessage) {
+        self.queues.entry(msg.priority).or_default().push_back(msg);
+        self.flush_queues();
+    }
+
+    // Records the completion of a send, making room for the next queued one.
+    fn complete_send(&mut self, token: Token, target: SocketAddr) {
+        let removed = if let Some(tokens) = self.in_flight_sends.get_mut(&target) {
+            let removed = tokens.remove(&token);
+            if tokens.is_empty() {
+                let _ = self.in_flight_sends.remove(&target);
+            }
+            removed
+        } else {
+            false
+        };
+
+        if removed {
+            self.flush_queues();
+        }
+    }
+
+    // Stops waiting for the completion of the sends to the peer, as the backend might never report
+    // them once the connection is gone.
+    fn abandon_sends(&mut self, addr: &SocketAddr) {
+        if self.in_flight_sends.remove(addr).is_some() {
+            self.flush_queues();
+        }
+    }
+
+    // Hands the queued messages over to the backend, highest priority first, to the nodes with
+    // fewer than `MAX_IN_FLIGHT_SENDS` sends in flight. The messages to the other nodes stay
+    // queued in order.
+    fn flush_queues(&mut self) {
+        for queue in self.queues.values_mut().rev() {
+            let mut index = 0;
+
+            while index < queue.len() {
+                let target = queue[index].target;
+                let tokens = self.in_flight_sends.entry(target).or_default();
+                if tokens.len() >= MAX_IN_FLIGHT_SENDS {
+                    index += 1;
+                    continue;
+                }
+
+                let msg = if let Some(msg) = queue.remove(index) {
+                    msg
+                } else {
+                    break;
+                };
+
+                let _ = tokens.insert(msg.token);
+                self.bandwidth.handle_sent(msg.target, msg.content.len());
+                self.backend
+                    .send(Peer::Node(msg.target), msg.content, msg.token);
+            }
+        }
     }
 }
 
@@ -197,10 +559,251 @@ struct ScheduledMessage {
     content: Bytes,
     token: Token,
     target: SocketAddr,
+    priority: Priority,
+    to_client: bool,
 }
 
+/// Consequences of a failed send that need handling by the upper layer.
 #[derive(Debug)]
-pub enum PeerStatus {
-    Normal,
-    Lost,
+pub struct SendFailure {
+    /// The target failed too many times in a row and should be considered lost.
+    pub peer_lost: bool,
+    /// The message that definitively failed to reach any of its targets, if any.
+    pub undelivered: Option<Bytes>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rng, unwrap, TransportEvent};
+    use crossbeam_channel::{self as mpmc, Receiver};
+
+    fn gen_transport(network: &MemoryNetwork) -> (Transport, Receiver<TransportEvent>) {
+        gen_throttled_transport(network, BandwidthConfig::default())
+    }
+
+    fn gen_throttled_transport(
+        network: &MemoryNetwork,
+        bandwidth_config: BandwidthConfig,
+    ) -> (Transport, Receiver<TransportEvent>) {
+        let (node_tx, node_rx) = mpmc::unbounded();
+        let (client_tx, _) = mpmc::unbounded();
+        let kind = TransportKind::Memory(MemoryConfig {
+            network: network.clone(),
+            hard_coded_contacts: vec![],
+        });
+        let transport = unwrap!(Transport::new(
+            EventSenders { node_tx, client_tx },
+            TransportConfig::default(),
+            kind,
+            None,
+            bandwidth_config,
+        ));
+
+        (transport, node_rx)
+    }
+
+    fn received(rx: &Receiver<TransportEvent>) -> Vec<Bytes> {
+        rx.try_iter()
+            .filter_map(|event| match event {
+                TransportEvent::NewMessage { msg, .. } => Some(msg),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn higher_priority_first() {
+        let network = MemoryNetwork::new();
+        let (mut sender, _sender_rx) = gen_transport(&network);
+        let (mut receiver, receiver_rx) = gen_transport(&network);
+        let receiver_addr = unwrap!(receiver.our_connection_info());
+        let timer = Timer::new(mpmc::unbounded().0);
+        let mut rng = rng::new();
+
+        let mut send = |msg: &'static [u8], priority| {
+            sender.send_message_to_targets(
+                slice::from_ref(&receiver_addr),
+                1,
+                Bytes::from_static(msg),
+                priority,
+                &timer,
+            )
+        };
+
+        for _ in 0..MAX_IN_FLIGHT_SENDS {
+            send(b"bulk", Priority::UserLow);
+        }
+        send(b"bulk", Priority::UserLow);
+        send(b"user", Priority::UserNormal);
+        send(b"churn", Priority::Churn);
+
+        // Only the sends fitting in flight went out.
+        assert_eq!(received(&receiver_rx).len(), MAX_IN_FLIGHT_SENDS);
+
+        // As the sends in flight complete, the queued messages follow in order of priority.
+        let mut next = |token| {
+            sender.target_succeeded(token, receiver_addr, &timer, &mut rng);
+            received(&receiver_rx)
+        };
+        assert_eq!(next(1), vec![Bytes::from_static(b"churn")]);
+        assert_eq!(next(2), vec![Bytes::from_static(b"user")]);
+        assert_eq!(next(3), vec![Bytes::from_static(b"bulk")]);
+        assert!(next(4).is_empty());
+    }
+
+    #[test]
+    fn in_flight_limit_per_peer() {
+        let network = MemoryNetwork::new();
+        let (mut sender, _sender_rx) = gen_transport(&network);
+        let (mut receiver0, receiver0_rx) = gen_transport(&network);
+        let (mut receiver1, receiver1_rx) = gen_transport(&network);
+        let receiver0_addr = unwrap!(receiver0.our_connection_info());
+        let receiver1_addr = unwrap!(receiver1.our_connection_info());
+        let timer = Timer::new(mpmc::unbounded().0);
+
+        for _ in 0..MAX_IN_FLIGHT_SENDS {
+            sender.send_message_to_targets(
+                slice::from_ref(&receiver0_addr),
+                1,
+                Bytes::from_static(b"first"),
+                Priority::UserNormal,
+                &timer,
+            );
+        }
+        sender.send_message_to_targets(
+            slice::from_ref(&receiver1_addr),
+            1,
+            Bytes::from_static(b"second"),
+            Priority::UserNormal,
+            &timer,
+        );
+
+        // The node with its window full doesn't hold up the sends to the other nodes.
+        assert_eq!(received(&receiver0_rx).len(), MAX_IN_FLIGHT_SENDS);
+        assert_eq!(received(&receiver1_rx), vec![Bytes::from_static(b"second")]);
+
+        // Further sends to the node wait for its sends in flight to complete.
+        sender.send_message_to_targets(
+            slice::from_ref(&receiver0_addr),
+            1,
+            Bytes::from_static(b"third"),
+            Priority::UserNormal,
+            &timer,
+        );
+        assert!(received(&receiver0_rx).is_empty());
+
+        // The completions of the sends to the disconnected node never come, so they are released.
+        sender.disconnect(receiver0_addr);
+        assert!(!sender.in_flight_sends.contains_key(&receiver0_addr));
+    }
+
+    #[test]
+    fn throttle_only_user_traffic() {
+        let network = MemoryNetwork::new();
+        let (mut sender, _sender_rx) = gen_throttled_transport(
+            &network,
+            BandwidthConfig {
+                inbound: None,
+                outbound: Some(RateLimit {
+                    bytes_per_sec: 100,
+                    burst_bytes: 100,
+                    msgs_per_sec: 1,
+                    burst_msgs: 1,
+                }),
+                disconnect_after: None,
+            },
+        );
+        let (mut receiver, receiver_rx) = gen_transport(&network);
+        let receiver_addr = unwrap!(receiver.our_connection_info());
+        let timer = Timer::new(mpmc::unbounded().0);
+
+        let mut send = |msg: &'static [u8], priority| {
+            sender.send_message_to_targets(
+                slice::from_ref(&receiver_addr),
+                1,
+                Bytes::from_static(msg),
+                priority,
+                &timer,
+            )
+        };
+
+        // The bulk traffic uses up the limit, but the internal traffic still goes out right away.
+        send(b"bulk", Priority::UserLow);
+        send(b"bulk", Priority::UserLow);
+        send(b"churn", Priority::Churn);
+        send(b"consensus", Priority::Consensus);
+
+        assert_eq!(
+            received(&receiver_rx),
+            vec![
+                Bytes::from_static(b"bulk"),
+                Bytes::from_static(b"churn"),
+                Bytes::from_static(b"consensus"),
+            ]
+        );
+    }
+
+    #[test]
+    fn refuse_over_max_outbound_delay() {
+        let network = MemoryNetwork::new();
+        let (mut sender, _sender_rx) = gen_throttled_transport(
+            &network,
+            BandwidthConfig {
+                inbound: None,
+                outbound: Some(RateLimit {
+                    bytes_per_sec: 100,
+                    burst_bytes: 100,
+                    msgs_per_sec: 1,
+                    burst_msgs: 1,
+                }),
+                disconnect_after: None,
+            },
+        );
+        let (mut receiver0, receiver0_rx) = gen_transport(&network);
+        let (mut receiver1, receiver1_rx) = gen_transport(&network);
+        let receiver0_addr = unwrap!(receiver0.our_connection_info());
+        let receiver1_addr = unwrap!(receiver1.our_connection_info());
+        let timer = Timer::new(mpmc::unbounded().0);
+        let mut rng = rng::new();
+
+        // One message goes out right away, the next ones are delayed up to `MAX_OUTBOUND_DELAY`.
+        for _ in 0..=MAX_OUTBOUND_DELAY.as_secs() {
+            sender.send_message_to_targets(
+                slice::from_ref(&receiver0_addr),
+                1,
+                Bytes::from_static(b"bulk"),
+                Priority::UserLow,
+                &timer,
+            );
+        }
+        assert_eq!(received(&receiver0_rx).len(), 1);
+        assert!(sender.handle_refused_sends(&timer, &mut rng).is_empty());
+
+        // Beyond that, the message moves on to its next target.
+        sender.send_message_to_targets(
+            &[receiver0_addr, receiver1_addr],
+            1,
+            Bytes::from_static(b"user"),
+            Priority::UserNormal,
+            &timer,
+        );
+        assert!(sender.handle_refused_sends(&timer, &mut rng).is_empty());
+        assert!(received(&receiver0_rx).is_empty());
+        assert_eq!(received(&receiver1_rx), vec![Bytes::from_static(b"user")]);
+
+        // Or is reported undelivered if there is none.
+        sender.send_message_to_targets(
+            slice::from_ref(&receiver0_addr),
+            1,
+            Bytes::from_static(b"user"),
+            Priority::UserNormal,
+            &timer,
+        );
+        assert_eq!(
+            sender.handle_refused_sends(&timer, &mut rng),
+            vec![Bytes::from_static(b"user")]
+        );
+        assert!(received(&receiver0_rx).is_empty());
+    }
 }
@@ -6,48 +6,117 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{quic_p2p::Token, time::Duration};
-use std::{collections::HashMap, net::SocketAddr};
+use crate::{
+    messages::Priority,
+    quic_p2p::Token,
+    rng::MainRng,
+    time::{Duration, Instant},
+};
+use bytes::Bytes;
+use rand::Rng;
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+};
 
-/// Maximal number of resend attempts to the same target.
+/// Maximal number of send attempts to the same target.
 pub const RESEND_MAX_ATTEMPTS: u8 = 3;
-/// Delay before attempting to resend a previously failed message.
+/// Delay before the first resend to a previously failed target. Doubles with every further
+/// failure of the target.
 pub const RESEND_DELAY: Duration = Duration::from_secs(10);
+// Maximal number of resends in flight at any time. Further resends wait until some of them
+// complete.
+const MAX_IN_FLIGHT_RESENDS: usize = 64;
+// Maximal deviation of a resend delay from its nominal value, as a fraction of it.
+const RESEND_JITTER: f64 = 0.25;
+// How long after a successful send the target is preferred when choosing where to resend.
+const RECENT_SUCCESS_WINDOW: Duration = Duration::from_secs(60);
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum TargetState {
     /// we don't know whether the last send attempt succeeded or failed
     /// the stored number of attempts already failed before
-    Sending(u8),
+    Sending { failures: u8, resend: bool },
     /// the last sending attempt (if any) failed; in total, the stored number of attempts failed
     Failed(u8),
     /// sending to this target succeeded
     Sent,
 }
 
-impl TargetState {
-    pub fn is_sending(&self) -> bool {
-        match *self {
-            Self::Failed(_) | Self::Sent => false,
-            Self::Sending(_) => true,
-        }
+struct Entry {
+    msg: Bytes,
+    priority: Priority,
+    targets: Vec<(SocketAddr, TargetState)>,
+    // Whether the message is waiting for a free resend slot.
+    parked: bool,
+}
+
+impl Entry {
+    fn is_sending(&self) -> bool {
+        self.targets.iter().any(|(_, state)| match state {
+            TargetState::Sending { .. } => true,
+            TargetState::Failed(_) | TargetState::Sent => false,
+        })
+    }
+
+    fn is_sent(&self) -> bool {
+        self.targets
+            .iter()
+            .any(|(_, state)| *state == TargetState::Sent)
     }
 }
 
+/// Send to be performed by the transport.
+#[derive(Debug)]
+pub struct Resend {
+    pub token: Token,
+    pub target: SocketAddr,
+    pub msg: Bytes,
+    pub priority: Priority,
+    /// Delay before the send, or `None` to send immediately.
+    pub delay: Option<Duration>,
+}
+
+/// Outcome of handling a failed send to a single target.
+#[derive(Debug, Default)]
+pub struct FailureOutcome {
+    /// Sends to perform in response.
+    pub resends: Vec<Resend>,
+    /// The target failed `RESEND_MAX_ATTEMPTS` times in a row.
+    pub target_lost: bool,
+    /// The message definitively failed to reach any of its targets.
+    pub undelivered: Option<Bytes>,
+}
+
+/// Tracks the targets of the messages being sent and decides where and when to resend them on
+/// failure.
+///
+/// A failed target is replaced by the next untried target immediately, preferring the ones we
+/// recently sent to successfully. Once all targets have been tried, the failed ones are retried
+/// with exponential backoff and jitter, up to `RESEND_MAX_ATTEMPTS` attempts each. At most
+/// `MAX_IN_FLIGHT_RESENDS` resends are in flight at once, the rest wait for their turn in order.
 #[derive(Default)]
 pub struct SendingTargetsCache {
-    cache: HashMap<Token, Vec<(SocketAddr, TargetState)>>,
+    cache: HashMap<Token, Entry>,
+    // When the last send to each target succeeded, if recently.
+    recent_successes: HashMap<SocketAddr, Instant>,
+    in_flight_resends: usize,
+    // Messages waiting for a free resend slot, in order.
+    parked: VecDeque<Token>,
 }
 
 impl SendingTargetsCache {
     pub fn insert_message(
         &mut self,
         token: Token,
+        msg: Bytes,
+        priority: Priority,
         initial_targets: &[SocketAddr],
         delivery_group_size: usize,
     ) {
         // When a message is inserted into the cache initially, we are only sending it to
         // `delivery_group_size` targets with the highest priority - thus, we will set the first
-        // `delivery_group_size` targets' states to Sending(0), and the rest to Failed(0)
+        // `delivery_group_size` targets' states to Sending, and the rest to Failed(0)
         // (indicating that we haven't sent to them, and so they haven't failed yet)
         let targets = initial_targets
             .iter()
@@ -56,114 +125,379 @@ impl SendingTargetsCache {
                 (
                     *tgt_info,
                     if idx < delivery_group_size {
-                        TargetState::Sending(0)
+                        TargetState::Sending {
+                            failures: 0,
+                            resend: false,
+                        }
                     } else {
                         TargetState::Failed(0)
                     },
                 )
             })
             .collect();
-        let _ = self.cache.insert(token, targets);
+        let _ = self.cache.insert(
+            token,
+            Entry {
+                msg,
+                priority,
+                targets,
+                parked: false,
+            },
+        );
     }
 
-    fn target_states(&self, token: Token) -> impl Iterator<Item = &(SocketAddr, TargetState)> {
-        self.cache.get(&token).into_iter().flatten()
+    pub fn target_succeeded(
+        &mut self,
+        token: Token,
+        target: SocketAddr,
+        rng: &mut MainRng,
+    ) -> Vec<Resend> {
+        if self
+            .recent_successes
+            .insert(target, Instant::now())
+            .is_none()
+        {
+            // New target - a good time to forget the ones that are no longer recent.
+            self.recent_successes
+                .retain(|_, time| time.elapsed() < RECENT_SUCCESS_WINDOW);
+        }
+
+        if let Some(state) = self.target_state_mut(token, target) {
+            if let TargetState::Sending { resend, .. } = *state {
+                *state = TargetState::Sent;
+                self.resend_completed(resend);
+            }
+        }
+
+        let _ = self.drop_if_finished(token);
+        self.unpark(rng)
     }
 
-    fn target_states_mut(
+    pub fn target_failed(
         &mut self,
         token: Token,
-    ) -> impl Iterator<Item = &mut (SocketAddr, TargetState)> {
-        self.cache.get_mut(&token).into_iter().flatten()
+        target: SocketAddr,
+        rng: &mut MainRng,
+    ) -> FailureOutcome {
+        let mut outcome = FailureOutcome::default();
+
+        let state = if let Some(state) = self.target_state_mut(token, target) {
+            state
+        } else {
+            return outcome;
+        };
+
+        match *state {
+            TargetState::Sending { failures, resend } => {
+                *state = TargetState::Failed(failures + 1);
+                outcome.target_lost = failures + 1 >= RESEND_MAX_ATTEMPTS;
+                self.resend_completed(resend);
+            }
+            TargetState::Failed(_) => {
+                log_or_panic!(log::Level::Error, "Got a failure from a failed target!");
+                return outcome;
+            }
+            TargetState::Sent => {
+                log_or_panic!(
+                    log::Level::Error,
+                    "A target that should no longer fail - failed!"
+                );
+                return outcome;
+            }
+        }
+
+        self.replace_target(token, rng, outcome)
     }
 
-    fn fail_target(&mut self, token: Token, target: SocketAddr) {
-        if let Some((_addr, state)) = self
-            .target_states_mut(token)
-            .find(|(addr, _state)| *addr == target)
-        {
-            match *state {
-                TargetState::Failed(_) => {
-                    log_or_panic!(log::Level::Error, "Got a failure from a failed target!");
-                }
-                TargetState::Sending(x) => {
-                    *state = TargetState::Failed(x + 1);
-                }
-                TargetState::Sent => {
-                    log_or_panic!(
-                        log::Level::Error,
-                        "A target that should no longer fail - failed!"
-                    );
-                }
+    /// Handles a send to the target dropped before it was attempted, for exceeding its outbound
+    /// limit. The target isn't tried again for this message, but it isn't considered lost either.
+    pub fn target_refused(
+        &mut self,
+        token: Token,
+        target: SocketAddr,
+        rng: &mut MainRng,
+    ) -> FailureOutcome {
+        let outcome = FailureOutcome::default();
+
+        let state = if let Some(state) = self.target_state_mut(token, target) {
+            state
+        } else {
+            return outcome;
+        };
+
+        match *state {
+            TargetState::Sending { resend, .. } => {
+                *state = TargetState::Failed(RESEND_MAX_ATTEMPTS);
+                self.resend_completed(resend);
             }
+            TargetState::Failed(_) | TargetState::Sent => return outcome,
         }
+
+        self.replace_target(token, rng, outcome)
+    }
+
+    // Sends the message to the next target in place of a failed one, or drops it if there are none
+    // left.
+    fn replace_target(
+        &mut self,
+        token: Token,
+        rng: &mut MainRng,
+        mut outcome: FailureOutcome,
+    ) -> FailureOutcome {
+        if self.in_flight_resends < MAX_IN_FLIGHT_RESENDS {
+            outcome.resends.extend(self.take_next_target(token, rng));
+        } else if self.has_next_target(token) {
+            self.park(token);
+        }
+
+        outcome.undelivered = self.drop_if_finished(token);
+        outcome.resends.extend(self.unpark(rng));
+        outcome
+    }
+
+    fn target_state_mut(&mut self, token: Token, target: SocketAddr) -> Option<&mut TargetState> {
+        self.cache
+            .get_mut(&token)?
+            .targets
+            .iter_mut()
+            .find(|(addr, _)| *addr == target)
+            .map(|(_, state)| state)
+    }
+
+    fn resend_completed(&mut self, resend: bool) {
+        if resend {
+            self.in_flight_resends = self.in_flight_resends.saturating_sub(1);
+        }
+    }
+
+    fn has_next_target(&self, token: Token) -> bool {
+        self.cache.get(&token).map_or(false, |entry| {
+            entry.targets.iter().any(|(_, state)| match state {
+                TargetState::Failed(failures) => *failures < RESEND_MAX_ATTEMPTS,
+                TargetState::Sending { .. } | TargetState::Sent => false,
+            })
+        })
     }
 
     /// Finds a Failed target with the lowest number of failed attempts so far. If there are
-    /// multiple possibilities, the one with the highest priority (earliest in the list) is taken.
-    /// Returns `Never` if no such targets exist.
-    fn take_next_target(&mut self, token: Token) -> Resend {
-        if let Some((addr, failed_attempts, state)) = self
-            .target_states_mut(token)
-            .filter_map(|(addr, state)| match state {
-                TargetState::Failed(x) if *x < RESEND_MAX_ATTEMPTS => Some((*addr, *x, state)),
-                TargetState::Failed(_) | TargetState::Sending(_) | TargetState::Sent => None,
+    /// multiple possibilities, the ones that recently succeeded are preferred and then the one
+    /// with the highest priority (earliest in the list) is taken. Returns `None` if no such targets
+    /// exist.
+    fn take_next_target(&mut self, token: Token, rng: &mut MainRng) -> Option<Resend> {
+        let recent_successes = &self.recent_successes;
+        let entry = self.cache.get_mut(&token)?;
+        let (target, failures, state) = entry
+            .targets
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, (addr, state))| {
+                let failures = match *state {
+                    TargetState::Failed(failures) if failures < RESEND_MAX_ATTEMPTS => failures,
+                    TargetState::Failed(_) | TargetState::Sending { .. } | TargetState::Sent => {
+                        return None
+                    }
+                };
+                let recent = recent_successes
+                    .get(addr)
+                    .map_or(false, |time| time.elapsed() < RECENT_SUCCESS_WINDOW);
+                Some(((failures, !recent, index), (*addr, failures, state)))
             })
-            .min_by_key(|(_addr, failed_attempts, _state)| *failed_attempts)
-        {
-            *state = TargetState::Sending(failed_attempts);
+            .min_by_key(|(key, _)| *key)
+            .map(|(_, target)| target)?;
+
+        *state = TargetState::Sending {
+            failures,
+            resend: true,
+        };
+        entry.parked = false;
+        self.in_flight_resends += 1;
 
-            if failed_attempts == 0 {
-                Resend::Now(addr)
+        Some(Resend {
+            token,
+            target,
+            msg: entry.msg.clone(),
+            priority: entry.priority,
+            delay: if failures == 0 {
+                None
             } else {
-                Resend::Later(addr, RESEND_DELAY)
+                Some(backoff(failures, rng))
+            },
+        })
+    }
+
+    fn park(&mut self, token: Token) {
+        if let Some(entry) = self.cache.get_mut(&token) {
+            if !entry.parked {
+                entry.parked = true;
+                self.parked.push_back(token);
             }
-        } else {
-            Resend::Never
         }
     }
 
-    fn should_drop(&self, token: Token) -> bool {
+    // Resends the parked messages while there are free resend slots.
+    fn unpark(&mut self, rng: &mut MainRng) -> Vec<Resend> {
+        let mut resends = Vec::new();
+
+        while self.in_flight_resends < MAX_IN_FLIGHT_RESENDS {
+            let token = if let Some(token) = self.parked.pop_front() {
+                token
+            } else {
+                break;
+            };
+
+            if self.cache.get(&token).map_or(true, |entry| !entry.parked) {
+                continue;
+            }
+
+            resends.extend(self.take_next_target(token, rng));
+            let _ = self.drop_if_finished(token);
+        }
+
+        resends
+    }
+
+    // Drops the message if its handling is finished. Returns it if it failed to reach any target.
+    fn drop_if_finished(&mut self, token: Token) -> Option<Bytes> {
         // Other methods maintain the invariant that exactly one of these is true:
-        // - some target is in the Sending state
+        // - some target is in the Sending state or the message is waiting for a resend slot
         // - we succeeded (no further sending needed)
         // - we failed (no more targets available)
-        // So if none are sending, the handling of the message is finished and we can drop it
-        self.target_states(token)
-            .all(|(_info, state)| !state.is_sending())
+        // So if neither is sending nor waiting, the handling of the message is finished and we
+        // can drop it
+        match self.cache.get(&token) {
+            Some(entry) if !entry.is_sending() && !entry.parked => (),
+            _ => return None,
+        }
+
+        let entry = self.cache.remove(&token)?;
+        if entry.is_sent() {
+            None
+        } else {
+            Some(entry.msg)
+        }
     }
+}
 
-    pub fn target_failed(&mut self, token: Token, target: SocketAddr) -> Resend {
-        self.fail_target(token, target);
+// Delay before resending to a target that failed the given number of times.
+fn backoff(failures: u8, rng: &mut MainRng) -> Duration {
+    let nominal = RESEND_DELAY * 2u32.pow(u32::from(failures.saturating_sub(1)));
+    nominal.mul_f64(rng.gen_range(1.0 - RESEND_JITTER, 1.0 + RESEND_JITTER))
+}
 
-        let next_target = self.take_next_target(token);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng;
 
-        if self.should_drop(token) {
-            let _ = self.cache.remove(&token);
-        }
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
 
-        next_target
+    fn targets(resends: &[Resend]) -> Vec<SocketAddr> {
+        resends.iter().map(|resend| resend.target).collect()
     }
 
-    pub fn target_succeeded(&mut self, token: Token, target: SocketAddr) {
-        let _ = self
-            .target_states_mut(token)
-            .find(|(addr, _state)| *addr == target)
-            .map(|(_addr, state)| {
-                *state = TargetState::Sent;
-            });
-        if self.should_drop(token) {
-            let _ = self.cache.remove(&token);
+    #[test]
+    fn backoff_until_lost() {
+        let mut rng = rng::new();
+        let mut cache = SendingTargetsCache::default();
+        let msg = Bytes::from_static(b"hello");
+        cache.insert_message(1, msg.clone(), Priority::Routing, &[addr(1)], 1);
+
+        let mut last_delay = Duration::from_secs(0);
+        for _ in 1..RESEND_MAX_ATTEMPTS {
+            let outcome = cache.target_failed(1, addr(1), &mut rng);
+            assert!(!outcome.target_lost);
+            assert!(outcome.undelivered.is_none());
+            assert_eq!(targets(&outcome.resends), vec![addr(1)]);
+
+            let delay = outcome.resends[0].delay.unwrap();
+            assert!(delay > last_delay);
+            last_delay = delay;
         }
+
+        let outcome = cache.target_failed(1, addr(1), &mut rng);
+        assert!(outcome.target_lost);
+        assert!(outcome.resends.is_empty());
+        assert_eq!(outcome.undelivered, Some(msg));
+    }
+
+    #[test]
+    fn refused_target_not_retried() {
+        let mut rng = rng::new();
+        let mut cache = SendingTargetsCache::default();
+        let msg = Bytes::from_static(b"hello");
+        cache.insert_message(1, msg.clone(), Priority::UserNormal, &[addr(1), addr(2)], 1);
+
+        let outcome = cache.target_refused(1, addr(1), &mut rng);
+        assert!(!outcome.target_lost);
+        assert_eq!(targets(&outcome.resends), vec![addr(2)]);
+
+        let outcome = cache.target_refused(1, addr(2), &mut rng);
+        assert!(!outcome.target_lost);
+        assert!(outcome.resends.is_empty());
+        assert_eq!(outcome.undelivered, Some(msg));
     }
-}
 
-// How to resend a previously failed message.
-pub enum Resend {
-    // Resend it now to the given target.
-    Now(SocketAddr),
-    // Resend it to the given target after the given delay.
-    Later(SocketAddr, Duration),
-    // Don't resend - all attempts failed.
-    Never,
+    #[test]
+    fn prefer_recently_succeeded() {
+        let mut rng = rng::new();
+        let mut cache = SendingTargetsCache::default();
+        cache.insert_message(
+            1,
+            Bytes::from_static(b"a"),
+            Priority::Routing,
+            &[addr(3)],
+            1,
+        );
+        let _ = cache.target_succeeded(1, addr(3), &mut rng);
+
+        cache.insert_message(
+            2,
+            Bytes::from_static(b"b"),
+            Priority::Routing,
+            &[addr(1), addr(2), addr(3)],
+            1,
+        );
+        let outcome = cache.target_failed(2, addr(1), &mut rng);
+        assert_eq!(targets(&outcome.resends), vec![addr(3)]);
+        assert_eq!(outcome.resends[0].delay, None);
+
+        // A message delivered to one target is not reported as undelivered.
+        let _ = cache.target_succeeded(2, addr(3), &mut rng);
+        assert!(cache.cache.is_empty());
+    }
+
+    #[test]
+    fn cap_in_flight_resends() {
+        let mut rng = rng::new();
+        let mut cache = SendingTargetsCache::default();
+
+        for token in 0..=MAX_IN_FLIGHT_RESENDS as Token {
+            cache.insert_message(
+                token,
+                Bytes::from_static(b"m"),
+                Priority::Routing,
+                &[addr(1), addr(2)],
+                1,
+            );
+        }
+
+        for token in 0..MAX_IN_FLIGHT_RESENDS as Token {
+            let outcome = cache.target_failed(token, addr(1), &mut rng);
+            assert_eq!(targets(&outcome.resends), vec![addr(2)]);
+        }
+
+        // No free slot - the resend has to wait.
+        let last = MAX_IN_FLIGHT_RESENDS as Token;
+        let outcome = cache.target_failed(last, addr(1), &mut rng);
+        assert!(outcome.resends.is_empty());
+        assert!(outcome.undelivered.is_none());
+
+        // A resend completing frees a slot for it.
+        let resends = cache.target_succeeded(0, addr(2), &mut rng);
+        assert_eq!(resends.len(), 1);
+        assert_eq!(resends[0].token, last);
+        assert_eq!(resends[0].target, addr(2));
+    }
 }
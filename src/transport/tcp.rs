@@ -0,0 +1,624 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::TransportBackend;
+use crate::{
+    error::Result,
+    quic_p2p::{EventSenders, Peer, Token},
+    TransportEvent,
+};
+use bytes::Bytes;
+use crossbeam_channel::{Receiver, Sender};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io::{self, BufReader, Read, Write},
+    net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Maximum time to wait for a connection to a peer to be established.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum number of connections in each direction. Further connections from other peers are
+/// refused, and sends to further peers fail.
+const MAX_CONNECTIONS: usize = 256;
+/// Maximum size of a single message. Peers sending bigger messages are disconnected.
+const MAX_MESSAGE_SIZE: usize = 20 * 1024 * 1024;
+
+/// Configuration of the plain TCP transport, for environments where QUIC/UDP is blocked. The
+/// traffic is sent in plaintext and the peers aren't authenticated, so it's only fit for trusted
+/// networks.
+#[derive(Clone, Debug)]
+pub struct TcpConfig {
+    /// Address to listen on. Port 0 means any free port. The IP is also the address we advertise
+    /// to the other peers, so it must be reachable by them.
+    pub endpoint: SocketAddr,
+    /// Addresses of the nodes to bootstrap off.
+    pub hard_coded_contacts: Vec<SocketAddr>,
+}
+
+impl Default for TcpConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+            hard_coded_contacts: Vec::new(),
+        }
+    }
+}
+
+// Transport backend exchanging length-prefixed messages over TCP connections. Every peer sends
+// over its own outgoing connections only, introduced by its listening port. The receiver combines
+// the port with the IP the connection comes from to tell who the messages come from, so a peer
+// can't pass for one at another IP. It can pass for one at the same IP though, as the claimed port
+// isn't verified, and nothing is encrypted - unlike QUIC, this transport relies on the network
+// being trusted. Each connection is served by its own thread, so connecting and writing never
+// block the caller. Only nodes are supported, sending to clients always fails.
+pub struct TcpBackend {
+    our_addr: SocketAddr,
+    contacts: Vec<SocketAddr>,
+    event_tx: Sender<TransportEvent>,
+    outgoing: Outgoing,
+    // Connections of the other peers to us, by their listening addresses.
+    incoming: Connections<TcpStream>,
+    closed: Arc<AtomicBool>,
+}
+
+impl TcpBackend {
+    pub fn new(event_tx: EventSenders, config: TcpConfig) -> Result<Self> {
+        let listener = TcpListener::bind(config.endpoint)?;
+        let our_addr = listener.local_addr()?;
+        let incoming = Connections::default();
+        let closed = Arc::new(AtomicBool::new(false));
+
+        {
+            let event_tx = event_tx.node_tx.clone();
+            let incoming = incoming.clone();
+            let closed = Arc::clone(&closed);
+            let _ = thread::Builder::new()
+                .name(format!("tcp-listener-{}", our_addr))
+                .spawn(move || accept(listener, event_tx, incoming, closed))?;
+        }
+
+        Ok(Self {
+            our_addr,
+            contacts: config.hard_coded_contacts,
+            event_tx: event_tx.node_tx.clone(),
+            outgoing: Outgoing {
+                our_port: our_addr.port(),
+                event_tx: event_tx.node_tx,
+                connections: Connections::default(),
+                next_id: Arc::new(AtomicU64::new(0)),
+            },
+            incoming,
+            closed,
+        })
+    }
+
+    fn report(&self, event: TransportEvent) {
+        let _ = self.event_tx.send(event);
+    }
+}
+
+impl TransportBackend for TcpBackend {
+    fn bootstrap(&mut self) {
+        let contacts = self.contacts.clone();
+        let outgoing = self.outgoing.clone();
+
+        let result = thread::Builder::new()
+            .name("tcp-bootstrap".to_string())
+            .spawn(move || {
+                for contact in contacts {
+                    match connect(contact, outgoing.our_port) {
+                        Ok(stream) => {
+                            if let Err(error) = outgoing.add(contact, stream) {
+                                debug!("Failed to add connection to {}: {}", contact, error);
+                                continue;
+                            }
+
+                            outgoing.report(TransportEvent::BootstrappedTo { node: contact });
+                            return;
+                        }
+                        Err(error) => debug!("Failed to connect to {}: {}", contact, error),
+                    }
+                }
+
+                outgoing.report(TransportEvent::BootstrapFailure)
+            });
+
+        if let Err(error) = result {
+            debug!("Failed to spawn bootstrap thread: {}", error);
+            self.report(TransportEvent::BootstrapFailure)
+        }
+    }
+
+    fn send(&mut self, peer: Peer, msg: Bytes, token: Token) {
+        match peer {
+            Peer::Node(addr) => self.outgoing.send(addr, msg, token),
+            Peer::Client(_) => self.report(TransportEvent::UnsentUserMessage { peer, msg, token }),
+        }
+    }
+
+    fn our_connection_info(&mut self) -> Result<SocketAddr> {
+        Ok(self.our_addr)
+    }
+
+    fn disconnect_from(&mut self, addr: SocketAddr) {
+        // Dropping the sender makes the connection thread close the connection once it's written
+        // the messages already sent to it.
+        let _ = self.outgoing.connections.lock().remove(&addr);
+
+        if let Some(stream) = self.incoming.lock().remove(&addr) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Drop for TcpBackend {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Release);
+
+        self.outgoing.connections.lock().clear();
+
+        for (_, stream) in self.incoming.lock().drain() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+
+        // Wake up the listener thread so it notices we are closed.
+        let _ = TcpStream::connect_timeout(&self.our_addr, CONNECT_TIMEOUT);
+    }
+}
+
+struct Connections<T>(Arc<Mutex<HashMap<SocketAddr, T>>>);
+
+impl<T> Connections<T> {
+    fn lock(&self) -> MutexGuard<HashMap<SocketAddr, T>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// Not derived, as that would require `T: Clone`.
+impl<T> Clone for Connections<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T> Default for Connections<T> {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(HashMap::new())))
+    }
+}
+
+// Message waiting to be written to a connection, with the token to report its outcome with.
+type Frame = (Bytes, Token);
+
+// Our connection to a peer, written by its own thread.
+struct Writer {
+    // Tells the connection apart from later connections to the same peer.
+    id: u64,
+    tx: Sender<Frame>,
+}
+
+// Our connections to the other peers, by their listening addresses.
+#[derive(Clone)]
+struct Outgoing {
+    our_port: u16,
+    event_tx: Sender<TransportEvent>,
+    connections: Connections<Writer>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Outgoing {
+    // Sends the message over our connection to the peer, opening the connection first if there is
+    // none yet.
+    fn send(&self, addr: SocketAddr, msg: Bytes, token: Token) {
+        let mut connections = self.connections.lock();
+        let full = connections.len() >= MAX_CONNECTIONS;
+
+        let result = match connections.entry(addr) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(_) if full => {
+                Err(io::Error::new(io::ErrorKind::Other, "too many connections"))
+            }
+            Entry::Vacant(entry) => self.spawn(addr, None).map(|writer| entry.insert(writer)),
+        };
+
+        let unsent = match result {
+            Ok(writer) => writer
+                .tx
+                .send((msg, token))
+                .err()
+                .map(|error| error.into_inner()),
+            Err(error) => {
+                debug!("Failed to send message to {}: {}", addr, error);
+                Some((msg, token))
+            }
+        };
+
+        if let Some((msg, token)) = unsent {
+            self.report(TransportEvent::UnsentUserMessage {
+                peer: Peer::Node(addr),
+                msg,
+                token,
+            })
+        }
+    }
+
+    // Adds the already established connection to the peer, unless we have one already.
+    fn add(&self, addr: SocketAddr, stream: TcpStream) -> io::Result<()> {
+        let mut connections = self.connections.lock();
+        if connections.len() >= MAX_CONNECTIONS {
+            return Err(io::Error::new(io::ErrorKind::Other, "too many connections"));
+        }
+
+        if let Entry::Vacant(entry) = connections.entry(addr) {
+            let _ = entry.insert(self.spawn(addr, Some(stream))?);
+        }
+
+        Ok(())
+    }
+
+    // Spawns the thread writing the connection to the peer. Unless given an established
+    // connection, the thread connects first.
+    fn spawn(&self, addr: SocketAddr, stream: Option<TcpStream>) -> io::Result<Writer> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let outgoing = self.clone();
+
+        let _ = thread::Builder::new()
+            .name(format!("tcp-writer-{}", addr))
+            .spawn(move || outgoing.write(addr, id, stream, rx))?;
+
+        Ok(Writer { id, tx })
+    }
+
+    // Writes the messages sent to the connection until it fails or we disconnect from the peer.
+    fn write(&self, addr: SocketAddr, id: u64, stream: Option<TcpStream>, rx: Receiver<Frame>) {
+        let error = match stream.map_or_else(|| connect(addr, self.our_port), Ok) {
+            Ok(mut stream) => {
+                let error = rx.iter().find_map(|(msg, token)| {
+                    if let Err(error) = write_frame(&mut stream, &msg) {
+                        self.report(TransportEvent::UnsentUserMessage {
+                            peer: Peer::Node(addr),
+                            msg,
+                            token,
+                        });
+                        Some(error)
+                    } else {
+                        self.report(TransportEvent::SentUserMessage {
+                            peer: Peer::Node(addr),
+                            msg,
+                            token,
+                        });
+                        None
+                    }
+                });
+                let _ = stream.shutdown(Shutdown::Both);
+                error
+            }
+            Err(error) => Some(error),
+        };
+
+        // The senders are gone, so we disconnected from the peer.
+        let error = if let Some(error) = error {
+            error
+        } else {
+            return;
+        };
+
+        debug!("Connection to {} failed: {}", addr, error);
+
+        // Stop taking messages, then fail the ones that didn't make it.
+        {
+            let mut connections = self.connections.lock();
+            if connections.get(&addr).map(|writer| writer.id) == Some(id) {
+                let _ = connections.remove(&addr);
+            }
+        }
+
+        for (msg, token) in rx.try_iter() {
+            self.report(TransportEvent::UnsentUserMessage {
+                peer: Peer::Node(addr),
+                msg,
+                token,
+            });
+        }
+
+        self.report(TransportEvent::ConnectionFailure {
+            peer: Peer::Node(addr),
+            err: error.into(),
+        });
+    }
+
+    fn report(&self, event: TransportEvent) {
+        let _ = self.event_tx.send(event);
+    }
+}
+
+// Connects to the peer and introduces us by our listening port.
+fn connect(addr: SocketAddr, our_port: u16) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+    stream.set_nodelay(true)?;
+    write_frame(&mut stream, &our_port.to_be_bytes())?;
+    Ok(stream)
+}
+
+// Accepts the connections of the other peers and spawns a thread receiving their messages.
+fn accept(
+    listener: TcpListener,
+    event_tx: Sender<TransportEvent>,
+    incoming: Connections<TcpStream>,
+    closed: Arc<AtomicBool>,
+) {
+    // Number of connections being received, including those not through the handshake yet.
+    let receiving = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        if closed.load(Ordering::Acquire) {
+            break;
+        }
+
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                debug!("Failed to accept connection: {}", error);
+                continue;
+            }
+        };
+
+        if receiving.load(Ordering::Acquire) >= MAX_CONNECTIONS {
+            debug!(
+                "Refusing connection from {:?} - too many connections.",
+                stream.peer_addr()
+            );
+            continue;
+        }
+
+        let _ = receiving.fetch_add(1, Ordering::AcqRel);
+
+        let event_tx = event_tx.clone();
+        let incoming = incoming.clone();
+        let closed = Arc::clone(&closed);
+        let receiving_on_exit = Arc::clone(&receiving);
+        if let Err(error) = thread::Builder::new()
+            .name("tcp-receiver".to_string())
+            .spawn(move || {
+                receive(stream, event_tx, incoming, closed);
+                let _ = receiving_on_exit.fetch_sub(1, Ordering::AcqRel);
+            })
+        {
+            debug!("Failed to spawn receiver thread: {}", error);
+            let _ = receiving.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+// Receives the messages sent over the connection until it is closed.
+fn receive(
+    stream: TcpStream,
+    event_tx: Sender<TransportEvent>,
+    incoming: Connections<TcpStream>,
+    closed: Arc<AtomicBool>,
+) {
+    let mut reader = match stream.try_clone() {
+        Ok(stream) => BufReader::new(stream),
+        Err(error) => {
+            debug!("Failed to clone connection: {}", error);
+            return;
+        }
+    };
+
+    let peer_addr = match handshake(&stream, &mut reader) {
+        Ok(addr) => addr,
+        Err(error) => {
+            debug!("Invalid handshake from {:?}: {}", stream.peer_addr(), error);
+            return;
+        }
+    };
+    let conn_addr = stream.peer_addr().ok();
+
+    // A new connection from the peer replaces the previous one.
+    if let Some(previous) = incoming.lock().insert(peer_addr, stream) {
+        let _ = previous.shutdown(Shutdown::Both);
+    }
+
+    let event = TransportEvent::ConnectedTo {
+        peer: Peer::Node(peer_addr),
+    };
+    let error = if event_tx.send(event).is_ok() {
+        loop {
+            match read_frame(&mut reader) {
+                Ok(msg) => {
+                    let event = TransportEvent::NewMessage {
+                        peer: Peer::Node(peer_addr),
+                        msg: Bytes::from(msg),
+                    };
+                    if event_tx.send(event).is_err() {
+                        return;
+                    }
+                }
+                Err(error) => break error,
+            }
+        }
+    } else {
+        return;
+    };
+
+    trace!("Connection from {} closed: {}", peer_addr, error);
+
+    // Unless we closed the connection ourselves, or the peer replaced it, it failed.
+    let removed = {
+        let mut incoming = incoming.lock();
+        if incoming
+            .get(&peer_addr)
+            .map(|stream| stream.peer_addr().ok())
+            == Some(conn_addr)
+        {
+            incoming.remove(&peer_addr).is_some()
+        } else {
+            false
+        }
+    };
+
+    if removed && !closed.load(Ordering::Acquire) {
+        let _ = event_tx.send(TransportEvent::ConnectionFailure {
+            peer: Peer::Node(peer_addr),
+            err: error.into(),
+        });
+    }
+}
+
+// Reads the listening port of the peer, the first message over its connection, and returns the
+// peer's listening address. The IP is the one the connection comes from, not one it could claim.
+fn handshake(stream: &TcpStream, reader: &mut impl Read) -> io::Result<SocketAddr> {
+    // Don't let a peer hold a connection open without ever introducing itself.
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+    let bytes = read_frame(reader)?;
+    stream.set_read_timeout(None)?;
+
+    let mut port = [0; 2];
+    if bytes.len() != port.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid handshake",
+        ));
+    }
+    port.copy_from_slice(&bytes);
+
+    Ok(SocketAddr::new(
+        stream.peer_addr()?.ip(),
+        u16::from_be_bytes(port),
+    ))
+}
+
+fn write_frame(stream: &mut TcpStream, bytes: &[u8]) -> io::Result<()> {
+    if bytes.len() > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "message too big",
+        ));
+    }
+
+    stream.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    stream.write_all(bytes)?;
+    stream.flush()
+}
+
+fn read_frame(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message too big",
+        ));
+    }
+
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::{self as mpmc, Receiver};
+
+    const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn gen_backend(contacts: Vec<SocketAddr>) -> (TcpBackend, Receiver<TransportEvent>) {
+        let (node_tx, node_rx) = mpmc::unbounded();
+        let (client_tx, _) = mpmc::unbounded();
+        let config = TcpConfig {
+            hard_coded_contacts: contacts,
+            ..Default::default()
+        };
+
+        (
+            TcpBackend::new(EventSenders { node_tx, client_tx }, config).unwrap(),
+            node_rx,
+        )
+    }
+
+    #[test]
+    fn bootstrap_and_send() {
+        let (mut a, a_rx) = gen_backend(vec![]);
+        let a_addr = a.our_connection_info().unwrap();
+        let (mut b, b_rx) = gen_backend(vec![a_addr]);
+        let b_addr = b.our_connection_info().unwrap();
+
+        b.bootstrap();
+        match b_rx.recv_timeout(RECV_TIMEOUT).unwrap() {
+            TransportEvent::BootstrappedTo { node } => assert_eq!(node, a_addr),
+            event => panic!("unexpected event {:?}", event),
+        }
+        match a_rx.recv_timeout(RECV_TIMEOUT).unwrap() {
+            TransportEvent::ConnectedTo { peer } => assert_eq!(peer.peer_addr(), b_addr),
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        b.send(Peer::Node(a_addr), Bytes::from_static(b"hello"), 7);
+        match b_rx.recv_timeout(RECV_TIMEOUT).unwrap() {
+            TransportEvent::SentUserMessage { token, .. } => assert_eq!(token, 7),
+            event => panic!("unexpected event {:?}", event),
+        }
+        match a_rx.recv_timeout(RECV_TIMEOUT).unwrap() {
+            TransportEvent::NewMessage { peer, msg } => {
+                assert_eq!(peer.peer_addr(), b_addr);
+                assert_eq!(&msg[..], b"hello");
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        // Messages to a peer that isn't listening can't be sent.
+        drop(a);
+        b.disconnect_from(a_addr);
+        b.send(Peer::Node(a_addr), Bytes::from_static(b"bye"), 8);
+        match b_rx.recv_timeout(RECV_TIMEOUT).unwrap() {
+            TransportEvent::UnsentUserMessage { token, .. } => assert_eq!(token, 8),
+            event => panic!("unexpected event {:?}", event),
+        }
+        match b_rx.recv_timeout(RECV_TIMEOUT).unwrap() {
+            TransportEvent::ConnectionFailure { peer, .. } => assert_eq!(peer.peer_addr(), a_addr),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn connection_failure_on_close() {
+        let (mut a, a_rx) = gen_backend(vec![]);
+        let a_addr = a.our_connection_info().unwrap();
+        let (mut b, _b_rx) = gen_backend(vec![a_addr]);
+        let b_addr = b.our_connection_info().unwrap();
+
+        b.send(Peer::Node(a_addr), Bytes::from_static(b"hello"), 1);
+        match a_rx.recv_timeout(RECV_TIMEOUT).unwrap() {
+            TransportEvent::ConnectedTo { peer } => assert_eq!(peer.peer_addr(), b_addr),
+            event => panic!("unexpected event {:?}", event),
+        }
+        match a_rx.recv_timeout(RECV_TIMEOUT).unwrap() {
+            TransportEvent::NewMessage { .. } => (),
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        // The peer closing its connection is reported.
+        drop(b);
+        match a_rx.recv_timeout(RECV_TIMEOUT).unwrap() {
+            TransportEvent::ConnectionFailure { peer, .. } => assert_eq!(peer.peer_addr(), b_addr),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+}
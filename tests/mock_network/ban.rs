@@ -0,0 +1,128 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    create_connected_nodes_until_split, elders_with_prefix, node_joined, poll_all, TestNode,
+    LOWERED_ELDER_SIZE,
+};
+use routing::{
+    generate_bls_threshold_secret_key, mock::Environment, BanReason, DstLocation, FullId,
+    JoinRequest, Message, NetworkParams, Prefix, RelocateDetails, RelocatePayload,
+    SignedRelocateDetails, SrcAuthority, TransportConfig, Variant, XorName,
+};
+
+// Creates a join request of `new_id` claiming a relocation to section `prefix` that the section
+// never signed.
+fn forged_relocation_join_request(
+    env: &Environment,
+    node: &TestNode,
+    prefix: Prefix<XorName>,
+    new_id: &FullId,
+) -> Message {
+    let mut rng = env.new_rng();
+    let old_id = FullId::gen(&mut rng);
+
+    let details = RelocateDetails {
+        pub_id: *old_id.public_id(),
+        destination: *new_id.public_id().name(),
+        destination_key: *node.inner.section_key().unwrap(),
+        age: 10,
+    };
+    let mut relocate = Message::single_src(
+        &old_id,
+        DstLocation::Node(*old_id.public_id().name()),
+        Variant::Relocate(Box::new(details)),
+    )
+    .unwrap();
+
+    // Proven by the real history of the section, but signed with a key it never had.
+    relocate.src = SrcAuthority::Section {
+        prefix,
+        signature: generate_bls_threshold_secret_key(&mut rng, 1)
+            .secret_key()
+            .sign(b"forged"),
+        proof: node.inner.our_history().unwrap().clone(),
+    };
+
+    let details = SignedRelocateDetails::new(relocate).unwrap();
+    let payload = RelocatePayload::new(details, new_id.public_id(), &old_id).unwrap();
+
+    let join_request = JoinRequest {
+        elders_version: node.inner.our_section().unwrap().version,
+        relocate_payload: Some(payload),
+        rejoin_payload: None,
+        resource_proof: None,
+        reachability_proofs: Vec::new(),
+    };
+
+    Message::single_src(
+        new_id,
+        DstLocation::Direct,
+        Variant::JoinRequest(Box::new(join_request)),
+    )
+    .unwrap()
+}
+
+fn is_banned(node: &TestNode, id: &FullId) -> bool {
+    node.inner.banned_peers().iter().any(|details| {
+        details.pub_id == *id.public_id() && details.reason == BanReason::ForgedRelocation
+    })
+}
+
+#[test]
+fn ban_forged_relocation() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        recommended_section_size: LOWERED_ELDER_SIZE,
+    });
+    let mut rng = env.new_rng();
+    let mut nodes = create_connected_nodes_until_split(&env, &[1, 1]);
+
+    let our_prefix = *nodes[0].our_prefix();
+    let their_prefix = our_prefix.sibling();
+    let forger_id = FullId::within_range(&mut rng, &our_prefix.range_inclusive());
+
+    // The forger sends the join request to all our elders, through a node of the other section.
+    let our_elders: Vec<_> = nodes[0]
+        .inner
+        .our_elders()
+        .map(|p2p_node| *p2p_node.peer_addr())
+        .collect();
+    let their_node_pos = nodes
+        .iter()
+        .position(|node| *node.our_prefix() == their_prefix)
+        .unwrap();
+    let msg = forged_relocation_join_request(&env, &nodes[0], our_prefix, &forger_id);
+    let _ = nodes[their_node_pos]
+        .inner
+        .send_message_to_targets(&our_elders, our_elders.len(), msg);
+    poll_all(&env, &mut nodes);
+
+    // Every elder checked the forgery for itself, so our section agreed on the ban and told its
+    // neighbour about it.
+    for node in
+        elders_with_prefix(&nodes, &our_prefix).chain(elders_with_prefix(&nodes, &their_prefix))
+    {
+        assert!(
+            is_banned(node, &forger_id),
+            "{} didn't ban the forger",
+            node.name()
+        );
+    }
+
+    // The banned peer can't join anymore, not even without claiming a relocation.
+    let contact = nodes[0].endpoint();
+    nodes.push(
+        TestNode::builder(&env)
+            .transport_config(TransportConfig::node().with_hard_coded_contact(contact))
+            .full_id(forger_id)
+            .create(),
+    );
+    poll_all(&env, &mut nodes);
+    assert!(!node_joined(&nodes, nodes.len() - 1));
+}
@@ -614,7 +614,10 @@ impl Expectations {
     // Returns whether all expectations have been met.
     fn verify(&mut self, nodes: &[TestNode]) -> bool {
         for node in nodes {
-            if let Some(Event::MessageReceived { content, src, dst }) = node.try_recv_event() {
+            if let Some(Event::MessageReceived {
+                content, src, dst, ..
+            }) = node.try_recv_event()
+            {
                 self.handle_message_received(node, content, src, dst);
             }
         }
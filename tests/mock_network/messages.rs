@@ -9,7 +9,8 @@
 use super::utils::*;
 use rand::Rng;
 use routing::{
-    event::Event, mock::Environment, quorum_count, DstLocation, NetworkParams, SrcLocation,
+    event::Event, mock::Environment, quorum_count, DstLocation, NetworkParams, Prefix, RouteConfig,
+    RouteHop, SrcLocation, XorName,
 };
 use std::collections::HashMap;
 
@@ -103,6 +104,224 @@ fn send_and_receive() {
     })
 }
 
+#[test]
+fn send_with_ack() {
+    let elder_size = 8;
+    let recommended_section_size = 8;
+    let env = Environment::new(NetworkParams {
+        elder_size,
+        recommended_section_size,
+    });
+    let mut rng = env.new_rng();
+    let mut nodes = create_connected_nodes(&env, elder_size + 1);
+
+    let sender_index = gen_elder_index(&mut rng, &nodes);
+    let src = SrcLocation::Node(*nodes[sender_index].name());
+    let dst = DstLocation::Section(rng.gen());
+    let content = gen_vec(&mut rng, 32);
+    let hash = nodes[sender_index]
+        .inner
+        .send_message_with_ack(src, dst, content.clone())
+        .unwrap();
+
+    // Poll until the sender learns the section received the message.
+    poll_until(&env, &mut nodes, |nodes| {
+        while let Some(event) = nodes[sender_index].try_recv_event() {
+            match event {
+                Event::Delivered(delivered) if delivered == hash => return true,
+                Event::DeliveryFailed(failed) if failed == hash => {
+                    panic!("Message {:?} not delivered", hash)
+                }
+                _ => (),
+            }
+        }
+
+        false
+    });
+}
+
+#[test]
+fn send_identical_messages_with_ack() {
+    let elder_size = 8;
+    let recommended_section_size = 8;
+    let env = Environment::new(NetworkParams {
+        elder_size,
+        recommended_section_size,
+    });
+    let mut rng = env.new_rng();
+    let mut nodes = create_connected_nodes(&env, elder_size + 1);
+
+    let sender_index = gen_elder_index(&mut rng, &nodes);
+    let recipient_index = (sender_index + 1) % nodes.len();
+    let src = SrcLocation::Node(*nodes[sender_index].name());
+    let dst = DstLocation::Node(*nodes[recipient_index].name());
+    let content = gen_vec(&mut rng, 32);
+
+    let hashes: Vec<_> = (0..2)
+        .map(|_| {
+            nodes[sender_index]
+                .inner
+                .send_message_with_ack(src, dst, content.clone())
+                .unwrap()
+        })
+        .collect();
+    assert_ne!(hashes[0], hashes[1]);
+
+    let mut delivered = 0;
+    let mut received = 0;
+    poll_until(&env, &mut nodes, |nodes| {
+        while let Some(event) = nodes[sender_index].try_recv_event() {
+            match event {
+                Event::Delivered(hash) if hashes.contains(&hash) => delivered += 1,
+                Event::DeliveryFailed(hash) if hashes.contains(&hash) => {
+                    panic!("Message {:?} not delivered", hash)
+                }
+                _ => (),
+            }
+        }
+
+        while let Some(event) = nodes[recipient_index].try_recv_event() {
+            if let Event::MessageReceived {
+                content: actual, ..
+            } = event
+            {
+                if actual == content {
+                    received += 1;
+                }
+            }
+        }
+
+        delivered == hashes.len() && received == hashes.len()
+    });
+}
+
+#[test]
+fn drop_at_max_hops() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        recommended_section_size: LOWERED_ELDER_SIZE,
+    });
+    let mut rng = env.new_rng();
+    let (mut nodes, sender_index, recipient_index) = create_two_hop_route(&env);
+
+    // The elders of the recipient's section are the second hop, so they drop the message.
+    for node in &mut nodes {
+        node.inner.set_route_config(RouteConfig {
+            max_hops: 1,
+            ..RouteConfig::default()
+        });
+    }
+
+    let src = SrcLocation::Node(*nodes[sender_index].name());
+    let dst = DstLocation::Node(*nodes[recipient_index].name());
+    let content = gen_vec(&mut rng, 32);
+    assert!(nodes[sender_index]
+        .inner
+        .send_message(src, dst, content.clone())
+        .is_ok());
+
+    poll_all(&env, &mut nodes);
+    assert!(!message_received(&nodes[recipient_index], &content));
+
+    // One more hop is enough for the message to arrive.
+    for node in &mut nodes {
+        node.inner.set_route_config(RouteConfig {
+            max_hops: 2,
+            ..RouteConfig::default()
+        });
+    }
+
+    let content = gen_vec(&mut rng, 32);
+    assert!(nodes[sender_index]
+        .inner
+        .send_message(src, dst, content.clone())
+        .is_ok());
+
+    poll_until(&env, &mut nodes, |nodes| {
+        message_received(&nodes[recipient_index], &content)
+    });
+}
+
+#[test]
+fn trace_route() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        recommended_section_size: LOWERED_ELDER_SIZE,
+    });
+    let mut rng = env.new_rng();
+    let (mut nodes, sender_index, recipient_index) = create_two_hop_route(&env);
+
+    for node in &mut nodes {
+        node.inner.set_route_config(RouteConfig {
+            trace: true,
+            ..RouteConfig::default()
+        });
+    }
+
+    let sender = *nodes[sender_index].name();
+    let sender_prefix = *nodes[sender_index].our_prefix();
+    let recipient_prefix = *nodes[recipient_index].our_prefix();
+    let src = SrcLocation::Node(sender);
+    let dst = DstLocation::Node(*nodes[recipient_index].name());
+    let content = gen_vec(&mut rng, 32);
+    assert!(nodes[sender_index]
+        .inner
+        .send_message(src, dst, content.clone())
+        .is_ok());
+
+    let mut trace = None;
+    poll_until(&env, &mut nodes, |nodes| {
+        while let Some(event) = nodes[recipient_index].try_recv_event() {
+            if let Event::MessageReceived {
+                content: actual,
+                trace: actual_trace,
+                ..
+            } = event
+            {
+                if actual == content {
+                    trace = Some(actual_trace);
+                }
+            }
+        }
+
+        trace.is_some()
+    });
+
+    // The message went from the sender through an elder of the recipient's section.
+    let trace: Vec<RouteHop> = unwrap!(unwrap!(trace));
+    assert_eq!(trace.len(), 2);
+    assert_eq!(
+        trace[0],
+        RouteHop {
+            name: sender,
+            prefix: sender_prefix,
+        }
+    );
+    assert_eq!(trace[1].prefix, recipient_prefix);
+    assert!(nodes
+        .iter()
+        .any(|node| node.name() == &trace[1].name && node.inner.is_elder()));
+}
+
+// Creates a network of two sections and returns it with the indices of an elder of one section
+// and of a new node in the other. A message from the elder to the node takes two hops: to the
+// elders of the node's section, which relay it to the node.
+fn create_two_hop_route(env: &Environment) -> (Vec<TestNode>, usize, usize) {
+    let mut nodes = create_connected_nodes_until_split(env, &[1, 1]);
+    let prefixes: Vec<Prefix<XorName>> = current_sections(&nodes).collect();
+
+    let sender_index = unwrap!(nodes
+        .iter()
+        .position(|node| node.inner.is_elder() && prefixes[0].matches(node.name())));
+
+    add_node_to_section(env, &mut nodes, &prefixes[1]);
+    let recipient_index = nodes.len() - 1;
+    poll_until(env, &mut nodes, |nodes| node_joined(nodes, recipient_index));
+    assert!(!nodes[recipient_index].inner.is_elder());
+
+    (nodes, sender_index, recipient_index)
+}
+
 // Returns the indices of the nodes that are expected to receive a message with the given
 // destination.
 fn expected_recipients<'a>(
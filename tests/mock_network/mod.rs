@@ -7,11 +7,14 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 mod accumulate;
+mod ban;
 mod churn;
 mod drop;
 mod messages;
 mod node_ageing;
 mod secure_message_delivery;
+mod split;
+mod transport;
 pub mod utils;
 
 use self::utils::*;
@@ -19,10 +22,10 @@ use fake_clock::FakeClock;
 use itertools::Itertools;
 use rand::{seq::SliceRandom, Rng};
 use routing::{
-    event::Event, mock::Environment, test_consts, NetworkParams, PausedState, Prefix,
-    RelocationOverrides, TransportConfig, XorName,
+    event::Event, mock::Environment, test_consts, ConsensusAlgorithm, NetworkParams, PausedState,
+    Prefix, RelocationOverrides, TransportConfig, XorName,
 };
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 // -----  Miscellaneous tests below  -----
 
@@ -60,7 +63,7 @@ fn disconnect_on_rebootstrap() {
     // When retrying to bootstrap, we should have disconnected from the bootstrap node.
     assert!(!env.is_connected(&nodes[2].endpoint(), &nodes[1].endpoint()));
 
-    expect_next_event!(nodes.last_mut().unwrap(), Event::Terminated);
+    expect_next_event!(nodes.last_mut().unwrap(), Event::RestartRequired);
 }
 
 #[test]
@@ -74,6 +77,94 @@ fn single_section() {
     verify_invariants_for_nodes(&env, &nodes);
 }
 
+#[test]
+fn single_section_with_leader_consensus() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        recommended_section_size: LOWERED_ELDER_SIZE + 1,
+    });
+    let nodes = create_connected_nodes_with_consensus(
+        &env,
+        LOWERED_ELDER_SIZE + 2,
+        ConsensusAlgorithm::Leader,
+    );
+    verify_invariants_for_nodes(&env, &nodes);
+}
+
+#[test]
+fn consensus_stall_detected() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        recommended_section_size: LOWERED_ELDER_SIZE,
+    });
+    let mut nodes = create_connected_nodes(&env, LOWERED_ELDER_SIZE);
+
+    // Drop more than a third of the elders at once, so the `Offline` votes for them can't reach
+    // consensus.
+    let dropped_count = LOWERED_ELDER_SIZE / 3 + 1;
+    let dropped: BTreeSet<_> = nodes
+        .drain(..dropped_count)
+        .map(|node| *node.name())
+        .collect();
+
+    let mut stalled = BTreeSet::new();
+    poll_until(&env, &mut nodes, |nodes| {
+        for node in nodes {
+            while let Some(event) = node.try_recv_event() {
+                if let Event::ConsensusStalled { unresponsive } = event {
+                    assert_eq!(unresponsive, dropped);
+                    let _ = stalled.insert(*node.name());
+                }
+            }
+        }
+
+        stalled.len() == nodes.len()
+    });
+
+    // The stall is reported only once.
+    FakeClock::advance_time(test_consts::STALL_TIMEOUT.as_millis() as u64);
+    poll_all(&env, &mut nodes);
+    for node in &nodes {
+        while let Some(event) = node.try_recv_event() {
+            assert!(
+                !matches!(event, Event::ConsensusStalled { .. }),
+                "{} reported the stall again",
+                node.name()
+            );
+        }
+    }
+}
+
+#[test]
+fn consensus_stall_recovered() {
+    let elder_size = 6;
+    let env = Environment::new(NetworkParams {
+        elder_size,
+        recommended_section_size: elder_size,
+    });
+    let mut nodes = create_connected_nodes(&env, elder_size);
+
+    // Drop a third of the elders at once. The remaining ones aren't more than two thirds, so the
+    // `Offline` votes for the dropped ones can't reach consensus until the stall is recovered from.
+    let dropped: Vec<_> = nodes
+        .drain(..elder_size / 3)
+        .map(|node| *node.name())
+        .collect();
+
+    poll_until(&env, &mut nodes, |nodes| {
+        dropped.iter().all(|name| node_left(nodes, name))
+    });
+
+    // The re-formed section keeps reaching consensus: new nodes can join it.
+    while nodes.len() < elder_size {
+        add_node_to_section(&env, &mut nodes, &Prefix::default());
+        let index = nodes.len() - 1;
+        poll_until(&env, &mut nodes, |nodes| node_joined(nodes, index));
+    }
+
+    verify_invariants_for_nodes(&env, &nodes);
+}
+
 #[test]
 fn less_than_section_size_nodes() {
     test_nodes(80);
@@ -471,6 +562,37 @@ fn carry_out_parsec_pruning() {
     verify_invariants_for_nodes(&env, &nodes);
 }
 
+#[test]
+fn join_deferred_while_not_accepting_members() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        recommended_section_size: LOWERED_ELDER_SIZE + 1,
+    });
+    let mut nodes = create_connected_nodes(&env, LOWERED_ELDER_SIZE);
+
+    for node in &mut nodes {
+        node.inner.set_accepting_members(false).unwrap();
+    }
+    poll_until(&env, &mut nodes, |nodes| {
+        nodes.iter().all(|node| !node.inner.is_accepting_members())
+    });
+
+    // The joining node is asked to wait instead of being approved...
+    let node = create_node_with_contact(&env, &mut nodes[0]);
+    nodes.push(node);
+    poll_all(&env, &mut nodes);
+    assert!(!node_joined(&nodes, nodes.len() - 1));
+
+    // ...and joins on retry once the section accepts new members again.
+    for node in &mut nodes[..LOWERED_ELDER_SIZE] {
+        node.inner.set_accepting_members(true).unwrap();
+    }
+    poll_until(&env, &mut nodes, |nodes| {
+        node_joined(nodes, nodes.len() - 1)
+    });
+    verify_invariants_for_nodes(&env, &nodes);
+}
+
 #[test]
 fn node_pause_and_resume_simple() {
     let env = Environment::new(NetworkParams {
@@ -612,3 +734,67 @@ fn neighbour_update() {
         section_knowledge_is_up_to_date(nodes, &prefix_a, &prefix_b, env.elder_size())
     });
 }
+
+#[test]
+fn merge_small_section_into_sibling() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        recommended_section_size: LOWERED_ELDER_SIZE + 1,
+    });
+    let mut rng = env.new_rng();
+    let mut nodes = create_connected_nodes_until_split(&env, &[1, 1]);
+
+    let prefix = Prefix::default().pushed(rng.gen());
+    let sibling = prefix.sibling();
+
+    // Shrink the sibling below the size that justified the split. Its elders keep telling the
+    // section how big it is.
+    while nodes_with_prefix(&nodes, &sibling).count() >= env.recommended_section_size() {
+        let name = remove_elder_from_section(&mut nodes, &sibling);
+        poll_until(&env, &mut nodes, |nodes| node_left(nodes, &name));
+    }
+
+    // Remove nodes from the section until it can't have a full set of elders anymore.
+    while nodes_with_prefix(&nodes, &prefix).count() >= env.elder_size() {
+        let name = remove_elder_from_section(&mut nodes, &prefix);
+        poll_until(&env, &mut nodes, |nodes| node_left(nodes, &name));
+    }
+
+    // The section asks its sibling to absorb it, so we end up with a single section again.
+    poll_until(&env, &mut nodes, |nodes| {
+        nodes
+            .iter()
+            .all(|node| *node.our_prefix() == Prefix::default())
+    });
+    verify_invariants_for_nodes(&env, &nodes);
+
+    // The section alone can't staff a full set of elders, so the elders of the merged section were
+    // chosen out of the members of both sections.
+    let elders: Vec<_> = elders_with_prefix(&nodes, &Prefix::default()).collect();
+    assert_eq!(elders.len(), env.elder_size());
+    assert!(elders.iter().any(|node| sibling.matches(node.name())));
+}
+
+#[test]
+fn no_merge_into_big_sibling() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        recommended_section_size: LOWERED_ELDER_SIZE + 1,
+    });
+    let mut rng = env.new_rng();
+    let mut nodes = create_connected_nodes_until_split(&env, &[1, 1]);
+
+    let prefix = Prefix::default().pushed(rng.gen());
+
+    // The sibling still has enough members to justify the split, so the section stays split even
+    // though it can't have a full set of elders anymore.
+    while nodes_with_prefix(&nodes, &prefix).count() >= env.elder_size() {
+        let name = remove_elder_from_section(&mut nodes, &prefix);
+        poll_until(&env, &mut nodes, |nodes| node_left(nodes, &name));
+    }
+    poll_all(&env, &mut nodes);
+
+    assert!(nodes
+        .iter()
+        .all(|node| *node.our_prefix() != Prefix::default()));
+}
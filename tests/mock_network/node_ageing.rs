@@ -13,7 +13,8 @@ use rand::{
     Rng,
 };
 use routing::{
-    mock::Environment, rng::MainRng, NetworkParams, Prefix, RelocationOverrides, XorName, MIN_AGE,
+    mock::Environment, rng::MainRng, FullId, NetworkParams, Prefix, RebalancingRelocationPolicy,
+    RejoinRules, RelocationOverrides, TransportConfig, XorName, MIN_AGE,
 };
 use std::iter;
 
@@ -166,6 +167,146 @@ fn startup_phase() {
     }
 }
 
+// Test that the elders can relocate a member on the request of their operators.
+#[test]
+fn relocate_on_request() {
+    let env = Environment::new(NETWORK_PARAMS);
+    let mut rng = env.new_rng();
+    let mut nodes = create_connected_nodes_until_split(&env, &[1, 1]);
+    verify_invariants_for_nodes(&env, &nodes);
+
+    let prefixes: Vec<_> = current_sections(&nodes).collect();
+    let source_prefix = *prefixes.choose(&mut rng).unwrap();
+    let target_prefix = *choose_other_prefix(&mut rng, &prefixes, &source_prefix);
+
+    add_node_to_section(&env, &mut nodes, &source_prefix);
+    let relocate_index = nodes.len() - 1;
+    poll_until(&env, &mut nodes, |nodes| node_joined(nodes, relocate_index));
+
+    let relocate_name = *nodes[relocate_index].name();
+    let relocate_age_counter = node_age_counter(&nodes, &relocate_name);
+    let destination = target_prefix.substituted_in(rng.gen());
+
+    for node in elders_with_prefix_mut(&mut nodes, &source_prefix) {
+        node.inner
+            .vote_for_relocation(relocate_name, destination)
+            .unwrap();
+    }
+
+    poll_until(&env, &mut nodes, |nodes| {
+        node_relocated(nodes, relocate_index, &source_prefix, &target_prefix)
+    });
+
+    // The node keeps its age, but not its progress towards the next one.
+    let relocate_age = 31 - relocate_age_counter.leading_zeros();
+    let relocated_name = *nodes[relocate_index].name();
+    assert_eq!(
+        node_age_counter(&nodes, &relocated_name),
+        2u32.pow(relocate_age)
+    );
+}
+
+// Test that the relocation policy can defer relocations until the nodes are old enough.
+#[test]
+fn relocate_only_from_min_relocation_age() {
+    let env =
+        Environment::new(NETWORK_PARAMS).with_relocation_policy(RebalancingRelocationPolicy {
+            min_relocation_age: MIN_AGE + 2,
+            rejoin: RejoinRules::default(),
+        });
+    let mut overrides = RelocationOverrides::new();
+
+    let mut rng = env.new_rng();
+    let mut nodes = create_connected_nodes_until_split(&env, &[1, 1]);
+    verify_invariants_for_nodes(&env, &nodes);
+
+    let prefixes: Vec<_> = current_sections(&nodes).collect();
+    let source_prefix = *prefixes.choose(&mut rng).unwrap();
+    let target_prefix = *choose_other_prefix(&mut rng, &prefixes, &source_prefix);
+
+    add_node_to_section(&env, &mut nodes, &source_prefix);
+    let mut relocate_index = nodes.len() - 1;
+    poll_until(&env, &mut nodes, |nodes| node_joined(nodes, relocate_index));
+
+    let destination = target_prefix.substituted_in(rng.gen());
+    overrides.set(source_prefix, destination);
+
+    // The age of the node increases, but not enough for it to be relocated.
+    relocate_index = churn_until_age_counter(&env, &mut nodes, &source_prefix, relocate_index, 32);
+    let relocate_name = *nodes[relocate_index].name();
+    assert!(elders_with_prefix(&nodes, &source_prefix)
+        .all(|node| node.inner.is_peer_our_member(&relocate_name)));
+
+    // Once it reaches the minimum relocation age, it gets relocated.
+    relocate_index = churn_until_age_counter(&env, &mut nodes, &source_prefix, relocate_index, 64);
+    poll_until(&env, &mut nodes, |nodes| {
+        node_relocated(nodes, relocate_index, &source_prefix, &target_prefix)
+    });
+}
+
+// Test that a node returning after a temporary absence gets its previous age back, decreased by
+// the age penalty.
+#[test]
+fn rejoin_with_retained_age() {
+    let env = Environment::new(NETWORK_PARAMS);
+    let mut rng = env.new_rng();
+    let mut nodes = create_connected_nodes(&env, env.elder_size());
+
+    // Add a node whose id we know. It ages during the startup phase, as the remaining nodes join.
+    let full_id = FullId::gen(&mut rng);
+    let config = TransportConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    nodes.push(
+        TestNode::builder(&env)
+            .transport_config(config)
+            .full_id(full_id.clone())
+            .create(),
+    );
+    poll_until(&env, &mut nodes, |nodes| {
+        node_joined(nodes, nodes.len() - 1)
+    });
+
+    for _ in 0..(env.recommended_section_size() - env.elder_size() - 1) {
+        add_node_to_section(&env, &mut nodes, &Prefix::default());
+        poll_until(&env, &mut nodes, |nodes| {
+            node_joined(nodes, nodes.len() - 1)
+        });
+    }
+
+    let previous_name = *full_id.public_id().name();
+    let previous_age_counter = node_age_counter(&nodes, &previous_name);
+    assert!(previous_age_counter > 2u32.pow(u32::from(MIN_AGE) + 1));
+
+    // The node leaves...
+    let index = nodes
+        .iter()
+        .position(|node| *node.name() == previous_name)
+        .unwrap();
+    drop(nodes.remove(index));
+    poll_until(&env, &mut nodes, |nodes| node_left(nodes, &previous_name));
+
+    // ...and returns under a new id.
+    let config = TransportConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    nodes.push(
+        TestNode::builder(&env)
+            .transport_config(config)
+            .previous_id(full_id)
+            .create(),
+    );
+    poll_until(&env, &mut nodes, |nodes| {
+        node_joined(nodes, nodes.len() - 1)
+    });
+
+    let new_name = *nodes[nodes.len() - 1].name();
+    assert_ne!(new_name, previous_name);
+    assert_eq!(
+        node_age_counter(&nodes, &new_name),
+        previous_age_counter / 2
+    );
+    assert!(nodes
+        .iter()
+        .all(|node| node.inner.member_age_counter(&previous_name).is_none()));
+}
+
 // Verify that the age counters of all the nodes in the root section are as expected, assuming we
 // were only adding nodes, not removing.
 fn check_root_section_age_counters_after_only_adds(env: &Environment, nodes: &[TestNode]) -> bool {
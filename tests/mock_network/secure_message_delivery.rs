@@ -10,7 +10,7 @@ use super::{create_connected_nodes_until_split, poll_all, TestNode, LOWERED_ELDE
 use routing::{
     generate_bls_threshold_secret_key, mock::Environment, rng::MainRng, AccumulatingMessage,
     DstLocation, EldersInfo, FullId, IndexedSecretKeyShare, Message, MessageHash, NetworkParams,
-    P2pNode, PlainMessage, Prefix, SectionProofChain, Variant, XorName,
+    P2pNode, PlainMessage, Prefix, Priority, SectionProofChain, Variant, XorName,
 };
 use std::{collections::BTreeMap, iter, net::SocketAddr};
 
@@ -82,6 +82,7 @@ fn message_with_invalid_security(fail_type: FailType) {
             elders_info: new_info,
             nonce: MessageHash::from_bytes(b"hello"),
         },
+        priority: Priority::Routing,
     };
 
     let message = {
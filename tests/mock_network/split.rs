@@ -0,0 +1,70 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::utils::*;
+use routing::{mock::Environment, MaxSizeSplitPolicy, NetworkParams, Prefix};
+
+#[test]
+fn default_policy_does_not_split_unbalanced_section() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        recommended_section_size: LOWERED_ELDER_SIZE + 1,
+    });
+    let mut nodes = Vec::new();
+    let prefix = Prefix::default();
+
+    // One of the halves is one node short of `recommended_section_size`.
+    add_mature_nodes(
+        &env,
+        &mut nodes,
+        &prefix,
+        env.recommended_section_size(),
+        env.recommended_section_size() - 1,
+    );
+    poll_all(&env, &mut nodes);
+
+    assert_eq!(count_sections(&nodes), 1);
+    verify_invariants_for_nodes(&env, &nodes);
+}
+
+#[test]
+fn default_policy_splits_balanced_section() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        recommended_section_size: LOWERED_ELDER_SIZE + 1,
+    });
+    let mut nodes = Vec::new();
+
+    trigger_split(&env, &mut nodes, &Prefix::default());
+    verify_invariants_for_nodes(&env, &nodes);
+}
+
+#[test]
+fn max_size_policy_splits_section_over_max_size() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        recommended_section_size: LOWERED_ELDER_SIZE + 1,
+    })
+    .with_split_policy(MaxSizeSplitPolicy {
+        max_section_size: 2 * LOWERED_ELDER_SIZE - 1,
+    });
+    let mut nodes = Vec::new();
+    let prefix = Prefix::default();
+
+    // Both halves have less than `recommended_section_size` mature nodes, so the default policy
+    // wouldn't split, but the section is over its maximum size.
+    add_mature_nodes(
+        &env,
+        &mut nodes,
+        &prefix,
+        env.elder_size(),
+        env.elder_size(),
+    );
+    poll_until(&env, &mut nodes, |nodes| section_split(nodes, &prefix));
+    verify_invariants_for_nodes(&env, &nodes);
+}
@@ -0,0 +1,118 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::utils::*;
+use fake_clock::FakeClock;
+use routing::{
+    event::Event, mock::Environment, test_consts, DstLocation, MemoryConfig, MemoryNetwork,
+    NetworkParams, SrcLocation, TcpConfig, TransportKind,
+};
+use std::{net::SocketAddr, thread, time::Duration};
+
+const NETWORK_SIZE: usize = 3;
+// Maximum number of iterations of `poll_until_real_time`.
+const MAX_ITERATIONS: usize = 2000;
+// Real time to wait in each iteration of `poll_until_real_time`, for the TCP backend to deliver the
+// messages from its threads.
+const REAL_TIME_STEP: Duration = Duration::from_millis(5);
+
+#[test]
+fn network_over_memory_backend() {
+    let network = MemoryNetwork::new();
+    run_network(|contacts| {
+        TransportKind::Memory(MemoryConfig {
+            network: network.clone(),
+            hard_coded_contacts: contacts,
+        })
+    });
+}
+
+#[test]
+fn network_over_tcp_backend() {
+    run_network(|contacts| {
+        TransportKind::Tcp(TcpConfig {
+            hard_coded_contacts: contacts,
+            ..TcpConfig::default()
+        })
+    });
+}
+
+// Starts a network whose nodes communicate through the given transport and sends a message
+// between two of them.
+fn run_network<F>(mut transport_kind: F)
+where
+    F: FnMut(Vec<SocketAddr>) -> TransportKind,
+{
+    let env = Environment::new(NetworkParams {
+        elder_size: NETWORK_SIZE,
+        recommended_section_size: NETWORK_SIZE,
+    });
+
+    let mut nodes = vec![TestNode::builder(&env)
+        .first()
+        .transport_kind(transport_kind(vec![]))
+        .create()];
+    let endpoint = nodes[0].endpoint();
+
+    for _ in 1..NETWORK_SIZE {
+        nodes.push(
+            TestNode::builder(&env)
+                .transport_kind(transport_kind(vec![endpoint]))
+                .create(),
+        );
+        poll_until_real_time(&env, &mut nodes, |nodes| {
+            node_joined(nodes, nodes.len() - 1)
+        });
+    }
+
+    let src = SrcLocation::Node(*nodes[0].name());
+    let dst = DstLocation::Node(*nodes[NETWORK_SIZE - 1].name());
+    let content = b"hello".to_vec();
+    assert!(nodes[0]
+        .inner
+        .send_message(src, dst, content.clone())
+        .is_ok());
+
+    poll_until_real_time(&env, &mut nodes, |nodes| {
+        while let Some(event) = nodes[NETWORK_SIZE - 1].try_recv_event() {
+            if let Event::MessageReceived {
+                content: received, ..
+            } = event
+            {
+                if received == content {
+                    return true;
+                }
+            }
+        }
+        false
+    });
+}
+
+// Like `poll_until`, but also waits in real time, as the TCP backend delivers the messages from its
+// own threads rather than synchronously.
+fn poll_until_real_time<F>(env: &Environment, nodes: &mut [TestNode], mut predicate: F)
+where
+    F: FnMut(&[TestNode]) -> bool,
+{
+    for _ in 0..MAX_ITERATIONS {
+        poll_all(env, nodes);
+
+        if predicate(nodes) {
+            return;
+        }
+
+        thread::sleep(REAL_TIME_STEP);
+        poll_all(env, nodes);
+        FakeClock::advance_time(test_consts::GOSSIP_PERIOD.as_millis() as u64 + 1);
+    }
+
+    panic!(
+        "poll_until_real_time has been called {} times.",
+        MAX_ITERATIONS
+    );
+}
@@ -17,8 +17,8 @@ use routing::{
     event::{Connected, Event},
     mock::Environment,
     rng::MainRng,
-    test_consts, DstLocation, FullId, Node, NodeConfig, PausedState, Prefix, PublicId,
-    RelocationOverrides, SrcLocation, TransportConfig, XorName, Xorable,
+    test_consts, ConsensusAlgorithm, DstLocation, FullId, Node, NodeConfig, PausedState, Prefix,
+    PublicId, RelocationOverrides, SrcLocation, TransportConfig, TransportKind, XorName, Xorable,
 };
 use std::{
     cmp, collections::BTreeSet, convert::TryInto, iter, net::SocketAddr, ops::Range, time::Duration,
@@ -154,13 +154,31 @@ impl<'a> TestNodeBuilder<'a> {
         self
     }
 
+    pub fn transport_kind(mut self, kind: TransportKind) -> Self {
+        self.config.transport_kind = kind;
+        self
+    }
+
     pub fn full_id(mut self, full_id: FullId) -> Self {
         self.config.full_id = Some(full_id);
         self
     }
 
+    pub fn previous_id(mut self, previous_id: FullId) -> Self {
+        self.config.previous_id = Some(previous_id);
+        self
+    }
+
+    pub fn consensus_algorithm(mut self, algorithm: ConsensusAlgorithm) -> Self {
+        self.config.consensus.algorithm = algorithm;
+        self
+    }
+
     pub fn create(mut self) -> TestNode {
         self.config.network_params = self.env.network_params();
+        self.config.split_policy = self.env.split_policy();
+        self.config.relocation_policy = self.env.relocation_policy();
+        self.config.admission_policy = self.env.admission_policy();
         self.config.rng = self.env.new_rng();
 
         let (inner, user_event_rx, _client_rx) = Node::new(self.config);
@@ -361,10 +379,23 @@ pub fn section_knowledge_is_up_to_date(
 }
 
 pub fn create_connected_nodes(env: &Environment, size: usize) -> Vec<TestNode> {
+    create_connected_nodes_with_consensus(env, size, ConsensusAlgorithm::default())
+}
+
+pub fn create_connected_nodes_with_consensus(
+    env: &Environment,
+    size: usize,
+    algorithm: ConsensusAlgorithm,
+) -> Vec<TestNode> {
     let mut nodes = Vec::new();
 
     // Create the seed node.
-    nodes.push(TestNode::builder(env).first().create());
+    nodes.push(
+        TestNode::builder(env)
+            .first()
+            .consensus_algorithm(algorithm)
+            .create(),
+    );
     let _ = nodes[0].poll();
     let endpoint = nodes[0].endpoint();
     info!("Seed node: {}", nodes[0].name());
@@ -372,18 +403,35 @@ pub fn create_connected_nodes(env: &Environment, size: usize) -> Vec<TestNode> {
     // Create other nodes using the seed node endpoint as bootstrap contact.
     for _ in 1..size {
         let config = TransportConfig::node().with_hard_coded_contact(endpoint);
-        nodes.push(TestNode::builder(env).transport_config(config).create());
+        nodes.push(
+            TestNode::builder(env)
+                .transport_config(config)
+                .consensus_algorithm(algorithm)
+                .create(),
+        );
 
         poll_until(env, &mut nodes, |nodes| node_joined(nodes, nodes.len() - 1));
         verify_invariants_for_nodes(&env, &nodes);
     }
 
     for node in &mut nodes {
-        expect_next_event!(node, Event::Connected(Connected::First));
+        // Skip the progress reports of bootstrapping.
+        loop {
+            match node.try_recv_event() {
+                Some(Event::Bootstrapping(_)) => (),
+                Some(Event::Connected(Connected::First)) => break,
+                other => panic!(
+                    "Expected Connected(First) at {}, got {:?}",
+                    node.name(),
+                    other
+                ),
+            }
+        }
 
         while let Some(event) = node.try_recv_event() {
             match event {
                 Event::EldersChanged { .. }
+                | Event::Bootstrapping(_)
                 | Event::RestartRequired
                 | Event::Connected(Connected::Relocate)
                 | Event::Promoted